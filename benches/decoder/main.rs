@@ -0,0 +1,114 @@
+use std::io;
+use std::io::{Read, Write};
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+
+use ::boomnet::ws::{Websocket, WebsocketFrame};
+
+/// Serves the recorded frames over and over, never returning a partial frame at the wrap around.
+struct ReplayStream {
+    data: Vec<u8>,
+    position: usize,
+}
+
+impl ReplayStream {
+    fn new(data: Vec<u8>) -> ReplayStream {
+        Self { data, position: 0 }
+    }
+}
+
+impl Read for ReplayStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.position == self.data.len() {
+            self.position = 0;
+        }
+        let len = buf.len().min(self.data.len() - self.position);
+        buf[..len].copy_from_slice(&self.data[self.position..self.position + len]);
+        self.position += len;
+        Ok(len)
+    }
+}
+
+impl Write for ReplayStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+fn encode_frame(payload: &[u8], data: &mut Vec<u8>) {
+    data.push(0x81);
+    match payload.len() {
+        len @ 0..=125 => data.push(len as u8),
+        len @ 126..=0xFFFF => {
+            data.push(126);
+            data.extend_from_slice(&(len as u16).to_be_bytes());
+        }
+        len => {
+            data.push(127);
+            data.extend_from_slice(&(len as u64).to_be_bytes());
+        }
+    }
+    data.extend_from_slice(payload);
+}
+
+/// Trade messages in the shape of the Binance `@trade` stream.
+fn trades(count: usize) -> Vec<u8> {
+    let mut data = Vec::new();
+    for id in 0..count {
+        let trade = format!(
+            r#"{{"e":"trade","E":1700000000{id:03},"s":"BTCUSDT","t":{id},"p":"37000.{id:02}","q":"0.001","T":1700000000{id:03},"m":true,"M":true}}"#
+        );
+        encode_frame(trade.as_bytes(), &mut data);
+    }
+    data
+}
+
+/// Order book snapshots in the shape of the Binance `@depth` stream, roughly 16KB each.
+fn depth_snapshots(count: usize) -> Vec<u8> {
+    let mut data = Vec::new();
+    for id in 0..count {
+        let levels = (0..250)
+            .map(|level| format!(r#"["37{level:03}.{id:02}","1.{level:05}"]"#))
+            .collect::<Vec<_>>()
+            .join(",");
+        let snapshot = format!(r#"{{"lastUpdateId":{id},"bids":[{levels}],"asks":[{levels}]}}"#);
+        encode_frame(snapshot.as_bytes(), &mut data);
+    }
+    data
+}
+
+fn decode_benchmark(c: &mut Criterion, name: &str, data: Vec<u8>, frames: usize) {
+    let mut group = c.benchmark_group("decoder");
+    group.throughput(Throughput::Bytes(data.len() as u64));
+
+    let mut ws = Websocket::new_connected(ReplayStream::new(data));
+
+    group.bench_function(name, |b| {
+        b.iter(|| {
+            let mut decoded = 0;
+            while decoded < frames {
+                if let Some(WebsocketFrame::Text(_, _, payload)) = ws.receive_next().unwrap() {
+                    black_box(payload);
+                    decoded += 1;
+                }
+            }
+        })
+    });
+
+    group.finish();
+}
+
+fn small_frames_benchmark(c: &mut Criterion) {
+    decode_benchmark(c, "decode_trades", trades(1000), 1000);
+}
+
+fn large_frames_benchmark(c: &mut Criterion) {
+    decode_benchmark(c, "decode_depth_snapshots", depth_snapshots(16), 16);
+}
+
+criterion_group!(benches, small_frames_benchmark, large_frames_benchmark);
+criterion_main!(benches);
@@ -0,0 +1,95 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use serde::Deserialize;
+
+use ::boomnet::ws::json;
+
+/// Trade message in the shape of the Binance combined `@trade` stream.
+const TRADE: &[u8] = br#"{"stream":"btcusdt@trade","data":{"e":"trade","E":1700000000123,"s":"BTCUSDT","t":3312345678,"p":"37000.01000000","q":"0.00100000","b":88,"a":50,"T":1700000000122,"m":true,"M":true}}"#;
+
+#[derive(Deserialize)]
+struct Envelope<'a> {
+    #[serde(borrow)]
+    data: Trade<'a>,
+}
+
+#[derive(Deserialize)]
+struct Trade<'a> {
+    #[serde(rename = "p")]
+    price: &'a str,
+    #[serde(rename = "q")]
+    qty: &'a str,
+    #[serde(rename = "T")]
+    time: u64,
+}
+
+#[derive(Deserialize)]
+struct Depth {
+    #[serde(rename = "lastUpdateId")]
+    last_update_id: u64,
+}
+
+/// Order book snapshot in the shape of the Binance `@depth` stream, roughly 16KB.
+fn depth_snapshot() -> Vec<u8> {
+    let levels = (0..250)
+        .map(|level| format!(r#"["37{level:03}.01","1.{level:05}"]"#))
+        .collect::<Vec<_>>()
+        .join(",");
+    format!(r#"{{"lastUpdateId":160,"bids":[{levels}],"asks":[{levels}]}}"#).into_bytes()
+}
+
+fn trade_benchmark(c: &mut Criterion) {
+    let mut group = c.benchmark_group("json_trade");
+    group.throughput(Throughput::Bytes(TRADE.len() as u64));
+
+    group.bench_function("find_all", |b| {
+        b.iter(|| {
+            let [price, qty, time] = json::find_all(black_box(TRADE), &["data.p", "data.q", "data.T"]);
+            black_box((price.and_then(json::parse_f64), qty.and_then(json::parse_f64), time.and_then(json::parse_u64)))
+        })
+    });
+
+    group.bench_function("serde_json_struct", |b| {
+        b.iter(|| {
+            let envelope: Envelope = serde_json::from_slice(black_box(TRADE)).unwrap();
+            let trade = envelope.data;
+            black_box((trade.price.parse::<f64>().ok(), trade.qty.parse::<f64>().ok(), trade.time))
+        })
+    });
+
+    group.bench_function("serde_json_value", |b| {
+        b.iter(|| {
+            let value: serde_json::Value = serde_json::from_slice(black_box(TRADE)).unwrap();
+            let data = &value["data"];
+            black_box((
+                data["p"].as_str().and_then(|price| price.parse::<f64>().ok()),
+                data["q"].as_str().and_then(|qty| qty.parse::<f64>().ok()),
+                data["T"].as_u64(),
+            ))
+        })
+    });
+
+    group.finish();
+}
+
+fn depth_benchmark(c: &mut Criterion) {
+    let depth = depth_snapshot();
+    let mut group = c.benchmark_group("json_depth");
+    group.throughput(Throughput::Bytes(depth.len() as u64));
+
+    // the scan stops once the field has been found
+    group.bench_function("find", |b| {
+        b.iter(|| black_box(json::find(black_box(&depth), "lastUpdateId").and_then(json::parse_u64)))
+    });
+
+    group.bench_function("serde_json_struct", |b| {
+        b.iter(|| {
+            let depth: Depth = serde_json::from_slice(black_box(&depth)).unwrap();
+            black_box(depth.last_update_id)
+        })
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, trade_benchmark, depth_benchmark);
+criterion_main!(benches);
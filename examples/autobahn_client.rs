@@ -0,0 +1,94 @@
+//! Runs the websocket client against the [Autobahn](https://github.com/crossbario/autobahn-testsuite)
+//! fuzzing server and reports the cases that did not pass. Start the server first (the compression
+//! cases are excluded as `permessage-deflate` is not supported):
+//!
+//! ```text
+//! docker run -it --rm -v ${PWD}/autobahn:/config -v ${PWD}/autobahn/reports:/reports -p 9001:9001 \
+//!     crossbario/autobahn-testsuite wstest -m fuzzingserver -s /config/fuzzingserver.json
+//! cargo run --example autobahn_client --features ws
+//! ```
+//!
+//! The full report is written to `autobahn/reports/clients/index.html`.
+
+use std::net::TcpStream;
+
+use boomnet::ws::{Error, IntoWebsocket, Websocket, WebsocketFrame};
+
+const SERVER: &str = "ws://127.0.0.1:9001";
+const AGENT: &str = "boomnet";
+
+fn connect(path: &str) -> anyhow::Result<Websocket<TcpStream>> {
+    let stream = TcpStream::connect("127.0.0.1:9001")?;
+    stream.set_nodelay(true)?;
+    Ok(stream.into_websocket(&format!("{SERVER}{path}")))
+}
+
+/// Receives the single text message, used to query the server.
+fn receive_text(path: &str) -> anyhow::Result<String> {
+    let mut ws = connect(path)?;
+    loop {
+        if let Some(WebsocketFrame::Text(_, _, data)) = ws.receive_next()? {
+            return Ok(String::from_utf8_lossy(data).into_owned());
+        }
+    }
+}
+
+/// Echoes back every message until the server closes the connection.
+fn run_case(case: u32) -> anyhow::Result<()> {
+    let mut ws = connect(&format!("/runCase?case={case}&agent={AGENT}"))?;
+    let mut message = Vec::new();
+    let mut text = false;
+    loop {
+        let fin = match ws.receive_next() {
+            Ok(Some(WebsocketFrame::Text(_, fin, data))) => {
+                text = true;
+                message.extend_from_slice(data);
+                fin
+            }
+            Ok(Some(WebsocketFrame::Binary(_, fin, data))) => {
+                text = false;
+                message.extend_from_slice(data);
+                fin
+            }
+            Ok(Some(WebsocketFrame::Continuation(_, fin, data))) => {
+                message.extend_from_slice(data);
+                fin
+            }
+            Ok(_) => false,
+            Err(Error::ReceivedCloseFrame(..)) => return Ok(()),
+            Err(err) => return Err(err.into()),
+        };
+        if fin {
+            match text {
+                true => ws.send_text(true, Some(&message))?,
+                false => ws.send_binary(true, Some(&message))?,
+            }
+            message.clear();
+        }
+    }
+}
+
+fn main() -> anyhow::Result<()> {
+    let case_count: u32 = receive_text("/getCaseCount")?.trim().parse()?;
+    for case in 1..=case_count {
+        if let Err(err) = run_case(case) {
+            // failing the connection can be the expected behaviour, the server decides
+            println!("case {case}: {err}");
+        }
+    }
+
+    let mut ws = connect(&format!("/updateReports?agent={AGENT}"))?;
+    while ws.receive_next().is_ok() {}
+
+    let mut failed = 0;
+    for case in 1..=case_count {
+        let status = receive_text(&format!("/getCaseStatus?case={case}&agent={AGENT}"))?;
+        if !status.contains("\"OK\"") && !status.contains("\"INFORMATIONAL\"") {
+            println!("case {case}: {status}");
+            failed += 1;
+        }
+    }
+    println!("{} of {case_count} cases passed", case_count - failed);
+
+    Ok(())
+}
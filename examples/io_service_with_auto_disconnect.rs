@@ -43,7 +43,7 @@ impl TlsWebsocketEndpoint for TradeEndpoint {
     fn create_websocket(&mut self, addr: SocketAddr) -> io::Result<TlsWebsocket<Self::Stream>> {
         let mut ws = TcpStream::bind_and_connect(addr, self.net_iface, None)?
             .into_mio_stream()
-            .into_tls_websocket(self.url.as_str());
+            .into_tls_websocket(self.url.as_str())?;
 
         ws.send_text(
             true,
@@ -66,7 +66,7 @@ impl TlsWebsocketEndpointWithContext<FeedContext> for TradeEndpoint {
     fn create_websocket(&mut self, addr: SocketAddr, ctx: &mut FeedContext) -> io::Result<TlsWebsocket<Self::Stream>> {
         let mut ws = TcpStream::bind_and_connect(addr, self.net_iface, None)?
             .into_mio_stream()
-            .into_tls_websocket(self.url);
+            .into_tls_websocket(self.url)?;
 
         info!("{:?}", ctx.get_aeron_mut());
 
@@ -39,7 +39,7 @@ impl TlsWebsocketEndpoint for TradeEndpoint {
     }
 
     fn create_websocket(&mut self, addr: SocketAddr) -> io::Result<TlsWebsocket<Self::Stream>> {
-        let mut ws = TcpStream::bind_and_connect(addr, self.net_iface, None)?.into_tls_websocket(self.url);
+        let mut ws = TcpStream::bind_and_connect(addr, self.net_iface, None)?.into_tls_websocket(self.url)?;
 
         ws.send_text(
             true,
@@ -42,7 +42,7 @@ impl TlsWebsocketEndpoint for TradeEndpoint {
     fn create_websocket(&mut self, addr: SocketAddr) -> io::Result<TlsWebsocket<Self::Stream>> {
         let mut ws = TcpStream::bind_and_connect(addr, self.net_iface, None)?
             .into_mio_stream()
-            .into_tls_websocket(self.url);
+            .into_tls_websocket(self.url)?;
 
         ws.send_text(
             true,
@@ -0,0 +1,145 @@
+use std::fmt::{Display, Formatter};
+use std::io;
+use std::net::{SocketAddr, TcpStream};
+use std::time::Duration;
+
+use idle::IdleStrategy;
+use log::{info, warn};
+
+use boomnet::correlation::PendingRequests;
+use boomnet::endpoint::ws::{TlsWebsocket, TlsWebsocketEndpoint};
+use boomnet::select::direct::DirectSelector;
+use boomnet::service::IntoIOService;
+use boomnet::stream::BindAndConnect;
+use boomnet::time::{MonotonicClockSource, TimeSource};
+use boomnet::timer::TimerId;
+use boomnet::ws::{IntoTlsWebsocket, WebsocketFrame};
+
+/// This example demonstrates how to correlate the requests (such as orders) sent over the websocket
+/// with the responses (acks or rejects) by the request id. Each request is tracked in the pending
+/// request table until its response arrives, the requests not answered in time are expired from
+/// the periodic timer. The endpoint uses the same clock as the `IOService` so that the timeouts are
+/// consistent with the timers. The requests are not signed, so expect them to be rejected by the
+/// exchange, which is still a response that is correlated with the request.
+struct OrderEntryEndpoint {
+    url: &'static str,
+    clock: MonotonicClockSource,
+    orders: PendingRequests<Order>,
+    next_price: u32,
+}
+
+struct Order {
+    symbol: &'static str,
+    price: u32,
+}
+
+impl Display for Order {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}@{}", self.symbol, self.price)
+    }
+}
+
+impl OrderEntryEndpoint {
+    pub fn new(url: &'static str, clock: MonotonicClockSource) -> OrderEntryEndpoint {
+        Self {
+            url,
+            clock,
+            orders: PendingRequests::new(Duration::from_secs(5)),
+            next_price: 10_000,
+        }
+    }
+
+    fn send_order(&mut self, ws: &mut TlsWebsocket<TcpStream>) -> io::Result<()> {
+        let (symbol, price) = ("BTCUSDT", self.next_price);
+        self.next_price += 1;
+        // the id is allocated by the table and echoed back by the exchange in the response
+        let id = self
+            .orders
+            .register(self.clock.current_time_nanos(), Order { symbol, price });
+        let request = format!(
+            r#"{{"id":{id},"method":"order.test","params":{{"symbol":"{symbol}","side":"BUY","type":"LIMIT","timeInForce":"GTC","price":"{price}","quantity":"0.001"}}}}"#
+        );
+        ws.send_text(true, Some(request.as_bytes()))?;
+        Ok(())
+    }
+
+    fn on_response(&mut self, response: &[u8]) {
+        let Some(id) = parse_id(response) else {
+            warn!("uncorrelated message: {}", String::from_utf8_lossy(response));
+            return;
+        };
+        match self.orders.complete(id) {
+            Some(request) => {
+                let latency = Duration::from_nanos(self.clock.current_time_nanos() - request.sent_time_ns);
+                let accepted = response.windows(12).any(|window| window == br#""status":200"#);
+                info!(
+                    "order {id} ({}) {} after {latency:?}: {}",
+                    request.value,
+                    if accepted { "accepted" } else { "rejected" },
+                    String::from_utf8_lossy(response)
+                );
+            }
+            // the request has already timed out (or was never sent)
+            None => warn!("late response for order {id}"),
+        }
+    }
+}
+
+impl TlsWebsocketEndpoint for OrderEntryEndpoint {
+    type Stream = TcpStream;
+
+    fn url(&self) -> &str {
+        self.url
+    }
+
+    fn create_websocket(&mut self, addr: SocketAddr) -> io::Result<TlsWebsocket<Self::Stream>> {
+        // responses to the requests sent over the previous connection will never arrive
+        for (id, request) in self.orders.drain() {
+            warn!("order {id} ({}) lost due to disconnect", request.value);
+        }
+        TcpStream::bind_and_connect(addr, None, None)?.into_tls_websocket(self.url)
+    }
+
+    #[inline]
+    fn poll(&mut self, ws: &mut TlsWebsocket<Self::Stream>) -> io::Result<()> {
+        while let Some(WebsocketFrame::Text(_, _, data)) = ws.receive_next()? {
+            self.on_response(data);
+        }
+        Ok(())
+    }
+
+    fn on_timer(&mut self, ws: &mut TlsWebsocket<Self::Stream>, _timer_id: TimerId) -> io::Result<()> {
+        self.orders.expire(self.clock.current_time_nanos(), |id, request| {
+            warn!("order {id} ({}) timed out", request.value);
+        });
+        self.send_order(ws)
+    }
+}
+
+/// Extracts the numeric `id` from the response, a complete application would use a JSON parser.
+fn parse_id(response: &[u8]) -> Option<u64> {
+    const ID: &[u8] = br#""id":"#;
+    let start = response.windows(ID.len()).position(|window| window == ID)? + ID.len();
+    let digits = response[start..]
+        .iter()
+        .take_while(|byte| byte.is_ascii_digit())
+        .count();
+    std::str::from_utf8(&response[start..start + digits]).ok()?.parse().ok()
+}
+
+fn main() -> anyhow::Result<()> {
+    env_logger::init();
+
+    let clock = MonotonicClockSource::new();
+
+    let mut io_service = DirectSelector::new()?
+        .into_io_service(IdleStrategy::Sleep(Duration::from_millis(1)))
+        .with_time_source(clock);
+
+    let handle = io_service.register(OrderEntryEndpoint::new("wss://ws-api.binance.com:443/ws-api/v3", clock));
+    io_service.schedule_periodic_timer(handle, Duration::from_secs(1));
+
+    loop {
+        io_service.poll()?;
+    }
+}
@@ -0,0 +1,134 @@
+use std::cell::RefCell;
+use std::io;
+use std::net::{SocketAddr, TcpStream};
+use std::rc::Rc;
+use std::time::Duration;
+
+use idle::IdleStrategy;
+use log::{info, warn};
+
+use boomnet::endpoint::http::{HttpEndpoint, MixedEndpoint};
+use boomnet::endpoint::ws::{TlsWebsocket, TlsWebsocketEndpoint};
+use boomnet::http::HttpClient;
+use boomnet::select::mio::MioSelector;
+use boomnet::service::IntoIOService;
+use boomnet::stream::mio::{IntoMioStream, MioStream};
+use boomnet::stream::tls::{IntoTlsStream, TlsStream};
+use boomnet::timer::TimerId;
+use boomnet::ws::{json, IntoTlsWebsocket, WebsocketFrame};
+
+const REST_URL: &str = "https://api.binance.com";
+const REST_HOST: &str = "api.binance.com";
+const WS_URL: &str = "wss://stream.binance.com:9443";
+const LISTEN_KEY_PATH: &str = "/api/v3/userDataStream";
+/// Listen key expires after 60 minutes unless kept alive.
+const KEEPALIVE_INTERVAL: Duration = Duration::from_secs(30 * 60);
+
+/// Listen key shared between the REST and the websocket endpoints.
+type ListenKey = Rc<RefCell<Option<String>>>;
+
+/// This example demonstrates how to combine the REST calls with the websocket feed in the same
+/// `IOService` using the `MixedEndpoint`. The http endpoint creates the Binance user data stream
+/// listen key and keeps it alive every 30 minutes (driven by the periodic timer), while the
+/// websocket endpoint receives the user data stream events. The API key is taken from the
+/// `BINANCE_API_KEY` environment variable.
+struct ListenKeyEndpoint {
+    api_key: String,
+    listen_key: ListenKey,
+}
+
+impl HttpEndpoint for ListenKeyEndpoint {
+    type Stream = TlsStream<MioStream>;
+
+    fn url(&self) -> &str {
+        REST_URL
+    }
+
+    fn create_client(&mut self, addr: SocketAddr) -> io::Result<HttpClient<Self::Stream>> {
+        let stream = TcpStream::connect(addr)?.into_mio_stream().into_tls_stream(REST_HOST);
+        let mut client = HttpClient::new(stream, REST_HOST);
+        // creating the listen key returns the existing one if it is still active
+        client.send_request("POST", LISTEN_KEY_PATH, &[("X-MBX-APIKEY", &self.api_key)], Some(b""))?;
+        Ok(client)
+    }
+
+    fn poll(&mut self, client: &mut HttpClient<Self::Stream>) -> io::Result<()> {
+        if let Some(response) = client.poll_response()? {
+            if !response.is_success() {
+                warn!("listen key request failed: {} {}", response.status, String::from_utf8_lossy(response.body));
+                return Ok(());
+            }
+            if let Some(listen_key) = json::find(response.body, "listenKey") {
+                let listen_key = String::from_utf8_lossy(listen_key).into_owned();
+                info!("listen key: {listen_key}");
+                self.listen_key.replace(Some(listen_key));
+            } else {
+                info!("listen key kept alive");
+            }
+        }
+        Ok(())
+    }
+
+    fn on_timer(&mut self, client: &mut HttpClient<Self::Stream>, _timer_id: TimerId) -> io::Result<()> {
+        if let Some(listen_key) = self.listen_key.borrow().as_ref() {
+            let path = format!("{LISTEN_KEY_PATH}?listenKey={listen_key}");
+            client.send_request("PUT", &path, &[("X-MBX-APIKEY", &self.api_key)], Some(b""))?;
+        }
+        Ok(())
+    }
+}
+
+struct UserDataEndpoint {
+    listen_key: ListenKey,
+}
+
+impl TlsWebsocketEndpoint for UserDataEndpoint {
+    type Stream = MioStream;
+
+    fn url(&self) -> &str {
+        WS_URL
+    }
+
+    fn create_websocket(&mut self, addr: SocketAddr) -> io::Result<TlsWebsocket<Self::Stream>> {
+        let listen_key = self.listen_key.borrow();
+        let listen_key = listen_key
+            .as_ref()
+            .ok_or_else(|| io::Error::other("listen key not available"))?;
+        let url = format!("{WS_URL}/ws/{listen_key}");
+        TcpStream::connect(addr)?.into_mio_stream().into_tls_websocket(&url)
+    }
+
+    fn poll(&mut self, ws: &mut TlsWebsocket<Self::Stream>) -> io::Result<()> {
+        while let Some(WebsocketFrame::Text(ts, _fin, data)) = ws.receive_next()? {
+            info!("{ts}: {}", String::from_utf8_lossy(data));
+        }
+        Ok(())
+    }
+}
+
+fn main() -> anyhow::Result<()> {
+    env_logger::init();
+
+    let api_key = std::env::var("BINANCE_API_KEY")?;
+    let listen_key = ListenKey::default();
+
+    let mut io_service = MioSelector::new()?.into_io_service(IdleStrategy::Sleep(Duration::from_millis(1)));
+
+    let rest = io_service.register(MixedEndpoint::Http(ListenKeyEndpoint {
+        api_key,
+        listen_key: listen_key.clone(),
+    }));
+    io_service.schedule_periodic_timer(rest, KEEPALIVE_INTERVAL);
+
+    let mut user_data_registered = false;
+    loop {
+        io_service.poll()?;
+        // the websocket can only connect once the listen key has been created
+        if !user_data_registered && listen_key.borrow().is_some() {
+            io_service.register(MixedEndpoint::Websocket(UserDataEndpoint {
+                listen_key: listen_key.clone(),
+            }));
+            user_data_registered = true;
+        }
+    }
+}
@@ -8,13 +8,32 @@ use std::{io, ptr};
 
 use crate::util::NoBlock;
 
-const DEFAULT_INITIAL_CAPACITY: usize = 32768;
+/// Initial capacity of the [`ReadBuffer`] unless specified otherwise.
+pub const DEFAULT_INITIAL_CAPACITY: usize = 32768;
 
 #[derive(Debug)]
 pub struct ReadBuffer<const CHUNK_SIZE: usize, const INITIAL_CAPACITY: usize = DEFAULT_INITIAL_CAPACITY> {
     inner: Vec<u8>,
     head: usize,
     tail: usize,
+    high_watermark: usize,
+    shrink_policy: Option<ShrinkPolicy>,
+    cycles_below_low_watermark: usize,
+}
+
+/// Controls when the [`ReadBuffer`] that has grown past its initial capacity gives the memory back.
+/// The buffer shrinks to the initial capacity once it has held fewer than `low_watermark` bytes
+/// on `cycles` consecutive reads.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct ShrinkPolicy {
+    low_watermark: usize,
+    cycles: usize,
+}
+
+impl ShrinkPolicy {
+    pub const fn new(low_watermark: usize, cycles: usize) -> ShrinkPolicy {
+        Self { low_watermark, cycles }
+    }
 }
 
 impl<const CHUNK_SIZE: usize, const INITIAL_CAPACITY: usize> Default for ReadBuffer<CHUNK_SIZE, INITIAL_CAPACITY> {
@@ -33,9 +52,33 @@ impl<const CHUNK_SIZE: usize, const INITIAL_CAPACITY: usize> ReadBuffer<CHUNK_SI
             inner: vec![0u8; INITIAL_CAPACITY],
             head: 0,
             tail: 0,
+            high_watermark: 0,
+            shrink_policy: None,
+            cycles_below_low_watermark: 0,
+        }
+    }
+
+    /// Enable shrinking the buffer back to `INITIAL_CAPACITY` according to the `policy`, by default
+    /// the buffer never shrinks.
+    pub fn with_shrink_policy(self, policy: ShrinkPolicy) -> ReadBuffer<CHUNK_SIZE, INITIAL_CAPACITY> {
+        Self {
+            shrink_policy: Some(policy),
+            ..self
         }
     }
 
+    /// Current size of the underlying storage in bytes.
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.inner.len()
+    }
+
+    /// Largest number of bytes held by the buffer at any point.
+    #[inline]
+    pub const fn high_watermark(&self) -> usize {
+        self.high_watermark
+    }
+
     #[inline]
     pub const fn available(&self) -> usize {
         self.tail - self.head
@@ -67,6 +110,29 @@ impl<const CHUNK_SIZE: usize, const INITIAL_CAPACITY: usize> ReadBuffer<CHUNK_SI
             self.tail = 0;
         }
 
+        #[cold]
+        fn shrink<const CHUNK_SIZE: usize, const INITIAL_CAPACITY: usize>(
+            buf: &mut ReadBuffer<CHUNK_SIZE, INITIAL_CAPACITY>,
+        ) {
+            buf.inner.truncate(INITIAL_CAPACITY);
+            buf.inner.shrink_to_fit();
+            buf.cycles_below_low_watermark = 0;
+        }
+
+        // shrink (only once the leftover has been moved to the front so it fits)
+        if let Some(policy) = self.shrink_policy {
+            if self.inner.len() > INITIAL_CAPACITY {
+                if self.available() < policy.low_watermark {
+                    self.cycles_below_low_watermark += 1;
+                } else {
+                    self.cycles_below_low_watermark = 0;
+                }
+                if self.cycles_below_low_watermark >= policy.cycles && self.tail + CHUNK_SIZE <= INITIAL_CAPACITY {
+                    shrink(self);
+                }
+            }
+        }
+
         // ensure capacity
         if self.tail + CHUNK_SIZE > self.inner.len() {
             grow(&mut self.inner);
@@ -77,6 +143,7 @@ impl<const CHUNK_SIZE: usize, const INITIAL_CAPACITY: usize> ReadBuffer<CHUNK_SI
             .no_block()?;
 
         self.tail += read;
+        self.high_watermark = self.high_watermark.max(self.available());
         Ok(())
     }
 
@@ -241,6 +308,27 @@ mod tests {
         assert_eq!(16, buf.inner.len());
     }
 
+    #[test]
+    fn should_shrink_when_below_low_watermark() {
+        let mut buf = ReadBuffer::<4, 8>::new().with_shrink_policy(ShrinkPolicy::new(4, 2));
+        let mut stream = Cursor::new(b"hello world!".repeat(2));
+        for _ in 0..3 {
+            buf.read_from(&mut stream).expect("unable to read from the stream");
+        }
+        assert_eq!(12, buf.available());
+        assert_eq!(16, buf.capacity());
+        assert_eq!(12, buf.high_watermark());
+
+        assert_eq!(b"hello world!", buf.consume_next(12));
+        buf.read_from(&mut stream).expect("unable to read from the stream");
+        assert_eq!(16, buf.capacity());
+        assert_eq!(b"hell", buf.consume_next(4));
+        buf.read_from(&mut stream).expect("unable to read from the stream");
+        assert_eq!(8, buf.capacity());
+        assert_eq!(b"o wo", buf.view());
+        assert_eq!(12, buf.high_watermark());
+    }
+
     #[test]
     fn should_handle_reader_with_no_data() {
         struct StreamWithNoData;
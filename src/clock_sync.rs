@@ -0,0 +1,226 @@
+//! Utilities to estimate the venue clock skew by pairing the venue event timestamps with the
+//! local receive time.
+//!
+//! For each frame the observed offset is `local_receive_time - venue_time`, which is the sum of
+//! the clock skew and the one-way delay. The minimum offset over a sliding window approximates
+//! the skew (plus the minimal one-way delay) while the EWMA of the offset above that minimum
+//! tracks the one-way delay experienced by the endpoint. When several connections to the same
+//! venue are tracked, [`ClockSync::combined_stats`] uses the tightest minimum across all of them.
+//!
+//! # Examples
+//!
+//! ```
+//! use boomnet::clock_sync::ClockSync;
+//!
+//! // venue timestamp is the first 8 bytes of the frame (little endian)
+//! let mut clock_sync = ClockSync::new(|frame: &[u8]| Some(u64::from_le_bytes(frame.get(..8)?.try_into().ok()?)));
+//!
+//! clock_sync.on_frame(0, 1_500, &1_000u64.to_le_bytes());
+//! clock_sync.on_frame(1, 1_600, &1_200u64.to_le_bytes());
+//!
+//! assert_eq!(500, clock_sync.stats(0).unwrap().min_offset_ns);
+//! assert_eq!(400, clock_sync.combined_stats().unwrap().min_offset_ns);
+//! ```
+
+use std::collections::{HashMap, VecDeque};
+
+use crate::service::Handle;
+
+const DEFAULT_WINDOW_SIZE: usize = 1024;
+const DEFAULT_ALPHA: f64 = 0.05;
+
+/// Snapshot of the clock skew estimates.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct ClockSyncStats {
+    /// Number of samples recorded so far.
+    pub samples: u64,
+    /// Minimum offset (local minus venue time) observed within the window, approximates the skew.
+    pub min_offset_ns: i64,
+    /// Exponentially weighted moving average of the offset.
+    pub ewma_offset_ns: i64,
+    /// Exponentially weighted moving average of the offset above the windowed minimum,
+    /// approximates the one-way delay.
+    pub ewma_delay_ns: i64,
+}
+
+/// Maintains the clock skew and one-way delay estimates for a single connection.
+#[derive(Debug)]
+pub struct ClockSkewEstimator {
+    window_size: usize,
+    alpha: f64,
+    // monotonic queue of (sequence, offset) used to obtain windowed minimum
+    window: VecDeque<(u64, i64)>,
+    samples: u64,
+    ewma_offset_ns: f64,
+    ewma_delay_ns: f64,
+}
+
+impl ClockSkewEstimator {
+    /// Creates new estimator with the minimum filter over the last `window_size` samples and
+    /// the EWMA smoothing factor `alpha` (in the range `(0, 1]`).
+    pub fn new(window_size: usize, alpha: f64) -> ClockSkewEstimator {
+        assert!(window_size > 0, "window size must be positive");
+        assert!(alpha > 0.0 && alpha <= 1.0, "alpha must be in the range (0, 1]");
+        Self {
+            window_size,
+            alpha,
+            window: VecDeque::with_capacity(window_size),
+            samples: 0,
+            ewma_offset_ns: 0.0,
+            ewma_delay_ns: 0.0,
+        }
+    }
+
+    /// Records new sample given the local receive time and the venue timestamp (both in nanoseconds).
+    pub fn record(&mut self, local_receive_time_ns: u64, venue_time_ns: u64) {
+        let offset_ns = local_receive_time_ns as i64 - venue_time_ns as i64;
+        let sequence = self.samples;
+
+        while matches!(self.window.back(), Some((_, last)) if *last >= offset_ns) {
+            self.window.pop_back();
+        }
+        self.window.push_back((sequence, offset_ns));
+        while matches!(self.window.front(), Some((seq, _)) if sequence - seq >= self.window_size as u64) {
+            self.window.pop_front();
+        }
+
+        let min_offset_ns = self.window.front().map(|(_, min)| *min).unwrap_or(offset_ns);
+        let delay_ns = (offset_ns - min_offset_ns) as f64;
+        if self.samples == 0 {
+            self.ewma_offset_ns = offset_ns as f64;
+            self.ewma_delay_ns = delay_ns;
+        } else {
+            self.ewma_offset_ns += self.alpha * (offset_ns as f64 - self.ewma_offset_ns);
+            self.ewma_delay_ns += self.alpha * (delay_ns - self.ewma_delay_ns);
+        }
+        self.samples += 1;
+    }
+
+    /// Returns current estimates or `None` if no samples have been recorded yet.
+    pub fn stats(&self) -> Option<ClockSyncStats> {
+        let (_, min_offset_ns) = self.window.front()?;
+        Some(ClockSyncStats {
+            samples: self.samples,
+            min_offset_ns: *min_offset_ns,
+            ewma_offset_ns: self.ewma_offset_ns as i64,
+            ewma_delay_ns: self.ewma_delay_ns as i64,
+        })
+    }
+}
+
+/// Tracks [`ClockSkewEstimator`] per endpoint (identified by [`Handle`]) using the user provided
+/// extractor to obtain the venue timestamp from the frame payload.
+pub struct ClockSync<F> {
+    extractor: F,
+    window_size: usize,
+    alpha: f64,
+    estimators: HashMap<Handle, ClockSkewEstimator>,
+}
+
+impl<F> ClockSync<F>
+where
+    F: FnMut(&[u8]) -> Option<u64>,
+{
+    /// Creates new instance with the `extractor` that returns the venue timestamp (in nanoseconds)
+    /// from the frame payload or `None` if the frame does not carry one.
+    pub fn new(extractor: F) -> ClockSync<F> {
+        Self {
+            extractor,
+            window_size: DEFAULT_WINDOW_SIZE,
+            alpha: DEFAULT_ALPHA,
+            estimators: HashMap::new(),
+        }
+    }
+
+    /// Specify the number of samples the minimum filter operates on.
+    pub fn with_window_size(self, window_size: usize) -> ClockSync<F> {
+        Self { window_size, ..self }
+    }
+
+    /// Specify the EWMA smoothing factor.
+    pub fn with_alpha(self, alpha: f64) -> ClockSync<F> {
+        Self { alpha, ..self }
+    }
+
+    /// Records the frame received by the endpoint at `local_receive_time_ns`. Returns `false` if
+    /// the venue timestamp could not be extracted from the frame.
+    pub fn on_frame(&mut self, handle: Handle, local_receive_time_ns: u64, frame: &[u8]) -> bool {
+        match (self.extractor)(frame) {
+            Some(venue_time_ns) => {
+                self.record(handle, local_receive_time_ns, venue_time_ns);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Records the sample for the endpoint when the venue timestamp is already known.
+    pub fn record(&mut self, handle: Handle, local_receive_time_ns: u64, venue_time_ns: u64) {
+        let (window_size, alpha) = (self.window_size, self.alpha);
+        self.estimators
+            .entry(handle)
+            .or_insert_with(|| ClockSkewEstimator::new(window_size, alpha))
+            .record(local_receive_time_ns, venue_time_ns);
+    }
+
+    /// Returns current estimates for the endpoint.
+    pub fn stats(&self, handle: Handle) -> Option<ClockSyncStats> {
+        self.estimators.get(&handle)?.stats()
+    }
+
+    /// Returns estimates combined across all endpoints, using the tightest skew estimate and the
+    /// lowest one-way delay.
+    pub fn combined_stats(&self) -> Option<ClockSyncStats> {
+        self.estimators
+            .values()
+            .filter_map(|estimator| estimator.stats())
+            .reduce(|acc, stats| ClockSyncStats {
+                samples: acc.samples + stats.samples,
+                min_offset_ns: acc.min_offset_ns.min(stats.min_offset_ns),
+                ewma_offset_ns: acc.ewma_offset_ns.min(stats.ewma_offset_ns),
+                ewma_delay_ns: acc.ewma_delay_ns.min(stats.ewma_delay_ns),
+            })
+    }
+
+    /// Discards estimates for the endpoint, typically after it has been reconnected.
+    pub fn reset(&mut self, handle: Handle) {
+        self.estimators.remove(&handle);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_track_windowed_minimum() {
+        let mut estimator = ClockSkewEstimator::new(3, 1.0);
+        assert!(estimator.stats().is_none());
+
+        estimator.record(1_100, 1_000);
+        estimator.record(2_050, 2_000);
+        estimator.record(3_200, 3_000);
+        assert_eq!(50, estimator.stats().unwrap().min_offset_ns);
+
+        estimator.record(4_300, 4_000);
+        assert_eq!(50, estimator.stats().unwrap().min_offset_ns);
+
+        // minimum falls out of the window
+        estimator.record(5_250, 5_000);
+        let stats = estimator.stats().unwrap();
+        assert_eq!(200, stats.min_offset_ns);
+        assert_eq!(250, stats.ewma_offset_ns);
+        assert_eq!(50, stats.ewma_delay_ns);
+        assert_eq!(5, stats.samples);
+    }
+
+    #[test]
+    fn should_handle_negative_offset() {
+        let mut estimator = ClockSkewEstimator::new(8, 0.5);
+        estimator.record(1_000, 1_100);
+        estimator.record(2_000, 2_200);
+        let stats = estimator.stats().unwrap();
+        assert_eq!(-200, stats.min_offset_ns);
+        assert_eq!(-150, stats.ewma_offset_ns);
+    }
+}
@@ -0,0 +1,298 @@
+//! Single connection that is automatically re-established after it has failed, for the applications
+//! that want the reconnection handling without adopting the whole `IOService` (and its selector).
+//! The connection is driven by the application calling [`ManagedConnection::poll`] in its own loop,
+//! the handler is then notified about the lifecycle of the connection with the [`ConnectionEvent`].
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use std::io::{self, Read};
+//! use std::net::TcpStream;
+//! use boomnet::connection::{ConnectionEvent, ManagedConnection};
+//!
+//! let mut connection = ManagedConnection::new(|| {
+//!     let stream = TcpStream::connect("127.0.0.1:9001")?;
+//!     stream.set_nonblocking(true)?;
+//!     Ok(stream)
+//! });
+//!
+//! let mut buf = [0u8; 1024];
+//! loop {
+//!     connection
+//!         .poll(|event| {
+//!             match event {
+//!                 ConnectionEvent::Connected(_) => println!("connected"),
+//!                 ConnectionEvent::Update(stream) => match stream.read(&mut buf) {
+//!                     Ok(0) => return Err(io::Error::from(io::ErrorKind::UnexpectedEof)),
+//!                     Ok(read) => println!("received {read} bytes"),
+//!                     Err(err) if err.kind() == io::ErrorKind::WouldBlock => {}
+//!                     Err(err) => return Err(err),
+//!                 },
+//!                 ConnectionEvent::Disconnected(err) => println!("disconnected: {err}"),
+//!             }
+//!             Ok(())
+//!         })
+//!         .unwrap();
+//! }
+//! ```
+
+use std::io;
+use std::io::{ErrorKind, Read, Write};
+use std::time::Duration;
+
+use crate::time::{MonotonicClockSource, TimeSource};
+
+/// Default minimum interval between the connection attempts.
+pub const DEFAULT_RECONNECT_THROTTLE: Duration = Duration::from_secs(1);
+
+/// Creates the connection (target) of the [`ManagedConnection`]. Implemented for the closures
+/// returning the target.
+pub trait ConnectionFactory {
+    type Target: Read + Write;
+
+    /// Establishes new connection, invoked each time the connection is (re)created.
+    fn connect(&mut self) -> io::Result<Self::Target>;
+
+    /// Determines if the connection should be re-established after it has failed (or could not be
+    /// established) with the `err`. Defaults to always reconnecting.
+    fn can_reconnect(&mut self, _err: &io::Error) -> bool {
+        true
+    }
+}
+
+impl<T: Read + Write, F: FnMut() -> io::Result<T>> ConnectionFactory for F {
+    type Target = T;
+
+    fn connect(&mut self) -> io::Result<Self::Target> {
+        self()
+    }
+}
+
+/// Lifecycle event of the [`ManagedConnection`] passed to the `poll` handler.
+pub enum ConnectionEvent<'a, T> {
+    /// The connection has been established, typically used to subscribe or log on.
+    Connected(&'a mut T),
+    /// The connection is established, the handler is expected to perform the I/O.
+    Update(&'a mut T),
+    /// The connection has failed (or could not be established) with the error.
+    Disconnected(&'a io::Error),
+}
+
+/// Single connection re-established with the [`ConnectionFactory`] after it has failed, see the
+/// [module](self) documentation. The connection attempts are throttled (see
+/// [`ManagedConnection::with_reconnect_throttle`]), which are driven by the [`TimeSource`].
+pub struct ManagedConnection<F: ConnectionFactory, T = MonotonicClockSource> {
+    factory: F,
+    target: Option<F::Target>,
+    reconnect_throttle: Duration,
+    next_connect_time_ns: u64,
+    time_source: T,
+    closed: bool,
+}
+
+impl<F: ConnectionFactory> ManagedConnection<F> {
+    /// Creates new connection, the first attempt to connect is made on the first poll.
+    pub fn new(factory: F) -> ManagedConnection<F> {
+        Self {
+            factory,
+            target: None,
+            reconnect_throttle: DEFAULT_RECONNECT_THROTTLE,
+            next_connect_time_ns: 0,
+            time_source: MonotonicClockSource::new(),
+            closed: false,
+        }
+    }
+}
+
+impl<F: ConnectionFactory, T: TimeSource> ManagedConnection<F, T> {
+    /// Specify the minimum interval between the connection attempts.
+    pub fn with_reconnect_throttle(self, reconnect_throttle: Duration) -> ManagedConnection<F, T> {
+        Self {
+            reconnect_throttle,
+            ..self
+        }
+    }
+
+    /// Use the custom [`TimeSource`] to throttle the connection attempts.
+    pub fn with_time_source<U: TimeSource>(self, time_source: U) -> ManagedConnection<F, U> {
+        ManagedConnection {
+            factory: self.factory,
+            target: self.target,
+            reconnect_throttle: self.reconnect_throttle,
+            next_connect_time_ns: self.next_connect_time_ns,
+            time_source,
+            closed: self.closed,
+        }
+    }
+
+    /// Returns `true` if the connection is currently established.
+    pub const fn is_connected(&self) -> bool {
+        self.target.is_some()
+    }
+
+    /// Returns `true` if the connection will not be re-established anymore, as refused by the
+    /// [`ConnectionFactory::can_reconnect`].
+    pub const fn is_closed(&self) -> bool {
+        self.closed
+    }
+
+    /// Returns the current connection, if established.
+    pub fn target(&self) -> Option<&F::Target> {
+        self.target.as_ref()
+    }
+
+    /// Returns the current connection, if established.
+    pub fn target_mut(&mut self) -> Option<&mut F::Target> {
+        self.target.as_mut()
+    }
+
+    /// Drops the current connection (without notifying the handler), it will be re-established
+    /// on the next poll (subject to the throttle).
+    pub fn disconnect(&mut self) {
+        self.target = None;
+    }
+
+    /// Invokes the `handler` with the [`ConnectionEvent::Update`] if connected, otherwise attempts to
+    /// establish the connection (if the throttle allows) and reports it with the
+    /// [`ConnectionEvent::Connected`]. The error returned by the handler drops the
+    /// connection and is reported back with the [`ConnectionEvent::Disconnected`]. Returns the error
+    /// only once the [`ConnectionFactory`] has refused to reconnect, after which the connection is
+    /// closed and the subsequent polls fail with [`ErrorKind::NotConnected`].
+    pub fn poll<H>(&mut self, mut handler: H) -> io::Result<()>
+    where
+        H: FnMut(ConnectionEvent<'_, F::Target>) -> io::Result<()>,
+    {
+        if self.closed {
+            return Err(io::Error::new(ErrorKind::NotConnected, "connection has been closed"));
+        }
+
+        let result = match &mut self.target {
+            Some(target) => handler(ConnectionEvent::Update(target)),
+            None => {
+                let current_time_ns = self.time_source.current_time_nanos();
+                if current_time_ns < self.next_connect_time_ns {
+                    return Ok(());
+                }
+                self.next_connect_time_ns = current_time_ns + self.reconnect_throttle.as_nanos() as u64;
+                match self.factory.connect() {
+                    Ok(target) => handler(ConnectionEvent::Connected(self.target.insert(target))),
+                    Err(err) => Err(err),
+                }
+            }
+        };
+
+        if let Err(err) = result {
+            self.target = None;
+            handler(ConnectionEvent::Disconnected(&err))?;
+            if !self.factory.can_reconnect(&err) {
+                self.closed = true;
+                return Err(err);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use crate::time::ManualTimeSource;
+
+    use super::*;
+
+    // records the events seen by the handler
+    fn handler<'a>(
+        events: &'a mut Vec<String>,
+    ) -> impl FnMut(ConnectionEvent<'_, Cursor<Vec<u8>>>) -> io::Result<()> + 'a {
+        move |event| {
+            match event {
+                ConnectionEvent::Connected(target) => events.push(format!("connected {}", target.get_ref().len())),
+                ConnectionEvent::Update(target) => {
+                    let mut buf = [0u8; 1];
+                    if target.read(&mut buf)? == 0 {
+                        return Err(io::Error::from(ErrorKind::UnexpectedEof));
+                    }
+                    events.push(format!("update {}", buf[0]));
+                }
+                ConnectionEvent::Disconnected(err) => events.push(format!("disconnected {:?}", err.kind())),
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn should_reconnect_with_throttle() {
+        let time_source = ManualTimeSource::new(0);
+        let mut attempts = 0u8;
+        let mut connection = ManagedConnection::new(move || {
+            attempts += 1;
+            match attempts {
+                1 => Err(io::Error::from(ErrorKind::ConnectionRefused)),
+                _ => Ok(Cursor::new(vec![attempts])),
+            }
+        })
+        .with_reconnect_throttle(Duration::from_secs(1))
+        .with_time_source(time_source.clone());
+
+        let mut events = Vec::new();
+        connection.poll(handler(&mut events)).unwrap();
+        // throttled until the interval has elapsed
+        connection.poll(handler(&mut events)).unwrap();
+        assert!(!connection.is_connected());
+        time_source.advance(Duration::from_secs(1));
+        for _ in 0..3 {
+            connection.poll(handler(&mut events)).unwrap();
+        }
+        assert!(!connection.is_connected());
+        time_source.advance(Duration::from_secs(1));
+        connection.poll(handler(&mut events)).unwrap();
+        assert!(connection.is_connected());
+
+        assert_eq!(
+            vec![
+                "disconnected ConnectionRefused",
+                "connected 1",
+                "update 2",
+                "disconnected UnexpectedEof",
+                "connected 1",
+            ],
+            events
+        );
+    }
+
+    #[test]
+    fn should_close_when_reconnect_refused() {
+        struct Once(bool);
+
+        impl ConnectionFactory for Once {
+            type Target = Cursor<Vec<u8>>;
+
+            fn connect(&mut self) -> io::Result<Self::Target> {
+                Ok(Cursor::new(vec![1]))
+            }
+
+            fn can_reconnect(&mut self, _err: &io::Error) -> bool {
+                std::mem::replace(&mut self.0, false)
+            }
+        }
+
+        let mut connection = ManagedConnection::new(Once(true)).with_reconnect_throttle(Duration::ZERO);
+        let mut events = Vec::new();
+        while !connection.is_closed() {
+            let _ = connection.poll(handler(&mut events));
+        }
+        assert_eq!(ErrorKind::NotConnected, connection.poll(handler(&mut events)).unwrap_err().kind());
+        assert_eq!(
+            vec![
+                "connected 1",
+                "update 1",
+                "disconnected UnexpectedEof",
+                "connected 1",
+                "update 1",
+                "disconnected UnexpectedEof",
+            ],
+            events
+        );
+    }
+}
@@ -0,0 +1,176 @@
+//! Correlation of the outbound requests with the inbound responses.
+
+use std::collections::{HashMap, VecDeque};
+use std::time::Duration;
+
+/// Identifies request tracked by the [`PendingRequests`].
+pub type RequestId = u64;
+
+/// Request awaiting the response.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct PendingRequest<T> {
+    /// Application data associated with the request (such as the order details).
+    pub value: T,
+    /// Time the request has been sent.
+    pub sent_time_ns: u64,
+    /// Time after which the request is considered timed out.
+    pub deadline_ns: u64,
+}
+
+/// Table of the requests (such as subscriptions or orders) that have been sent and are awaiting
+/// the response, correlated by the id carried in both the request and the response. Requests that
+/// are not answered within the timeout are expired, the time is provided by the caller (typically
+/// from the same `TimeSource` as used by the `IOService`) so the table never reads the clock itself.
+///
+/// # Examples
+///
+/// ```
+/// use std::time::Duration;
+/// use boomnet::correlation::PendingRequests;
+///
+/// let mut requests = PendingRequests::new(Duration::from_secs(5));
+/// let subscribe = requests.register(0, "subscribe");
+/// let order = requests.register(0, "order");
+///
+/// // response to the subscription arrives
+/// let completed = requests.complete(subscribe).unwrap();
+/// assert_eq!("subscribe", completed.value);
+///
+/// // but the order is never acknowledged
+/// let mut timed_out = Vec::new();
+/// requests.expire(Duration::from_secs(6).as_nanos() as u64, |id, request| timed_out.push((id, request.value)));
+/// assert_eq!(vec![(order, "order")], timed_out);
+/// assert!(requests.is_empty());
+/// ```
+#[derive(Debug)]
+pub struct PendingRequests<T> {
+    timeout_ns: u64,
+    next_id: RequestId,
+    requests: HashMap<RequestId, PendingRequest<T>>,
+    // deadlines in the order the requests were sent, may refer to the requests already completed
+    deadlines: VecDeque<(u64, RequestId)>,
+}
+
+impl<T> PendingRequests<T> {
+    /// Creates empty table that expires the requests not answered within the `timeout`.
+    pub fn new(timeout: Duration) -> PendingRequests<T> {
+        Self {
+            timeout_ns: timeout.as_nanos() as u64,
+            next_id: 1,
+            requests: HashMap::new(),
+            deadlines: VecDeque::new(),
+        }
+    }
+
+    /// Number of requests awaiting the response.
+    pub fn len(&self) -> usize {
+        self.requests.len()
+    }
+
+    /// Checks if there are no requests awaiting the response.
+    pub fn is_empty(&self) -> bool {
+        self.requests.is_empty()
+    }
+
+    /// Checks if the request with the `id` is awaiting the response.
+    pub fn contains(&self, id: RequestId) -> bool {
+        self.requests.contains_key(&id)
+    }
+
+    /// Returns the request with the `id` if it is awaiting the response.
+    pub fn get(&self, id: RequestId) -> Option<&PendingRequest<T>> {
+        self.requests.get(&id)
+    }
+
+    /// Tracks the request sent at `current_time_ns` under the newly allocated id, which should then
+    /// be included in the request itself. Ids are allocated sequentially starting from `1`.
+    pub fn register(&mut self, current_time_ns: u64, value: T) -> RequestId {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.insert(id, current_time_ns, value);
+        id
+    }
+
+    /// Tracks the request sent at `current_time_ns` under the `id` assigned by the caller, replacing
+    /// the request with the same `id` (if any).
+    pub fn insert(&mut self, id: RequestId, current_time_ns: u64, value: T) -> Option<PendingRequest<T>> {
+        let deadline_ns = current_time_ns.saturating_add(self.timeout_ns);
+        self.deadlines.push_back((deadline_ns, id));
+        self.requests.insert(
+            id,
+            PendingRequest {
+                value,
+                sent_time_ns: current_time_ns,
+                deadline_ns,
+            },
+        )
+    }
+
+    /// Stops tracking the request once its response has been received. Returns `None` if the `id`
+    /// is unknown, for example because the request has already timed out.
+    pub fn complete(&mut self, id: RequestId) -> Option<PendingRequest<T>> {
+        self.requests.remove(&id)
+    }
+
+    /// Removes the requests whose deadline has passed as of `current_time_ns`, passing each of them
+    /// to `on_timeout`. Should be called regularly, such as on every poll or from a periodic timer.
+    pub fn expire<F>(&mut self, current_time_ns: u64, mut on_timeout: F)
+    where
+        F: FnMut(RequestId, PendingRequest<T>),
+    {
+        while let Some(&(deadline_ns, id)) = self.deadlines.front() {
+            if deadline_ns > current_time_ns {
+                break;
+            }
+            self.deadlines.pop_front();
+            // the request may have been completed or replaced with a later deadline
+            if self.requests.get(&id).map(|request| request.deadline_ns) == Some(deadline_ns) {
+                if let Some(request) = self.requests.remove(&id) {
+                    on_timeout(id, request);
+                }
+            }
+        }
+    }
+
+    /// Removes all the requests, typically when the connection is lost and the responses will
+    /// never arrive.
+    pub fn drain(&mut self) -> impl Iterator<Item = (RequestId, PendingRequest<T>)> + '_ {
+        self.deadlines.clear();
+        self.requests.drain()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_correlate_and_expire_requests() {
+        let mut requests = PendingRequests::new(Duration::from_nanos(100));
+        let first = requests.register(0, "first");
+        let second = requests.register(10, "second");
+        assert!(requests.insert(7, 20, "third").is_none());
+        assert_eq!(3, requests.len());
+
+        let completed = requests.complete(second).unwrap();
+        assert_eq!(10, completed.sent_time_ns);
+        assert_eq!(110, completed.deadline_ns);
+        assert!(requests.complete(second).is_none());
+
+        // re-sent request gets a new deadline
+        requests.insert(first, 50, "first");
+
+        let mut timed_out = Vec::new();
+        requests.expire(120, |id, request| timed_out.push((id, request.value)));
+        assert_eq!(vec![(7, "third")], timed_out);
+        assert!(requests.contains(first));
+
+        requests.expire(150, |id, request| timed_out.push((id, request.value)));
+        assert_eq!(vec![(7, "third"), (first, "first")], timed_out);
+        assert!(requests.is_empty());
+
+        requests.register(200, "fourth");
+        assert_eq!(1, requests.drain().count());
+        requests.expire(u64::MAX, |_, _| panic!("nothing to expire"));
+    }
+}
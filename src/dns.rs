@@ -0,0 +1,343 @@
+//! DNS resolution used by the `IOService` to obtain endpoint addresses.
+
+use std::collections::HashMap;
+use std::io;
+use std::io::ErrorKind;
+use std::net::{IpAddr, SocketAddr, ToSocketAddrs};
+use std::time::Duration;
+
+use crate::time::{MonotonicClockSource, TimeSource};
+
+/// Resolves host name into list of socket addresses.
+pub trait DnsResolver {
+    /// Resolves `host` and `port` into one or more socket addresses.
+    fn resolve(&mut self, host: &str, port: u16) -> io::Result<Vec<SocketAddr>>;
+}
+
+/// Address family preference applied to the resolved addresses, see
+/// [`ConnectionInfo::with_address_family`](crate::endpoint::ConnectionInfo::with_address_family).
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq)]
+pub enum AddressFamily {
+    /// Keep the addresses in the order returned by the resolver.
+    #[default]
+    Any,
+    /// Use IPv4 addresses only.
+    V4Only,
+    /// Use IPv6 addresses only.
+    V6Only,
+    /// Move IPv4 addresses in front of IPv6 ones, otherwise keeping the resolver order.
+    PreferV4,
+    /// Move IPv6 addresses in front of IPv4 ones, otherwise keeping the resolver order.
+    PreferV6,
+}
+
+impl AddressFamily {
+    /// Filters and orders the `addrs` according to the preference.
+    pub fn apply(&self, addrs: &mut Vec<SocketAddr>) {
+        match self {
+            AddressFamily::Any => {}
+            AddressFamily::V4Only => addrs.retain(SocketAddr::is_ipv4),
+            AddressFamily::V6Only => addrs.retain(SocketAddr::is_ipv6),
+            // stable sort keeps the resolver order within each family
+            AddressFamily::PreferV4 => addrs.sort_by_key(SocketAddr::is_ipv6),
+            AddressFamily::PreferV6 => addrs.sort_by_key(SocketAddr::is_ipv4),
+        }
+    }
+}
+
+/// Resolves host name using the operating system resolver. Will block the calling thread
+/// until the query completes.
+#[derive(Debug, Default, Copy, Clone)]
+pub struct BlockingDnsResolver;
+
+impl DnsResolver for BlockingDnsResolver {
+    fn resolve(&mut self, host: &str, port: u16) -> io::Result<Vec<SocketAddr>> {
+        let addrs = (host, port).to_socket_addrs()?.collect::<Vec<_>>();
+        if addrs.is_empty() {
+            return Err(io::Error::other("unable to resolve dns address"));
+        }
+        Ok(addrs)
+    }
+}
+
+/// Resolves the IP address literals and the hosts mapped with [`StaticResolver::with_host`] without
+/// ever querying the DNS, for the co-located setups where the addresses are fixed. Any other host fails
+/// to resolve. Endpoints can also bypass the resolver with
+/// [`ConnectionInfo::from_socket_addr`](crate::endpoint::ConnectionInfo::from_socket_addr).
+///
+/// # Examples
+///
+/// ```
+/// use std::net::SocketAddr;
+/// use boomnet::dns::{DnsResolver, StaticResolver};
+///
+/// let mut resolver = StaticResolver::new().with_host("fix.venue.internal", ["10.0.0.7".parse().unwrap()]);
+/// let addr: SocketAddr = "10.0.0.7:9443".parse().unwrap();
+/// assert_eq!(vec![addr], resolver.resolve("fix.venue.internal", 9443).unwrap());
+/// let addr: SocketAddr = "10.0.0.8:443".parse().unwrap();
+/// assert_eq!(vec![addr], resolver.resolve("10.0.0.8", 443).unwrap());
+/// assert!(resolver.resolve("stream.binance.com", 443).is_err());
+/// ```
+#[derive(Debug, Default, Clone)]
+pub struct StaticResolver {
+    hosts: HashMap<String, Vec<IpAddr>>,
+}
+
+impl StaticResolver {
+    pub fn new() -> StaticResolver {
+        Self::default()
+    }
+
+    /// Map the `host` to the `addrs`, which are combined with the port of the endpoint.
+    pub fn with_host(mut self, host: impl Into<String>, addrs: impl IntoIterator<Item = IpAddr>) -> StaticResolver {
+        self.hosts.insert(host.into(), addrs.into_iter().collect());
+        self
+    }
+}
+
+impl DnsResolver for StaticResolver {
+    fn resolve(&mut self, host: &str, port: u16) -> io::Result<Vec<SocketAddr>> {
+        if let Ok(ip) = host.parse::<IpAddr>() {
+            return Ok(vec![SocketAddr::new(ip, port)]);
+        }
+        match self.hosts.get(host) {
+            Some(addrs) if !addrs.is_empty() => Ok(addrs.iter().map(|ip| SocketAddr::new(*ip, port)).collect()),
+            _ => Err(io::Error::new(ErrorKind::NotFound, format!("host not statically mapped: {host}"))),
+        }
+    }
+}
+
+/// Caches addresses resolved by the inner resolver for the configured TTL. Failed queries are
+/// cached for the (typically shorter) negative TTL. Each time the cached entry is used the
+/// addresses are rotated, so that successive reconnects spread across all resolved addresses.
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::time::Duration;
+/// use boomnet::dns::{BlockingDnsResolver, CachingDnsResolver};
+///
+/// let resolver = CachingDnsResolver::new(BlockingDnsResolver, Duration::from_secs(60))
+///     .with_negative_ttl(Duration::from_secs(1));
+/// ```
+pub struct CachingDnsResolver<R, T = MonotonicClockSource> {
+    inner: R,
+    ttl_ns: u64,
+    negative_ttl_ns: u64,
+    cache: HashMap<(String, u16), CacheEntry>,
+    time_source: T,
+}
+
+enum CacheEntry {
+    Resolved {
+        addrs: Vec<SocketAddr>,
+        next: usize,
+        expiry_time_ns: u64,
+    },
+    Failed {
+        kind: ErrorKind,
+        reason: String,
+        expiry_time_ns: u64,
+    },
+}
+
+impl CacheEntry {
+    const fn expiry_time_ns(&self) -> u64 {
+        match self {
+            CacheEntry::Resolved { expiry_time_ns, .. } => *expiry_time_ns,
+            CacheEntry::Failed { expiry_time_ns, .. } => *expiry_time_ns,
+        }
+    }
+}
+
+impl<R> CachingDnsResolver<R> {
+    /// Wraps the `inner` resolver caching successfully resolved addresses for `ttl`. By
+    /// default failed queries are not cached.
+    pub fn new(inner: R, ttl: Duration) -> CachingDnsResolver<R> {
+        Self {
+            inner,
+            ttl_ns: ttl.as_nanos() as u64,
+            negative_ttl_ns: 0,
+            cache: HashMap::new(),
+            time_source: MonotonicClockSource::new(),
+        }
+    }
+}
+
+impl<R, T> CachingDnsResolver<R, T> {
+    /// Specify [`TimeSource`] used to expire the cached entries.
+    pub fn with_time_source<U: TimeSource>(self, time_source: U) -> CachingDnsResolver<R, U> {
+        CachingDnsResolver {
+            inner: self.inner,
+            ttl_ns: self.ttl_ns,
+            negative_ttl_ns: self.negative_ttl_ns,
+            cache: self.cache,
+            time_source,
+        }
+    }
+
+    /// Specify for how long failed queries should be cached.
+    pub fn with_negative_ttl(self, negative_ttl: Duration) -> CachingDnsResolver<R, T> {
+        Self {
+            negative_ttl_ns: negative_ttl.as_nanos() as u64,
+            ..self
+        }
+    }
+
+    /// Removes all cached entries.
+    pub fn clear(&mut self) {
+        self.cache.clear();
+    }
+}
+
+impl<R: DnsResolver, T: TimeSource> DnsResolver for CachingDnsResolver<R, T> {
+    fn resolve(&mut self, host: &str, port: u16) -> io::Result<Vec<SocketAddr>> {
+        let current_time_ns = self.time_source.current_time_nanos();
+        let key = (host.to_owned(), port);
+
+        let expired = self
+            .cache
+            .get(&key)
+            .map(|entry| current_time_ns > entry.expiry_time_ns())
+            .unwrap_or(true);
+
+        if expired {
+            let entry = match self.inner.resolve(host, port) {
+                Ok(addrs) => CacheEntry::Resolved {
+                    addrs,
+                    next: 0,
+                    expiry_time_ns: current_time_ns.saturating_add(self.ttl_ns),
+                },
+                Err(err) if self.negative_ttl_ns > 0 => CacheEntry::Failed {
+                    kind: err.kind(),
+                    reason: err.to_string(),
+                    expiry_time_ns: current_time_ns.saturating_add(self.negative_ttl_ns),
+                },
+                Err(err) => {
+                    self.cache.remove(&key);
+                    return Err(err);
+                }
+            };
+            self.cache.insert(key.clone(), entry);
+        }
+
+        match self.cache.get_mut(&key).expect("cache entry not found") {
+            CacheEntry::Resolved { addrs, next, .. } => {
+                let mut rotated = addrs.clone();
+                rotated.rotate_left(*next % addrs.len());
+                *next = next.wrapping_add(1);
+                Ok(rotated)
+            }
+            CacheEntry::Failed { kind, reason, .. } => Err(io::Error::new(*kind, reason.clone())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use crate::time::ManualTimeSource;
+
+    use super::*;
+
+    struct CountingResolver {
+        queries: usize,
+        result: io::Result<Vec<SocketAddr>>,
+    }
+
+    impl CountingResolver {
+        fn new(result: io::Result<Vec<SocketAddr>>) -> Self {
+            Self { queries: 0, result }
+        }
+    }
+
+    impl DnsResolver for CountingResolver {
+        fn resolve(&mut self, _host: &str, _port: u16) -> io::Result<Vec<SocketAddr>> {
+            self.queries += 1;
+            match &self.result {
+                Ok(addrs) => Ok(addrs.clone()),
+                Err(err) => Err(io::Error::new(err.kind(), err.to_string())),
+            }
+        }
+    }
+
+    fn addr(last_octet: u8) -> SocketAddr {
+        SocketAddr::new(IpAddr::V4(Ipv4Addr::new(10, 0, 0, last_octet)), 443)
+    }
+
+    #[test]
+    fn should_cache_and_rotate_addresses() {
+        let inner = CountingResolver::new(Ok(vec![addr(1), addr(2), addr(3)]));
+        let mut resolver = CachingDnsResolver::new(inner, Duration::from_secs(60));
+
+        assert_eq!(vec![addr(1), addr(2), addr(3)], resolver.resolve("example.com", 443).unwrap());
+        assert_eq!(vec![addr(2), addr(3), addr(1)], resolver.resolve("example.com", 443).unwrap());
+        assert_eq!(vec![addr(3), addr(1), addr(2)], resolver.resolve("example.com", 443).unwrap());
+        assert_eq!(vec![addr(1), addr(2), addr(3)], resolver.resolve("example.com", 443).unwrap());
+        assert_eq!(1, resolver.inner.queries);
+
+        resolver.resolve("example.com", 80).unwrap();
+        assert_eq!(2, resolver.inner.queries);
+    }
+
+    #[test]
+    fn should_resolve_static_addresses() {
+        let mut resolver = StaticResolver::new().with_host("venue", [addr(1).ip(), addr(2).ip()]);
+        assert_eq!(vec![addr(1), addr(2)], resolver.resolve("venue", 443).unwrap());
+        assert_eq!(vec![addr(3)], resolver.resolve("10.0.0.3", 443).unwrap());
+        assert_eq!(vec![SocketAddr::new("::1".parse().unwrap(), 80)], resolver.resolve("::1", 80).unwrap());
+        let err = resolver.resolve("localhost", 443).unwrap_err();
+        assert_eq!(ErrorKind::NotFound, err.kind());
+    }
+
+    #[test]
+    fn should_apply_address_family_preference() {
+        let v6 = |port| SocketAddr::new("::1".parse().unwrap(), port);
+        let resolved = vec![v6(1), addr(1), v6(2), addr(2)];
+        let apply = |family: AddressFamily| {
+            let mut addrs = resolved.clone();
+            family.apply(&mut addrs);
+            addrs
+        };
+        assert_eq!(resolved, apply(AddressFamily::Any));
+        assert_eq!(vec![addr(1), addr(2)], apply(AddressFamily::V4Only));
+        assert_eq!(vec![v6(1), v6(2)], apply(AddressFamily::V6Only));
+        assert_eq!(vec![addr(1), addr(2), v6(1), v6(2)], apply(AddressFamily::PreferV4));
+        assert_eq!(vec![v6(1), v6(2), addr(1), addr(2)], apply(AddressFamily::PreferV6));
+    }
+
+    #[test]
+    fn should_query_again_once_entry_expired() {
+        let inner = CountingResolver::new(Ok(vec![addr(1)]));
+        let time_source = ManualTimeSource::new(0);
+        let mut resolver =
+            CachingDnsResolver::new(inner, Duration::from_secs(60)).with_time_source(time_source.clone());
+
+        resolver.resolve("example.com", 443).unwrap();
+        time_source.advance(Duration::from_secs(60));
+        resolver.resolve("example.com", 443).unwrap();
+        assert_eq!(1, resolver.inner.queries);
+
+        time_source.advance(Duration::from_nanos(1));
+        resolver.resolve("example.com", 443).unwrap();
+        assert_eq!(2, resolver.inner.queries);
+    }
+
+    #[test]
+    fn should_cache_failed_queries_if_negative_ttl_specified() {
+        let inner = CountingResolver::new(Err(io::Error::new(ErrorKind::NotFound, "no such host")));
+        let mut resolver = CachingDnsResolver::new(inner, Duration::from_secs(60));
+
+        resolver.resolve("example.com", 443).unwrap_err();
+        resolver.resolve("example.com", 443).unwrap_err();
+        assert_eq!(2, resolver.inner.queries);
+
+        let mut resolver = resolver.with_negative_ttl(Duration::from_secs(60));
+        let err = resolver.resolve("example.com", 443).unwrap_err();
+        assert_eq!(ErrorKind::NotFound, err.kind());
+        assert_eq!("no such host", err.to_string());
+        resolver.resolve("example.com", 443).unwrap_err();
+        assert_eq!(3, resolver.inner.queries);
+    }
+}
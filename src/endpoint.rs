@@ -3,12 +3,97 @@
 use std::fmt::{Display, Formatter};
 use std::io;
 use std::net::SocketAddr;
+use std::ops::Range;
+use std::time::Duration;
 
-use url::{ParseError, Url};
+use url::{Host, ParseError, Url};
 
+use crate::dns::AddressFamily;
+use crate::service::DisconnectReason;
+use crate::stream::record::SessionRecorder;
+use crate::stream::SocketOptions;
+use crate::timer::TimerId;
+
+#[derive(Debug, Clone)]
 pub struct ConnectionInfo {
     pub host: String,
     pub port: u16,
+    /// Options applied to the socket every time the connection is (re)created by the `IOService`.
+    pub socket_options: SocketOptions,
+    /// Address family preference applied to the resolved addresses by the `IOService`.
+    pub address_family: AddressFamily,
+    /// Local address the socket is bound to before connecting, see [`ConnectionInfo::with_bind_addr`].
+    pub bind_addr: Option<SocketAddr>,
+    /// Local ports tried in turn when binding the socket, see [`ConnectionInfo::with_local_port_range`].
+    pub local_port_range: Option<Range<u16>>,
+    /// Address the `IOService` connects to without resolving the `host`, see [`ConnectionInfo::from_socket_addr`].
+    pub addr: Option<SocketAddr>,
+}
+
+impl ConnectionInfo {
+    /// Creates connection info for the `host` and `port` with default [`SocketOptions`].
+    pub fn new(host: &str, port: u16) -> ConnectionInfo {
+        Self {
+            host: host.to_owned(),
+            port,
+            socket_options: SocketOptions::default(),
+            address_family: AddressFamily::default(),
+            bind_addr: None,
+            local_port_range: None,
+            addr: None,
+        }
+    }
+
+    /// Creates connection info for the static `addr` (such as the fixed IP and port of the co-located
+    /// venue), the `IOService` connects to it directly without querying the `DnsResolver`.
+    pub fn from_socket_addr(addr: SocketAddr) -> ConnectionInfo {
+        Self {
+            addr: Some(addr),
+            ..Self::new(&addr.ip().to_string(), addr.port())
+        }
+    }
+
+    /// Specify [`SocketOptions`] for this connection.
+    pub fn with_socket_options(self, socket_options: SocketOptions) -> ConnectionInfo {
+        Self { socket_options, ..self }
+    }
+
+    /// Specify [`AddressFamily`] preference, for example to avoid the IPv6 addresses that cannot
+    /// be routed on the dual-stack hosts.
+    pub fn with_address_family(self, address_family: AddressFamily) -> ConnectionInfo {
+        Self { address_family, ..self }
+    }
+
+    /// Bind the socket to the local `bind_addr` before connecting, useful when only the source IP is
+    /// known (such as in containers) and the network interface cannot be looked up by name.
+    pub fn with_bind_addr(self, bind_addr: SocketAddr) -> ConnectionInfo {
+        Self {
+            bind_addr: Some(bind_addr),
+            ..self
+        }
+    }
+
+    /// Bind the socket to the first available local port in the `range`, for example to match the
+    /// firewall rules. Combined with the IP of the [`ConnectionInfo::with_bind_addr`] (if specified),
+    /// the port of which is then ignored.
+    pub fn with_local_port_range(self, range: Range<u16>) -> ConnectionInfo {
+        Self {
+            local_port_range: Some(range),
+            ..self
+        }
+    }
+
+    /// Creates one copy of this connection info per CPU in `cpus`, each with `SO_REUSEPORT` enabled
+    /// and `SO_INCOMING_CPU` set to the respective CPU. Useful when sharding single logical feed across
+    /// multiple connections, see [`register_sharded`](crate::service::register_sharded).
+    pub fn shards(&self, cpus: impl IntoIterator<Item = usize>) -> Vec<ConnectionInfo> {
+        cpus.into_iter()
+            .map(|cpu| {
+                let socket_options = self.socket_options.with_reuse_port(true).with_incoming_cpu(cpu);
+                self.clone().with_socket_options(socket_options)
+            })
+            .collect()
+    }
 }
 
 impl Display for ConnectionInfo {
@@ -17,19 +102,27 @@ impl Display for ConnectionInfo {
     }
 }
 
+impl From<SocketAddr> for ConnectionInfo {
+    fn from(addr: SocketAddr) -> Self {
+        ConnectionInfo::from_socket_addr(addr)
+    }
+}
+
 impl TryFrom<Url> for ConnectionInfo {
     type Error = io::Error;
 
     fn try_from(url: Url) -> Result<Self, Self::Error> {
-        Ok(ConnectionInfo {
-            host: url
-                .host_str()
-                .ok_or_else(|| io::Error::other("host not present"))?
-                .to_owned(),
-            port: url
-                .port_or_known_default()
+        let host = match url.host() {
+            // IPv6 literals without the brackets so that they can be resolved
+            Some(Host::Ipv6(addr)) => addr.to_string(),
+            Some(host) => host.to_string(),
+            None => return Err(io::Error::other("host not present")),
+        };
+        Ok(ConnectionInfo::new(
+            &host,
+            url.port_or_known_default()
                 .ok_or_else(|| io::Error::other("port not present"))?,
-        })
+        ))
     }
 }
 
@@ -44,6 +137,58 @@ impl TryFrom<Result<Url, ParseError>> for ConnectionInfo {
     }
 }
 
+/// Redirect of the connection to another location by the peer, such as the websocket upgrade
+/// request answered with `302 Found` by the gateway under maintenance. Returned as the source of
+/// the error that caused the disconnect, see [`Endpoint::on_redirect`].
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Redirect {
+    /// Status code of the response, such as `301`, `302` or `307`.
+    pub status: u16,
+    /// Target of the redirect as received in the `Location` header, possibly relative.
+    pub location: String,
+}
+
+impl Redirect {
+    /// Finds the redirect in the chain of errors that starts with `err`.
+    pub fn find(err: &io::Error) -> Option<&Redirect> {
+        let mut source: Option<&(dyn std::error::Error + 'static)> = err.get_ref().map(|err| err as _);
+        while let Some(err) = source {
+            if let Some(redirect) = err.downcast_ref::<Redirect>() {
+                return Some(redirect);
+            }
+            source = err.source();
+        }
+        None
+    }
+
+    /// Resolves the `location` against the `url` the redirect has been received for, mapping the
+    /// `http` and `https` schemes to `ws` and `wss` respectively.
+    pub fn resolve(&self, url: &str) -> io::Result<String> {
+        let mut target = Url::parse(url)
+            .and_then(|url| url.join(&self.location))
+            .map_err(io::Error::other)?;
+        let scheme = match target.scheme() {
+            "http" => Some("ws"),
+            "https" => Some("wss"),
+            _ => None,
+        };
+        if let Some(scheme) = scheme {
+            target
+                .set_scheme(scheme)
+                .map_err(|_| io::Error::other("unable to change url scheme"))?;
+        }
+        Ok(target.into())
+    }
+}
+
+impl Display for Redirect {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "redirected with status {} to {}", self.status, self.location)
+    }
+}
+
+impl std::error::Error for Redirect {}
+
 /// Entry point for the application logic. Endpoints are registered and Managed by 'IOService'.
 pub trait Endpoint {
     /// Defines protocol and stream this endpoint operates on.
@@ -58,18 +203,72 @@ pub trait Endpoint {
     /// Called by the `IOService` on each duty cycle.
     fn poll(&mut self, target: &mut Self::Target) -> io::Result<()>;
 
+    /// Upon disconnection `IOService` will query the endpoint for the token (such as the last
+    /// processed sequence number) that can be used to resume the session. If provided, the token
+    /// will be passed to [`Endpoint::create_target_with_resume`] when recreating the connection,
+    /// otherwise the token the current connection was created with (if any) is retained.
+    fn resume_token(&self, _target: &Self::Target) -> Option<u64> {
+        None
+    }
+
+    /// Used by the `IOService` to create connection upon disconnect if the endpoint has provided
+    /// the token to resume the previous session.
+    fn create_target_with_resume(&mut self, addr: SocketAddr, _resume_token: u64) -> io::Result<Self::Target> {
+        self.create_target(addr)
+    }
+
     /// Upon disconnection `IOService` will query the endpoint if the connection can be
-    /// recreated. If not, it will cause program to panic.
+    /// recreated. If not, the endpoint is dropped and reported as [`crate::service::EndpointStatus::Failed`].
     fn can_recreate(&mut self) -> bool {
         true
     }
 
+    /// Same as [`Endpoint::can_recreate`] but with the [`DisconnectReason`], so that the decision
+    /// can depend on what caused the disconnect. Delegates to [`Endpoint::can_recreate`] by default.
+    fn can_recreate_with_reason(&mut self, _reason: &DisconnectReason) -> bool {
+        self.can_recreate()
+    }
+
+    /// Called by the `IOService` when the connection has been redirected by the peer (see [`Redirect`]).
+    /// Returning `true` means the endpoint has updated its [`ConnectionInfo`] (and anything derived
+    /// from it, such as the url) to the redirect target and the connection should be recreated,
+    /// otherwise the disconnect is handled as any other error. Redirects are not followed by default.
+    fn on_redirect(&mut self, _redirect: &Redirect) -> bool {
+        false
+    }
+
+    /// Called by the `IOService` with recording enabled (see `IOService::with_recording`) before each
+    /// connection is created. The endpoint that supports recording starts the new session with
+    /// [`SessionRecorder::next_session`] and wraps the stream it is about to create with the returned
+    /// recorder (see [`RecordedStream`](crate::stream::record::RecordedStream)). Not recorded by default.
+    fn on_session_recording(&mut self, _recorder: &mut SessionRecorder) {}
+
     /// When `auto_disconnect` is used the service will check with the endpoint before
     /// disconnecting. If `false` is returned the service will update the endpoint next
     /// disconnect time as per the `auto_disconnect` configuration.
     fn can_auto_disconnect(&mut self) -> bool {
         true
     }
+
+    /// TTL of the connection overriding the `auto_disconnect` configured with the `IOService`, so
+    /// that each venue can be reconnected at its own interval. Consulted every time the connection
+    /// is created, `None` (default) applies the service wide setting.
+    fn ttl(&self) -> Option<Duration> {
+        None
+    }
+
+    /// Called by the `IOService` when the timer scheduled for this endpoint (see
+    /// `IOService::schedule_timer`) has expired. Returning an error is treated the same as
+    /// an error returned from [`Endpoint::poll`].
+    fn on_timer(&mut self, _target: &mut Self::Target, _timer_id: TimerId) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Called by the `IOService` during the shutdown (see `IOService::shutdown`), before the pending
+    /// writes are flushed and the connection is dropped, for example to send the logout message.
+    fn on_shutdown(&mut self, _target: &mut Self::Target) -> io::Result<()> {
+        Ok(())
+    }
 }
 
 /// Marker trait to be applied on user defined `struct` that is registered with 'IOService'
@@ -92,57 +291,154 @@ pub trait EndpointWithContext<C> {
     /// Called by the `IOService` on each duty cycle passing user provided `Context`.
     fn poll(&mut self, target: &mut Self::Target, context: &mut C) -> io::Result<()>;
 
+    /// Upon disconnection `IOService` will query the endpoint for the token (such as the last
+    /// processed sequence number) that can be used to resume the session. If provided, the token
+    /// will be passed to [`EndpointWithContext::create_target_with_resume`] when recreating the connection,
+    /// otherwise the token the current connection was created with (if any) is retained.
+    fn resume_token(&self, _target: &Self::Target, _context: &mut C) -> Option<u64> {
+        None
+    }
+
+    /// Used by the `IOService` to create connection upon disconnect if the endpoint has provided
+    /// the token to resume the previous session, passing user provided `Context`.
+    fn create_target_with_resume(
+        &mut self,
+        addr: SocketAddr,
+        _resume_token: u64,
+        context: &mut C,
+    ) -> io::Result<Self::Target> {
+        self.create_target(addr, context)
+    }
+
     /// Upon disconnection `IOService` will query the endpoint if the connection can be
-    /// recreated. If not, it will cause program to panic.
+    /// recreated. If not, the endpoint is dropped and reported as [`crate::service::EndpointStatus::Failed`].
     fn can_recreate(&mut self, _context: &mut C) -> bool {
         true
     }
 
+    /// Same as [`EndpointWithContext::can_recreate`] but with the [`DisconnectReason`], so that the
+    /// decision can depend on what caused the disconnect. Delegates to [`EndpointWithContext::can_recreate`]
+    /// by default.
+    fn can_recreate_with_reason(&mut self, _reason: &DisconnectReason, context: &mut C) -> bool {
+        self.can_recreate(context)
+    }
+
+    /// Same as [`Endpoint::on_redirect`] but with access to the context.
+    fn on_redirect(&mut self, _redirect: &Redirect, _context: &mut C) -> bool {
+        false
+    }
+
+    /// Same as [`Endpoint::on_session_recording`] but with access to the context.
+    fn on_session_recording(&mut self, _recorder: &mut SessionRecorder, _context: &mut C) {}
+
     /// When `auto_disconnect` is used the service will check with the endpoint before
     /// disconnecting. If `false` is returned the service will update the endpoint next
     /// disconnect time as per the `auto_disconnect` configuration.
     fn can_auto_disconnect(&mut self, _context: &mut C) -> bool {
         true
     }
+
+    /// Same as [`Endpoint::ttl`].
+    fn ttl(&self) -> Option<Duration> {
+        None
+    }
+
+    /// Called by the `IOService` when the timer scheduled for this endpoint (see
+    /// `IOService::schedule_timer`) has expired, passing user provided `Context`. Returning an
+    /// error is treated the same as an error returned from [`EndpointWithContext::poll`].
+    fn on_timer(&mut self, _target: &mut Self::Target, _timer_id: TimerId, _context: &mut C) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Same as [`Endpoint::on_shutdown`] but with access to the context.
+    fn on_shutdown(&mut self, _target: &mut Self::Target, _context: &mut C) -> io::Result<()> {
+        Ok(())
+    }
 }
 
-#[cfg(all(feature = "ws", any(feature = "tls-webpki", feature = "tls-native")))]
+#[cfg(feature = "ws")]
 pub mod ws {
     use std::io;
     use std::io::{Read, Write};
     use std::net::SocketAddr;
+    use std::time::Duration;
 
     use url::Url;
 
-    use crate::endpoint::{ConnectionInfo, Endpoint, EndpointWithContext};
+    use crate::endpoint::{ConnectionInfo, Endpoint, EndpointWithContext, Redirect};
+    use crate::service::DisconnectReason;
+    use crate::stream::record::SessionRecorder;
+    #[cfg(any(feature = "tls-webpki", feature = "tls-native"))]
     use crate::stream::tls::TlsStream;
+    use crate::timer::TimerId;
     use crate::ws::Websocket;
 
+    /// Status code of the close frame sent when the `IOService` shuts down.
+    const GOING_AWAY: u16 = 1001;
+
+    #[cfg(any(feature = "tls-webpki", feature = "tls-native"))]
     pub type TlsWebsocket<S> = Websocket<TlsStream<S>>;
 
-    pub trait TlsWebsocketEndpoint {
+    /// Websocket endpoint over any `Stream`, such as plain TCP, proxied, kTLS or replay stream. Unlike
+    /// [`TlsWebsocketEndpoint`] the endpoint is responsible for wrapping the stream as required.
+    pub trait WebsocketEndpoint {
         type Stream: Read + Write;
 
         fn url(&self) -> &str;
 
-        fn create_websocket(&mut self, addr: SocketAddr) -> io::Result<Websocket<TlsStream<Self::Stream>>>;
+        fn create_websocket(&mut self, addr: SocketAddr) -> io::Result<Websocket<Self::Stream>>;
 
-        fn poll(&mut self, ws: &mut Websocket<TlsStream<Self::Stream>>) -> io::Result<()>;
+        fn poll(&mut self, ws: &mut Websocket<Self::Stream>) -> io::Result<()>;
+
+        fn resume_token(&self, _ws: &Websocket<Self::Stream>) -> Option<u64> {
+            None
+        }
+
+        fn create_websocket_with_resume(
+            &mut self,
+            addr: SocketAddr,
+            _resume_token: u64,
+        ) -> io::Result<Websocket<Self::Stream>> {
+            self.create_websocket(addr)
+        }
 
         fn can_recreate(&mut self) -> bool {
             true
         }
 
+        fn can_recreate_with_reason(&mut self, _reason: &DisconnectReason) -> bool {
+            self.can_recreate()
+        }
+
+        fn on_redirect(&mut self, _redirect: &Redirect) -> bool {
+            false
+        }
+
+        fn on_session_recording(&mut self, _recorder: &mut SessionRecorder) {}
+
         fn can_auto_disconnect(&mut self) -> bool {
             true
         }
+
+        fn ttl(&self) -> Option<Duration> {
+            None
+        }
+
+        fn on_timer(&mut self, _ws: &mut Websocket<Self::Stream>, _timer_id: TimerId) -> io::Result<()> {
+            Ok(())
+        }
+
+        /// Sends the close frame with the `1001` (going away) status code by default.
+        fn on_shutdown(&mut self, ws: &mut Websocket<Self::Stream>) -> io::Result<()> {
+            Ok(ws.send_close(GOING_AWAY, None)?)
+        }
     }
 
     impl<T> Endpoint for T
     where
-        T: TlsWebsocketEndpoint,
+        T: WebsocketEndpoint,
     {
-        type Target = Websocket<TlsStream<T::Stream>>;
+        type Target = Websocket<T::Stream>;
 
         #[inline]
         fn connection_info(&self) -> io::Result<ConnectionInfo> {
@@ -159,41 +455,116 @@ pub mod ws {
             self.poll(target)
         }
 
+        #[inline]
+        fn resume_token(&self, target: &Self::Target) -> Option<u64> {
+            self.resume_token(target)
+        }
+
+        #[inline]
+        fn create_target_with_resume(&mut self, addr: SocketAddr, resume_token: u64) -> io::Result<Self::Target> {
+            self.create_websocket_with_resume(addr, resume_token)
+        }
+
         #[inline]
         fn can_recreate(&mut self) -> bool {
             self.can_recreate()
         }
 
+        #[inline]
+        fn can_recreate_with_reason(&mut self, reason: &DisconnectReason) -> bool {
+            self.can_recreate_with_reason(reason)
+        }
+
+        #[inline]
+        fn on_redirect(&mut self, redirect: &Redirect) -> bool {
+            self.on_redirect(redirect)
+        }
+
+        #[inline]
+        fn on_session_recording(&mut self, recorder: &mut SessionRecorder) {
+            self.on_session_recording(recorder)
+        }
+
         #[inline]
         fn can_auto_disconnect(&mut self) -> bool {
             self.can_auto_disconnect()
         }
+
+        #[inline]
+        fn ttl(&self) -> Option<Duration> {
+            self.ttl()
+        }
+
+        #[inline]
+        fn on_timer(&mut self, target: &mut Self::Target, timer_id: TimerId) -> io::Result<()> {
+            self.on_timer(target, timer_id)
+        }
+
+        #[inline]
+        fn on_shutdown(&mut self, target: &mut Self::Target) -> io::Result<()> {
+            self.on_shutdown(target)
+        }
     }
 
-    pub trait TlsWebsocketEndpointWithContext<C> {
+    /// Same as [`WebsocketEndpoint`] but with access to the context.
+    pub trait WebsocketEndpointWithContext<C> {
         type Stream: Read + Write;
 
         fn url(&self) -> &str;
 
-        fn create_websocket(&mut self, addr: SocketAddr, ctx: &mut C)
-            -> io::Result<Websocket<TlsStream<Self::Stream>>>;
+        fn create_websocket(&mut self, addr: SocketAddr, ctx: &mut C) -> io::Result<Websocket<Self::Stream>>;
 
-        fn poll(&mut self, ws: &mut Websocket<TlsStream<Self::Stream>>, ctx: &mut C) -> io::Result<()>;
+        fn poll(&mut self, ws: &mut Websocket<Self::Stream>, ctx: &mut C) -> io::Result<()>;
+
+        fn resume_token(&self, _ws: &Websocket<Self::Stream>, _ctx: &mut C) -> Option<u64> {
+            None
+        }
+
+        fn create_websocket_with_resume(
+            &mut self,
+            addr: SocketAddr,
+            _resume_token: u64,
+            ctx: &mut C,
+        ) -> io::Result<Websocket<Self::Stream>> {
+            self.create_websocket(addr, ctx)
+        }
 
         fn can_recreate(&mut self, _ctx: &mut C) -> bool {
             true
         }
 
+        fn can_recreate_with_reason(&mut self, _reason: &DisconnectReason, ctx: &mut C) -> bool {
+            self.can_recreate(ctx)
+        }
+
+        fn on_redirect(&mut self, _redirect: &Redirect, _ctx: &mut C) -> bool {
+            false
+        }
+
+        fn on_session_recording(&mut self, _recorder: &mut SessionRecorder, _ctx: &mut C) {}
+
         fn can_auto_disconnect(&mut self, _ctx: &mut C) -> bool {
             true
         }
+
+        fn ttl(&self) -> Option<Duration> {
+            None
+        }
+
+        fn on_timer(&mut self, _ws: &mut Websocket<Self::Stream>, _timer_id: TimerId, _ctx: &mut C) -> io::Result<()> {
+            Ok(())
+        }
+
+        fn on_shutdown(&mut self, ws: &mut Websocket<Self::Stream>, _ctx: &mut C) -> io::Result<()> {
+            Ok(ws.send_close(GOING_AWAY, None)?)
+        }
     }
 
     impl<T, C> EndpointWithContext<C> for T
     where
-        T: TlsWebsocketEndpointWithContext<C>,
+        T: WebsocketEndpointWithContext<C>,
     {
-        type Target = Websocket<TlsStream<T::Stream>>;
+        type Target = Websocket<T::Stream>;
 
         #[inline]
         fn connection_info(&self) -> io::Result<ConnectionInfo> {
@@ -210,14 +581,712 @@ pub mod ws {
             self.poll(target, context)
         }
 
+        #[inline]
+        fn resume_token(&self, target: &Self::Target, context: &mut C) -> Option<u64> {
+            self.resume_token(target, context)
+        }
+
+        #[inline]
+        fn create_target_with_resume(
+            &mut self,
+            addr: SocketAddr,
+            resume_token: u64,
+            context: &mut C,
+        ) -> io::Result<Self::Target> {
+            self.create_websocket_with_resume(addr, resume_token, context)
+        }
+
         #[inline]
         fn can_recreate(&mut self, context: &mut C) -> bool {
             self.can_recreate(context)
         }
 
+        #[inline]
+        fn can_recreate_with_reason(&mut self, reason: &DisconnectReason, context: &mut C) -> bool {
+            self.can_recreate_with_reason(reason, context)
+        }
+
+        #[inline]
+        fn on_redirect(&mut self, redirect: &Redirect, context: &mut C) -> bool {
+            self.on_redirect(redirect, context)
+        }
+
+        #[inline]
+        fn on_session_recording(&mut self, recorder: &mut SessionRecorder, context: &mut C) {
+            self.on_session_recording(recorder, context)
+        }
+
         #[inline]
         fn can_auto_disconnect(&mut self, context: &mut C) -> bool {
             self.can_auto_disconnect(context)
         }
+
+        #[inline]
+        fn ttl(&self) -> Option<Duration> {
+            self.ttl()
+        }
+
+        #[inline]
+        fn on_timer(&mut self, target: &mut Self::Target, timer_id: TimerId, context: &mut C) -> io::Result<()> {
+            self.on_timer(target, timer_id, context)
+        }
+
+        #[inline]
+        fn on_shutdown(&mut self, target: &mut Self::Target, context: &mut C) -> io::Result<()> {
+            self.on_shutdown(target, context)
+        }
+    }
+
+    /// Websocket endpoint over the TLS `Stream`.
+    #[cfg(any(feature = "tls-webpki", feature = "tls-native"))]
+    pub trait TlsWebsocketEndpoint {
+        type Stream: Read + Write;
+
+        fn url(&self) -> &str;
+
+        fn create_websocket(&mut self, addr: SocketAddr) -> io::Result<Websocket<TlsStream<Self::Stream>>>;
+
+        fn poll(&mut self, ws: &mut Websocket<TlsStream<Self::Stream>>) -> io::Result<()>;
+
+        fn resume_token(&self, _ws: &Websocket<TlsStream<Self::Stream>>) -> Option<u64> {
+            None
+        }
+
+        fn create_websocket_with_resume(
+            &mut self,
+            addr: SocketAddr,
+            _resume_token: u64,
+        ) -> io::Result<Websocket<TlsStream<Self::Stream>>> {
+            self.create_websocket(addr)
+        }
+
+        fn can_recreate(&mut self) -> bool {
+            true
+        }
+
+        fn can_recreate_with_reason(&mut self, _reason: &DisconnectReason) -> bool {
+            self.can_recreate()
+        }
+
+        fn on_redirect(&mut self, _redirect: &Redirect) -> bool {
+            false
+        }
+
+        fn on_session_recording(&mut self, _recorder: &mut SessionRecorder) {}
+
+        fn can_auto_disconnect(&mut self) -> bool {
+            true
+        }
+
+        fn ttl(&self) -> Option<Duration> {
+            None
+        }
+
+        fn on_timer(&mut self, _ws: &mut Websocket<TlsStream<Self::Stream>>, _timer_id: TimerId) -> io::Result<()> {
+            Ok(())
+        }
+
+        fn on_shutdown(&mut self, ws: &mut Websocket<TlsStream<Self::Stream>>) -> io::Result<()> {
+            Ok(ws.send_close(GOING_AWAY, None)?)
+        }
+    }
+
+    #[cfg(any(feature = "tls-webpki", feature = "tls-native"))]
+    impl<T> WebsocketEndpoint for T
+    where
+        T: TlsWebsocketEndpoint,
+    {
+        type Stream = TlsStream<T::Stream>;
+
+        #[inline]
+        fn url(&self) -> &str {
+            self.url()
+        }
+
+        #[inline]
+        fn create_websocket(&mut self, addr: SocketAddr) -> io::Result<Websocket<Self::Stream>> {
+            self.create_websocket(addr)
+        }
+
+        #[inline]
+        fn poll(&mut self, ws: &mut Websocket<Self::Stream>) -> io::Result<()> {
+            self.poll(ws)
+        }
+
+        #[inline]
+        fn resume_token(&self, ws: &Websocket<Self::Stream>) -> Option<u64> {
+            self.resume_token(ws)
+        }
+
+        #[inline]
+        fn create_websocket_with_resume(
+            &mut self,
+            addr: SocketAddr,
+            resume_token: u64,
+        ) -> io::Result<Websocket<Self::Stream>> {
+            self.create_websocket_with_resume(addr, resume_token)
+        }
+
+        #[inline]
+        fn can_recreate(&mut self) -> bool {
+            self.can_recreate()
+        }
+
+        #[inline]
+        fn can_recreate_with_reason(&mut self, reason: &DisconnectReason) -> bool {
+            self.can_recreate_with_reason(reason)
+        }
+
+        #[inline]
+        fn on_redirect(&mut self, redirect: &Redirect) -> bool {
+            self.on_redirect(redirect)
+        }
+
+        #[inline]
+        fn on_session_recording(&mut self, recorder: &mut SessionRecorder) {
+            self.on_session_recording(recorder)
+        }
+
+        #[inline]
+        fn can_auto_disconnect(&mut self) -> bool {
+            self.can_auto_disconnect()
+        }
+
+        #[inline]
+        fn ttl(&self) -> Option<Duration> {
+            self.ttl()
+        }
+
+        #[inline]
+        fn on_timer(&mut self, ws: &mut Websocket<Self::Stream>, timer_id: TimerId) -> io::Result<()> {
+            self.on_timer(ws, timer_id)
+        }
+
+        #[inline]
+        fn on_shutdown(&mut self, ws: &mut Websocket<Self::Stream>) -> io::Result<()> {
+            self.on_shutdown(ws)
+        }
+    }
+
+    #[cfg(any(feature = "tls-webpki", feature = "tls-native"))]
+    pub trait TlsWebsocketEndpointWithContext<C> {
+        type Stream: Read + Write;
+
+        fn url(&self) -> &str;
+
+        fn create_websocket(&mut self, addr: SocketAddr, ctx: &mut C)
+            -> io::Result<Websocket<TlsStream<Self::Stream>>>;
+
+        fn poll(&mut self, ws: &mut Websocket<TlsStream<Self::Stream>>, ctx: &mut C) -> io::Result<()>;
+
+        fn resume_token(&self, _ws: &Websocket<TlsStream<Self::Stream>>, _ctx: &mut C) -> Option<u64> {
+            None
+        }
+
+        fn create_websocket_with_resume(
+            &mut self,
+            addr: SocketAddr,
+            _resume_token: u64,
+            ctx: &mut C,
+        ) -> io::Result<Websocket<TlsStream<Self::Stream>>> {
+            self.create_websocket(addr, ctx)
+        }
+
+        fn can_recreate(&mut self, _ctx: &mut C) -> bool {
+            true
+        }
+
+        fn can_recreate_with_reason(&mut self, _reason: &DisconnectReason, ctx: &mut C) -> bool {
+            self.can_recreate(ctx)
+        }
+
+        fn on_redirect(&mut self, _redirect: &Redirect, _ctx: &mut C) -> bool {
+            false
+        }
+
+        fn on_session_recording(&mut self, _recorder: &mut SessionRecorder, _ctx: &mut C) {}
+
+        fn can_auto_disconnect(&mut self, _ctx: &mut C) -> bool {
+            true
+        }
+
+        fn ttl(&self) -> Option<Duration> {
+            None
+        }
+
+        fn on_timer(
+            &mut self,
+            _ws: &mut Websocket<TlsStream<Self::Stream>>,
+            _timer_id: TimerId,
+            _ctx: &mut C,
+        ) -> io::Result<()> {
+            Ok(())
+        }
+
+        fn on_shutdown(&mut self, ws: &mut Websocket<TlsStream<Self::Stream>>, _ctx: &mut C) -> io::Result<()> {
+            Ok(ws.send_close(GOING_AWAY, None)?)
+        }
+    }
+
+    #[cfg(any(feature = "tls-webpki", feature = "tls-native"))]
+    impl<T, C> WebsocketEndpointWithContext<C> for T
+    where
+        T: TlsWebsocketEndpointWithContext<C>,
+    {
+        type Stream = TlsStream<T::Stream>;
+
+        #[inline]
+        fn url(&self) -> &str {
+            self.url()
+        }
+
+        #[inline]
+        fn create_websocket(&mut self, addr: SocketAddr, ctx: &mut C) -> io::Result<Websocket<Self::Stream>> {
+            self.create_websocket(addr, ctx)
+        }
+
+        #[inline]
+        fn poll(&mut self, ws: &mut Websocket<Self::Stream>, ctx: &mut C) -> io::Result<()> {
+            self.poll(ws, ctx)
+        }
+
+        #[inline]
+        fn resume_token(&self, ws: &Websocket<Self::Stream>, ctx: &mut C) -> Option<u64> {
+            self.resume_token(ws, ctx)
+        }
+
+        #[inline]
+        fn create_websocket_with_resume(
+            &mut self,
+            addr: SocketAddr,
+            resume_token: u64,
+            ctx: &mut C,
+        ) -> io::Result<Websocket<Self::Stream>> {
+            self.create_websocket_with_resume(addr, resume_token, ctx)
+        }
+
+        #[inline]
+        fn can_recreate(&mut self, ctx: &mut C) -> bool {
+            self.can_recreate(ctx)
+        }
+
+        #[inline]
+        fn can_recreate_with_reason(&mut self, reason: &DisconnectReason, ctx: &mut C) -> bool {
+            self.can_recreate_with_reason(reason, ctx)
+        }
+
+        #[inline]
+        fn on_redirect(&mut self, redirect: &Redirect, ctx: &mut C) -> bool {
+            self.on_redirect(redirect, ctx)
+        }
+
+        #[inline]
+        fn on_session_recording(&mut self, recorder: &mut SessionRecorder, ctx: &mut C) {
+            self.on_session_recording(recorder, ctx)
+        }
+
+        #[inline]
+        fn can_auto_disconnect(&mut self, ctx: &mut C) -> bool {
+            self.can_auto_disconnect(ctx)
+        }
+
+        #[inline]
+        fn ttl(&self) -> Option<Duration> {
+            self.ttl()
+        }
+
+        #[inline]
+        fn on_timer(&mut self, ws: &mut Websocket<Self::Stream>, timer_id: TimerId, ctx: &mut C) -> io::Result<()> {
+            self.on_timer(ws, timer_id, ctx)
+        }
+
+        #[inline]
+        fn on_shutdown(&mut self, ws: &mut Websocket<Self::Stream>, ctx: &mut C) -> io::Result<()> {
+            self.on_shutdown(ws, ctx)
+        }
+    }
+}
+
+/// Endpoints driving the [`HttpClient`](crate::http::HttpClient) request cycle, registered with the
+/// same `IOService` as the websocket endpoints using the [`MixedEndpoint`](http::MixedEndpoint).
+#[cfg(all(feature = "ws", feature = "http-client"))]
+pub mod http {
+    use std::io;
+    use std::io::{ErrorKind, Read, Write};
+    use std::net::SocketAddr;
+    use std::time::Duration;
+
+    #[cfg(feature = "mio")]
+    use mio::{event::Source, Interest, Registry, Token};
+    use url::Url;
+
+    use crate::endpoint::ws::WebsocketEndpoint;
+    use crate::endpoint::{ConnectionInfo, Endpoint, Redirect};
+    use crate::http::HttpClient;
+    use crate::select::Selectable;
+    use crate::service::DisconnectReason;
+    use crate::stream::record::SessionRecorder;
+    use crate::stream::{SocketOptions, SocketQueues};
+    use crate::timer::TimerId;
+    use crate::ws::Websocket;
+
+    /// Endpoint performing the REST calls with the [`HttpClient`], such as refreshing the listen key
+    /// of the user data stream or fetching the order book snapshot. Typically sends the requests from
+    /// [`HttpEndpoint::on_timer`] and handles the responses in [`HttpEndpoint::poll`].
+    pub trait HttpEndpoint {
+        type Stream: Read + Write;
+
+        /// Base url of the REST API, such as `https://api.binance.com`.
+        fn url(&self) -> &str;
+
+        fn create_client(&mut self, addr: SocketAddr) -> io::Result<HttpClient<Self::Stream>>;
+
+        fn poll(&mut self, client: &mut HttpClient<Self::Stream>) -> io::Result<()>;
+
+        fn can_recreate(&mut self) -> bool {
+            true
+        }
+
+        fn can_recreate_with_reason(&mut self, _reason: &DisconnectReason) -> bool {
+            self.can_recreate()
+        }
+
+        fn ttl(&self) -> Option<Duration> {
+            None
+        }
+
+        fn on_timer(&mut self, _client: &mut HttpClient<Self::Stream>, _timer_id: TimerId) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// Either the websocket or the http endpoint, so that both can be registered with the same
+    /// `IOService` (the endpoints of single service share the same target type).
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use std::io;
+    /// use std::net::{SocketAddr, TcpStream};
+    /// use std::time::Duration;
+    /// use idle::IdleStrategy;
+    /// use boomnet::endpoint::http::{HttpEndpoint, MixedEndpoint};
+    /// use boomnet::endpoint::ws::{TlsWebsocket, TlsWebsocketEndpoint};
+    /// use boomnet::http::HttpClient;
+    /// use boomnet::select::mio::MioSelector;
+    /// use boomnet::service::IntoIOService;
+    /// use boomnet::stream::mio::{IntoMioStream, MioStream};
+    /// use boomnet::stream::tls::{IntoTlsStream, TlsStream};
+    /// use boomnet::timer::TimerId;
+    /// use boomnet::ws::IntoTlsWebsocket;
+    ///
+    /// struct Trades;
+    ///
+    /// impl TlsWebsocketEndpoint for Trades {
+    ///     type Stream = MioStream;
+    ///
+    ///     fn url(&self) -> &str {
+    ///         "wss://stream.binance.com:9443/ws/btcusdt@trade"
+    ///     }
+    ///
+    ///     fn create_websocket(&mut self, addr: SocketAddr) -> io::Result<TlsWebsocket<MioStream>> {
+    ///         TcpStream::connect(addr)?.into_mio_stream().into_tls_websocket(self.url())
+    ///     }
+    ///
+    ///     fn poll(&mut self, ws: &mut TlsWebsocket<MioStream>) -> io::Result<()> {
+    ///         while ws.receive_next()?.is_some() {}
+    ///         Ok(())
+    ///     }
+    /// }
+    ///
+    /// struct ServerTime;
+    ///
+    /// impl HttpEndpoint for ServerTime {
+    ///     type Stream = TlsStream<MioStream>;
+    ///
+    ///     fn url(&self) -> &str {
+    ///         "https://api.binance.com"
+    ///     }
+    ///
+    ///     fn create_client(&mut self, addr: SocketAddr) -> io::Result<HttpClient<Self::Stream>> {
+    ///         let stream = TcpStream::connect(addr)?.into_mio_stream().into_tls_stream("api.binance.com");
+    ///         Ok(HttpClient::new(stream, "api.binance.com"))
+    ///     }
+    ///
+    ///     fn poll(&mut self, client: &mut HttpClient<Self::Stream>) -> io::Result<()> {
+    ///         if let Some(response) = client.poll_response()? {
+    ///             println!("{}", String::from_utf8_lossy(response.body));
+    ///         }
+    ///         Ok(())
+    ///     }
+    ///
+    ///     fn on_timer(&mut self, client: &mut HttpClient<Self::Stream>, _timer_id: TimerId) -> io::Result<()> {
+    ///         client.send_request("GET", "/api/v3/time", &[], None)
+    ///     }
+    /// }
+    ///
+    /// let mut io_service = MioSelector::new().unwrap().into_io_service(IdleStrategy::Sleep(Duration::from_millis(1)));
+    /// io_service.register(MixedEndpoint::Websocket(Trades));
+    /// let handle = io_service.register(MixedEndpoint::Http(ServerTime));
+    /// io_service.schedule_periodic_timer(handle, Duration::from_secs(60));
+    /// loop {
+    ///     io_service.poll().unwrap();
+    /// }
+    /// ```
+    pub enum MixedEndpoint<W, H> {
+        Websocket(W),
+        Http(H),
+    }
+
+    /// Target of the [`MixedEndpoint`].
+    #[allow(clippy::large_enum_variant)]
+    pub enum MixedTarget<S> {
+        Websocket(Websocket<S>),
+        Http(HttpClient<S>),
+    }
+
+    #[cold]
+    fn target_mismatch() -> io::Error {
+        io::Error::new(ErrorKind::InvalidInput, "target does not match the endpoint")
+    }
+
+    impl<W, H> Endpoint for MixedEndpoint<W, H>
+    where
+        W: WebsocketEndpoint,
+        H: HttpEndpoint<Stream = W::Stream>,
+    {
+        type Target = MixedTarget<W::Stream>;
+
+        fn connection_info(&self) -> io::Result<ConnectionInfo> {
+            match self {
+                MixedEndpoint::Websocket(endpoint) => Endpoint::connection_info(endpoint),
+                MixedEndpoint::Http(endpoint) => Url::parse(endpoint.url()).try_into(),
+            }
+        }
+
+        fn create_target(&mut self, addr: SocketAddr) -> io::Result<Self::Target> {
+            match self {
+                MixedEndpoint::Websocket(endpoint) => Ok(MixedTarget::Websocket(endpoint.create_websocket(addr)?)),
+                MixedEndpoint::Http(endpoint) => Ok(MixedTarget::Http(endpoint.create_client(addr)?)),
+            }
+        }
+
+        fn poll(&mut self, target: &mut Self::Target) -> io::Result<()> {
+            match (self, target) {
+                (MixedEndpoint::Websocket(endpoint), MixedTarget::Websocket(ws)) => {
+                    WebsocketEndpoint::poll(endpoint, ws)
+                }
+                (MixedEndpoint::Http(endpoint), MixedTarget::Http(client)) => endpoint.poll(client),
+                _ => Err(target_mismatch()),
+            }
+        }
+
+        fn resume_token(&self, target: &Self::Target) -> Option<u64> {
+            match (self, target) {
+                (MixedEndpoint::Websocket(endpoint), MixedTarget::Websocket(ws)) => endpoint.resume_token(ws),
+                _ => None,
+            }
+        }
+
+        fn create_target_with_resume(&mut self, addr: SocketAddr, resume_token: u64) -> io::Result<Self::Target> {
+            match self {
+                MixedEndpoint::Websocket(endpoint) => {
+                    Ok(MixedTarget::Websocket(endpoint.create_websocket_with_resume(addr, resume_token)?))
+                }
+                MixedEndpoint::Http(endpoint) => Ok(MixedTarget::Http(endpoint.create_client(addr)?)),
+            }
+        }
+
+        fn can_recreate(&mut self) -> bool {
+            match self {
+                MixedEndpoint::Websocket(endpoint) => WebsocketEndpoint::can_recreate(endpoint),
+                MixedEndpoint::Http(endpoint) => endpoint.can_recreate(),
+            }
+        }
+
+        fn can_recreate_with_reason(&mut self, reason: &DisconnectReason) -> bool {
+            match self {
+                MixedEndpoint::Websocket(endpoint) => WebsocketEndpoint::can_recreate_with_reason(endpoint, reason),
+                MixedEndpoint::Http(endpoint) => endpoint.can_recreate_with_reason(reason),
+            }
+        }
+
+        fn on_redirect(&mut self, redirect: &Redirect) -> bool {
+            match self {
+                MixedEndpoint::Websocket(endpoint) => WebsocketEndpoint::on_redirect(endpoint, redirect),
+                MixedEndpoint::Http(_) => false,
+            }
+        }
+
+        fn on_session_recording(&mut self, recorder: &mut SessionRecorder) {
+            if let MixedEndpoint::Websocket(endpoint) = self {
+                WebsocketEndpoint::on_session_recording(endpoint, recorder)
+            }
+        }
+
+        fn can_auto_disconnect(&mut self) -> bool {
+            match self {
+                MixedEndpoint::Websocket(endpoint) => WebsocketEndpoint::can_auto_disconnect(endpoint),
+                MixedEndpoint::Http(_) => true,
+            }
+        }
+
+        fn ttl(&self) -> Option<Duration> {
+            match self {
+                MixedEndpoint::Websocket(endpoint) => WebsocketEndpoint::ttl(endpoint),
+                MixedEndpoint::Http(endpoint) => endpoint.ttl(),
+            }
+        }
+
+        fn on_timer(&mut self, target: &mut Self::Target, timer_id: TimerId) -> io::Result<()> {
+            match (self, target) {
+                (MixedEndpoint::Websocket(endpoint), MixedTarget::Websocket(ws)) => {
+                    WebsocketEndpoint::on_timer(endpoint, ws, timer_id)
+                }
+                (MixedEndpoint::Http(endpoint), MixedTarget::Http(client)) => endpoint.on_timer(client, timer_id),
+                _ => Err(target_mismatch()),
+            }
+        }
+
+        fn on_shutdown(&mut self, target: &mut Self::Target) -> io::Result<()> {
+            match (self, target) {
+                (MixedEndpoint::Websocket(endpoint), MixedTarget::Websocket(ws)) => {
+                    WebsocketEndpoint::on_shutdown(endpoint, ws)
+                }
+                _ => Ok(()),
+            }
+        }
+    }
+
+    #[cfg(feature = "mio")]
+    impl<S: Source> Source for MixedTarget<S> {
+        fn register(&mut self, registry: &Registry, token: Token, interests: Interest) -> io::Result<()> {
+            match self {
+                MixedTarget::Websocket(ws) => registry.register(ws, token, interests),
+                MixedTarget::Http(client) => registry.register(client, token, interests),
+            }
+        }
+
+        fn reregister(&mut self, registry: &Registry, token: Token, interests: Interest) -> io::Result<()> {
+            match self {
+                MixedTarget::Websocket(ws) => registry.reregister(ws, token, interests),
+                MixedTarget::Http(client) => registry.reregister(client, token, interests),
+            }
+        }
+
+        fn deregister(&mut self, registry: &Registry) -> io::Result<()> {
+            match self {
+                MixedTarget::Websocket(ws) => registry.deregister(ws),
+                MixedTarget::Http(client) => registry.deregister(client),
+            }
+        }
+    }
+
+    impl<S: Selectable> Selectable for MixedTarget<S> {
+        fn connected(&mut self) -> io::Result<bool> {
+            match self {
+                MixedTarget::Websocket(ws) => ws.connected(),
+                MixedTarget::Http(client) => client.connected(),
+            }
+        }
+
+        fn make_writable(&mut self) {
+            match self {
+                MixedTarget::Websocket(ws) => ws.make_writable(),
+                MixedTarget::Http(client) => client.make_writable(),
+            }
+        }
+
+        fn make_readable(&mut self) {
+            match self {
+                MixedTarget::Websocket(ws) => ws.make_readable(),
+                MixedTarget::Http(client) => client.make_readable(),
+            }
+        }
+
+        fn make_unreadable(&mut self) {
+            match self {
+                MixedTarget::Websocket(ws) => ws.make_unreadable(),
+                MixedTarget::Http(client) => client.make_unreadable(),
+            }
+        }
+
+        fn socket_queues(&self) -> Option<SocketQueues> {
+            match self {
+                MixedTarget::Websocket(ws) => ws.socket_queues(),
+                MixedTarget::Http(client) => client.socket_queues(),
+            }
+        }
+
+        fn rx_timestamp_ns(&self) -> Option<u64> {
+            match self {
+                MixedTarget::Websocket(ws) => ws.rx_timestamp_ns(),
+                MixedTarget::Http(client) => client.rx_timestamp_ns(),
+            }
+        }
+
+        #[cfg(feature = "stats")]
+        fn io_counters(&self) -> Option<crate::stream::IoCounters> {
+            match self {
+                MixedTarget::Websocket(ws) => ws.io_counters(),
+                MixedTarget::Http(client) => client.io_counters(),
+            }
+        }
+
+        fn apply_socket_options(&mut self, options: &SocketOptions) -> io::Result<()> {
+            match self {
+                MixedTarget::Websocket(ws) => ws.apply_socket_options(options),
+                MixedTarget::Http(client) => client.apply_socket_options(options),
+            }
+        }
+
+        fn has_pending_writes(&self) -> bool {
+            match self {
+                MixedTarget::Websocket(ws) => ws.has_pending_writes(),
+                MixedTarget::Http(client) => client.has_pending_writes(),
+            }
+        }
+
+        fn flush_pending_writes(&mut self) -> io::Result<()> {
+            match self {
+                MixedTarget::Websocket(ws) => ws.flush_pending_writes(),
+                MixedTarget::Http(client) => client.flush_pending_writes(),
+            }
+        }
+
+        fn peer_closed(&self) -> bool {
+            match self {
+                MixedTarget::Websocket(ws) => ws.peer_closed(),
+                MixedTarget::Http(client) => client.peer_closed(),
+            }
+        }
+
+        fn write_closed(&self) -> bool {
+            match self {
+                MixedTarget::Websocket(ws) => ws.write_closed(),
+                MixedTarget::Http(client) => client.write_closed(),
+            }
+        }
+
+        fn make_write_closed(&mut self) {
+            match self {
+                MixedTarget::Websocket(ws) => ws.make_write_closed(),
+                MixedTarget::Http(client) => client.make_write_closed(),
+            }
+        }
+
+        fn take_socket_error(&mut self) -> io::Result<Option<io::Error>> {
+            match self {
+                MixedTarget::Websocket(ws) => ws.take_socket_error(),
+                MixedTarget::Http(client) => client.take_socket_error(),
+            }
+        }
+
+        fn connect_error(&self) -> Option<io::Error> {
+            match self {
+                MixedTarget::Websocket(ws) => ws.connect_error(),
+                MixedTarget::Http(client) => client.connect_error(),
+            }
+        }
     }
 }
@@ -0,0 +1,293 @@
+//! Binance [combined streams](https://binance-docs.github.io/apidocs/spot/en/#websocket-market-streams),
+//! where multiple streams are multiplexed over a single connection and each message is wrapped as
+//! `{"stream":"<name>","data":<payload>}`.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use std::io;
+//! use std::time::Duration;
+//! use idle::IdleStrategy;
+//! use boomnet::exchange::binance::{CombinedMessage, CombinedStreamEndpoint, CombinedStreamHandler};
+//! use boomnet::select::mio::MioSelector;
+//! use boomnet::service::IntoIOService;
+//! use boomnet::stream::mio::MioStream;
+//!
+//! struct Printer;
+//!
+//! impl CombinedStreamHandler for Printer {
+//!     fn on_trade(&mut self, message: CombinedMessage<'_>) -> io::Result<()> {
+//!         println!("[{}] {}", message.symbol, String::from_utf8_lossy(message.data));
+//!         Ok(())
+//!     }
+//! }
+//!
+//! let mut io_service = MioSelector::new().unwrap().into_io_service(IdleStrategy::Sleep(Duration::from_millis(1)));
+//! let endpoint = CombinedStreamEndpoint::<_, MioStream>::new(
+//!     "wss://stream.binance.com:9443",
+//!     ["btcusdt@trade", "ethusdt@trade", "btcusdt@bookTicker"],
+//!     Printer,
+//! );
+//! io_service.register(endpoint);
+//! loop {
+//!     io_service.poll().unwrap();
+//! }
+//! ```
+
+use std::io;
+use std::marker::PhantomData;
+use std::net::{SocketAddr, TcpStream};
+
+use crate::endpoint::ws::{TlsWebsocket, TlsWebsocketEndpoint};
+use crate::exchange::FromTcpStream;
+use crate::stream::BindAndConnect;
+use crate::ws::{IntoTlsWebsocket, WebsocketFrame};
+
+/// Type of the stream, derived from the stream name suffix (such as `btcusdt@trade`).
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum StreamKind {
+    Trade,
+    AggTrade,
+    BookTicker,
+    Depth,
+    Kline,
+    Other,
+}
+
+impl StreamKind {
+    fn from_suffix(suffix: &str) -> StreamKind {
+        match suffix {
+            "trade" => StreamKind::Trade,
+            "aggTrade" => StreamKind::AggTrade,
+            "bookTicker" => StreamKind::BookTicker,
+            _ if suffix.starts_with("depth") => StreamKind::Depth,
+            _ if suffix.starts_with("kline_") => StreamKind::Kline,
+            _ => StreamKind::Other,
+        }
+    }
+}
+
+/// Message received on the combined stream. All the fields borrow the websocket read buffer.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct CombinedMessage<'a> {
+    /// Time the frame has been received, see [`WebsocketFrame`].
+    pub ts: u64,
+    /// Full stream name, for example `btcusdt@depth20@100ms`.
+    pub stream: &'a str,
+    /// Part of the stream name before the `@`.
+    pub symbol: &'a str,
+    pub kind: StreamKind,
+    /// Content of the `data` field as raw JSON.
+    pub data: &'a [u8],
+}
+
+impl<'a> CombinedMessage<'a> {
+    /// Splits the combined stream message, returns `None` if the message is not wrapped (such as
+    /// the response to the subscribe request).
+    pub fn parse(ts: u64, message: &'a [u8]) -> Option<CombinedMessage<'a>> {
+        let (stream, data) = split_combined(message)?;
+        let (symbol, suffix) = stream.split_once('@').unwrap_or((stream, ""));
+        Some(Self {
+            ts,
+            stream,
+            symbol,
+            kind: StreamKind::from_suffix(suffix),
+            data,
+        })
+    }
+}
+
+/// Receives the messages from the [`CombinedStreamEndpoint`]. By default [`CombinedStreamHandler::on_message`]
+/// dispatches to the callback matching the [`StreamKind`], so only the callbacks of interest need
+/// to be implemented.
+pub trait CombinedStreamHandler {
+    fn on_message(&mut self, message: CombinedMessage<'_>) -> io::Result<()> {
+        match message.kind {
+            StreamKind::Trade => self.on_trade(message),
+            StreamKind::AggTrade => self.on_agg_trade(message),
+            StreamKind::BookTicker => self.on_book_ticker(message),
+            StreamKind::Depth => self.on_depth(message),
+            StreamKind::Kline => self.on_kline(message),
+            StreamKind::Other => self.on_other(message),
+        }
+    }
+
+    fn on_trade(&mut self, _message: CombinedMessage<'_>) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn on_agg_trade(&mut self, _message: CombinedMessage<'_>) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn on_book_ticker(&mut self, _message: CombinedMessage<'_>) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn on_depth(&mut self, _message: CombinedMessage<'_>) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn on_kline(&mut self, _message: CombinedMessage<'_>) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn on_other(&mut self, _message: CombinedMessage<'_>) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Invoked with the text messages that are not wrapped, such as the responses to the requests.
+    fn on_response(&mut self, _ts: u64, _data: &[u8]) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Builds the combined stream url, for example `wss://stream.binance.com:9443/stream?streams=a/b/c`.
+pub fn combined_stream_url<I>(base_url: &str, streams: I) -> String
+where
+    I: IntoIterator,
+    I::Item: AsRef<str>,
+{
+    let mut url = format!("{}/stream?streams=", base_url.trim_end_matches('/'));
+    for (i, stream) in streams.into_iter().enumerate() {
+        if i > 0 {
+            url.push('/');
+        }
+        url.push_str(stream.as_ref());
+    }
+    url
+}
+
+/// Splits the combined stream `message` into the stream name and the raw `data` payload without
+/// any copy. Returns `None` if the message is not wrapped.
+pub fn split_combined(message: &[u8]) -> Option<(&str, &[u8])> {
+    let rest = message.strip_prefix(br#"{"stream":""#)?;
+    let end = rest.iter().position(|b| *b == b'"')?;
+    let (stream, rest) = rest.split_at(end);
+    let data = rest.strip_prefix(br#"","data":"#)?.strip_suffix(b"}")?;
+    Some((std::str::from_utf8(stream).ok()?, data))
+}
+
+/// Endpoint subscribed to the combined `streams` (the subscription is part of the url, so nothing
+/// needs to be sent when the endpoint is recreated). The messages are delivered to the `handler`.
+pub struct CombinedStreamEndpoint<H, S = TcpStream> {
+    url: String,
+    net_iface: Option<SocketAddr>,
+    handler: H,
+    phantom: PhantomData<fn() -> S>,
+}
+
+impl<H: CombinedStreamHandler, S: FromTcpStream> CombinedStreamEndpoint<H, S> {
+    /// Creates endpoint connecting to the `base_url` (such as `wss://stream.binance.com:9443`).
+    pub fn new<I>(base_url: &str, streams: I, handler: H) -> CombinedStreamEndpoint<H, S>
+    where
+        I: IntoIterator,
+        I::Item: AsRef<str>,
+    {
+        Self {
+            url: combined_stream_url(base_url, streams),
+            net_iface: None,
+            handler,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Bind the connection to the network interface, see [`BindAndConnect`].
+    pub fn with_net_iface(self, net_iface: SocketAddr) -> CombinedStreamEndpoint<H, S> {
+        Self {
+            net_iface: Some(net_iface),
+            ..self
+        }
+    }
+
+    pub fn handler(&self) -> &H {
+        &self.handler
+    }
+
+    pub fn handler_mut(&mut self) -> &mut H {
+        &mut self.handler
+    }
+
+    #[inline]
+    fn dispatch(&mut self, ts: u64, data: &[u8]) -> io::Result<()> {
+        match CombinedMessage::parse(ts, data) {
+            Some(message) => self.handler.on_message(message),
+            None => self.handler.on_response(ts, data),
+        }
+    }
+}
+
+impl<H: CombinedStreamHandler, S: FromTcpStream> TlsWebsocketEndpoint for CombinedStreamEndpoint<H, S> {
+    type Stream = S;
+
+    fn url(&self) -> &str {
+        &self.url
+    }
+
+    fn create_websocket(&mut self, addr: SocketAddr) -> io::Result<TlsWebsocket<Self::Stream>> {
+        let stream = TcpStream::bind_and_connect(addr, self.net_iface, None)?;
+        S::from_tcp_stream(stream)?.into_tls_websocket(&self.url)
+    }
+
+    #[inline]
+    fn poll(&mut self, ws: &mut TlsWebsocket<Self::Stream>) -> io::Result<()> {
+        while let Some(frame) = ws.receive_next()? {
+            if let WebsocketFrame::Text(ts, true, data) = frame {
+                self.dispatch(ts, data)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Default)]
+    struct Recorder {
+        trades: Vec<(String, Vec<u8>)>,
+        depths: Vec<String>,
+        responses: usize,
+    }
+
+    impl CombinedStreamHandler for Recorder {
+        fn on_trade(&mut self, message: CombinedMessage<'_>) -> io::Result<()> {
+            self.trades.push((message.symbol.to_owned(), message.data.to_vec()));
+            Ok(())
+        }
+
+        fn on_depth(&mut self, message: CombinedMessage<'_>) -> io::Result<()> {
+            self.depths.push(message.stream.to_owned());
+            Ok(())
+        }
+
+        fn on_response(&mut self, _ts: u64, _data: &[u8]) -> io::Result<()> {
+            self.responses += 1;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn should_split_combined_messages() {
+        let mut endpoint = CombinedStreamEndpoint::<_, TcpStream>::new(
+            "wss://stream.binance.com:9443/",
+            ["btcusdt@trade", "ethusdt@depth20@100ms"],
+            Recorder::default(),
+        );
+        assert_eq!("wss://stream.binance.com:9443/stream?streams=btcusdt@trade/ethusdt@depth20@100ms", endpoint.url());
+
+        endpoint
+            .dispatch(1, br#"{"stream":"btcusdt@trade","data":{"e":"trade","p":"1.0"}}"#)
+            .unwrap();
+        endpoint
+            .dispatch(2, br#"{"stream":"ethusdt@depth20@100ms","data":{"bids":[]}}"#)
+            .unwrap();
+        endpoint.dispatch(3, br#"{"result":null,"id":1}"#).unwrap();
+
+        let recorder = endpoint.handler();
+        assert_eq!(vec![("btcusdt".to_owned(), br#"{"e":"trade","p":"1.0"}"#.to_vec())], recorder.trades);
+        assert_eq!(vec!["ethusdt@depth20@100ms".to_owned()], recorder.depths);
+        assert_eq!(1, recorder.responses);
+    }
+}
@@ -0,0 +1,30 @@
+//! Ready made endpoints for the common venues, available with the `exchange-adapters` feature
+//! (together with one of the `tls-*` features).
+
+use std::io;
+use std::io::{Read, Write};
+use std::net::TcpStream;
+
+use crate::stream::tls::NotTlsStream;
+
+pub mod binance;
+
+/// Stream the adapter endpoints connect over, created from the connected `TcpStream`. Implemented
+/// for the `TcpStream` itself and, with the `mio` feature, for `MioStream`.
+pub trait FromTcpStream: Read + Write + NotTlsStream + Sized {
+    fn from_tcp_stream(stream: TcpStream) -> io::Result<Self>;
+}
+
+impl FromTcpStream for TcpStream {
+    fn from_tcp_stream(stream: TcpStream) -> io::Result<Self> {
+        Ok(stream)
+    }
+}
+
+#[cfg(feature = "mio")]
+impl FromTcpStream for crate::stream::mio::MioStream {
+    fn from_tcp_stream(stream: TcpStream) -> io::Result<Self> {
+        use crate::stream::mio::IntoMioStream;
+        Ok(stream.into_mio_stream())
+    }
+}
@@ -0,0 +1,81 @@
+use std::io;
+use std::io::Read;
+
+use crate::fix::{checksum, FixMessage, ReadBuffer, DEFAULT_MAX_BODY_LENGTH, SOH};
+
+#[derive(Debug)]
+pub struct Decoder {
+    buffer: ReadBuffer,
+    max_body_length: usize,
+}
+
+impl Decoder {
+    pub fn new() -> Self {
+        Self {
+            buffer: ReadBuffer::new(),
+            max_body_length: DEFAULT_MAX_BODY_LENGTH,
+        }
+    }
+
+    /// Messages with the `BodyLength(9)` above the `max_body_length` are rejected as invalid instead
+    /// of buffering the data until the (possibly never arriving) end of the message.
+    pub fn with_max_body_length(self, max_body_length: usize) -> Self {
+        Self {
+            max_body_length,
+            ..self
+        }
+    }
+
+    #[inline]
+    pub fn decode_next<S: Read>(&mut self, stream: &mut S) -> io::Result<Option<FixMessage>> {
+        if let Some(len) = frame_length(self.buffer.view(), self.max_body_length)? {
+            let raw = self.buffer.consume_next(len);
+            return Ok(Some(FixMessage::new(raw)));
+        }
+
+        // await for more data
+        self.buffer.read_from(stream)?;
+        Ok(None)
+    }
+}
+
+/// Returns the length of the complete message at the start of the `buf` (validating the
+/// checksum and the body length limit) or `None` if more data is required.
+fn frame_length(buf: &[u8], max_body_length: usize) -> io::Result<Option<usize>> {
+    // 8=<begin string><SOH>9=<body length><SOH>
+    let begin_string_end = match buf.iter().position(|b| *b == SOH) {
+        Some(pos) => pos + 1,
+        None => return Ok(None),
+    };
+    if !buf.starts_with(b"8=") {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "message does not start with BeginString"));
+    }
+    let rest = &buf[begin_string_end..];
+    let body_length_end = match rest.iter().position(|b| *b == SOH) {
+        Some(pos) => pos,
+        None => return Ok(None),
+    };
+    let body_length = rest[..body_length_end]
+        .strip_prefix(b"9=")
+        .and_then(super::parse_uint)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "invalid BodyLength"))?;
+    let body_length = usize::try_from(body_length)
+        .ok()
+        .filter(|&body_length| body_length <= max_body_length)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "BodyLength exceeds the limit"))?;
+
+    // header + body + 10=nnn<SOH>, cannot overflow as the body length is limited
+    let checksum_start = begin_string_end + body_length_end + 1 + body_length;
+    let len = checksum_start + 7;
+    if buf.len() < len {
+        return Ok(None);
+    }
+    let expected = buf[checksum_start..len - 1]
+        .strip_prefix(b"10=")
+        .and_then(super::parse_uint)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "invalid CheckSum"))?;
+    if checksum(&buf[..checksum_start]) as u64 != expected {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "CheckSum mismatch"));
+    }
+    Ok(Some(len))
+}
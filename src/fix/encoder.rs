@@ -0,0 +1,65 @@
+use std::io;
+use std::io::Write;
+
+use crate::fix::{checksum, format_sending_time, tag, SOH};
+
+#[derive(Debug)]
+pub struct Encoder {
+    begin_string: String,
+    sender_comp_id: String,
+    target_comp_id: String,
+    body: Vec<u8>,
+    message: Vec<u8>,
+}
+
+impl Encoder {
+    pub fn new(begin_string: &str, sender_comp_id: &str, target_comp_id: &str) -> Self {
+        Self {
+            begin_string: begin_string.to_owned(),
+            sender_comp_id: sender_comp_id.to_owned(),
+            target_comp_id: target_comp_id.to_owned(),
+            body: Vec::with_capacity(1024),
+            message: Vec::with_capacity(1024),
+        }
+    }
+
+    /// Encodes the message with the standard header and trailer and writes it to the `stream`.
+    pub fn send<S: Write>(
+        &mut self,
+        stream: &mut S,
+        msg_type: &[u8],
+        seq_num: u64,
+        time_ns: u64,
+        fields: &[(u32, &[u8])],
+    ) -> io::Result<()> {
+        self.body.clear();
+        put(&mut self.body, tag::MSG_TYPE, msg_type);
+        put(&mut self.body, tag::SENDER_COMP_ID, self.sender_comp_id.as_bytes());
+        put(&mut self.body, tag::TARGET_COMP_ID, self.target_comp_id.as_bytes());
+        write!(self.body, "{}={}\x01", tag::MSG_SEQ_NUM, seq_num)?;
+        write!(self.body, "{}=", tag::SENDING_TIME)?;
+        format_sending_time(time_ns, &mut self.body);
+        self.body.push(SOH);
+        for (tag, value) in fields {
+            put(&mut self.body, *tag, value);
+        }
+
+        self.message.clear();
+        put(&mut self.message, tag::BEGIN_STRING, self.begin_string.as_bytes());
+        write!(self.message, "{}={}\x01", tag::BODY_LENGTH, self.body.len())?;
+        self.message.extend_from_slice(&self.body);
+        let checksum = checksum(&self.message);
+        write!(self.message, "{}={:03}\x01", tag::CHECKSUM, checksum)?;
+
+        stream.write_all(&self.message)?;
+        stream.flush()
+    }
+}
+
+#[inline]
+fn put(buf: &mut Vec<u8>, tag: u32, value: &[u8]) {
+    // writing to vec can't fail
+    let _ = write!(buf, "{}=", tag);
+    buf.extend_from_slice(value);
+    buf.push(SOH);
+}
@@ -0,0 +1,717 @@
+//! FIX protocol session layer. Messages are decoded into zero-copy [`FixMessage`] views over the
+//! read buffer while the [`FixSession`] takes care of the logon, heartbeats, test requests,
+//! sequence numbers and resend requests. Only application messages are returned to the caller.
+//!
+//! The session can be used as the [`Endpoint`](crate::endpoint::Endpoint) target, the sequence
+//! numbers can be carried across reconnects with [`FixSession::with_sequence_numbers`].
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use std::net::TcpStream;
+//! use boomnet::fix::{msg_type, tag, IntoFixSession, SessionConfig};
+//!
+//! let mut session = TcpStream::connect("127.0.0.1:9878")
+//!     .unwrap()
+//!     .into_fix_session(SessionConfig::new("CLIENT", "VENUE"));
+//!
+//! loop {
+//!     if let Some(message) = session.receive_next().unwrap() {
+//!         if message.msg_type() == msg_type::EXECUTION_REPORT {
+//!             println!("{:?}", message.get(tag::TEXT));
+//!         }
+//!     }
+//! }
+//! ```
+
+#[cfg(feature = "mio")]
+use mio::{event::Source, Interest, Registry, Token};
+use std::io;
+use std::io::ErrorKind::{ConnectionAborted, InvalidData, NotConnected, TimedOut};
+use std::io::{Read, Write};
+use std::time::Duration;
+
+use crate::buffer;
+use crate::endpoint::ConnectionInfo;
+use crate::fix::decoder::Decoder;
+use crate::fix::encoder::Encoder;
+use crate::select::Selectable;
+use crate::stream::{ConnectionInfoProvider, SocketOptions, SocketQueues};
+use crate::util::current_time_nanos;
+
+mod decoder;
+mod encoder;
+
+type ReadBuffer = buffer::ReadBuffer<4096>;
+
+const SOH: u8 = 0x01;
+
+/// Default maximum `BodyLength(9)` of the received message, see [`SessionConfig::with_max_body_length`].
+pub const DEFAULT_MAX_BODY_LENGTH: usize = 1024 * 1024;
+
+/// Commonly used tags.
+pub mod tag {
+    pub const BEGIN_SEQ_NO: u32 = 7;
+    pub const BEGIN_STRING: u32 = 8;
+    pub const BODY_LENGTH: u32 = 9;
+    pub const CHECKSUM: u32 = 10;
+    pub const END_SEQ_NO: u32 = 16;
+    pub const MSG_SEQ_NUM: u32 = 34;
+    pub const MSG_TYPE: u32 = 35;
+    pub const NEW_SEQ_NO: u32 = 36;
+    pub const POSS_DUP_FLAG: u32 = 43;
+    pub const SENDER_COMP_ID: u32 = 49;
+    pub const SENDING_TIME: u32 = 52;
+    pub const TARGET_COMP_ID: u32 = 56;
+    pub const TEXT: u32 = 58;
+    pub const ENCRYPT_METHOD: u32 = 98;
+    pub const HEART_BT_INT: u32 = 108;
+    pub const TEST_REQ_ID: u32 = 112;
+    pub const GAP_FILL_FLAG: u32 = 123;
+    pub const RESET_SEQ_NUM_FLAG: u32 = 141;
+}
+
+/// Commonly used message types.
+pub mod msg_type {
+    pub const HEARTBEAT: &[u8] = b"0";
+    pub const TEST_REQUEST: &[u8] = b"1";
+    pub const RESEND_REQUEST: &[u8] = b"2";
+    pub const REJECT: &[u8] = b"3";
+    pub const SEQUENCE_RESET: &[u8] = b"4";
+    pub const LOGOUT: &[u8] = b"5";
+    pub const EXECUTION_REPORT: &[u8] = b"8";
+    pub const LOGON: &[u8] = b"A";
+    pub const NEW_ORDER_SINGLE: &[u8] = b"D";
+    pub const ORDER_CANCEL_REQUEST: &[u8] = b"F";
+}
+
+/// Zero-copy view over the complete FIX message, including the standard header and trailer.
+#[derive(Debug, Copy, Clone)]
+pub struct FixMessage {
+    raw: &'static [u8],
+}
+
+/// Single `tag=value` field of the [`FixMessage`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Field {
+    pub tag: u32,
+    pub value: &'static [u8],
+}
+
+/// Iterator over the [`FixMessage`] fields in the order they appear on the wire.
+pub struct Fields {
+    remaining: &'static [u8],
+}
+
+impl Iterator for Fields {
+    type Item = Field;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let end = self.remaining.iter().position(|b| *b == SOH)?;
+        let (field, remaining) = self.remaining.split_at(end);
+        self.remaining = &remaining[1..];
+        let separator = field.iter().position(|b| *b == b'=')?;
+        Some(Field {
+            tag: parse_uint(&field[..separator])? as u32,
+            value: &field[separator + 1..],
+        })
+    }
+}
+
+impl FixMessage {
+    const fn new(raw: &'static [u8]) -> Self {
+        Self { raw }
+    }
+
+    /// Raw bytes of the message as received.
+    pub const fn as_bytes(&self) -> &'static [u8] {
+        self.raw
+    }
+
+    /// Returns iterator over all fields of the message.
+    pub const fn fields(&self) -> Fields {
+        Fields { remaining: self.raw }
+    }
+
+    /// Returns the value of the first field with the given `tag`.
+    pub fn get(&self, tag: u32) -> Option<&'static [u8]> {
+        self.fields().find(|field| field.tag == tag).map(|field| field.value)
+    }
+
+    /// Returns the value of the first field with the given `tag` parsed as unsigned integer.
+    pub fn get_uint(&self, tag: u32) -> Option<u64> {
+        self.get(tag).and_then(parse_uint)
+    }
+
+    /// Returns the `MsgType(35)` of the message.
+    pub fn msg_type(&self) -> &'static [u8] {
+        self.get(tag::MSG_TYPE).unwrap_or_default()
+    }
+
+    /// Returns the `MsgSeqNum(34)` of the message.
+    pub fn seq_num(&self) -> Option<u64> {
+        self.get_uint(tag::MSG_SEQ_NUM)
+    }
+
+    fn is_flag_set(&self, tag: u32) -> bool {
+        self.get(tag) == Some(b"Y")
+    }
+}
+
+/// Static configuration of the [`FixSession`].
+#[derive(Debug, Clone)]
+pub struct SessionConfig {
+    begin_string: String,
+    sender_comp_id: String,
+    target_comp_id: String,
+    heartbeat_interval: Duration,
+    reset_seq_num: bool,
+    max_body_length: usize,
+}
+
+impl SessionConfig {
+    /// Creates `FIX.4.4` session config with 30 seconds heartbeat interval.
+    pub fn new(sender_comp_id: &str, target_comp_id: &str) -> SessionConfig {
+        Self {
+            begin_string: "FIX.4.4".to_owned(),
+            sender_comp_id: sender_comp_id.to_owned(),
+            target_comp_id: target_comp_id.to_owned(),
+            heartbeat_interval: Duration::from_secs(30),
+            reset_seq_num: false,
+            max_body_length: DEFAULT_MAX_BODY_LENGTH,
+        }
+    }
+
+    /// Specify `BeginString(8)` other than `FIX.4.4`.
+    pub fn with_begin_string(self, begin_string: &str) -> SessionConfig {
+        Self {
+            begin_string: begin_string.to_owned(),
+            ..self
+        }
+    }
+
+    /// Specify heartbeat interval (whole seconds) sent with the logon.
+    pub fn with_heartbeat_interval(self, heartbeat_interval: Duration) -> SessionConfig {
+        Self {
+            heartbeat_interval,
+            ..self
+        }
+    }
+
+    /// Request both sides to reset the sequence numbers on logon.
+    pub fn with_reset_seq_num(self, reset_seq_num: bool) -> SessionConfig {
+        Self { reset_seq_num, ..self }
+    }
+
+    /// Specify the maximum `BodyLength(9)` of the received message, the larger message fails the
+    /// session with [`InvalidData`] error.
+    pub fn with_max_body_length(self, max_body_length: usize) -> SessionConfig {
+        Self {
+            max_body_length,
+            ..self
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum SessionState {
+    NotStarted,
+    LogonSent,
+    Active,
+    LogoutSent,
+}
+
+/// FIX session over the `stream`. The logon is sent on the first call to [`FixSession::receive_next`],
+/// which should then be called on each duty cycle to drive the heartbeats and timeouts.
+#[derive(Debug)]
+pub struct FixSession<S> {
+    stream: S,
+    decoder: Decoder,
+    encoder: Encoder,
+    heartbeat_interval: Duration,
+    reset_seq_num: bool,
+    state: SessionState,
+    next_outgoing_seq_num: u64,
+    next_incoming_seq_num: u64,
+    last_sent_time_ns: u64,
+    last_received_time_ns: u64,
+    test_request_pending: bool,
+    resend_requested: bool,
+}
+
+impl<S> FixSession<S> {
+    /// Continue the previous session from the given sequence numbers instead of starting from one.
+    pub fn with_sequence_numbers(self, next_outgoing_seq_num: u64, next_incoming_seq_num: u64) -> FixSession<S> {
+        Self {
+            next_outgoing_seq_num,
+            next_incoming_seq_num,
+            ..self
+        }
+    }
+
+    /// Checks if the logon has been acknowledged by the counterparty.
+    pub fn is_logged_on(&self) -> bool {
+        self.state == SessionState::Active
+    }
+
+    /// Sequence number of the next message to be sent.
+    pub const fn next_outgoing_seq_num(&self) -> u64 {
+        self.next_outgoing_seq_num
+    }
+
+    /// Sequence number expected on the next message received.
+    pub const fn next_incoming_seq_num(&self) -> u64 {
+        self.next_incoming_seq_num
+    }
+}
+
+impl<S: Read + Write> FixSession<S> {
+    pub fn new(stream: S, config: SessionConfig) -> FixSession<S> {
+        let current_time_ns = current_time_nanos();
+        Self {
+            stream,
+            decoder: Decoder::new().with_max_body_length(config.max_body_length),
+            encoder: Encoder::new(&config.begin_string, &config.sender_comp_id, &config.target_comp_id),
+            heartbeat_interval: config.heartbeat_interval,
+            reset_seq_num: config.reset_seq_num,
+            state: SessionState::NotStarted,
+            next_outgoing_seq_num: 1,
+            next_incoming_seq_num: 1,
+            last_sent_time_ns: current_time_ns,
+            last_received_time_ns: current_time_ns,
+            test_request_pending: false,
+            resend_requested: false,
+        }
+    }
+
+    /// Returns the next application message, administrative messages are handled by the session.
+    pub fn receive_next(&mut self) -> io::Result<Option<FixMessage>> {
+        let current_time_ns = current_time_nanos();
+        if self.state == SessionState::NotStarted {
+            self.send_logon(current_time_ns)?;
+        }
+        self.check_heartbeat(current_time_ns)?;
+
+        let message = match self.decoder.decode_next(&mut self.stream)? {
+            Some(message) => message,
+            None => return Ok(None),
+        };
+        self.last_received_time_ns = current_time_ns;
+        self.test_request_pending = false;
+
+        let seq_num = message
+            .seq_num()
+            .ok_or_else(|| io::Error::new(InvalidData, "MsgSeqNum missing"))?;
+        let msg_type = message.msg_type();
+
+        if msg_type == msg_type::LOGON {
+            if self.reset_seq_num {
+                self.next_incoming_seq_num = seq_num;
+            }
+            self.state = SessionState::Active;
+        }
+
+        if msg_type == msg_type::SEQUENCE_RESET {
+            let new_seq_num = message
+                .get_uint(tag::NEW_SEQ_NO)
+                .ok_or_else(|| io::Error::new(InvalidData, "NewSeqNo missing"))?;
+            self.next_incoming_seq_num = self.next_incoming_seq_num.max(new_seq_num);
+            self.resend_requested = false;
+            return Ok(None);
+        }
+
+        if seq_num > self.next_incoming_seq_num {
+            // the counterparty will resend the missing messages (including this one)
+            if !self.resend_requested {
+                let begin_seq_num = self.next_incoming_seq_num.to_string();
+                self.send_admin(
+                    msg_type::RESEND_REQUEST,
+                    &[(tag::BEGIN_SEQ_NO, begin_seq_num.as_bytes()), (tag::END_SEQ_NO, b"0")],
+                    current_time_ns,
+                )?;
+                self.resend_requested = true;
+            }
+            if msg_type != msg_type::LOGOUT {
+                return Ok(None);
+            }
+        } else if seq_num < self.next_incoming_seq_num {
+            if message.is_flag_set(tag::POSS_DUP_FLAG) {
+                return Ok(None);
+            }
+            return Err(io::Error::new(
+                InvalidData,
+                format!("MsgSeqNum too low, expected {} but received {}", self.next_incoming_seq_num, seq_num),
+            ));
+        } else {
+            self.next_incoming_seq_num = seq_num + 1;
+        }
+
+        match msg_type {
+            msg_type::LOGON | msg_type::HEARTBEAT => Ok(None),
+            msg_type::TEST_REQUEST => {
+                let test_req_id = message.get(tag::TEST_REQ_ID).unwrap_or_default();
+                self.send_admin(msg_type::HEARTBEAT, &[(tag::TEST_REQ_ID, test_req_id)], current_time_ns)?;
+                Ok(None)
+            }
+            msg_type::RESEND_REQUEST => {
+                // messages are not stored so fill the whole gap
+                let begin_seq_num = message.get_uint(tag::BEGIN_SEQ_NO).unwrap_or(1);
+                let new_seq_num = self.next_outgoing_seq_num.to_string();
+                self.encoder.send(
+                    &mut self.stream,
+                    msg_type::SEQUENCE_RESET,
+                    begin_seq_num,
+                    current_time_ns,
+                    &[
+                        (tag::POSS_DUP_FLAG, b"Y"),
+                        (tag::GAP_FILL_FLAG, b"Y"),
+                        (tag::NEW_SEQ_NO, new_seq_num.as_bytes()),
+                    ],
+                )?;
+                self.last_sent_time_ns = current_time_ns;
+                Ok(None)
+            }
+            msg_type::LOGOUT => {
+                if self.state != SessionState::LogoutSent {
+                    self.send_admin(msg_type::LOGOUT, &[], current_time_ns)?;
+                }
+                let text = String::from_utf8_lossy(message.get(tag::TEXT).unwrap_or_default()).to_string();
+                Err(io::Error::new(ConnectionAborted, format!("logged out: {}", text)))
+            }
+            _ => Ok(Some(message)),
+        }
+    }
+
+    /// Sends application message with the given `msg_type` and body `fields`, the standard header
+    /// and trailer are populated by the session. Returns the `MsgSeqNum(34)` assigned to the message.
+    pub fn send(&mut self, msg_type: &[u8], fields: &[(u32, &[u8])]) -> io::Result<u64> {
+        if self.state != SessionState::Active {
+            return Err(io::Error::new(NotConnected, "session not logged on"));
+        }
+        let seq_num = self.next_outgoing_seq_num;
+        self.send_admin(msg_type, fields, current_time_nanos())?;
+        Ok(seq_num)
+    }
+
+    /// Initiates the logout, the session will end once the counterparty has confirmed it.
+    pub fn logout(&mut self, text: Option<&str>) -> io::Result<()> {
+        let text = text.unwrap_or_default().as_bytes();
+        let fields = [(tag::TEXT, text)];
+        let fields = if text.is_empty() { &fields[..0] } else { &fields[..] };
+        self.send_admin(msg_type::LOGOUT, fields, current_time_nanos())?;
+        self.state = SessionState::LogoutSent;
+        Ok(())
+    }
+
+    fn send_logon(&mut self, current_time_ns: u64) -> io::Result<()> {
+        if self.reset_seq_num {
+            self.next_outgoing_seq_num = 1;
+        }
+        let heartbeat_interval = self.heartbeat_interval.as_secs().to_string();
+        let reset_seq_num: &[u8] = if self.reset_seq_num { b"Y" } else { b"N" };
+        self.send_admin(
+            msg_type::LOGON,
+            &[
+                (tag::ENCRYPT_METHOD, b"0"),
+                (tag::HEART_BT_INT, heartbeat_interval.as_bytes()),
+                (tag::RESET_SEQ_NUM_FLAG, reset_seq_num),
+            ],
+            current_time_ns,
+        )?;
+        self.state = SessionState::LogonSent;
+        Ok(())
+    }
+
+    fn check_heartbeat(&mut self, current_time_ns: u64) -> io::Result<()> {
+        let interval_ns = self.heartbeat_interval.as_nanos() as u64;
+        if current_time_ns - self.last_sent_time_ns > interval_ns {
+            self.send_admin(msg_type::HEARTBEAT, &[], current_time_ns)?;
+        }
+        let silence_ns = current_time_ns - self.last_received_time_ns;
+        if self.test_request_pending {
+            if silence_ns > 2 * interval_ns {
+                return Err(io::Error::new(TimedOut, "no response to the test request"));
+            }
+        } else if silence_ns > interval_ns + interval_ns / 5 {
+            let test_req_id = current_time_ns.to_string();
+            self.send_admin(msg_type::TEST_REQUEST, &[(tag::TEST_REQ_ID, test_req_id.as_bytes())], current_time_ns)?;
+            self.test_request_pending = true;
+        }
+        Ok(())
+    }
+
+    fn send_admin(&mut self, msg_type: &[u8], fields: &[(u32, &[u8])], current_time_ns: u64) -> io::Result<()> {
+        self.encoder
+            .send(&mut self.stream, msg_type, self.next_outgoing_seq_num, current_time_ns, fields)?;
+        self.next_outgoing_seq_num += 1;
+        self.last_sent_time_ns = current_time_ns;
+        Ok(())
+    }
+}
+
+#[cfg(feature = "mio")]
+impl<S: Source> Source for FixSession<S> {
+    fn register(&mut self, registry: &Registry, token: Token, interests: Interest) -> io::Result<()> {
+        registry.register(&mut self.stream, token, interests)
+    }
+
+    fn reregister(&mut self, registry: &Registry, token: Token, interests: Interest) -> io::Result<()> {
+        registry.reregister(&mut self.stream, token, interests)
+    }
+
+    fn deregister(&mut self, registry: &Registry) -> io::Result<()> {
+        registry.deregister(&mut self.stream)
+    }
+}
+
+impl<S: Selectable> Selectable for FixSession<S> {
+    fn connected(&mut self) -> io::Result<bool> {
+        self.stream.connected()
+    }
+
+    fn make_writable(&mut self) {
+        self.stream.make_writable();
+    }
+
+    fn make_readable(&mut self) {
+        self.stream.make_readable();
+    }
+
+    fn make_unreadable(&mut self) {
+        self.stream.make_unreadable();
+    }
+
+    fn socket_queues(&self) -> Option<SocketQueues> {
+        self.stream.socket_queues()
+    }
+
+    fn rx_timestamp_ns(&self) -> Option<u64> {
+        self.stream.rx_timestamp_ns()
+    }
+
+    #[cfg(feature = "stats")]
+    fn io_counters(&self) -> Option<crate::stream::IoCounters> {
+        self.stream.io_counters()
+    }
+
+    fn apply_socket_options(&mut self, options: &SocketOptions) -> io::Result<()> {
+        self.stream.apply_socket_options(options)
+    }
+
+    fn has_pending_writes(&self) -> bool {
+        self.stream.has_pending_writes()
+    }
+
+    fn flush_pending_writes(&mut self) -> io::Result<()> {
+        self.stream.flush_pending_writes()
+    }
+
+    fn peer_closed(&self) -> bool {
+        self.stream.peer_closed()
+    }
+
+    fn write_closed(&self) -> bool {
+        self.stream.write_closed()
+    }
+
+    fn make_write_closed(&mut self) {
+        self.stream.make_write_closed()
+    }
+
+    fn take_socket_error(&mut self) -> io::Result<Option<io::Error>> {
+        self.stream.take_socket_error()
+    }
+
+    fn connect_error(&self) -> Option<io::Error> {
+        self.stream.connect_error()
+    }
+}
+
+impl<S: ConnectionInfoProvider> ConnectionInfoProvider for FixSession<S> {
+    fn connection_info(&self) -> &ConnectionInfo {
+        self.stream.connection_info()
+    }
+}
+
+pub trait IntoFixSession {
+    fn into_fix_session(self, config: SessionConfig) -> FixSession<Self>
+    where
+        Self: Sized;
+}
+
+impl<T> IntoFixSession for T
+where
+    T: Read + Write,
+{
+    fn into_fix_session(self, config: SessionConfig) -> FixSession<Self>
+    where
+        Self: Sized,
+    {
+        FixSession::new(self, config)
+    }
+}
+
+#[inline]
+fn checksum(buf: &[u8]) -> u8 {
+    buf.iter().fold(0u8, |sum, b| sum.wrapping_add(*b))
+}
+
+#[inline]
+fn parse_uint(value: &[u8]) -> Option<u64> {
+    if value.is_empty() {
+        return None;
+    }
+    value.iter().try_fold(0u64, |acc, b| match b {
+        b'0'..=b'9' => acc.checked_mul(10)?.checked_add((b - b'0') as u64),
+        _ => None,
+    })
+}
+
+/// Formats UTC timestamp as `YYYYMMDD-HH:MM:SS.sss`.
+fn format_sending_time(time_ns: u64, buf: &mut Vec<u8>) {
+    let millis = (time_ns / 1_000_000) % 1000;
+    let secs = time_ns / 1_000_000_000;
+    let (days, secs_of_day) = ((secs / 86400) as i64, secs % 86400);
+
+    // civil from days, see http://howardhinnant.github.io/date_algorithms.html
+    let z = days + 719468;
+    let era = z / 146097;
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    // writing to vec can't fail
+    let _ = write!(
+        buf,
+        "{:04}{:02}{:02}-{:02}:{:02}:{:02}.{:03}",
+        year,
+        month,
+        day,
+        secs_of_day / 3600,
+        (secs_of_day % 3600) / 60,
+        secs_of_day % 60,
+        millis
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use std::io::ErrorKind::WouldBlock;
+
+    use super::*;
+
+    struct MockStream {
+        input: Cursor<Vec<u8>>,
+        output: Vec<u8>,
+    }
+
+    impl Read for MockStream {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            match self.input.read(buf)? {
+                0 => Err(io::Error::from(WouldBlock)),
+                n => Ok(n),
+            }
+        }
+    }
+
+    impl Write for MockStream {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.output.write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn encode(msg_type: &[u8], seq_num: u64, fields: &[(u32, &[u8])]) -> Vec<u8> {
+        let mut buf = Vec::new();
+        Encoder::new("FIX.4.4", "VENUE", "CLIENT")
+            .send(&mut buf, msg_type, seq_num, 0, fields)
+            .unwrap();
+        buf
+    }
+
+    fn receive_all(session: &mut FixSession<MockStream>) -> Vec<FixMessage> {
+        let mut messages = Vec::new();
+        for _ in 0..8 {
+            if let Some(message) = session.receive_next().unwrap() {
+                messages.push(message);
+            }
+        }
+        messages
+    }
+
+    #[test]
+    fn should_format_sending_time() {
+        let mut buf = Vec::new();
+        format_sending_time(1_700_000_000_123_000_000, &mut buf);
+        assert_eq!(b"20231114-22:13:20.123", buf.as_slice());
+    }
+
+    #[test]
+    fn should_decode_message_fields() {
+        let raw = encode(msg_type::EXECUTION_REPORT, 2, &[(tag::TEXT, b"filled")]);
+        let mut decoder = Decoder::new();
+        let mut stream = MockStream {
+            input: Cursor::new(raw.clone()),
+            output: Vec::new(),
+        };
+        assert!(decoder.decode_next(&mut stream).unwrap().is_none());
+        let message = decoder.decode_next(&mut stream).unwrap().unwrap();
+        assert_eq!(raw.as_slice(), message.as_bytes());
+        assert_eq!(msg_type::EXECUTION_REPORT, message.msg_type());
+        assert_eq!(Some(2), message.seq_num());
+        assert_eq!(Some(b"filled".as_slice()), message.get(tag::TEXT));
+        assert_eq!(b"FIX.4.4", message.fields().next().unwrap().value);
+    }
+
+    #[test]
+    fn should_reject_body_length_above_limit() {
+        let decode = |input: &[u8], mut decoder: Decoder| {
+            let mut stream = MockStream {
+                input: Cursor::new(input.to_vec()),
+                output: Vec::new(),
+            };
+            (0..2).try_for_each(|_| decoder.decode_next(&mut stream).map(drop))
+        };
+        let err = decode(b"8=FIX.4.4\x019=18446744073709551615\x0135=0\x01", Decoder::new()).unwrap_err();
+        assert_eq!(InvalidData, err.kind());
+
+        let raw = encode(msg_type::EXECUTION_REPORT, 2, &[(tag::TEXT, b"filled")]);
+        assert!(decode(&raw, Decoder::new()).is_ok());
+        let err = decode(&raw, Decoder::new().with_max_body_length(16)).unwrap_err();
+        assert_eq!(InvalidData, err.kind());
+    }
+
+    #[test]
+    fn should_log_on_and_handle_admin_messages() {
+        let mut input = encode(msg_type::LOGON, 1, &[(tag::HEART_BT_INT, b"30")]);
+        input.extend(encode(msg_type::TEST_REQUEST, 2, &[(tag::TEST_REQ_ID, b"abc")]));
+        input.extend(encode(msg_type::EXECUTION_REPORT, 3, &[(tag::TEXT, b"filled")]));
+        input.extend(encode(msg_type::EXECUTION_REPORT, 5, &[]));
+        let mut session = MockStream {
+            input: Cursor::new(input),
+            output: Vec::new(),
+        }
+        .into_fix_session(SessionConfig::new("CLIENT", "VENUE"));
+
+        let messages = receive_all(&mut session);
+        assert!(session.is_logged_on());
+        assert_eq!(1, messages.len());
+        assert_eq!(Some(3), messages[0].seq_num());
+        assert_eq!(4, session.next_incoming_seq_num());
+
+        // logon, heartbeat with test request id and resend request for the gap
+        let output = String::from_utf8_lossy(&session.stream.output).replace('\x01', "|");
+        assert!(output.contains("|35=A|49=CLIENT|56=VENUE|34=1|"));
+        assert!(output.contains("|35=0|49=CLIENT|56=VENUE|34=2|") && output.contains("|112=abc|"));
+        assert!(output.contains("|35=2|49=CLIENT|56=VENUE|34=3|") && output.contains("|7=4|16=0|"));
+        assert_eq!(4, session.next_outgoing_seq_num());
+    }
+}
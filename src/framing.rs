@@ -0,0 +1,378 @@
+//! Length prefixed binary framing, where each frame consists of the fixed size length prefix
+//! followed by the payload. The prefix size and byte order is defined by the [`LengthPrefix`],
+//! so that any combination of `u16`, `u32` or `u64` in little or big endian can be used.
+//!
+//! The [`Framed`] stream can be used as the [`Endpoint`](crate::endpoint::Endpoint) target.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use std::net::TcpStream;
+//! use boomnet::framing::{IntoFramed, U32Le};
+//!
+//! let mut framed = TcpStream::connect("127.0.0.1:9000").unwrap().into_framed::<U32Le>();
+//! framed.send(b"hello").unwrap();
+//! loop {
+//!     if let Some(payload) = framed.receive_next().unwrap() {
+//!         println!("received {} bytes", payload.len());
+//!     }
+//! }
+//! ```
+
+#[cfg(feature = "mio")]
+use mio::{event::Source, Interest, Registry, Token};
+use std::io;
+use std::io::{Read, Write};
+use std::marker::PhantomData;
+
+use crate::buffer;
+use crate::endpoint::ConnectionInfo;
+use crate::select::Selectable;
+use crate::stream::{ConnectionInfoProvider, SocketOptions, SocketQueues};
+
+type ReadBuffer = buffer::ReadBuffer<4096>;
+
+/// Default maximum frame payload length accepted by the [`LengthPrefixedDecoder`].
+pub const DEFAULT_MAX_FRAME_LENGTH: usize = 16 * 1024 * 1024;
+
+/// Defines size and byte order of the frame length prefix.
+pub trait LengthPrefix {
+    /// Size of the prefix in bytes.
+    const SIZE: usize;
+
+    /// Decodes payload length from exactly [`LengthPrefix::SIZE`] bytes.
+    fn decode(bytes: &[u8]) -> usize;
+
+    /// Encodes payload length into exactly [`LengthPrefix::SIZE`] bytes, fails if the length
+    /// does not fit the prefix.
+    fn encode(len: usize, bytes: &mut [u8]) -> io::Result<()>;
+}
+
+macro_rules! length_prefix {
+    ($name:ident, $int:ty, $from:ident, $to:ident, $doc:literal) => {
+        #[doc = $doc]
+        #[derive(Debug, Copy, Clone)]
+        pub struct $name;
+
+        impl LengthPrefix for $name {
+            const SIZE: usize = std::mem::size_of::<$int>();
+
+            #[inline]
+            fn decode(bytes: &[u8]) -> usize {
+                <$int>::$from(bytes.try_into().expect("incorrect length")) as usize
+            }
+
+            #[inline]
+            fn encode(len: usize, bytes: &mut [u8]) -> io::Result<()> {
+                let len = <$int>::try_from(len)
+                    .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "frame too large for the prefix"))?;
+                bytes.copy_from_slice(&len.$to());
+                Ok(())
+            }
+        }
+    };
+}
+
+length_prefix!(U16Le, u16, from_le_bytes, to_le_bytes, "2-byte little endian length prefix.");
+length_prefix!(U16Be, u16, from_be_bytes, to_be_bytes, "2-byte big endian length prefix.");
+length_prefix!(U32Le, u32, from_le_bytes, to_le_bytes, "4-byte little endian length prefix.");
+length_prefix!(U32Be, u32, from_be_bytes, to_be_bytes, "4-byte big endian length prefix.");
+length_prefix!(U64Le, u64, from_le_bytes, to_le_bytes, "8-byte little endian length prefix.");
+length_prefix!(U64Be, u64, from_be_bytes, to_be_bytes, "8-byte big endian length prefix.");
+
+/// Decodes length prefixed frames from the stream with zero-copy semantics.
+#[derive(Debug)]
+pub struct LengthPrefixedDecoder<P> {
+    buffer: ReadBuffer,
+    max_frame_length: usize,
+    payload_length: Option<usize>,
+    prefix: PhantomData<P>,
+}
+
+impl<P: LengthPrefix> Default for LengthPrefixedDecoder<P> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<P: LengthPrefix> LengthPrefixedDecoder<P> {
+    pub fn new() -> LengthPrefixedDecoder<P> {
+        Self {
+            buffer: ReadBuffer::new(),
+            max_frame_length: DEFAULT_MAX_FRAME_LENGTH,
+            payload_length: None,
+            prefix: PhantomData,
+        }
+    }
+
+    /// Specify maximum frame payload length, larger frames are rejected with [`io::ErrorKind::InvalidData`].
+    pub fn with_max_frame_length(self, max_frame_length: usize) -> LengthPrefixedDecoder<P> {
+        Self {
+            max_frame_length,
+            ..self
+        }
+    }
+
+    /// Returns the next frame payload or `None` if more data is required.
+    #[inline]
+    pub fn decode_next<S: Read>(&mut self, stream: &mut S) -> io::Result<Option<&'static [u8]>> {
+        if self.payload_length.is_none() && self.buffer.available() >= P::SIZE {
+            let payload_length = P::decode(self.buffer.consume_next(P::SIZE));
+            if payload_length > self.max_frame_length {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("frame length {} exceeds {}", payload_length, self.max_frame_length),
+                ));
+            }
+            self.payload_length = Some(payload_length);
+        }
+        if let Some(payload_length) = self.payload_length {
+            if self.buffer.available() >= payload_length {
+                self.payload_length = None;
+                return Ok(Some(self.buffer.consume_next(payload_length)));
+            }
+        }
+
+        // await for more data
+        self.buffer.read_from(stream)?;
+        Ok(None)
+    }
+}
+
+/// Encodes length prefixed frames.
+#[derive(Debug)]
+pub struct LengthPrefixedEncoder<P> {
+    prefix: PhantomData<P>,
+}
+
+impl<P: LengthPrefix> Default for LengthPrefixedEncoder<P> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<P: LengthPrefix> LengthPrefixedEncoder<P> {
+    pub const fn new() -> LengthPrefixedEncoder<P> {
+        Self { prefix: PhantomData }
+    }
+
+    /// Writes the length prefix followed by the `payload` to the `stream`.
+    #[inline]
+    pub fn send<S: Write>(&mut self, stream: &mut S, payload: &[u8]) -> io::Result<()> {
+        let mut prefix = [0u8; 8];
+        P::encode(payload.len(), &mut prefix[..P::SIZE])?;
+        stream.write_all(&prefix[..P::SIZE])?;
+        stream.write_all(payload)?;
+        stream.flush()
+    }
+}
+
+/// Stream that sends and receives length prefixed frames.
+#[derive(Debug)]
+pub struct Framed<S, P> {
+    stream: S,
+    decoder: LengthPrefixedDecoder<P>,
+    encoder: LengthPrefixedEncoder<P>,
+}
+
+impl<S: Read + Write, P: LengthPrefix> Framed<S, P> {
+    pub fn new(stream: S) -> Framed<S, P> {
+        Self {
+            stream,
+            decoder: LengthPrefixedDecoder::new(),
+            encoder: LengthPrefixedEncoder::new(),
+        }
+    }
+
+    /// Specify maximum frame payload length, see [`LengthPrefixedDecoder::with_max_frame_length`].
+    pub fn with_max_frame_length(self, max_frame_length: usize) -> Framed<S, P> {
+        Self {
+            decoder: self.decoder.with_max_frame_length(max_frame_length),
+            ..self
+        }
+    }
+
+    /// Returns the next frame payload or `None` if more data is required.
+    #[inline]
+    pub fn receive_next(&mut self) -> io::Result<Option<&'static [u8]>> {
+        self.decoder.decode_next(&mut self.stream)
+    }
+
+    /// Sends the `payload` as a single frame.
+    #[inline]
+    pub fn send(&mut self, payload: &[u8]) -> io::Result<()> {
+        self.encoder.send(&mut self.stream, payload)
+    }
+}
+
+#[cfg(feature = "mio")]
+impl<S: Source, P> Source for Framed<S, P> {
+    fn register(&mut self, registry: &Registry, token: Token, interests: Interest) -> io::Result<()> {
+        registry.register(&mut self.stream, token, interests)
+    }
+
+    fn reregister(&mut self, registry: &Registry, token: Token, interests: Interest) -> io::Result<()> {
+        registry.reregister(&mut self.stream, token, interests)
+    }
+
+    fn deregister(&mut self, registry: &Registry) -> io::Result<()> {
+        registry.deregister(&mut self.stream)
+    }
+}
+
+impl<S: Selectable, P> Selectable for Framed<S, P> {
+    fn connected(&mut self) -> io::Result<bool> {
+        self.stream.connected()
+    }
+
+    fn make_writable(&mut self) {
+        self.stream.make_writable();
+    }
+
+    fn make_readable(&mut self) {
+        self.stream.make_readable();
+    }
+
+    fn make_unreadable(&mut self) {
+        self.stream.make_unreadable();
+    }
+
+    fn socket_queues(&self) -> Option<SocketQueues> {
+        self.stream.socket_queues()
+    }
+
+    fn rx_timestamp_ns(&self) -> Option<u64> {
+        self.stream.rx_timestamp_ns()
+    }
+
+    #[cfg(feature = "stats")]
+    fn io_counters(&self) -> Option<crate::stream::IoCounters> {
+        self.stream.io_counters()
+    }
+
+    fn apply_socket_options(&mut self, options: &SocketOptions) -> io::Result<()> {
+        self.stream.apply_socket_options(options)
+    }
+
+    fn has_pending_writes(&self) -> bool {
+        self.stream.has_pending_writes()
+    }
+
+    fn flush_pending_writes(&mut self) -> io::Result<()> {
+        self.stream.flush_pending_writes()
+    }
+
+    fn peer_closed(&self) -> bool {
+        self.stream.peer_closed()
+    }
+
+    fn write_closed(&self) -> bool {
+        self.stream.write_closed()
+    }
+
+    fn make_write_closed(&mut self) {
+        self.stream.make_write_closed()
+    }
+
+    fn take_socket_error(&mut self) -> io::Result<Option<io::Error>> {
+        self.stream.take_socket_error()
+    }
+
+    fn connect_error(&self) -> Option<io::Error> {
+        self.stream.connect_error()
+    }
+}
+
+impl<S: ConnectionInfoProvider, P> ConnectionInfoProvider for Framed<S, P> {
+    fn connection_info(&self) -> &ConnectionInfo {
+        self.stream.connection_info()
+    }
+}
+
+pub trait IntoFramed {
+    fn into_framed<P: LengthPrefix>(self) -> Framed<Self, P>
+    where
+        Self: Sized;
+}
+
+impl<T> IntoFramed for T
+where
+    T: Read + Write,
+{
+    fn into_framed<P: LengthPrefix>(self) -> Framed<Self, P>
+    where
+        Self: Sized,
+    {
+        Framed::new(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use std::io::ErrorKind::WouldBlock;
+
+    use super::*;
+
+    struct MockStream {
+        input: Cursor<Vec<u8>>,
+        output: Vec<u8>,
+    }
+
+    impl Read for MockStream {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            match self.input.read(buf)? {
+                0 => Err(io::Error::from(WouldBlock)),
+                n => Ok(n),
+            }
+        }
+    }
+
+    impl Write for MockStream {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.output.write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn should_round_trip_frames() {
+        let mut writer = MockStream {
+            input: Cursor::new(Vec::new()),
+            output: Vec::new(),
+        }
+        .into_framed::<U32Be>();
+        writer.send(b"hello").unwrap();
+        writer.send(b"").unwrap();
+        writer.send(b"world!").unwrap();
+        assert_eq!(b"\x00\x00\x00\x05hello", &writer.stream.output[..9]);
+
+        let mut reader = MockStream {
+            input: Cursor::new(writer.stream.output),
+            output: Vec::new(),
+        }
+        .into_framed::<U32Be>();
+        let mut frames = Vec::new();
+        for _ in 0..8 {
+            if let Some(payload) = reader.receive_next().unwrap() {
+                frames.push(payload.to_vec());
+            }
+        }
+        assert_eq!(vec![b"hello".to_vec(), b"".to_vec(), b"world!".to_vec()], frames);
+    }
+
+    #[test]
+    fn should_reject_frame_above_max_length() {
+        let mut framed = MockStream {
+            input: Cursor::new(b"\x10\x00abc".to_vec()),
+            output: Vec::new(),
+        }
+        .into_framed::<U16Le>()
+        .with_max_frame_length(8);
+        assert!(framed.receive_next().unwrap().is_none());
+        assert_eq!(io::ErrorKind::InvalidData, framed.receive_next().unwrap_err().kind());
+    }
+}
@@ -0,0 +1,1469 @@
+//! Minimal HTTP/1.1 client for the REST calls (such as the listen key keepalive or the order book
+//! snapshot) made over the same non-blocking streams as the websockets, so that the request cycle
+//! can be driven by the `IOService` alongside the websocket endpoints (see `endpoint::http`).
+//!
+//! The requests are sent over the persistent connection and can be pipelined, the responses are
+//! returned in the order the requests have been sent. The response body is delimited by the
+//! `Content-Length`, `chunked` transfer encoding or the connection close. The body and the headers
+//! of the response are borrowed from the client buffers, or the body can be appended to the buffer
+//! owned by the caller (see [`HttpClient::poll_into`]).
+//!
+//! The `Host` header can be overridden per request and the request target can be given in the
+//! absolute form (such as `http://api.example.com/time`, as expected by the forward proxies), both
+//! are validated against the hosts the connection is known to serve (see [`HttpClient::with_allowed_host`]).
+//!
+//! The redirects are followed over the same connection up to the configured hop limit (see
+//! [`HttpClient::with_max_redirects`]). The connection the server has asked to close (`Connection:
+//! close`) or that has been idle for longer than the keep-alive window (see [`HttpClient::with_keep_alive`])
+//! is reported as closed by the peer, so that the `IOService` recreates it rather than reusing it.
+//!
+//! The `text/event-stream` response (server-sent events) is decoded incrementally, the events are
+//! returned as they arrive rather than once the body is complete (see [`EventSource`]).
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use std::net::TcpStream;
+//! use boomnet::http::HttpClient;
+//!
+//! let stream = TcpStream::connect("127.0.0.1:8080").unwrap();
+//! stream.set_nonblocking(true).unwrap();
+//! let mut client = HttpClient::new(stream, "127.0.0.1");
+//! client.send_request("GET", "/api/v3/time", &[], None).unwrap();
+//! loop {
+//!     if let Some(response) = client.poll_response().unwrap() {
+//!         println!("{} {}", response.status, String::from_utf8_lossy(response.body));
+//!         break;
+//!     }
+//! }
+//! ```
+
+use std::collections::VecDeque;
+use std::io;
+use std::io::ErrorKind::{ConnectionAborted, InvalidData, InvalidInput, UnexpectedEof, WouldBlock};
+use std::io::{Read, Write};
+use std::ops::Range;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use httparse::Status;
+#[cfg(feature = "mio")]
+use mio::{event::Source, Interest, Registry, Token};
+
+use crate::select::Selectable;
+use crate::stream::{SocketOptions, SocketQueues};
+use crate::util::current_time_nanos;
+
+/// Number of bytes the read buffer is extended by before each read.
+const READ_CHUNK_SIZE: usize = 4096;
+/// Maximum number of the response headers.
+const MAX_HEADERS: usize = 64;
+/// Maximum size of single chunk of the `chunked` body, the larger one is rejected as invalid.
+const MAX_CHUNK_SIZE: usize = 64 * 1024 * 1024;
+
+/// HTTP/1.1 client over the (typically non-blocking) `stream`, see the [module](self) documentation.
+pub struct HttpClient<S> {
+    stream: S,
+    host: String,
+    // other hosts the peer is known to serve, see `with_allowed_host`
+    allowed_hosts: Vec<String>,
+    buffer: Vec<u8>,
+    consumed: usize,
+    in_flight: VecDeque<InFlight>,
+    headers: Vec<(Range<usize>, Range<usize>)>,
+    chunked_body: Vec<u8>,
+    // position (relative to the body) of the next chunk to decode, kept until the response completes
+    chunked_pos: usize,
+    request: Vec<u8>,
+    read_closed: bool,
+    closed_by_server: bool,
+    // set once the event stream has been requested, see `send_event_stream_request`
+    event_stream: Option<EventStream>,
+    max_redirects: u32,
+    keep_alive: Option<Duration>,
+    // keep-alive timeout advised by the server with the `Keep-Alive` header
+    server_keep_alive: Option<Duration>,
+    // since when there has been no request in flight
+    idle_since_ns: u64,
+}
+
+/// Request sent for which the response has not been returned yet.
+struct InFlight {
+    // whether the response carries the body (not the case for `HEAD`)
+    has_body: bool,
+    // redirects followed so far, see `HttpClient::with_max_redirects`
+    redirects: u32,
+    // request as sent, kept to be sent again to the redirect location (only if the redirects are followed)
+    request: Vec<u8>,
+}
+
+/// Response returned by [`HttpClient::poll_response`], borrows the client buffers.
+#[derive(Debug)]
+pub struct HttpResponse<'a> {
+    /// Status code of the response, such as `200`.
+    pub status: u16,
+    /// Body of the response, already decoded if the `chunked` transfer encoding has been used.
+    pub body: &'a [u8],
+    headers: Headers<'a>,
+}
+
+impl<'a> HttpResponse<'a> {
+    /// Returns `true` if the status code is `2xx`.
+    pub const fn is_success(&self) -> bool {
+        self.status >= 200 && self.status < 300
+    }
+
+    /// Returns the value of the first header with the `name` (case insensitive).
+    pub fn header(&self, name: &str) -> Option<&'a [u8]> {
+        self.headers.get_ignore_case(name)
+    }
+
+    /// Returns the headers of the response, see [`Headers`].
+    pub const fn headers(&self) -> Headers<'a> {
+        self.headers
+    }
+}
+
+/// Event of the `text/event-stream` response returned by [`HttpClient::poll_event`], borrows the
+/// client buffers.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct SseEvent<'a> {
+    /// Type of the event, `message` unless specified by the server with the `event` field.
+    pub event: &'a str,
+    /// Data of the event, the `data` fields joined with the line feed.
+    pub data: &'a str,
+    /// Last event id set by the server with the `id` field (of this or any preceding event), sent
+    /// with the `Last-Event-ID` header when the stream is requested again (see [`EventSource`]).
+    pub id: Option<&'a str>,
+}
+
+/// Borrowed view of the response headers, the names and values are sliced out of the buffer the
+/// response has been parsed from without any copy. The names are matched exactly with
+/// [`Headers::get`], the other lookups ignore the case (as the header names are case insensitive).
+#[derive(Debug, Copy, Clone)]
+pub struct Headers<'a> {
+    buffer: &'a [u8],
+    headers: &'a [(Range<usize>, Range<usize>)],
+}
+
+impl<'a> Headers<'a> {
+    /// Number of the headers, including the duplicates.
+    pub const fn len(&self) -> usize {
+        self.headers.len()
+    }
+
+    /// Returns `true` if there are no headers.
+    pub const fn is_empty(&self) -> bool {
+        self.headers.is_empty()
+    }
+
+    /// Returns the headers in the order they have been received.
+    pub fn iter(&self) -> impl Iterator<Item = (&'a str, &'a [u8])> {
+        let buffer = self.buffer;
+        self.headers.iter().map(move |(name, value)| {
+            // header names are validated by the parser to be the ASCII tokens
+            let name = std::str::from_utf8(&buffer[name.clone()]).unwrap_or_default();
+            (name, &buffer[value.clone()])
+        })
+    }
+
+    /// Returns the value of the first header with exactly the `name`.
+    pub fn get(&self, name: &str) -> Option<&'a [u8]> {
+        self.iter().find(|(header, _)| *header == name).map(|(_, value)| value)
+    }
+
+    /// Returns the value of the first header with the `name` (case insensitive).
+    pub fn get_ignore_case(&self, name: &str) -> Option<&'a [u8]> {
+        self.get_all(name).next()
+    }
+
+    /// Returns the values of all the headers with the `name` (case insensitive), such as the
+    /// repeated `Set-Cookie`, in the order they have been received.
+    pub fn get_all<'n>(&self, name: &'n str) -> impl Iterator<Item = &'a [u8]> + 'n
+    where
+        'a: 'n,
+    {
+        self.iter()
+            .filter(move |(header, _)| header.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value)
+    }
+
+    /// Parses the value of the first header with the `name` (case insensitive) as the unsigned
+    /// integer, such as the `Content-Length` or the venue rate limit counters. Returns `None` if the
+    /// header is missing or not a number.
+    pub fn get_u64(&self, name: &str) -> Option<u64> {
+        std::str::from_utf8(self.get_ignore_case(name)?)
+            .ok()?
+            .trim()
+            .parse()
+            .ok()
+    }
+
+    /// Parses the value of the first header with the `name` (case insensitive) as the HTTP date in
+    /// the IMF-fixdate format (such as `Sun, 06 Nov 1994 08:49:37 GMT`), typically the `Date` header
+    /// used to estimate the clock offset to the venue. The obsolete date formats are not supported.
+    pub fn get_date(&self, name: &str) -> Option<SystemTime> {
+        parse_http_date(self.get_ignore_case(name)?)
+    }
+}
+
+/// Parses the IMF-fixdate (RFC 9110), such as `Sun, 06 Nov 1994 08:49:37 GMT`.
+fn parse_http_date(value: &[u8]) -> Option<SystemTime> {
+    const MONTHS: [&[u8]; 12] = [
+        b"Jan", b"Feb", b"Mar", b"Apr", b"May", b"Jun", b"Jul", b"Aug", b"Sep", b"Oct", b"Nov", b"Dec",
+    ];
+    let start = value
+        .iter()
+        .position(|byte| !byte.is_ascii_whitespace())
+        .unwrap_or(value.len());
+    let end = value
+        .iter()
+        .rposition(|byte| !byte.is_ascii_whitespace())
+        .map_or(start, |pos| pos + 1);
+    let value = &value[start..end];
+    if value.len() != 29 || &value[3..5] != b", " || &value[25..] != b" GMT" {
+        return None;
+    }
+    let number = |range: Range<usize>| -> Option<u64> {
+        value[range].iter().try_fold(0u64, |acc, byte| match byte {
+            b'0'..=b'9' => Some(acc * 10 + (byte - b'0') as u64),
+            _ => None,
+        })
+    };
+    let day = number(5..7)?;
+    let month = MONTHS.iter().position(|month| *month == &value[8..11])? as u64 + 1;
+    let year = number(12..16)?;
+    let (hour, minute, second) = (number(17..19)?, number(20..22)?, number(23..25)?);
+    if value[7] != b' ' || value[11] != b' ' || value[16] != b' ' || value[19] != b':' || value[22] != b':' {
+        return None;
+    }
+    if year < 1970 || !(1..=31).contains(&day) || hour > 23 || minute > 59 || second > 60 {
+        return None;
+    }
+    // days since the epoch of the proleptic Gregorian calendar date (with the year starting in March)
+    let (y, m) = if month <= 2 {
+        (year - 1, month + 9)
+    } else {
+        (year, month - 3)
+    };
+    let (era, year_of_era) = (y / 400, y % 400);
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + (153 * m + 2) / 5 + day - 1;
+    let days = (era * 146097 + day_of_era).checked_sub(719468)?;
+    let secs = days * 86400 + hour * 3600 + minute * 60 + second;
+    Some(UNIX_EPOCH + Duration::from_secs(secs))
+}
+
+/// Head of the response, the headers are kept by the client (see `HttpClient::parse_head`).
+struct Head {
+    status: u16,
+    len: usize,
+    content_length: Option<usize>,
+    chunked: bool,
+    close: bool,
+}
+
+/// Decoding state of the `text/event-stream` response, see [`HttpClient::poll_event`].
+struct EventStream {
+    // set once the response head has been received
+    started: bool,
+    chunked: bool,
+    // decoded body not parsed into the events yet
+    body: Vec<u8>,
+    event: String,
+    data: String,
+    last_event_id: String,
+    retry: Option<Duration>,
+    // fields of the event returned are cleared before the next one is parsed
+    dispatched: bool,
+}
+
+impl EventStream {
+    fn new(last_event_id: &str) -> EventStream {
+        Self {
+            started: false,
+            chunked: false,
+            body: Vec::new(),
+            event: String::new(),
+            data: String::new(),
+            last_event_id: last_event_id.to_owned(),
+            retry: None,
+            dispatched: false,
+        }
+    }
+
+    /// Parses the complete lines of the body up to the end of the next event, returns `true` if
+    /// the event is ready to be dispatched.
+    fn parse(&mut self) -> bool {
+        if self.dispatched {
+            self.event.clear();
+            self.data.clear();
+            self.dispatched = false;
+        }
+        let mut consumed = 0;
+        while let Some(end) = self.body[consumed..].iter().position(|&byte| byte == b'\n') {
+            let line = &self.body[consumed..consumed + end];
+            let line = line.strip_suffix(b"\r").unwrap_or(line);
+            consumed += end + 1;
+            if line.is_empty() {
+                // the event without any data is not dispatched
+                if !self.data.is_empty() {
+                    self.data.pop();
+                    self.dispatched = true;
+                    break;
+                }
+                self.event.clear();
+                continue;
+            }
+            let (field, value) = match line.iter().position(|&byte| byte == b':') {
+                // comment, typically sent to keep the connection alive
+                Some(0) => continue,
+                Some(pos) => (&line[..pos], &line[pos + 1..]),
+                None => (line, &line[line.len()..]),
+            };
+            let value = String::from_utf8_lossy(value.strip_prefix(b" ").unwrap_or(value));
+            match field {
+                b"event" => {
+                    self.event.clear();
+                    self.event.push_str(&value);
+                }
+                b"data" => {
+                    self.data.push_str(&value);
+                    self.data.push('\n');
+                }
+                b"id" if !value.contains('\0') => {
+                    self.last_event_id.clear();
+                    self.last_event_id.push_str(&value);
+                }
+                b"retry" if !value.is_empty() && value.bytes().all(|byte| byte.is_ascii_digit()) => {
+                    self.retry = value.parse().ok().map(Duration::from_millis);
+                }
+                _ => {}
+            }
+        }
+        self.body.drain(..consumed);
+        self.dispatched
+    }
+
+    fn event(&self) -> SseEvent<'_> {
+        SseEvent {
+            event: match self.event.is_empty() {
+                true => "message",
+                false => &self.event,
+            },
+            data: &self.data,
+            id: (!self.last_event_id.is_empty()).then_some(self.last_event_id.as_str()),
+        }
+    }
+}
+
+impl<S> HttpClient<S> {
+    /// Creates the client over the connected `stream`, the `host` is sent with each request unless
+    /// overridden (see [`HttpClient::send_request`]).
+    pub fn new(stream: S, host: &str) -> HttpClient<S> {
+        Self {
+            stream,
+            host: host.to_owned(),
+            allowed_hosts: Vec::new(),
+            buffer: Vec::new(),
+            consumed: 0,
+            in_flight: VecDeque::new(),
+            headers: Vec::new(),
+            chunked_body: Vec::new(),
+            chunked_pos: 0,
+            request: Vec::new(),
+            read_closed: false,
+            closed_by_server: false,
+            event_stream: None,
+            max_redirects: 0,
+            keep_alive: None,
+            server_keep_alive: None,
+            idle_since_ns: current_time_nanos(),
+        }
+    }
+
+    /// Allows the requests to name the `host` (with the `Host` header override or the absolute-form
+    /// request target) in addition to the one the client has been created for, such as the virtual
+    /// host served by the gateway or the origin reached through the forward proxy the stream is
+    /// connected to. Any other host is rejected, as the connection peer would not serve it.
+    pub fn with_allowed_host(self, host: &str) -> HttpClient<S> {
+        let mut client = self;
+        client.allowed_hosts.push(host.to_owned());
+        client
+    }
+
+    /// Follow at most `max_redirects` consecutive redirects (`3xx` response with the `Location`) of
+    /// each request (default is none). The redirect is followed over the same connection, so only
+    /// the location on the same host is followed, the method and the body are kept by `307` and `308`
+    /// while the other redirects are only followed for `GET` and `HEAD`. The redirect of the request
+    /// pipelined with the others is not followed, so that the responses are still returned in order.
+    /// The redirect response that is not followed is returned as any other.
+    pub fn with_max_redirects(self, max_redirects: u32) -> HttpClient<S> {
+        Self { max_redirects, ..self }
+    }
+
+    /// Recycle the connection once it has been idle (no request in flight) for the `keep_alive`
+    /// (or the shorter timeout advised by the server with the `Keep-Alive` header), as the server is
+    /// likely to close it. The connection is then reported as closed by the peer (see
+    /// [`Selectable::peer_closed`]) and no more requests can be sent.
+    pub fn with_keep_alive(self, keep_alive: Duration) -> HttpClient<S> {
+        Self {
+            keep_alive: Some(keep_alive),
+            ..self
+        }
+    }
+
+    /// Number of requests sent for which the response has not been returned yet.
+    pub fn pending_requests(&self) -> usize {
+        self.in_flight.len()
+    }
+
+    pub fn host(&self) -> &str {
+        &self.host
+    }
+
+    pub const fn stream(&self) -> &S {
+        &self.stream
+    }
+
+    pub fn stream_mut(&mut self) -> &mut S {
+        &mut self.stream
+    }
+
+    /// Checks if the connection has been idle for longer than the keep-alive window.
+    fn keep_alive_expired(&self) -> bool {
+        let Some(keep_alive) = self.keep_alive.into_iter().chain(self.server_keep_alive).min() else {
+            return false;
+        };
+        self.in_flight.is_empty()
+            && self.event_stream.is_none()
+            && current_time_nanos().saturating_sub(self.idle_since_ns) >= keep_alive.as_nanos() as u64
+    }
+}
+
+impl<S: Read + Write> HttpClient<S> {
+    /// Sends the request for the `path` (including the query string) with the additional `headers`.
+    /// The `Content-Length` header is only sent if the `body` is present, pass the empty body if the
+    /// server requires it.
+    ///
+    /// The `Host` header passed with the `headers` replaces the one the client has been created for
+    /// and the `path` can also be the absolute uri (such as `http://api.example.com/time`), whose
+    /// authority has to match the `Host`. Fails with the [`InvalidInput`] error if the host is not
+    /// served by the connection peer (see [`HttpClient::with_allowed_host`]).
+    pub fn send_request(
+        &mut self,
+        method: &str,
+        path: &str,
+        headers: &[(&str, &str)],
+        body: Option<&[u8]>,
+    ) -> io::Result<()> {
+        if self.closed_by_server {
+            return Err(closed_by_server());
+        }
+        if self.event_stream.is_some() {
+            return Err(dedicated_to_event_stream());
+        }
+        if self.keep_alive_expired() {
+            return Err(io::Error::new(ConnectionAborted, "idle connection recycled"));
+        }
+        let is_host = |name: &str| name.eq_ignore_ascii_case("host");
+        let host = match headers.iter().find(|(name, _)| is_host(name)) {
+            Some((_, host)) => *host,
+            None => self.host.as_str(),
+        };
+        if let Some(authority) = absolute_authority(path)? {
+            if !authority.eq_ignore_ascii_case(host) {
+                return Err(io::Error::new(InvalidInput, "request target does not match the host"));
+            }
+        }
+        if !host.eq_ignore_ascii_case(&self.host)
+            && !self
+                .allowed_hosts
+                .iter()
+                .any(|allowed| allowed.eq_ignore_ascii_case(host))
+        {
+            return Err(io::Error::new(InvalidInput, "host is not served by the connection peer"));
+        }
+        self.request.clear();
+        write!(self.request, "{method} {path} HTTP/1.1\r\nHost: {host}\r\n")?;
+        for (name, value) in headers.iter().filter(|(name, _)| !is_host(name)) {
+            write!(self.request, "{name}: {value}\r\n")?;
+        }
+        if let Some(body) = body {
+            write!(self.request, "Content-Length: {}\r\n", body.len())?;
+        }
+        self.request.extend_from_slice(b"\r\n");
+        if let Some(body) = body {
+            self.request.extend_from_slice(body);
+        }
+        self.stream.write_all(&self.request)?;
+        self.stream.flush()?;
+        self.in_flight.push_back(InFlight {
+            has_body: !method.eq_ignore_ascii_case("HEAD"),
+            redirects: 0,
+            request: match self.max_redirects {
+                0 => Vec::new(),
+                _ => self.request.clone(),
+            },
+        });
+        Ok(())
+    }
+
+    /// Reads from the stream and returns the next complete response, if any. Should be called on
+    /// each duty cycle (also when no request is pending) to drive the I/O of the underlying stream.
+    /// Once the server has asked to close the connection (`Connection: close`) the subsequent calls
+    /// fail with the [`ConnectionAborted`] error.
+    pub fn poll_response(&mut self) -> io::Result<Option<HttpResponse<'_>>> {
+        let Some((status, body)) = self.next_response()? else {
+            return Ok(None);
+        };
+        Ok(Some(HttpResponse {
+            status,
+            body: match body {
+                Some(body) => &self.buffer[body],
+                None => &self.chunked_body,
+            },
+            headers: self.response_headers(),
+        }))
+    }
+
+    /// Same as [`HttpClient::poll_response`] but appends the body of the response to the `body`
+    /// buffer owned by the caller (such as the one reused for the order book snapshots) and returns
+    /// the status with the headers. The decoded `chunked` body is handed over without any copy if
+    /// the `body` is empty.
+    pub fn poll_into(&mut self, body: &mut Vec<u8>) -> io::Result<Option<(u16, Headers<'_>)>> {
+        let Some((status, range)) = self.next_response()? else {
+            return Ok(None);
+        };
+        match range {
+            Some(range) => body.extend_from_slice(&self.buffer[range]),
+            None if body.is_empty() => std::mem::swap(body, &mut self.chunked_body),
+            None => body.extend_from_slice(&self.chunked_body),
+        }
+        Ok(Some((status, self.response_headers())))
+    }
+
+    /// Returns the status and the body range (`None` if decoded into the chunked body buffer) of
+    /// the next complete response, which stays in the buffer until the next call.
+    fn next_response(&mut self) -> io::Result<Option<(u16, Option<Range<usize>>)>> {
+        if self.event_stream.is_some() {
+            return Err(dedicated_to_event_stream());
+        }
+        // the previously returned response is no longer borrowed
+        if self.consumed > 0 {
+            self.buffer.drain(..self.consumed);
+            self.consumed = 0;
+            self.chunked_body.clear();
+            self.chunked_pos = 0;
+        }
+        if self.closed_by_server {
+            return Err(closed_by_server());
+        }
+        self.read_stream()?;
+
+        let (status, body, len, close) = loop {
+            let Some(response) = self.parse_response()? else {
+                if self.read_closed && !(self.buffer.is_empty() && self.in_flight.is_empty()) {
+                    return Err(io::Error::new(UnexpectedEof, "connection closed before the response was complete"));
+                }
+                return Ok(None);
+            };
+            match response {
+                // informational response (such as `100 Continue`) is followed by the final one
+                (status, _, len, _) if (100..200).contains(&status) => {
+                    self.buffer.drain(..len);
+                }
+                (status, _, len, false) if self.follow_redirect(status)? => {
+                    self.buffer.drain(..len);
+                    self.chunked_body.clear();
+                    self.chunked_pos = 0;
+                }
+                response => break response,
+            }
+        };
+
+        self.in_flight.pop_front();
+        if self.in_flight.is_empty() {
+            self.idle_since_ns = current_time_nanos();
+        }
+        self.consumed = len;
+        self.closed_by_server = close;
+        Ok(Some((status, body)))
+    }
+
+    /// Sends the request again to the location of the redirect response, returns `false` if the
+    /// redirect is not followed (see [`HttpClient::with_max_redirects`]).
+    fn follow_redirect(&mut self, status: u16) -> io::Result<bool> {
+        if !matches!(status, 301 | 302 | 303 | 307 | 308) || self.in_flight.len() != 1 {
+            return Ok(false);
+        }
+        let Some(in_flight) = self.in_flight.front() else {
+            return Ok(false);
+        };
+        if in_flight.redirects >= self.max_redirects {
+            return Ok(false);
+        }
+        // request line and the `Host` header as written by `send_request`
+        let mut parts = in_flight.request.splitn(3, |&byte| byte == b' ');
+        let (Some(method), Some(_), Some(rest)) = (parts.next(), parts.next(), parts.next()) else {
+            return Ok(false);
+        };
+        if (301..=303).contains(&status) && method != b"GET" && method != b"HEAD" {
+            return Ok(false);
+        }
+        let host = rest
+            .strip_prefix(b"HTTP/1.1\r\nHost: ")
+            .and_then(|host| host.split(|&byte| byte == b'\r').next())
+            .unwrap_or_default();
+        let headers = Headers {
+            buffer: &self.buffer,
+            headers: &self.headers,
+        };
+        let Some(location) = headers.get_ignore_case("location") else {
+            return Ok(false);
+        };
+        let location = std::str::from_utf8(location)
+            .map_err(|_| io::Error::new(InvalidData, "invalid redirect location"))?
+            .trim();
+        // the location on another host cannot be reached over this connection
+        match absolute_authority(location) {
+            Ok(Some(authority)) if authority.as_bytes().eq_ignore_ascii_case(host) => {}
+            Ok(None) if location.starts_with('/') => {}
+            _ => return Ok(false),
+        }
+
+        let mut request = Vec::with_capacity(in_flight.request.len() + location.len());
+        request.extend_from_slice(method);
+        request.push(b' ');
+        request.extend_from_slice(location.as_bytes());
+        request.push(b' ');
+        request.extend_from_slice(rest);
+        let redirected = InFlight {
+            has_body: in_flight.has_body,
+            redirects: in_flight.redirects + 1,
+            request,
+        };
+        self.stream.write_all(&redirected.request)?;
+        self.stream.flush()?;
+        self.in_flight[0] = redirected;
+        Ok(true)
+    }
+
+    fn response_headers(&self) -> Headers<'_> {
+        Headers {
+            buffer: &self.buffer,
+            headers: &self.headers,
+        }
+    }
+
+    /// Requests the `text/event-stream` (server-sent events) from the `path` with the additional
+    /// `headers`, resuming after the `last_event_id` if present. The events are then returned by
+    /// [`HttpClient::poll_event`] as they arrive. The connection is dedicated to the stream, so no
+    /// other request can be in flight or sent afterwards. Use the [`EventSource`] to request the
+    /// stream again with the last event id once the connection has been recreated.
+    pub fn send_event_stream_request(
+        &mut self,
+        path: &str,
+        headers: &[(&str, &str)],
+        last_event_id: Option<&str>,
+    ) -> io::Result<()> {
+        if !self.in_flight.is_empty() {
+            return Err(io::Error::new(InvalidInput, "event stream cannot be pipelined with other requests"));
+        }
+        let mut stream_headers = Vec::with_capacity(headers.len() + 3);
+        stream_headers.extend_from_slice(headers);
+        stream_headers.push(("Accept", "text/event-stream"));
+        stream_headers.push(("Cache-Control", "no-cache"));
+        if let Some(last_event_id) = last_event_id {
+            stream_headers.push(("Last-Event-ID", last_event_id));
+        }
+        self.send_request("GET", path, &stream_headers, None)?;
+        self.event_stream = Some(EventStream::new(last_event_id.unwrap_or_default()));
+        Ok(())
+    }
+
+    /// Reads from the stream and returns the next event of the stream requested with
+    /// [`HttpClient::send_event_stream_request`], if any. Should be called on each duty cycle. Fails
+    /// if the server has not responded with the `2xx` status and once the stream has ended, so that
+    /// the connection is recreated. The lines are expected to end with the line feed, optionally
+    /// preceded by the carriage return.
+    pub fn poll_event(&mut self) -> io::Result<Option<SseEvent<'_>>> {
+        if self.closed_by_server {
+            return Err(closed_by_server());
+        }
+        let started = match &self.event_stream {
+            Some(events) => events.started,
+            None => return Err(io::Error::new(InvalidInput, "event stream has not been requested")),
+        };
+        self.read_stream()?;
+
+        if !started {
+            let head = loop {
+                let Some(head) = self.parse_head()? else {
+                    if self.read_closed {
+                        return Err(io::Error::new(
+                            UnexpectedEof,
+                            "connection closed before the response was complete",
+                        ));
+                    }
+                    return Ok(None);
+                };
+                self.buffer.drain(..head.len);
+                // informational response (such as `100 Continue`) is followed by the final one
+                if !(100..200).contains(&head.status) {
+                    break head;
+                }
+            };
+            self.in_flight.pop_front();
+            if !(200..300).contains(&head.status) {
+                self.closed_by_server = true;
+                return Err(io::Error::other(format!("event stream request failed with status {}", head.status)));
+            }
+            if let Some(events) = self.event_stream.as_mut() {
+                events.started = true;
+                events.chunked = head.chunked;
+            }
+        }
+
+        let Some(events) = self.event_stream.as_mut() else {
+            return Ok(None);
+        };
+        let ended = match events.chunked {
+            true => {
+                let ended = decode_chunked(&self.buffer, &mut events.body, &mut self.chunked_pos)?.is_some();
+                self.buffer.drain(..self.chunked_pos);
+                self.chunked_pos = 0;
+                ended
+            }
+            false => {
+                events.body.extend_from_slice(&self.buffer);
+                self.buffer.clear();
+                self.read_closed
+            }
+        };
+        if events.parse() {
+            return Ok(Some(events.event()));
+        }
+        if ended {
+            self.closed_by_server = true;
+            return Err(io::Error::new(UnexpectedEof, "event stream closed by server"));
+        }
+        Ok(None)
+    }
+
+    fn read_stream(&mut self) -> io::Result<()> {
+        if self.read_closed {
+            return Ok(());
+        }
+        let len = self.buffer.len();
+        self.buffer.resize(len + READ_CHUNK_SIZE, 0);
+        let read = match self.stream.read(&mut self.buffer[len..]) {
+            Ok(read) => {
+                self.read_closed = read == 0;
+                read
+            }
+            Err(err) if err.kind() == WouldBlock => 0,
+            Err(err) => {
+                self.buffer.truncate(len);
+                return Err(err);
+            }
+        };
+        self.buffer.truncate(len + read);
+        Ok(())
+    }
+
+    /// Parses the response at the start of the buffer, returns the status, the body range (`None`
+    /// if decoded into the chunked body buffer), the length of the whole response and whether the
+    /// server has asked to close the connection, or `None` if the response is not complete yet.
+    #[allow(clippy::type_complexity)]
+    fn parse_response(&mut self) -> io::Result<Option<(u16, Option<Range<usize>>, usize, bool)>> {
+        let Some(head) = self.parse_head()? else {
+            return Ok(None);
+        };
+        let Head {
+            status,
+            len: head_len,
+            content_length,
+            chunked,
+            close,
+        } = head;
+
+        let has_body = self.in_flight.front().is_some_and(|in_flight| in_flight.has_body)
+            && !(100..200).contains(&status)
+            && status != 204
+            && status != 304;
+        let response = match (has_body, chunked, content_length) {
+            (false, _, _) => Some((status, Some(head_len..head_len), head_len, close)),
+            (true, true, _) => decode_chunked(&self.buffer[head_len..], &mut self.chunked_body, &mut self.chunked_pos)?
+                .map(|len| (status, None, head_len + len, close)),
+            (true, false, Some(length)) => {
+                let len = head_len + length;
+                (self.buffer.len() >= len).then_some((status, Some(head_len..len), len, close))
+            }
+            // delimited by the connection close
+            (true, false, None) => {
+                let len = self.buffer.len();
+                self.read_closed.then_some((status, Some(head_len..len), len, true))
+            }
+        };
+        Ok(response)
+    }
+
+    /// Parses the head of the response at the start of the buffer into the `headers`, or returns
+    /// `None` if it is not complete yet.
+    fn parse_head(&mut self) -> io::Result<Option<Head>> {
+        let mut headers = [httparse::EMPTY_HEADER; MAX_HEADERS];
+        let mut response = httparse::Response::new(&mut headers);
+        let head_len = match response
+            .parse(&self.buffer)
+            .map_err(|err| io::Error::new(InvalidData, err))?
+        {
+            Status::Complete(head_len) => head_len,
+            Status::Partial => return Ok(None),
+        };
+        let status = response.code.unwrap_or_default();
+        if self.in_flight.is_empty() {
+            return Err(io::Error::new(InvalidData, "received response without request"));
+        }
+
+        let base = self.buffer.as_ptr() as usize;
+        let mut content_length = None;
+        let mut chunked = false;
+        let mut close = false;
+        self.headers.clear();
+        for header in response.headers.iter() {
+            let name = header.name.as_ptr() as usize - base;
+            let value = header.value.as_ptr() as usize - base;
+            self.headers
+                .push((name..name + header.name.len(), value..value + header.value.len()));
+            if header.name.eq_ignore_ascii_case("content-length") {
+                let length = std::str::from_utf8(header.value)
+                    .ok()
+                    .and_then(|length| length.trim().parse::<usize>().ok());
+                content_length = Some(length.ok_or_else(|| io::Error::new(InvalidData, "invalid content length"))?);
+            } else if header.name.eq_ignore_ascii_case("transfer-encoding") {
+                chunked = header.value.ends_with(b"chunked");
+            } else if header.name.eq_ignore_ascii_case("connection") {
+                close = header.value.eq_ignore_ascii_case(b"close");
+            } else if header.name.eq_ignore_ascii_case("keep-alive") {
+                self.server_keep_alive = parse_keep_alive_timeout(header.value).or(self.server_keep_alive);
+            }
+        }
+
+        Ok(Some(Head {
+            status,
+            len: head_len,
+            content_length,
+            chunked,
+            close,
+        }))
+    }
+}
+
+/// Requests the event stream (see [`HttpClient::send_event_stream_request`]) and keeps track of the
+/// last event id and the reconnection delay advised by the server across the connections, so that
+/// the stream is resumed with the `Last-Event-ID` once the connection has been recreated (such as
+/// by the `IOService` when the stream ends).
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::net::TcpStream;
+/// use boomnet::http::{EventSource, HttpClient};
+///
+/// let mut events = EventSource::new("/prices").with_header("Authorization", "Bearer token");
+/// loop {
+///     let stream = TcpStream::connect("127.0.0.1:8080").unwrap();
+///     stream.set_nonblocking(true).unwrap();
+///     let mut client = HttpClient::new(stream, "127.0.0.1");
+///     // resumed after the last event received over the previous connection
+///     events.subscribe(&mut client).unwrap();
+///     while let Ok(event) = events.poll(&mut client) {
+///         if let Some(event) = event {
+///             println!("{} {}", event.event, event.data);
+///         }
+///     }
+///     std::thread::sleep(events.retry().unwrap_or_default());
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct EventSource {
+    path: String,
+    headers: Vec<(String, String)>,
+    last_event_id: Option<String>,
+    retry: Option<Duration>,
+}
+
+impl EventSource {
+    /// Creates the event source for the stream at the `path` (including the query string).
+    pub fn new(path: &str) -> EventSource {
+        Self {
+            path: path.to_owned(),
+            headers: Vec::new(),
+            last_event_id: None,
+            retry: None,
+        }
+    }
+
+    /// Adds the header sent each time the stream is requested.
+    pub fn with_header(self, name: &str, value: &str) -> EventSource {
+        let mut source = self;
+        source.headers.push((name.to_owned(), value.to_owned()));
+        source
+    }
+
+    /// Requests the stream over the fresh `client`, resuming after the last event received over
+    /// any of the previous connections.
+    pub fn subscribe<S: Read + Write>(&self, client: &mut HttpClient<S>) -> io::Result<()> {
+        let headers = self
+            .headers
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+            .collect::<Vec<_>>();
+        client.send_event_stream_request(&self.path, &headers, self.last_event_id.as_deref())
+    }
+
+    /// Returns the next event of the stream, see [`HttpClient::poll_event`].
+    pub fn poll<'a, S: Read + Write>(&mut self, client: &'a mut HttpClient<S>) -> io::Result<Option<SseEvent<'a>>> {
+        // the id and the retry can also be set without the event being dispatched
+        if let Some(events) = &client.event_stream {
+            self.retry = events.retry;
+            self.update_last_event_id((!events.last_event_id.is_empty()).then_some(events.last_event_id.as_str()));
+        }
+        let event = client.poll_event()?;
+        if let Some(event) = event {
+            self.update_last_event_id(event.id);
+        }
+        Ok(event)
+    }
+
+    /// Id of the last event received, if any.
+    pub fn last_event_id(&self) -> Option<&str> {
+        self.last_event_id.as_deref()
+    }
+
+    /// Reconnection delay advised by the server with the `retry` field, if any.
+    pub const fn retry(&self) -> Option<Duration> {
+        self.retry
+    }
+
+    fn update_last_event_id(&mut self, last_event_id: Option<&str>) {
+        if self.last_event_id.as_deref() != last_event_id {
+            self.last_event_id = last_event_id.map(str::to_owned);
+        }
+    }
+}
+
+/// Decodes the `chunked` body from the `data` into the `body`, resuming at the `pos` of the first
+/// chunk not decoded yet (so that each chunk is only decoded once, as the body arrives). Returns the
+/// length of the encoded body (including the trailers) or `None` if it is not complete yet.
+fn decode_chunked(data: &[u8], body: &mut Vec<u8>, pos: &mut usize) -> io::Result<Option<usize>> {
+    loop {
+        let (len, size) = match httparse::parse_chunk_size(&data[*pos..]) {
+            Ok(Status::Complete(chunk)) => chunk,
+            Ok(Status::Partial) => return Ok(None),
+            Err(_) => return Err(io::Error::new(InvalidData, "invalid chunk size")),
+        };
+        let start = *pos + len;
+        if size == 0 {
+            let mut trailers = [httparse::EMPTY_HEADER; MAX_HEADERS];
+            return match httparse::parse_headers(&data[start..], &mut trailers) {
+                Ok(Status::Complete((len, _))) => Ok(Some(start + len)),
+                Ok(Status::Partial) => Ok(None),
+                Err(err) => Err(io::Error::new(InvalidData, err)),
+            };
+        }
+        let end = usize::try_from(size)
+            .ok()
+            .filter(|&size| size <= MAX_CHUNK_SIZE)
+            .and_then(|size| start.checked_add(size))
+            .ok_or_else(|| io::Error::new(InvalidData, "invalid chunk size"))?;
+        let terminator_end = end
+            .checked_add(2)
+            .ok_or_else(|| io::Error::new(InvalidData, "invalid chunk size"))?;
+        match data.get(end..terminator_end) {
+            Some(b"\r\n") => body.extend_from_slice(&data[start..end]),
+            Some(_) => return Err(io::Error::new(InvalidData, "invalid chunk terminator")),
+            None => return Ok(None),
+        }
+        *pos = terminator_end;
+    }
+}
+
+/// Parses the `timeout` parameter of the `Keep-Alive` header, such as `timeout=5, max=1000`.
+fn parse_keep_alive_timeout(value: &[u8]) -> Option<Duration> {
+    std::str::from_utf8(value)
+        .ok()?
+        .split(',')
+        .filter_map(|param| param.split_once('='))
+        .find(|(name, _)| name.trim().eq_ignore_ascii_case("timeout"))
+        .and_then(|(_, timeout)| timeout.trim().parse().ok())
+        .map(Duration::from_secs)
+}
+
+/// Returns the authority of the absolute-form request `target` (such as `http://api.example.com/time`)
+/// or `None` if the target is the path.
+fn absolute_authority(target: &str) -> io::Result<Option<&str>> {
+    let Some((scheme, rest)) = target.split_once("://") else {
+        return Ok(None);
+    };
+    if !scheme.eq_ignore_ascii_case("http") && !scheme.eq_ignore_ascii_case("https") {
+        return Err(io::Error::new(InvalidInput, "unsupported request target scheme"));
+    }
+    let authority = &rest[..rest.find(['/', '?', '#']).unwrap_or(rest.len())];
+    if authority.is_empty() || authority.contains('@') {
+        return Err(io::Error::new(InvalidInput, "invalid request target authority"));
+    }
+    Ok(Some(authority))
+}
+
+#[cold]
+fn closed_by_server() -> io::Error {
+    io::Error::new(ConnectionAborted, "connection closed by server")
+}
+
+#[cold]
+fn dedicated_to_event_stream() -> io::Error {
+    io::Error::new(InvalidInput, "connection is dedicated to the event stream")
+}
+
+#[cfg(feature = "mio")]
+impl<S: Source> Source for HttpClient<S> {
+    fn register(&mut self, registry: &Registry, token: Token, interests: Interest) -> io::Result<()> {
+        registry.register(&mut self.stream, token, interests)
+    }
+
+    fn reregister(&mut self, registry: &Registry, token: Token, interests: Interest) -> io::Result<()> {
+        registry.reregister(&mut self.stream, token, interests)
+    }
+
+    fn deregister(&mut self, registry: &Registry) -> io::Result<()> {
+        registry.deregister(&mut self.stream)
+    }
+}
+
+impl<S: Selectable> Selectable for HttpClient<S> {
+    fn connected(&mut self) -> io::Result<bool> {
+        self.stream.connected()
+    }
+
+    fn make_writable(&mut self) {
+        self.stream.make_writable();
+    }
+
+    fn make_readable(&mut self) {
+        self.stream.make_readable();
+    }
+
+    fn make_unreadable(&mut self) {
+        self.stream.make_unreadable();
+    }
+
+    fn socket_queues(&self) -> Option<SocketQueues> {
+        self.stream.socket_queues()
+    }
+
+    fn rx_timestamp_ns(&self) -> Option<u64> {
+        self.stream.rx_timestamp_ns()
+    }
+
+    #[cfg(feature = "stats")]
+    fn io_counters(&self) -> Option<crate::stream::IoCounters> {
+        self.stream.io_counters()
+    }
+
+    fn apply_socket_options(&mut self, options: &SocketOptions) -> io::Result<()> {
+        self.stream.apply_socket_options(options)
+    }
+
+    fn has_pending_writes(&self) -> bool {
+        self.stream.has_pending_writes()
+    }
+
+    fn flush_pending_writes(&mut self) -> io::Result<()> {
+        self.stream.flush_pending_writes()
+    }
+
+    fn peer_closed(&self) -> bool {
+        // retired once the server has asked to close the connection or it has been idle for too long
+        self.closed_by_server || self.keep_alive_expired() || self.stream.peer_closed()
+    }
+
+    fn write_closed(&self) -> bool {
+        self.stream.write_closed()
+    }
+
+    fn make_write_closed(&mut self) {
+        self.stream.make_write_closed()
+    }
+
+    fn take_socket_error(&mut self) -> io::Result<Option<io::Error>> {
+        self.stream.take_socket_error()
+    }
+
+    fn connect_error(&self) -> Option<io::Error> {
+        self.stream.connect_error()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    // serves the canned responses one byte at a time and captures the requests
+    struct Server {
+        responses: Cursor<Vec<u8>>,
+        requests: Vec<u8>,
+    }
+
+    impl Server {
+        fn new(responses: &[u8]) -> Server {
+            Self {
+                responses: Cursor::new(responses.to_vec()),
+                requests: Vec::new(),
+            }
+        }
+    }
+
+    impl Read for Server {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.responses.read(&mut buf[..1])
+        }
+    }
+
+    impl Write for Server {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.requests.write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Selectable for Server {
+        fn connected(&mut self) -> io::Result<bool> {
+            Ok(true)
+        }
+
+        fn make_writable(&mut self) {}
+
+        fn make_readable(&mut self) {}
+    }
+
+    fn next_response<S: Read + Write>(client: &mut HttpClient<S>) -> (u16, Vec<u8>, Option<Vec<u8>>) {
+        loop {
+            if let Some(response) = client.poll_response().unwrap() {
+                let header = response.header("x-test").map(<[u8]>::to_vec);
+                return (response.status, response.body.to_vec(), header);
+            }
+        }
+    }
+
+    #[test]
+    fn should_return_pipelined_responses() {
+        let mut client = HttpClient::new(
+            Server::new(
+                b"HTTP/1.1 100 Continue\r\n\r\n\
+                HTTP/1.1 200 OK\r\nContent-Length: 5\r\nX-Test: a\r\n\r\nhello\
+                HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\n\
+                HTTP/1.1 201 Created\r\nTransfer-Encoding: chunked\r\nx-test: b\r\n\r\n\
+                4\r\nwiki\r\n5;ext=1\r\npedia\r\n0\r\nTrailer: 1\r\n\r\n\
+                HTTP/1.1 204 No Content\r\n\r\n",
+            ),
+            "api.example.com",
+        );
+        client
+            .send_request("POST", "/order", &[("X-Key", "k")], Some(b"{}"))
+            .unwrap();
+        client.send_request("HEAD", "/order", &[], None).unwrap();
+        client.send_request("GET", "/order?id=1", &[], None).unwrap();
+        client.send_request("DELETE", "/order", &[], None).unwrap();
+        assert_eq!(4, client.pending_requests());
+        assert!(client.stream().requests.starts_with(
+            b"POST /order HTTP/1.1\r\nHost: api.example.com\r\nX-Key: k\r\nContent-Length: 2\r\n\r\n{}\
+            HEAD /order HTTP/1.1\r\nHost: api.example.com\r\n\r\n"
+        ));
+
+        assert_eq!((200, b"hello".to_vec(), Some(b"a".to_vec())), next_response(&mut client));
+        // response to the `HEAD` request has no body
+        assert_eq!((200, vec![], None), next_response(&mut client));
+        assert_eq!((201, b"wikipedia".to_vec(), Some(b"b".to_vec())), next_response(&mut client));
+        assert_eq!((204, vec![], None), next_response(&mut client));
+        assert_eq!(0, client.pending_requests());
+        assert!(client.poll_response().unwrap().is_none());
+    }
+
+    #[test]
+    fn should_fail_when_closed_by_server() {
+        let mut client =
+            HttpClient::new(Server::new(b"HTTP/1.1 200 OK\r\nConnection: close\r\n\r\nuntil eof"), "api.example.com");
+        client.send_request("GET", "/", &[], None).unwrap();
+        // body delimited by the connection close
+        assert_eq!((200, b"until eof".to_vec(), None), next_response(&mut client));
+        assert_eq!(ConnectionAborted, client.poll_response().unwrap_err().kind());
+        assert_eq!(ConnectionAborted, client.send_request("GET", "/", &[], None).unwrap_err().kind());
+
+        let mut client = HttpClient::new(Server::new(b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nhel"), "");
+        client.send_request("GET", "/", &[], None).unwrap();
+        let err = loop {
+            match client.poll_response() {
+                Ok(response) => assert!(response.is_none()),
+                Err(err) => break err,
+            }
+        };
+        assert_eq!(UnexpectedEof, err.kind());
+    }
+
+    #[test]
+    fn should_reject_oversized_chunk() {
+        for size in ["fffffffffffffffd", "ffffffff"] {
+            let response = format!("HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n{size}\r\nabc");
+            let mut client = HttpClient::new(Server::new(response.as_bytes()), "api.example.com");
+            client.send_request("GET", "/", &[], None).unwrap();
+            let err = loop {
+                match client.poll_response() {
+                    Ok(response) => assert!(response.is_none()),
+                    Err(err) => break err,
+                }
+            };
+            assert_eq!(InvalidData, err.kind());
+        }
+
+        let mut body = Vec::new();
+        let mut pos = 0;
+        assert_eq!(None, decode_chunked(b"3\r\nabc\r\n2\r\nd", &mut body, &mut pos).unwrap());
+        assert_eq!((b"abc".as_slice(), 8), (body.as_slice(), pos));
+        // resumed from the first chunk not decoded yet
+        assert_eq!(Some(20), decode_chunked(b"3\r\nabc\r\n2\r\nde\r\n0\r\n\r\n", &mut body, &mut pos).unwrap());
+        assert_eq!(b"abcde", body.as_slice());
+    }
+
+    #[test]
+    fn should_send_request_with_host_override_and_absolute_target() {
+        let mut client = HttpClient::new(Server::new(b""), "gateway.example.com").with_allowed_host("api.example.com");
+        client
+            .send_request("GET", "/time", &[("host", "api.example.com"), ("X-Key", "k")], None)
+            .unwrap();
+        client
+            .send_request("GET", "HTTP://API.example.com/time?utc=1", &[("Host", "api.example.com")], None)
+            .unwrap();
+        client
+            .send_request("GET", "https://gateway.example.com", &[], None)
+            .unwrap();
+        assert_eq!(
+            b"GET /time HTTP/1.1\r\nHost: api.example.com\r\nX-Key: k\r\n\r\n\
+            GET HTTP://API.example.com/time?utc=1 HTTP/1.1\r\nHost: api.example.com\r\n\r\n\
+            GET https://gateway.example.com HTTP/1.1\r\nHost: gateway.example.com\r\n\r\n",
+            client.stream().requests.as_slice()
+        );
+
+        // not served by the peer
+        let rejected = [
+            ("/time", &[("Host", "other.example.com")][..]),
+            ("http://api.example.com/time", &[][..]),
+            ("http://other.example.com/time", &[("Host", "other.example.com")][..]),
+            ("ftp://gateway.example.com/time", &[][..]),
+            ("http://user@gateway.example.com/time", &[][..]),
+            ("http:///time", &[][..]),
+        ];
+        for (target, headers) in rejected {
+            let err = client.send_request("GET", target, headers, None).unwrap_err();
+            assert_eq!(InvalidInput, err.kind(), "{target}");
+        }
+        assert_eq!(3, client.pending_requests());
+    }
+
+    #[test]
+    fn should_return_events_as_they_arrive() {
+        let mut client = HttpClient::new(
+            Server::new(
+                b"HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nTransfer-Encoding: chunked\r\n\r\n\
+                1d\r\n: keep-alive\r\nretry: 2500\r\n\r\n\r\n\
+                35\r\nid: 1\ndata: {\"px\":1}\n\nevent: trade\ndata: a\r\ndata:\r\n\r\n\r\n\
+                12\r\nid: 7\r\n\r\ndata: b\n\n\r\n0\r\n\r\n",
+            ),
+            "stream.example.com",
+        );
+        let mut events = EventSource::new("/prices").with_header("X-Key", "k");
+        events.subscribe(&mut client).unwrap();
+        assert!(client.stream().requests.starts_with(
+            b"GET /prices HTTP/1.1\r\nHost: stream.example.com\r\nX-Key: k\r\nAccept: text/event-stream\r\n\
+            Cache-Control: no-cache\r\n\r\n"
+        ));
+        assert_eq!(InvalidInput, client.send_request("GET", "/", &[], None).unwrap_err().kind());
+
+        let mut received = Vec::new();
+        let err = loop {
+            match events.poll(&mut client) {
+                Ok(Some(event)) => {
+                    received.push((event.event.to_owned(), event.data.to_owned(), event.id.map(str::to_owned)))
+                }
+                Ok(None) => {}
+                Err(err) => break err,
+            }
+        };
+        assert_eq!(UnexpectedEof, err.kind());
+        assert_eq!(
+            vec![
+                (String::from("message"), String::from("{\"px\":1}"), Some(String::from("1"))),
+                (String::from("trade"), String::from("a\n"), Some(String::from("1"))),
+                (String::from("message"), String::from("b"), Some(String::from("7"))),
+            ],
+            received
+        );
+        assert_eq!(Some("7"), events.last_event_id());
+        assert_eq!(Some(Duration::from_millis(2500)), events.retry());
+
+        // resumed over the fresh connection
+        let mut client =
+            HttpClient::new(Server::new(b"HTTP/1.1 503 Service Unavailable\r\n\r\n"), "stream.example.com");
+        events.subscribe(&mut client).unwrap();
+        assert!(client.stream().requests.ends_with(b"Last-Event-ID: 7\r\n\r\n"));
+        let err = loop {
+            match events.poll(&mut client) {
+                Ok(event) => assert!(event.is_none()),
+                Err(err) => break err,
+            }
+        };
+        assert_eq!("event stream request failed with status 503", err.to_string());
+    }
+
+    #[test]
+    fn should_append_body_to_caller_buffer() {
+        let mut client = HttpClient::new(
+            Server::new(
+                b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\nX-Test: a\r\n\r\nhello\
+                HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n4\r\nwiki\r\n5\r\npedia\r\n0\r\n\r\n\
+                HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n3\r\nabc\r\n0\r\n\r\n",
+            ),
+            "api.example.com",
+        );
+        for _ in 0..3 {
+            client.send_request("GET", "/depth", &[], None).unwrap();
+        }
+
+        let mut next = |body: &mut Vec<u8>| loop {
+            if let Some((status, headers)) = client.poll_into(body).unwrap() {
+                return (status, headers.get_ignore_case("x-test").map(<[u8]>::to_vec));
+            }
+        };
+        let mut body = b">".to_vec();
+        assert_eq!((200, Some(b"a".to_vec())), next(&mut body));
+        assert_eq!(b">hello", body.as_slice());
+        assert_eq!((200, None), next(&mut body));
+        assert_eq!(b">hellowikipedia", body.as_slice());
+
+        // handed over to the empty buffer
+        let mut body = Vec::new();
+        assert_eq!((200, None), next(&mut body));
+        assert_eq!(b"abc", body.as_slice());
+        assert_eq!(0, client.pending_requests());
+    }
+
+    #[test]
+    fn should_follow_redirects_up_to_hop_limit() {
+        let responses = b"HTTP/1.1 302 Found\r\nLocation: /v2/time\r\nContent-Length: 5\r\n\r\nmoved\
+            HTTP/1.1 307 Temporary Redirect\r\nLocation: http://api.example.com/v3/time\r\nContent-Length: 0\r\n\r\n\
+            HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok";
+        let mut client = HttpClient::new(Server::new(responses), "api.example.com").with_max_redirects(2);
+        client.send_request("GET", "/time", &[("X-Key", "k")], None).unwrap();
+        assert_eq!((200, b"ok".to_vec(), None), next_response(&mut client));
+        assert_eq!(
+            b"GET /time HTTP/1.1\r\nHost: api.example.com\r\nX-Key: k\r\n\r\n\
+            GET /v2/time HTTP/1.1\r\nHost: api.example.com\r\nX-Key: k\r\n\r\n\
+            GET http://api.example.com/v3/time HTTP/1.1\r\nHost: api.example.com\r\nX-Key: k\r\n\r\n",
+            client.stream().requests.as_slice()
+        );
+        assert_eq!(0, client.pending_requests());
+
+        // hop limit reached
+        let mut client = HttpClient::new(Server::new(responses), "api.example.com").with_max_redirects(1);
+        client.send_request("GET", "/time", &[], None).unwrap();
+        assert_eq!((307, vec![], None), next_response(&mut client));
+
+        // the method is only kept by `307` and `308`
+        let mut client = HttpClient::new(Server::new(responses), "api.example.com").with_max_redirects(2);
+        client.send_request("POST", "/time", &[], Some(b"{}")).unwrap();
+        assert_eq!((302, b"moved".to_vec(), None), next_response(&mut client));
+
+        // not reachable over this connection
+        let mut client = HttpClient::new(
+            Server::new(b"HTTP/1.1 301 Moved Permanently\r\nLocation: https://other.example.com/\r\n\r\n"),
+            "api.example.com",
+        )
+        .with_max_redirects(2);
+        client.send_request("HEAD", "/", &[], None).unwrap();
+        assert_eq!((301, vec![], None), next_response(&mut client));
+    }
+
+    #[test]
+    fn should_retire_connection_closed_by_server_or_idle() {
+        let mut client = HttpClient::new(
+            Server::new(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"),
+            "api.example.com",
+        )
+        .with_keep_alive(Duration::from_secs(60));
+        client.send_request("GET", "/", &[], None).unwrap();
+        assert!(!client.peer_closed());
+        assert_eq!((200, vec![], None), next_response(&mut client));
+        assert!(client.peer_closed());
+
+        let client = HttpClient::new(Server::new(b""), "api.example.com").with_keep_alive(Duration::from_secs(60));
+        assert!(!client.peer_closed());
+        let mut client = client.with_keep_alive(Duration::ZERO);
+        assert!(client.peer_closed());
+        assert_eq!(ConnectionAborted, client.send_request("GET", "/", &[], None).unwrap_err().kind());
+
+        // shorter timeout advised by the server
+        let mut client = HttpClient::new(
+            Server::new(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\nKeep-Alive: timeout=0, max=100\r\n\r\n"),
+            "api.example.com",
+        )
+        .with_keep_alive(Duration::from_secs(60));
+        client.send_request("GET", "/", &[], None).unwrap();
+        // not idle while the request is in flight
+        assert!(!client.peer_closed());
+        assert_eq!((200, vec![], None), next_response(&mut client));
+        assert!(client.peer_closed());
+        assert_eq!(Some(Duration::from_secs(5)), parse_keep_alive_timeout(b"max=100 , timeout = 5"));
+    }
+
+    #[test]
+    fn should_look_up_headers() {
+        let mut client = HttpClient::new(
+            Server::new(
+                b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\nSet-Cookie: a=1\r\nX-MBX-USED-WEIGHT-1M: 42\r\n\
+                set-cookie: b=2\r\nDate: Sun, 06 Nov 1994 08:49:37 GMT\r\nExpires: 0\r\n\r\n",
+            ),
+            "api.example.com",
+        );
+        client.send_request("GET", "/", &[], None).unwrap();
+        let headers = loop {
+            if let Some(response) = client.poll_response().unwrap() {
+                break response.headers();
+            }
+        };
+        assert_eq!(6, headers.len());
+        assert_eq!(Some(b"a=1".as_slice()), headers.get("Set-Cookie"));
+        assert_eq!(None, headers.get("set-cookie-2"));
+        assert_eq!(None, headers.get("Date-"));
+        assert_eq!(None, headers.get("date"));
+        assert_eq!(Some(b"a=1".as_slice()), headers.get_ignore_case("SET-COOKIE"));
+        assert_eq!(vec![b"a=1".as_slice(), b"b=2"], headers.get_all("set-cookie").collect::<Vec<_>>());
+        assert_eq!(Some(42), headers.get_u64("x-mbx-used-weight-1m"));
+        assert_eq!(None, headers.get_u64("set-cookie"));
+        assert_eq!(Some(UNIX_EPOCH + Duration::from_secs(784111777)), headers.get_date("date"));
+        assert_eq!(None, headers.get_date("expires"));
+        assert_eq!(None, headers.get_date("missing"));
+        assert_eq!(Some(("Content-Length", b"0".as_slice())), headers.iter().next());
+
+        assert_eq!(
+            Some(UNIX_EPOCH + Duration::from_secs(951782400)),
+            parse_http_date(b"Tue, 29 Feb 2000 00:00:00 GMT")
+        );
+        assert_eq!(None, parse_http_date(b"Sunday, 06-Nov-94 08:49:37 GMT"));
+        assert_eq!(None, parse_http_date(b"Sun, 06 Nov 1994 08:49:37 UTC"));
+        assert_eq!(None, parse_http_date(b"Sun, 06 Foo 1994 08:49:37 GMT"));
+    }
+}
@@ -0,0 +1,155 @@
+//! Policies applied by the [`IOService`](crate::service::IOService) at the end of the poll cycle,
+//! letting the same application loop run either with the lowest latency or with low CPU usage.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use std::io;
+//! use std::net::{SocketAddr, TcpStream};
+//! use std::time::Duration;
+//! use idle::IdleStrategy;
+//! use boomnet::endpoint::{ConnectionInfo, Endpoint};
+//! use boomnet::idle_policy::Park;
+//! use boomnet::select::direct::DirectSelector;
+//! use boomnet::service::IntoIOService;
+//!
+//! struct FeedEndpoint;
+//!
+//! impl Endpoint for FeedEndpoint {
+//!     type Target = TcpStream;
+//!
+//!     fn connection_info(&self) -> io::Result<ConnectionInfo> {
+//!         Ok(ConnectionInfo::new("127.0.0.1", 9000))
+//!     }
+//!
+//!     fn create_target(&mut self, addr: SocketAddr) -> io::Result<Self::Target> {
+//!         TcpStream::connect(addr)
+//!     }
+//!
+//!     fn poll(&mut self, _target: &mut Self::Target) -> io::Result<()> {
+//!         Ok(())
+//!     }
+//! }
+//!
+//! // park the service thread when idle, the mailbox unparks it as soon as an action is sent
+//! let mut io_service = DirectSelector::new()
+//!     .unwrap()
+//!     .into_io_service(IdleStrategy::NoOp)
+//!     .with_idle_policy(Park::new(Duration::from_millis(1)));
+//! io_service.register(FeedEndpoint);
+//! let mailbox = io_service.mailbox();
+//! loop {
+//!     io_service.poll().unwrap();
+//! }
+//! ```
+
+use std::hint;
+use std::thread::Thread;
+use std::time::Duration;
+
+use idle::IdleStrategy;
+
+use crate::select::Waker;
+
+/// Invoked at the end of each poll cycle with the amount of work done during the cycle (readiness
+/// events, mailbox actions and expired timers), typically to back off when there was none.
+pub trait IdlePolicy {
+    fn idle(&mut self, work_count: usize);
+
+    /// Returns the [`Waker`] that interrupts the idle period, if supported by the policy.
+    fn waker(&self) -> Option<Waker> {
+        None
+    }
+}
+
+impl IdlePolicy for IdleStrategy {
+    #[inline]
+    fn idle(&mut self, work_count: usize) {
+        IdleStrategy::idle(self, work_count)
+    }
+}
+
+/// Busy spins for the number of consecutive idle cycles and then yields the thread on every idle
+/// cycle, until work is done again.
+#[derive(Debug, Clone)]
+pub struct SpinThenYield {
+    spins: usize,
+    idle_cycles: usize,
+}
+
+impl SpinThenYield {
+    pub const fn new(spins: usize) -> SpinThenYield {
+        Self { spins, idle_cycles: 0 }
+    }
+}
+
+impl IdlePolicy for SpinThenYield {
+    #[inline]
+    fn idle(&mut self, work_count: usize) {
+        if work_count > 0 {
+            self.idle_cycles = 0;
+        } else if self.idle_cycles < self.spins {
+            self.idle_cycles += 1;
+            hint::spin_loop();
+        } else {
+            std::thread::yield_now();
+        }
+    }
+}
+
+/// Parks the thread for up to the `timeout` on every idle cycle. The [`Waker`] unparks it early,
+/// so it must be created on the thread that polls the service.
+#[derive(Debug, Clone)]
+pub struct Park {
+    timeout: Duration,
+    thread: Thread,
+}
+
+impl Park {
+    /// Creates the policy that parks the current thread.
+    pub fn new(timeout: Duration) -> Park {
+        Self {
+            timeout,
+            thread: std::thread::current(),
+        }
+    }
+}
+
+impl IdlePolicy for Park {
+    #[inline]
+    fn idle(&mut self, work_count: usize) {
+        if work_count == 0 {
+            std::thread::park_timeout(self.timeout);
+        }
+    }
+
+    fn waker(&self) -> Option<Waker> {
+        let thread = self.thread.clone();
+        Some(Waker::new(move || {
+            thread.unpark();
+            Ok(())
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use super::*;
+
+    #[test]
+    fn should_unpark_when_woken() {
+        let mut park = Park::new(Duration::from_secs(30));
+        let waker = park.waker().unwrap();
+        let wake = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(50));
+            waker.wake().unwrap();
+        });
+
+        let start = Instant::now();
+        park.idle(0);
+        assert!(start.elapsed() < Duration::from_secs(10));
+        wake.join().unwrap();
+    }
+}
@@ -1,10 +1,33 @@
 pub mod buffer;
+#[cfg(feature = "clock-sync")]
+pub mod clock_sync;
+pub mod connection;
+pub mod correlation;
+pub mod dns;
 pub mod endpoint;
+#[cfg(all(feature = "exchange-adapters", any(feature = "tls-webpki", feature = "tls-native")))]
+pub mod exchange;
+#[cfg(feature = "fix")]
+pub mod fix;
+#[cfg(feature = "framing")]
+pub mod framing;
+#[cfg(feature = "http-client")]
+pub mod http;
+pub mod idle_policy;
 pub mod inet;
+#[cfg(feature = "stats")]
+pub mod metrics;
 mod node;
+#[cfg(feature = "probe")]
+pub mod probe;
+pub mod rate_limit;
 pub mod select;
 pub mod service;
 pub mod stream;
+pub mod time;
+pub mod timer;
+#[cfg(feature = "tracing")]
+pub mod trace;
 mod util;
 #[cfg(feature = "ws")]
 pub mod ws;
@@ -0,0 +1,174 @@
+//! Aggregate metrics collected by the [`IOService`](crate::service::IOService), available with the
+//! `stats` feature.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use boomnet::metrics::ServiceMetrics;
+//!
+//! fn export(metrics: &ServiceMetrics) {
+//!     println!("poll p99: {}ns", metrics.poll_duration.value_at_quantile(0.99));
+//!     println!("reconnects: {}", metrics.reconnects);
+//!     for (upper_bound, count) in metrics.dns_resolution.buckets() {
+//!         println!("dns_resolution_bucket{{le=\"{upper_bound}\"}} {count}");
+//!     }
+//!     for (handle, io) in &metrics.endpoint_io {
+//!         println!("endpoint {handle}: {} bytes in, {} bytes out", io.bytes_read, io.bytes_written);
+//!     }
+//! }
+//! ```
+
+use crate::service::Handle;
+use crate::stream::IoCounters;
+
+const BUCKETS: usize = u64::BITS as usize + 1;
+
+/// Histogram with power of two buckets, cheap enough to record every poll cycle. Bucket `0` counts
+/// zero values and bucket `i` counts values in the `[2^(i-1), 2^i)` range, so the reported quantiles
+/// are accurate within the factor of two.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Histogram {
+    buckets: [u64; BUCKETS],
+    count: u64,
+    sum: u64,
+    min: u64,
+    max: u64,
+}
+
+impl Default for Histogram {
+    fn default() -> Self {
+        Self {
+            buckets: [0; BUCKETS],
+            count: 0,
+            sum: 0,
+            min: u64::MAX,
+            max: 0,
+        }
+    }
+}
+
+impl Histogram {
+    pub fn new() -> Histogram {
+        Self::default()
+    }
+
+    #[inline]
+    pub fn record(&mut self, value: u64) {
+        self.buckets[(u64::BITS - value.leading_zeros()) as usize] += 1;
+        self.count += 1;
+        self.sum = self.sum.saturating_add(value);
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+    }
+
+    /// Number of recorded values.
+    pub const fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Sum of the recorded values (saturating).
+    pub const fn sum(&self) -> u64 {
+        self.sum
+    }
+
+    /// Smallest recorded value or `0` if nothing has been recorded.
+    pub const fn min(&self) -> u64 {
+        if self.count == 0 {
+            0
+        } else {
+            self.min
+        }
+    }
+
+    /// Largest recorded value or `0` if nothing has been recorded.
+    pub const fn max(&self) -> u64 {
+        self.max
+    }
+
+    pub fn mean(&self) -> f64 {
+        if self.count == 0 {
+            0.0
+        } else {
+            self.sum as f64 / self.count as f64
+        }
+    }
+
+    /// Returns the upper bound of the bucket that contains the `quantile` (from `0.0` to `1.0`),
+    /// capped at the largest recorded value.
+    pub fn value_at_quantile(&self, quantile: f64) -> u64 {
+        if self.count == 0 {
+            return 0;
+        }
+        let rank = ((quantile.clamp(0.0, 1.0) * self.count as f64).ceil() as u64).max(1);
+        let mut seen = 0;
+        for (upper_bound, count) in self.buckets() {
+            seen += count;
+            if seen >= rank {
+                return upper_bound.min(self.max);
+            }
+        }
+        self.max
+    }
+
+    /// Iterates over the non-empty buckets as `(upper_bound, count)` pairs, where the upper bound is
+    /// inclusive. Useful when exporting to the monitoring systems such as Prometheus.
+    pub fn buckets(&self) -> impl Iterator<Item = (u64, u64)> + '_ {
+        self.buckets
+            .iter()
+            .enumerate()
+            .filter(|(_, count)| **count > 0)
+            .map(|(bucket, count)| (upper_bound(bucket), *count))
+    }
+
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+}
+
+#[inline]
+const fn upper_bound(bucket: usize) -> u64 {
+    match bucket {
+        0 => 0,
+        _ => u64::MAX >> (u64::BITS as usize - bucket),
+    }
+}
+
+/// Snapshot of the [`IOService`](crate::service::IOService) metrics, see `IOService::metrics`.
+/// All durations are in nanoseconds as measured by the service time source.
+#[derive(Debug, Clone, Default)]
+pub struct ServiceMetrics {
+    /// Duration of the poll cycles.
+    pub poll_duration: Histogram,
+    /// Number of times the endpoints have been polled.
+    pub endpoints_polled: u64,
+    /// Number of times the disconnected endpoints have been queued to be recreated.
+    pub reconnects: u64,
+    /// Duration of the DNS resolution performed before connecting the endpoints.
+    pub dns_resolution: Histogram,
+    /// Bytes transferred by each connected endpoint since its connection was established, only
+    /// present for streams that report [`IoCounters`].
+    pub endpoint_io: Vec<(Handle, IoCounters)>,
+    /// Endpoints whose reading is currently paused, see `IOService::pause_reading`.
+    pub paused_endpoints: Vec<Handle>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_report_quantiles() {
+        let mut histogram = Histogram::new();
+        assert_eq!(0, histogram.value_at_quantile(0.5));
+        for value in [0, 1, 2, 3, 100, 1000] {
+            histogram.record(value);
+        }
+        assert_eq!(6, histogram.count());
+        assert_eq!(0, histogram.min());
+        assert_eq!(1000, histogram.max());
+        assert_eq!(3, histogram.value_at_quantile(0.5));
+        assert_eq!(127, histogram.value_at_quantile(0.8));
+        assert_eq!(1000, histogram.value_at_quantile(1.0));
+        assert_eq!(vec![(0, 1), (1, 1), (3, 2), (127, 1), (1023, 1)], histogram.buckets().collect::<Vec<_>>());
+    }
+}
@@ -1,26 +1,62 @@
+use std::collections::VecDeque;
+use std::net::SocketAddr;
 use std::time::Duration;
 
-use crate::util::current_time_nanos;
+use crate::service::Handle;
+use crate::stream::SocketQueues;
 
 pub struct IONode<S, E> {
     pub stream: S,
     pub endpoint: Option<E>,
+    pub handle: Handle,
     pub disconnect_time_ns: u64,
+    pub ttl: Option<Duration>,
+    pub paused: bool,
+    pub write_interest: bool,
+    pub connected: bool,
+    pub connected_since_ns: Option<u64>,
+    pub connect_deadline_ns: u64,
+    pub connect_attempt_deadline_ns: u64,
+    pub remaining_addrs: VecDeque<SocketAddr>,
+    pub resume_token: Option<u64>,
+    pub addr: Option<SocketAddr>,
+    pub pending_since_ns: u64,
+    pub connect_attempts: u32,
+    pub socket_queues: Option<SocketQueues>,
 }
 
 impl<S, E> IONode<S, E> {
-    pub fn new(stream: S, endpoint: E, ttl: Option<Duration>) -> IONode<S, E> {
+    pub fn new(stream: S, handle: Handle, endpoint: E, ttl: Option<Duration>, current_time_ns: u64) -> IONode<S, E> {
         let disconnect_time_ns = match ttl {
-            Some(ttl) => current_time_nanos() + ttl.as_nanos() as u64,
+            Some(ttl) => current_time_ns + ttl.as_nanos() as u64,
             None => u64::MAX,
         };
         Self {
             stream,
             endpoint: Some(endpoint),
+            handle,
             disconnect_time_ns,
+            ttl,
+            paused: false,
+            write_interest: false,
+            connected: false,
+            connected_since_ns: None,
+            connect_deadline_ns: u64::MAX,
+            connect_attempt_deadline_ns: u64::MAX,
+            remaining_addrs: VecDeque::new(),
+            resume_token: None,
+            addr: None,
+            pending_since_ns: current_time_ns,
+            connect_attempts: 0,
+            socket_queues: None,
         }
     }
 
+    pub fn mark_connected(&mut self, current_time_ns: u64) {
+        self.connected = true;
+        self.connected_since_ns.get_or_insert(current_time_ns);
+    }
+
     pub fn as_parts(&self) -> (&S, &E) {
         // SAFETY: safe to call as endpoint will never be None
         unsafe { (&self.stream, self.endpoint.as_ref().unwrap_unchecked()) }
@@ -0,0 +1,165 @@
+//! Latency instrumentation hooks, available with the `probe` feature. The [`IoProbe`] installed on
+//! the [`Websocket`](crate::ws::Websocket) or the [`IOService`](crate::service::IOService) is invoked
+//! with the timestamped [`ProbeEvent`]s, so that for example the time between the socket read and the
+//! frame delivery can be measured. Without the feature all the hooks are compiled away.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use std::net::TcpStream;
+//! use std::sync::{Arc, Mutex};
+//! use boomnet::probe::{IoProbe, ProbeEvent};
+//! use boomnet::stream::tls::IntoTlsStream;
+//! use boomnet::ws::IntoWebsocket;
+//!
+//! #[derive(Default)]
+//! struct ReadToFrame {
+//!     read_end_ns: u64,
+//! }
+//!
+//! impl IoProbe for ReadToFrame {
+//!     fn on_event(&mut self, event: ProbeEvent, time_ns: u64) {
+//!         match event {
+//!             ProbeEvent::ReadEnd(bytes) if bytes > 0 => self.read_end_ns = time_ns,
+//!             ProbeEvent::FrameDecoded => println!("frame decoded after {}ns", time_ns - self.read_end_ns),
+//!             _ => {}
+//!         }
+//!     }
+//! }
+//!
+//! // probes can be shared (for example across the websockets of the same endpoint)
+//! let probe = Arc::new(Mutex::new(ReadToFrame::default()));
+//! let mut ws = TcpStream::connect("stream.binance.com:9443")
+//!     .unwrap()
+//!     .into_tls_stream("stream.binance.com")
+//!     .into_websocket("wss://stream.binance.com:9443/ws")
+//!     .with_probe(probe.clone());
+//! ```
+
+use std::fmt;
+#[cfg(feature = "ws")]
+use std::io;
+#[cfg(feature = "ws")]
+use std::io::{Read, Write};
+use std::sync::{Arc, Mutex};
+
+#[cfg(feature = "ws")]
+use crate::util::current_time_nanos;
+
+/// Instrumentation event, see [`IoProbe`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum ProbeEvent {
+    /// About to read from the underlying stream.
+    ReadStart,
+    /// Read from the underlying stream completed with the number of bytes read (zero if no data was available).
+    ReadEnd(usize),
+    /// Frame has been decoded and is about to be delivered to the caller.
+    FrameDecoded,
+    /// Send has been requested, such as `Websocket::send_text`.
+    WriteStart,
+    /// Underlying stream has been flushed.
+    FlushEnd,
+    /// `IOService` poll cycle has started.
+    PollStart,
+    /// `IOService` poll cycle has ended.
+    PollEnd,
+}
+
+/// Receives the timestamped [`ProbeEvent`]s, the time is in nanoseconds since the UNIX epoch.
+/// The probe is invoked on the hot path so it should only record the event. Probes installed with
+/// `with_probe` must be `Send`, so that the instrumented `Websocket` or `IOService` can still be
+/// moved to the thread that drives it.
+pub trait IoProbe {
+    fn on_event(&mut self, event: ProbeEvent, time_ns: u64);
+}
+
+impl fmt::Debug for dyn IoProbe + Send {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("IoProbe")
+    }
+}
+
+impl<P: IoProbe> IoProbe for Arc<Mutex<P>> {
+    #[inline]
+    fn on_event(&mut self, event: ProbeEvent, time_ns: u64) {
+        // recover the probe if a previous holder of the lock has panicked
+        let mut probe = self.lock().unwrap_or_else(|err| err.into_inner());
+        probe.on_event(event, time_ns)
+    }
+}
+
+impl<P: IoProbe + ?Sized> IoProbe for Box<P> {
+    #[inline]
+    fn on_event(&mut self, event: ProbeEvent, time_ns: u64) {
+        (**self).on_event(event, time_ns)
+    }
+}
+
+/// Stream wrapper that reports reads and flushes of the underlying stream to the probe.
+#[cfg(feature = "ws")]
+pub(crate) struct ProbedStream<'a, S> {
+    stream: &'a mut S,
+    probe: &'a mut dyn IoProbe,
+}
+
+#[cfg(feature = "ws")]
+impl<'a, S> ProbedStream<'a, S> {
+    pub fn new(stream: &'a mut S, probe: &'a mut dyn IoProbe) -> ProbedStream<'a, S> {
+        Self { stream, probe }
+    }
+}
+
+#[cfg(feature = "ws")]
+impl<S: Read> Read for ProbedStream<'_, S> {
+    #[inline]
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.probe.on_event(ProbeEvent::ReadStart, current_time_nanos());
+        let result = self.stream.read(buf);
+        let read = result.as_ref().map(|read| *read).unwrap_or(0);
+        self.probe.on_event(ProbeEvent::ReadEnd(read), current_time_nanos());
+        result
+    }
+}
+
+#[cfg(feature = "ws")]
+impl<S: Write> Write for ProbedStream<'_, S> {
+    #[inline]
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.stream.write(buf)
+    }
+
+    #[inline]
+    fn flush(&mut self) -> io::Result<()> {
+        self.stream.flush()?;
+        self.probe.on_event(ProbeEvent::FlushEnd, current_time_nanos());
+        Ok(())
+    }
+}
+
+#[cfg(all(test, feature = "ws"))]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    #[derive(Default)]
+    struct EventLog(Vec<ProbeEvent>);
+
+    impl IoProbe for EventLog {
+        fn on_event(&mut self, event: ProbeEvent, _time_ns: u64) {
+            self.0.push(event);
+        }
+    }
+
+    #[test]
+    fn should_report_reads_and_flushes() {
+        let mut log = EventLog::default();
+        let mut stream = Cursor::new(b"abc".to_vec());
+        let mut probed = ProbedStream::new(&mut stream, &mut log);
+        let mut buf = [0u8; 8];
+        assert_eq!(3, probed.read(&mut buf).unwrap());
+        probed.write_all(b"de").unwrap();
+        probed.flush().unwrap();
+        assert_eq!(vec![ProbeEvent::ReadStart, ProbeEvent::ReadEnd(3), ProbeEvent::FlushEnd], log.0);
+    }
+}
@@ -0,0 +1,260 @@
+//! Rate limiting of outbound messages.
+
+use std::collections::VecDeque;
+use std::time::Duration;
+
+/// Token bucket rate limiter. The bucket holds up to `capacity` tokens and is continuously
+/// refilled at the rate of `capacity` tokens per `period`. Each message consumes one token.
+///
+/// # Examples
+///
+/// ```
+/// use std::time::Duration;
+/// use boomnet::rate_limit::TokenBucket;
+///
+/// // 5 messages per second
+/// let mut bucket = TokenBucket::new(5, Duration::from_secs(1));
+/// for _ in 0..5 {
+///     assert!(bucket.try_acquire(0));
+/// }
+/// assert!(!bucket.try_acquire(0));
+/// assert!(bucket.try_acquire(Duration::from_millis(200).as_nanos() as u64));
+/// ```
+#[derive(Debug, Clone)]
+pub struct TokenBucket {
+    capacity: u32,
+    tokens: u32,
+    refill_interval_ns: u64,
+    last_refill_time_ns: Option<u64>,
+}
+
+impl TokenBucket {
+    /// Creates new bucket that allows `capacity` messages per `period`. The bucket starts full.
+    pub fn new(capacity: u32, period: Duration) -> TokenBucket {
+        assert!(capacity > 0, "capacity must be positive");
+        Self {
+            capacity,
+            tokens: capacity,
+            refill_interval_ns: (period.as_nanos() as u64 / capacity as u64).max(1),
+            last_refill_time_ns: None,
+        }
+    }
+
+    /// Number of tokens currently available (as of the last refill).
+    pub const fn available(&self) -> u32 {
+        self.tokens
+    }
+
+    /// Attempts to consume single token, returns `false` if the limit has been reached.
+    pub fn try_acquire(&mut self, current_time_ns: u64) -> bool {
+        self.refill(current_time_ns);
+        if self.tokens > 0 {
+            self.tokens -= 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn refill(&mut self, current_time_ns: u64) {
+        let last_refill_time_ns = *self.last_refill_time_ns.get_or_insert(current_time_ns);
+        let elapsed_ns = current_time_ns.saturating_sub(last_refill_time_ns);
+        let new_tokens = elapsed_ns / self.refill_interval_ns;
+        if new_tokens == 0 {
+            return;
+        }
+        let tokens = (self.tokens as u64 + new_tokens).min(self.capacity as u64) as u32;
+        self.last_refill_time_ns = if tokens == self.capacity {
+            Some(current_time_ns)
+        } else {
+            Some(last_refill_time_ns + new_tokens * self.refill_interval_ns)
+        };
+        self.tokens = tokens;
+    }
+}
+
+/// Assigns the weight to the outbound message, for venues that count the requests of different
+/// types differently (such as order placement costing more than the order book snapshot).
+/// Implemented for any `Fn(&M) -> u32`.
+pub trait CostModel<M: ?Sized> {
+    /// Returns the weight of the `message`.
+    fn cost(&self, message: &M) -> u32;
+}
+
+impl<M: ?Sized, F: Fn(&M) -> u32> CostModel<M> for F {
+    fn cost(&self, message: &M) -> u32 {
+        self(message)
+    }
+}
+
+/// Sliding window limiter that allows at most `limit` total weight to be sent within any `window`.
+/// Unlike the [`TokenBucket`] it accounts for each message individually, so the remaining budget
+/// matches the accounting of the venue exactly.
+///
+/// # Examples
+///
+/// ```
+/// use std::time::Duration;
+/// use boomnet::rate_limit::SlidingWindow;
+///
+/// // 6000 weight per minute
+/// let mut window = SlidingWindow::new(6000, Duration::from_secs(60));
+/// assert!(window.try_acquire(0, 5000));
+/// assert!(!window.try_acquire(1, 1001));
+/// assert_eq!(1000, window.remaining(1));
+/// assert_eq!(6000, window.remaining(Duration::from_secs(60).as_nanos() as u64));
+/// ```
+#[derive(Debug, Clone)]
+pub struct SlidingWindow {
+    limit: u32,
+    window_ns: u64,
+    used: u32,
+    sent: VecDeque<(u64, u32)>,
+}
+
+impl SlidingWindow {
+    /// Creates new window that allows `limit` total weight per `window`.
+    pub fn new(limit: u32, window: Duration) -> SlidingWindow {
+        Self {
+            limit,
+            window_ns: window.as_nanos() as u64,
+            used: 0,
+            sent: VecDeque::new(),
+        }
+    }
+
+    /// Total weight that can be sent within the window.
+    pub const fn limit(&self) -> u32 {
+        self.limit
+    }
+
+    /// Weight sent within the window ending at `current_time_ns`.
+    pub fn used(&mut self, current_time_ns: u64) -> u32 {
+        self.evict(current_time_ns);
+        self.used
+    }
+
+    /// Weight that can still be sent at `current_time_ns`.
+    pub fn remaining(&mut self, current_time_ns: u64) -> u32 {
+        self.limit - self.used(current_time_ns)
+    }
+
+    /// Attempts to account for the message of the given `weight`, returns `false` if it would
+    /// exceed the limit.
+    pub fn try_acquire(&mut self, current_time_ns: u64, weight: u32) -> bool {
+        if weight > self.remaining(current_time_ns) {
+            return false;
+        }
+        if weight > 0 {
+            self.used += weight;
+            self.sent.push_back((current_time_ns, weight));
+        }
+        true
+    }
+
+    fn evict(&mut self, current_time_ns: u64) {
+        while let Some(&(sent_time_ns, weight)) = self.sent.front() {
+            if sent_time_ns.saturating_add(self.window_ns) > current_time_ns {
+                break;
+            }
+            self.sent.pop_front();
+            self.used -= weight;
+        }
+    }
+}
+
+/// [`SlidingWindow`] that obtains the weight of each message from the [`CostModel`].
+///
+/// # Examples
+///
+/// ```
+/// use std::time::Duration;
+/// use boomnet::rate_limit::{SlidingWindow, WeightedRateLimiter};
+///
+/// let cost_model = |request: &str| if request.starts_with("order") { 10 } else { 1 };
+/// let mut limiter = WeightedRateLimiter::new(SlidingWindow::new(12, Duration::from_secs(1)), cost_model);
+/// assert!(limiter.try_acquire(0, "order.place"));
+/// assert!(!limiter.try_acquire(0, "order.place"));
+/// assert!(limiter.try_acquire(0, "depth"));
+/// assert_eq!(1, limiter.remaining(0));
+/// ```
+#[derive(Debug, Clone)]
+pub struct WeightedRateLimiter<C> {
+    window: SlidingWindow,
+    cost_model: C,
+}
+
+impl<C> WeightedRateLimiter<C> {
+    /// Creates new limiter accounting the weights assigned by the `cost_model` within the `window`.
+    pub fn new(window: SlidingWindow, cost_model: C) -> WeightedRateLimiter<C> {
+        Self { window, cost_model }
+    }
+
+    /// Weight that can still be sent at `current_time_ns`.
+    pub fn remaining(&mut self, current_time_ns: u64) -> u32 {
+        self.window.remaining(current_time_ns)
+    }
+
+    /// Returns the weight of the `message` as per the cost model.
+    pub fn cost<M: ?Sized>(&self, message: &M) -> u32
+    where
+        C: CostModel<M>,
+    {
+        self.cost_model.cost(message)
+    }
+
+    /// Checks if the `message` can be sent at `current_time_ns` without accounting for it.
+    pub fn can_send<M: ?Sized>(&mut self, current_time_ns: u64, message: &M) -> bool
+    where
+        C: CostModel<M>,
+    {
+        self.cost(message) <= self.remaining(current_time_ns)
+    }
+
+    /// Attempts to account for the `message`, returns `false` if it would exceed the limit.
+    pub fn try_acquire<M: ?Sized>(&mut self, current_time_ns: u64, message: &M) -> bool
+    where
+        C: CostModel<M>,
+    {
+        let weight = self.cost(message);
+        self.window.try_acquire(current_time_ns, weight)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_refill_at_configured_rate() {
+        let mut bucket = TokenBucket::new(2, Duration::from_nanos(100));
+        assert!(bucket.try_acquire(1_000));
+        assert!(bucket.try_acquire(1_000));
+        assert!(!bucket.try_acquire(1_049));
+        assert!(bucket.try_acquire(1_050));
+        assert!(!bucket.try_acquire(1_099));
+        assert!(bucket.try_acquire(1_100));
+
+        // never exceeds capacity
+        assert!(bucket.try_acquire(10_000));
+        assert!(bucket.try_acquire(10_000));
+        assert!(!bucket.try_acquire(10_000));
+    }
+
+    #[test]
+    fn should_slide_window() {
+        let mut window = SlidingWindow::new(10, Duration::from_nanos(100));
+        assert!(window.try_acquire(0, 4));
+        assert!(window.try_acquire(50, 6));
+        assert!(!window.try_acquire(60, 1));
+        assert!(!window.try_acquire(0, 11));
+
+        // first message leaves the window
+        assert_eq!(4, window.remaining(100));
+        assert!(!window.try_acquire(100, 5));
+        assert!(window.try_acquire(100, 4));
+        assert_eq!(10, window.used(149));
+        assert_eq!(6, window.remaining(150));
+        assert_eq!(10, window.remaining(200));
+    }
+}
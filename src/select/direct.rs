@@ -35,8 +35,9 @@ impl<S: Selectable> Selector for DirectSelector<S> {
         Ok(())
     }
 
-    fn poll<E>(&mut self, _io_nodes: &mut HashMap<SelectorToken, IONode<Self::Target, E>>) -> io::Result<()> {
-        Ok(())
+    fn poll<E>(&mut self, _io_nodes: &mut HashMap<SelectorToken, IONode<Self::Target, E>>) -> io::Result<usize> {
+        // no readiness information, the endpoints are polled directly
+        Ok(0)
     }
 }
 
@@ -2,6 +2,7 @@ use idle::IdleStrategy;
 use std::collections::HashMap;
 use std::io;
 use std::marker::PhantomData;
+use std::sync::Arc;
 use std::time::Duration;
 
 use mio::event::Source;
@@ -9,15 +10,30 @@ use mio::{Events, Interest, Poll, Token};
 
 use crate::endpoint::{Context, Endpoint, EndpointWithContext};
 use crate::node::IONode;
-use crate::select::{Selectable, Selector, SelectorToken};
+use crate::select::{Selectable, Selector, SelectorToken, Waker};
 use crate::service::{IOService, IntoIOService, IntoIOServiceWithContext};
 
 const NO_WAIT: Option<Duration> = Some(Duration::from_millis(0));
+/// Reserved for the waker, the node tokens never exceed `u32::MAX`.
+const WAKER_TOKEN: Token = Token(usize::MAX);
+
+/// Interest of the connected (and not paused) node, write readiness is only monitored while
+/// the stream holds data it could not write to the socket.
+#[inline]
+fn interest(pending_writes: bool) -> Interest {
+    if pending_writes {
+        Interest::READABLE | Interest::WRITABLE
+    } else {
+        Interest::READABLE
+    }
+}
 
 pub struct MioSelector<S> {
     poll: Poll,
     events: Events,
     next_token: u32,
+    timeout: Option<Duration>,
+    waker: Option<Arc<mio::Waker>>,
     phantom: PhantomData<S>,
 }
 
@@ -27,9 +43,20 @@ impl<S> MioSelector<S> {
             poll: Poll::new()?,
             events: Events::with_capacity(1024),
             next_token: 0,
+            timeout: NO_WAIT,
+            waker: None,
             phantom: PhantomData,
         })
     }
+
+    /// Block waiting for the events for up to the `timeout` (`None` waits indefinitely) instead of
+    /// returning immediately, trading latency for CPU usage. The wait can be interrupted from other
+    /// threads with the [`Waker`] (the `ServiceMailbox` does that automatically). Note that the
+    /// timeout also bounds the resolution of the `IOService` timers and deadlines, and that the
+    /// service should use `IdleStrategy::NoOp` as the selector already idles.
+    pub fn with_timeout(self, timeout: Option<Duration>) -> MioSelector<S> {
+        Self { timeout, ..self }
+    }
 }
 
 impl<S: Source + Selectable> Selector for MioSelector<S> {
@@ -48,23 +75,105 @@ impl<S: Source + Selectable> Selector for MioSelector<S> {
         self.poll.registry().deregister(io_node.as_stream_mut())
     }
 
-    fn poll<E>(&mut self, io_nodes: &mut HashMap<SelectorToken, IONode<Self::Target, E>>) -> io::Result<()> {
-        self.poll.poll(&mut self.events, NO_WAIT)?;
+    fn pause_reading<E>(&mut self, token: SelectorToken, io_node: &mut IONode<Self::Target, E>) -> io::Result<()> {
+        let stream = io_node.as_stream_mut();
+        // the endpoint is still polled (to keep writing), so it must not read the pending data
+        stream.make_unreadable();
+        // keep waiting for the connection to complete if still in progress (or failed)
+        if matches!(stream.connected(), Ok(true)) {
+            self.poll
+                .registry()
+                .reregister(stream, Token(token as usize), Interest::WRITABLE)?;
+        }
+        Ok(())
+    }
+
+    fn resume_reading<E>(&mut self, token: SelectorToken, io_node: &mut IONode<Self::Target, E>) -> io::Result<()> {
+        let stream = io_node.as_stream_mut();
+        if matches!(stream.connected(), Ok(true)) {
+            let pending_writes = stream.has_pending_writes();
+            self.poll
+                .registry()
+                .reregister(stream, Token(token as usize), interest(pending_writes))?;
+            // data that arrived while paused will not generate new readiness event
+            stream.make_readable();
+            io_node.write_interest = pending_writes;
+        }
+        Ok(())
+    }
+
+    fn poll<E>(&mut self, io_nodes: &mut HashMap<SelectorToken, IONode<Self::Target, E>>) -> io::Result<usize> {
+        self.poll.poll(&mut self.events, self.timeout)?;
+        let mut event_count = 0;
         for ev in self.events.iter() {
             let token = ev.token();
-            let stream = io_nodes
+            if token == WAKER_TOKEN {
+                continue;
+            }
+            event_count += 1;
+            let io_node = io_nodes
                 .get_mut(&(token.0 as SelectorToken))
-                .expect("io node not found")
-                .as_stream_mut();
-            if ev.is_writable() && stream.connected()? {
+                .expect("io node not found");
+            let paused = io_node.paused;
+            let stream = &mut io_node.stream;
+            // the failed connection attempt is reported by the stream (see `Selectable::connect_error`)
+            if ev.is_writable() && matches!(stream.connected(), Ok(true)) {
                 stream.make_writable();
-                self.poll.registry().reregister(stream, token, Interest::READABLE)?;
+                // send data queued while the socket was not writable, the failed write (such as
+                // reset by the peer) only disconnects this node (see `Selectable::write_closed`)
+                match stream.flush_pending_writes() {
+                    Ok(()) => {
+                        let pending_writes = stream.has_pending_writes();
+                        if !paused {
+                            self.poll
+                                .registry()
+                                .reregister(stream, token, interest(pending_writes))?;
+                        }
+                        io_node.write_interest = pending_writes;
+                    }
+                    Err(_) => stream.make_write_closed(),
+                }
             }
-            if ev.is_readable() {
+            if ev.is_readable() && !paused {
                 stream.make_readable();
             }
+            if ev.is_write_closed() || ev.is_error() {
+                stream.make_write_closed();
+            }
         }
-        Ok(())
+
+        // monitor write readiness of the nodes that could not write all their data since the last poll
+        for (token, io_node) in io_nodes.iter_mut() {
+            if !io_node.paused
+                && !io_node.write_interest
+                && !io_node.stream.write_closed()
+                && io_node.stream.has_pending_writes()
+            {
+                let stream = &mut io_node.stream;
+                // nodes still connecting are already monitored for write readiness
+                if matches!(stream.connected(), Ok(true)) {
+                    self.poll.registry().reregister(
+                        stream,
+                        Token(*token as usize),
+                        Interest::READABLE | Interest::WRITABLE,
+                    )?;
+                    io_node.write_interest = true;
+                }
+            }
+        }
+        Ok(event_count)
+    }
+
+    fn waker(&mut self) -> io::Result<Option<Waker>> {
+        // only single waker can be registered with the poll
+        let waker = match &self.waker {
+            Some(waker) => waker.clone(),
+            None => self
+                .waker
+                .insert(Arc::new(mio::Waker::new(self.poll.registry(), WAKER_TOKEN)?))
+                .clone(),
+        };
+        Ok(Some(Waker::new(move || waker.wake())))
     }
 }
 
@@ -87,3 +196,63 @@ impl<C: Context, E: EndpointWithContext<C>> IntoIOServiceWithContext<E, C> for M
         IOService::new(self, idle_strategy)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+    use std::net::TcpListener;
+    use std::time::Instant;
+
+    use socket2::SockRef;
+
+    use crate::stream::mio::MioStream;
+
+    use super::*;
+
+    #[test]
+    fn should_interrupt_blocking_wait() {
+        let mut selector = MioSelector::<MioStream>::new()
+            .unwrap()
+            .with_timeout(Some(Duration::from_secs(30)));
+        let waker = selector.waker().unwrap().unwrap();
+        let wake = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(50));
+            waker.wake().unwrap();
+        });
+
+        let start = Instant::now();
+        let mut io_nodes = HashMap::<SelectorToken, IONode<MioStream, ()>>::new();
+        selector.poll(&mut io_nodes).unwrap();
+        assert!(start.elapsed() < Duration::from_secs(10));
+        wake.join().unwrap();
+    }
+
+    #[test]
+    fn should_close_only_the_stream_reset_while_writes_are_queued() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let socket = std::net::TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        socket.set_nonblocking(true).unwrap();
+        let (peer, _) = listener.accept().unwrap();
+        let mut stream = MioStream::from(mio::net::TcpStream::from_std(socket));
+        // queued as the selector has not reported write readiness yet
+        assert_eq!(5, stream.write(b"hello").unwrap());
+        assert!(stream.has_pending_writes());
+        // close with RST instead of FIN
+        SockRef::from(&peer).set_linger(Some(Duration::ZERO)).unwrap();
+        drop(peer);
+        std::thread::sleep(Duration::from_millis(50));
+
+        let mut selector = MioSelector::new()
+            .unwrap()
+            .with_timeout(Some(Duration::from_millis(100)));
+        let mut io_nodes = HashMap::new();
+        let mut io_node = IONode::new(stream, 0, (), None, 0);
+        let token = selector.register(&mut io_node).unwrap();
+        io_nodes.insert(token, io_node);
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while !io_nodes[&token].stream.write_closed() && Instant::now() < deadline {
+            selector.poll(&mut io_nodes).unwrap();
+        }
+        assert!(io_nodes[&token].stream.write_closed());
+    }
+}
@@ -1,8 +1,13 @@
 //! OS specific socket event notification mechanisms like `epoll`.
 
 use crate::node::IONode;
+#[cfg(feature = "stats")]
+use crate::stream::IoCounters;
+use crate::stream::{SocketOptions, SocketQueues};
 use std::collections::HashMap;
+use std::fmt;
 use std::io;
+use std::sync::Arc;
 
 pub mod direct;
 #[cfg(feature = "mio")]
@@ -10,12 +15,99 @@ pub mod mio;
 
 pub type SelectorToken = u32;
 
+/// Handle that other threads can use to interrupt the [`Selector`] blocked waiting for the events,
+/// see [`Selector::waker`].
+#[derive(Clone)]
+pub struct Waker {
+    wake: Arc<dyn Fn() -> io::Result<()> + Send + Sync>,
+}
+
+impl Waker {
+    pub fn new(wake: impl Fn() -> io::Result<()> + Send + Sync + 'static) -> Waker {
+        Self { wake: Arc::new(wake) }
+    }
+
+    /// Interrupts the current (or the next) blocking wait of the selector.
+    pub fn wake(&self) -> io::Result<()> {
+        (self.wake)()
+    }
+}
+
+impl fmt::Debug for Waker {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Waker")
+    }
+}
+
 pub trait Selectable {
     fn connected(&mut self) -> io::Result<bool>;
 
     fn make_writable(&mut self);
 
     fn make_readable(&mut self);
+
+    /// Clears the read readiness so that the stream does not read from the socket until made
+    /// readable again, used while the reading is paused.
+    fn make_unreadable(&mut self) {}
+
+    /// Returns the current occupancy of the kernel socket buffers, if supported by the stream.
+    fn socket_queues(&self) -> Option<SocketQueues> {
+        None
+    }
+
+    /// Returns the kernel receive timestamp (in nanoseconds since the UNIX epoch) of the data returned
+    /// by the last read, if supported by the stream and enabled with `SocketOptions::with_rx_timestamps`.
+    fn rx_timestamp_ns(&self) -> Option<u64> {
+        None
+    }
+
+    /// Returns the number of bytes transferred by the stream, if supported by the stream.
+    #[cfg(feature = "stats")]
+    fn io_counters(&self) -> Option<IoCounters> {
+        None
+    }
+
+    /// Applies the [`SocketOptions`] to the underlying socket, if supported by the stream.
+    fn apply_socket_options(&mut self, _options: &SocketOptions) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Returns `true` if the stream holds outbound data that could not be written to the socket
+    /// yet, in which case the selector will monitor the node for write readiness.
+    fn has_pending_writes(&self) -> bool {
+        false
+    }
+
+    /// Attempts to write the outbound data queued by the stream, called once the socket is writable.
+    fn flush_pending_writes(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Returns `true` once the peer has shut down its side of the connection and all the data it
+    /// sent has been read (the read returned `0`), if tracked by the stream.
+    fn peer_closed(&self) -> bool {
+        false
+    }
+
+    /// Returns `true` once the connection can no longer be written to, such as after it has been
+    /// reset by the peer, if tracked by the stream.
+    fn write_closed(&self) -> bool {
+        false
+    }
+
+    /// Called by the selector once the connection can no longer be written to or has failed.
+    fn make_write_closed(&mut self) {}
+
+    /// Returns (and clears) the pending socket error (`SO_ERROR`), if supported by the stream.
+    fn take_socket_error(&mut self) -> io::Result<Option<io::Error>> {
+        Ok(None)
+    }
+
+    /// Returns the error the connection attempt has failed with (such as [`ConnectionRefused`](io::ErrorKind::ConnectionRefused)),
+    /// if tracked by the stream. Once the attempt has failed the stream remains failed.
+    fn connect_error(&self) -> Option<io::Error> {
+        None
+    }
 }
 
 pub trait Selector {
@@ -25,5 +117,23 @@ pub trait Selector {
 
     fn unregister<E>(&mut self, io_node: &mut IONode<Self::Target, E>) -> io::Result<()>;
 
-    fn poll<E>(&mut self, io_nodes: &mut HashMap<SelectorToken, IONode<Self::Target, E>>) -> io::Result<()>;
+    /// Stops monitoring the node for read readiness so that the peer is subject to TCP backpressure.
+    fn pause_reading<E>(&mut self, _token: SelectorToken, _io_node: &mut IONode<Self::Target, E>) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Restores monitoring the node for read readiness.
+    fn resume_reading<E>(&mut self, _token: SelectorToken, _io_node: &mut IONode<Self::Target, E>) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Processes the readiness events and returns their number, which the [`IOService`](crate::service::IOService)
+    /// uses to decide whether to idle.
+    fn poll<E>(&mut self, io_nodes: &mut HashMap<SelectorToken, IONode<Self::Target, E>>) -> io::Result<usize>;
+
+    /// Returns the [`Waker`] that interrupts the blocking wait for the events, or `None` if the
+    /// selector never blocks.
+    fn waker(&mut self) -> io::Result<Option<Waker>> {
+        Ok(None)
+    }
 }
@@ -1,31 +1,342 @@
 //! Service to manage multiple endpoint lifecycle.
 
 use std::collections::{HashMap, VecDeque};
+use std::fmt::{Display, Formatter};
 use std::io;
+use std::io::ErrorKind;
 use std::marker::PhantomData;
-use std::net::{SocketAddr, ToSocketAddrs};
+use std::net::SocketAddr;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::path::PathBuf;
+use std::sync::mpsc::{channel, Receiver, Sender};
 use std::time::Duration;
 
 use idle::IdleStrategy;
-use log::{error, warn};
+use log::{error, info, warn};
+use thiserror::Error;
 
-use crate::endpoint::{Context, Endpoint, EndpointWithContext};
+use crate::dns::{BlockingDnsResolver, DnsResolver};
+use crate::endpoint::{ConnectionInfo, Context, Endpoint, EndpointWithContext, Redirect};
+use crate::idle_policy::IdlePolicy;
+#[cfg(feature = "stats")]
+use crate::metrics::ServiceMetrics;
 use crate::node::IONode;
-use crate::select::{Selector, SelectorToken};
+#[cfg(feature = "probe")]
+use crate::probe::{IoProbe, ProbeEvent};
+use crate::rate_limit::{SlidingWindow, TokenBucket};
+use crate::select::{Selectable, Selector, SelectorToken, Waker};
+use crate::stream::record::SessionRecorder;
+use crate::stream::SocketQueues;
+use crate::time::{MonotonicClockSource, TimeSource};
+use crate::timer::{TimerId, TimerWheel, DEFAULT_TICK};
+#[cfg(feature = "probe")]
 use crate::util::current_time_nanos;
 
-const ENDPOINT_CREATION_THROTTLE_NS: u64 = Duration::from_secs(1).as_nanos() as u64;
+const DEFAULT_ENDPOINT_CREATION_THROTTLE: Duration = Duration::from_secs(1);
+const DEFAULT_DNS_RETRY_BACKOFF: Duration = Duration::from_secs(1);
+const DEFAULT_MAX_REDIRECTS: u32 = 5;
+
+/// Name of the files the endpoint sessions are recorded into, see [`IOService::with_recording`].
+pub const RECORDING_FILE_NAME: &str = "endpoint";
+
+/// Identifies [`Endpoint`] registered with the [`IOService`]. The handle remains the same
+/// when the endpoint connection is recreated.
+pub type Handle = u32;
+
+/// Errors reported by the [`IOService`], identifying the stage at which the underlying
+/// [`io::Error`] occurred. Converts into [`io::Error`] of the same kind.
+#[derive(Debug, Error)]
+pub enum ServiceError {
+    #[error("dns resolution failed: {0}")]
+    Dns(io::Error),
+    #[error("unable to create connection: {0}")]
+    Connect(io::Error),
+    #[error("endpoint error: {0}")]
+    Endpoint(io::Error),
+    #[error("selector error: {0}")]
+    Selector(io::Error),
+    #[error("rate limit exceeded")]
+    RateLimited,
+    #[error("endpoint not connected")]
+    NotConnected,
+    #[error("service mailbox disconnected")]
+    MailboxDisconnected,
+    #[error(transparent)]
+    IO(#[from] io::Error),
+}
+
+impl ServiceError {
+    /// Returns the kind of the underlying [`io::Error`] (or the closest match).
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            ServiceError::Dns(err)
+            | ServiceError::Connect(err)
+            | ServiceError::Endpoint(err)
+            | ServiceError::Selector(err)
+            | ServiceError::IO(err) => err.kind(),
+            ServiceError::RateLimited => ErrorKind::WouldBlock,
+            ServiceError::NotConnected => ErrorKind::NotConnected,
+            ServiceError::MailboxDisconnected => ErrorKind::BrokenPipe,
+        }
+    }
+}
+
+impl From<ServiceError> for io::Error {
+    fn from(err: ServiceError) -> Self {
+        match err {
+            ServiceError::IO(err) => err,
+            err => io::Error::new(err.kind(), err),
+        }
+    }
+}
+
+/// The reason the endpoint connection has been dropped, passed to [`Endpoint::can_recreate_with_reason`].
+#[derive(Debug)]
+pub enum DisconnectReason {
+    /// Connection has not been established within the connect timeout.
+    ConnectTimeout(Duration),
+    /// Connection has been recycled as per the auto disconnect configuration.
+    AutoDisconnect(Duration),
+    /// Endpoint (or its stream) has failed with the error.
+    Error(ServiceError),
+    /// Endpoint has panicked when polled, with the panic message. Only reported if the panic
+    /// isolation is enabled, see [`IOService::with_panic_isolation`].
+    Panic(String),
+}
+
+impl Display for DisconnectReason {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DisconnectReason::ConnectTimeout(timeout) => write!(f, "connection timed out after {:?}", timeout),
+            DisconnectReason::AutoDisconnect(ttl) => write!(f, "auto disconnected after {:?}", ttl),
+            DisconnectReason::Error(err) => write!(f, "{}", err),
+            DisconnectReason::Panic(message) => write!(f, "endpoint panicked: {}", message),
+        }
+    }
+}
+
+/// Detects the connection that has been closed by the peer or has failed, so that the endpoint is
+/// recreated without waiting for the next read (or write) to fail. The failed connection attempt is
+/// reported as [`ServiceError::Connect`].
+fn check_connection<T: Selectable>(stream: &mut T, check_socket_error: bool) -> Result<(), DisconnectReason> {
+    if let Some(err) = stream.connect_error() {
+        return Err(DisconnectReason::Error(ServiceError::Connect(err)));
+    }
+    let write_closed = stream.write_closed();
+    let socket_error = match write_closed || check_socket_error {
+        true => stream.take_socket_error().unwrap_or_else(Some),
+        false => None,
+    };
+    let err = match socket_error {
+        Some(err) => err,
+        None if write_closed => io::Error::new(ErrorKind::BrokenPipe, "connection is no longer writable"),
+        None if stream.peer_closed() => io::Error::new(ErrorKind::UnexpectedEof, "connection closed by peer"),
+        None => return Ok(()),
+    };
+    Err(DisconnectReason::Error(ServiceError::Endpoint(err)))
+}
+
+/// Invokes the endpoint `poll`, converting the panic into [`DisconnectReason::Panic`] if `catch_panic` is set.
+fn poll_guarded<F: FnOnce() -> io::Result<()>>(catch_panic: bool, poll: F) -> Result<(), DisconnectReason> {
+    let result = match catch_panic {
+        true => catch_unwind(AssertUnwindSafe(poll)).map_err(|payload| {
+            let message = match (payload.downcast_ref::<&str>(), payload.downcast_ref::<String>()) {
+                (Some(message), _) => message.to_string(),
+                (_, Some(message)) => message.clone(),
+                _ => String::from("unknown panic"),
+            };
+            DisconnectReason::Panic(message)
+        })?,
+        false => poll(),
+    };
+    result.map_err(|err| DisconnectReason::Error(ServiceError::Endpoint(err)))
+}
+
+/// Defines how the [`IOService`] connects to the addresses resolved for the [`Endpoint`].
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq)]
+pub enum ConnectStrategy {
+    /// Connect to the first resolved address only.
+    #[default]
+    FirstAddress,
+    /// Try each resolved address in turn, moving on to the next one if the connection has not
+    /// been established within the specified attempt timeout. The last address is only abandoned
+    /// once the connect timeout (if configured) has elapsed.
+    Sequential(Duration),
+}
 
 /// Handles the lifecycle of endpoints (see [`Endpoint`]), which are typically network connections.
-/// It uses `SelectService` pattern for managing asynchronous I/O operations.
-pub struct IOService<S: Selector, E, C> {
+/// It uses `SelectService` pattern for managing asynchronous I/O operations. Endpoint addresses
+/// are obtained using the [`DnsResolver`], which by default is [`BlockingDnsResolver`]. Timeouts
+/// and deadlines are driven by the [`TimeSource`], which by default is [`MonotonicClockSource`].
+pub struct IOService<S: Selector, E, C, R = BlockingDnsResolver, T = MonotonicClockSource> {
     selector: S,
-    pending_endpoints: VecDeque<E>,
+    pending_endpoints: VecDeque<PendingEndpoint<E>>,
     io_nodes: HashMap<SelectorToken, IONode<S::Target, E>>,
-    idle_strategy: IdleStrategy,
+    next_handle: Handle,
+    idle_policy: Box<dyn IdlePolicy + Send>,
     next_endpoint_create_time_ns: u64,
+    endpoint_creation_throttle: Duration,
+    dns_retry_backoff: Duration,
+    max_redirects: u32,
+    redirects: HashMap<Handle, u32>,
+    failed: HashMap<Handle, String>,
+    recording_dir: Option<PathBuf>,
+    session_recorders: HashMap<Handle, SessionRecorder>,
     context: PhantomData<C>,
     auto_disconnect: Option<Duration>,
+    connect_strategy: ConnectStrategy,
+    connect_timeout: Option<Duration>,
+    dns_resolver: R,
+    rate_limits: HashMap<Handle, RateLimit<S::Target, E>>,
+    weight_limits: HashMap<Handle, SlidingWindow>,
+    groups: HashMap<String, Vec<Handle>>,
+    socket_queues_sample_interval: Option<Duration>,
+    next_socket_queues_sample_time_ns: u64,
+    socket_error_check_interval: Option<Duration>,
+    next_socket_error_check_time_ns: u64,
+    time_source: T,
+    timers: TimerWheel<Handle>,
+    expired_timers: Vec<(Handle, TimerId)>,
+    poll_order: Vec<SelectorToken>,
+    poll_cursor: usize,
+    mailbox: Option<Mailbox<S::Target, E>>,
+    panic_isolation: bool,
+    #[cfg(feature = "probe")]
+    probe: Option<Box<dyn IoProbe + Send>>,
+    #[cfg(feature = "stats")]
+    metrics: ServiceMetrics,
+}
+
+/// Deferred action queued with [`IOService::send`].
+type Action<T, E> = Box<dyn FnOnce(&mut T, &mut E) -> io::Result<()> + Send>;
+
+/// Action sent from another thread with the [`ServiceMailbox`].
+type Command<T, E> = (Handle, Action<T, E>);
+
+/// Both halves of the channel backing the [`ServiceMailbox`].
+type Mailbox<T, E> = (Sender<Command<T, E>>, Receiver<Command<T, E>>);
+
+/// Sending half of the [`IOService`] mailbox that can be cloned and moved to other threads, letting
+/// them enqueue actions targeted at the endpoint [`Handle`] without sharing the service itself.
+/// The actions are invoked at the start of the next [`IOService::poll`] cycle, subject to the
+/// endpoint rate limit (if set), and are discarded if the endpoint is not connected at the time.
+/// If the selector blocks waiting for the events, the wait is interrupted with its [`Waker`].
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::io::{Read, Write};
+/// use boomnet::service::{Handle, ServiceMailbox};
+/// use boomnet::ws::Websocket;
+///
+/// fn spawn_strategy<S: Read + Write + 'static, E: 'static>(mailbox: ServiceMailbox<Websocket<S>, E>, handle: Handle) {
+///     std::thread::spawn(move || {
+///         mailbox.send(handle, |ws, _endpoint| Ok(ws.send_text(true, Some(b"ping"))?)).unwrap();
+///     });
+/// }
+/// ```
+pub struct ServiceMailbox<T, E> {
+    sender: Sender<Command<T, E>>,
+    waker: Option<Waker>,
+}
+
+impl<T, E> Clone for ServiceMailbox<T, E> {
+    fn clone(&self) -> Self {
+        Self {
+            sender: self.sender.clone(),
+            waker: self.waker.clone(),
+        }
+    }
+}
+
+impl<T, E> ServiceMailbox<T, E> {
+    /// Enqueues the `action` for the endpoint. Returns [`ServiceError::MailboxDisconnected`] error
+    /// if the service no longer exists.
+    pub fn send<F>(&self, handle: Handle, action: F) -> Result<(), ServiceError>
+    where
+        F: FnOnce(&mut T, &mut E) -> io::Result<()> + Send + 'static,
+    {
+        self.sender
+            .send((handle, Box::new(action)))
+            .map_err(|_| ServiceError::MailboxDisconnected)?;
+        match &self.waker {
+            Some(waker) => Ok(waker.wake()?),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Token bucket together with the actions awaiting for the tokens to become available.
+struct RateLimit<T, E> {
+    bucket: TokenBucket,
+    queue: VecDeque<Action<T, E>>,
+}
+
+/// Endpoint awaiting connection together with the token used to resume the previous session.
+struct PendingEndpoint<E> {
+    handle: Handle,
+    endpoint: E,
+    resume_token: Option<u64>,
+    throttled: bool,
+    queued_ns: u64,
+    attempts: u32,
+    // not attempted again before this time, set when the DNS resolution has failed
+    retry_time_ns: u64,
+    // consecutive DNS resolution failures since the endpoint has been queued
+    dns_failures: u32,
+}
+
+/// Connection attempt that needs to be acted upon, see `IOService::check_connect_progress`.
+enum ConnectProgress {
+    /// Connection has not been established within the connect timeout.
+    TimedOut,
+    /// Connection attempt has not completed on time (or has failed), the next address can be tried.
+    Expired,
+    /// Connection to the last address has failed.
+    Failed(io::Error),
+}
+
+/// Connection progress of the endpoint that is not connected yet, see [`IOService::pending`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum PendingState {
+    /// Queued awaiting the DNS resolution and the creation of the connection, which is subject to
+    /// the endpoint creation throttle.
+    AwaitingDns,
+    /// Connection to the resolved `addr` is in progress, with `remaining_addrs` still to be tried
+    /// if it does not complete (see [`ConnectStrategy::Sequential`]).
+    Connecting { addr: SocketAddr, remaining_addrs: usize },
+}
+
+/// Lifecycle stage of the endpoint, see [`IOService::status`].
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum EndpointStatus {
+    /// The handle does not refer to any endpoint managed by the service.
+    Unknown,
+    /// Queued awaiting the DNS resolution and the creation of the connection, either after the
+    /// registration or after the previous connection has been dropped.
+    Pending { attempts: u32 },
+    /// DNS resolution has failed `failures` times in a row and will be retried once the backoff
+    /// has elapsed (see [`IOService::with_dns_retry_backoff`]).
+    Resolving { attempts: u32, failures: u32 },
+    /// Connection to the `addr` is in progress.
+    Connecting { addr: SocketAddr, attempts: u32 },
+    /// Connection has been established at `since_ns`, as per the service [`TimeSource`], `paused` if
+    /// the reading is currently paused (see [`IOService::pause_reading`]).
+    Active { since_ns: u64, paused: bool },
+    /// The endpoint has declined to be recreated after the `reason` (see [`Endpoint::can_recreate`])
+    /// and has been dropped by the service.
+    Failed { reason: String },
+}
+
+/// Describes the endpoint that is not connected yet, see [`IOService::pending`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct PendingInfo {
+    pub handle: Handle,
+    pub state: PendingState,
+    /// Time (in nanoseconds) since the endpoint has been queued to be (re)connected.
+    pub pending_ns: u64,
+    /// Number of connection attempts made for the endpoint since it was registered.
+    pub attempts: u32,
 }
 
 /// Defines how an instance that implements `SelectService` can be transformed
@@ -49,190 +360,2868 @@ pub trait IntoIOServiceWithContext<E, C: Context> {
 impl<S: Selector, E, C> IOService<S, E, C> {
     /// Creates new instance of [`IOService`].
     pub fn new(selector: S, idle_strategy: IdleStrategy) -> IOService<S, E, C> {
+        let time_source = MonotonicClockSource::new();
         Self {
             selector,
             pending_endpoints: VecDeque::new(),
             io_nodes: HashMap::new(),
-            idle_strategy,
+            next_handle: 0,
+            idle_policy: Box::new(idle_strategy),
             next_endpoint_create_time_ns: 0,
+            endpoint_creation_throttle: DEFAULT_ENDPOINT_CREATION_THROTTLE,
+            dns_retry_backoff: DEFAULT_DNS_RETRY_BACKOFF,
+            max_redirects: DEFAULT_MAX_REDIRECTS,
+            redirects: HashMap::new(),
+            failed: HashMap::new(),
+            recording_dir: None,
+            session_recorders: HashMap::new(),
             context: PhantomData,
             auto_disconnect: None,
+            connect_strategy: ConnectStrategy::default(),
+            connect_timeout: None,
+            dns_resolver: BlockingDnsResolver,
+            rate_limits: HashMap::new(),
+            weight_limits: HashMap::new(),
+            groups: HashMap::new(),
+            socket_queues_sample_interval: None,
+            next_socket_queues_sample_time_ns: 0,
+            socket_error_check_interval: None,
+            next_socket_error_check_time_ns: 0,
+            timers: TimerWheel::new(DEFAULT_TICK, time_source.current_time_nanos()),
+            time_source,
+            expired_timers: Vec::new(),
+            poll_order: Vec::new(),
+            poll_cursor: 0,
+            mailbox: None,
+            panic_isolation: false,
+            #[cfg(feature = "probe")]
+            probe: None,
+            #[cfg(feature = "stats")]
+            metrics: ServiceMetrics::default(),
+        }
+    }
+}
+
+impl<S: Selector, E, C, R: DnsResolver, T: TimeSource> IOService<S, E, C, R, T> {
+    /// Specify [`DnsResolver`] used to obtain the [`Endpoint`] addresses.
+    pub fn with_dns_resolver<D: DnsResolver>(self, dns_resolver: D) -> IOService<S, E, C, D, T> {
+        self.map_parts(|_, time_source| (dns_resolver, time_source))
+    }
+
+    /// Specify [`TimeSource`] used to drive the timeouts, deadlines and timers. The time is read once
+    /// per [`IOService::poll`] cycle. Any timers scheduled so far are discarded.
+    pub fn with_time_source<U: TimeSource>(self, time_source: U) -> IOService<S, E, C, R, U> {
+        let service = self.map_parts(|dns_resolver, _| (dns_resolver, time_source));
+        IOService {
+            timers: TimerWheel::new(DEFAULT_TICK, service.time_source.current_time_nanos()),
+            expired_timers: Vec::new(),
+            poll_order: Vec::new(),
+            poll_cursor: 0,
+            ..service
+        }
+    }
+
+    /// Replaces the [`DnsResolver`] and the [`TimeSource`] (which determine the service type) with
+    /// the ones returned by `f`, keeping the rest of the service as is.
+    fn map_parts<D, U, F>(self, f: F) -> IOService<S, E, C, D, U>
+    where
+        F: FnOnce(R, T) -> (D, U),
+    {
+        let (dns_resolver, time_source) = f(self.dns_resolver, self.time_source);
+        IOService {
+            selector: self.selector,
+            pending_endpoints: self.pending_endpoints,
+            io_nodes: self.io_nodes,
+            next_handle: self.next_handle,
+            idle_policy: self.idle_policy,
+            next_endpoint_create_time_ns: self.next_endpoint_create_time_ns,
+            endpoint_creation_throttle: self.endpoint_creation_throttle,
+            dns_retry_backoff: self.dns_retry_backoff,
+            max_redirects: self.max_redirects,
+            redirects: self.redirects,
+            failed: self.failed,
+            recording_dir: self.recording_dir,
+            session_recorders: self.session_recorders,
+            context: self.context,
+            auto_disconnect: self.auto_disconnect,
+            connect_strategy: self.connect_strategy,
+            connect_timeout: self.connect_timeout,
+            dns_resolver,
+            rate_limits: self.rate_limits,
+            weight_limits: self.weight_limits,
+            groups: self.groups,
+            socket_queues_sample_interval: self.socket_queues_sample_interval,
+            next_socket_queues_sample_time_ns: self.next_socket_queues_sample_time_ns,
+            socket_error_check_interval: self.socket_error_check_interval,
+            next_socket_error_check_time_ns: self.next_socket_error_check_time_ns,
+            time_source,
+            timers: self.timers,
+            expired_timers: self.expired_timers,
+            poll_order: self.poll_order,
+            poll_cursor: self.poll_cursor,
+            mailbox: self.mailbox,
+            panic_isolation: self.panic_isolation,
+            #[cfg(feature = "probe")]
+            probe: self.probe,
+            #[cfg(feature = "stats")]
+            metrics: self.metrics,
+        }
+    }
+
+    /// Specify how to connect to the resolved [`Endpoint`] addresses.
+    pub fn with_connect_strategy(self, connect_strategy: ConnectStrategy) -> IOService<S, E, C, R, T> {
+        Self {
+            connect_strategy,
+            ..self
+        }
+    }
+
+    /// Replace the [`IdleStrategy`] the service has been created with by the [`IdlePolicy`], which
+    /// is applied at the end of each poll cycle.
+    pub fn with_idle_policy(self, idle_policy: impl IdlePolicy + Send + 'static) -> IOService<S, E, C, R, T> {
+        Self {
+            idle_policy: Box::new(idle_policy),
+            ..self
+        }
+    }
+
+    /// Specify the minimum interval between creating connections for the pending endpoints, which
+    /// protects the remote peer from reconnect storms (default is one second). Use [`Duration::ZERO`]
+    /// to create one connection per [`IOService::poll`] cycle.
+    pub fn with_endpoint_creation_throttle(self, throttle: Duration) -> IOService<S, E, C, R, T> {
+        Self {
+            endpoint_creation_throttle: throttle,
+            ..self
+        }
+    }
+
+    /// Specify how long to wait before resolving the endpoint address again once the DNS resolution
+    /// has failed (default is one second). The failure is reported to [`Endpoint::can_recreate_with_reason`]
+    /// as [`ServiceError::Dns`] and the endpoint is reported as [`EndpointStatus::Resolving`] unless it
+    /// declines to be recreated, in which case it becomes [`EndpointStatus::Failed`] and the error is
+    /// returned from [`IOService::poll`].
+    pub fn with_dns_retry_backoff(self, dns_retry_backoff: Duration) -> IOService<S, E, C, R, T> {
+        Self {
+            dns_retry_backoff,
+            ..self
+        }
+    }
+
+    /// Specify how many consecutive redirects (see [`Redirect`]) are followed for each endpoint before
+    /// the redirect is handled as any other error (default is five). The count is reset once the
+    /// endpoint is disconnected for any other reason. The endpoint opts in to follow the redirects
+    /// with [`Endpoint::on_redirect`].
+    pub fn with_max_redirects(self, max_redirects: u32) -> IOService<S, E, C, R, T> {
+        Self { max_redirects, ..self }
+    }
+
+    /// Record the sessions of all the endpoints into the `dir`, which must exist. Each endpoint is
+    /// given its [`SessionRecorder`] writing into the `endpoint_{handle}_{unix_seconds}` files (see
+    /// [`RECORDING_FILE_NAME`]) before every connection is created, the endpoint opts in to record
+    /// the connection with [`Endpoint::on_session_recording`]. The recorded sessions can be replayed
+    /// through the same endpoint code with [`RecordedSessions`](crate::stream::replay::RecordedSessions).
+    pub fn with_recording(self, dir: impl Into<PathBuf>) -> IOService<S, E, C, R, T> {
+        Self {
+            recording_dir: Some(dir.into()),
+            ..self
+        }
+    }
+
+    /// Install the [`IoProbe`] that is notified when each [`IOService::poll`] cycle starts and ends.
+    /// Available with the `probe` feature.
+    #[cfg(feature = "probe")]
+    pub fn with_probe(self, probe: impl IoProbe + Send + 'static) -> IOService<S, E, C, R, T> {
+        Self {
+            probe: Some(Box::new(probe)),
+            ..self
         }
     }
 
-    /// Specify TTL for each [`Endpoint`] connection.
-    pub fn with_auto_disconnect(self, auto_disconnect: Duration) -> IOService<S, E, C> {
+    /// Specify TTL for each [`Endpoint`] connection, unless the endpoint provides its own with [`Endpoint::ttl`].
+    pub fn with_auto_disconnect(self, auto_disconnect: Duration) -> IOService<S, E, C, R, T> {
         Self {
             auto_disconnect: Some(auto_disconnect),
             ..self
         }
     }
 
-    /// Registers a new [`Endpoint`] with the service.
-    pub fn register(&mut self, endpoint: E) {
-        self.pending_endpoints.push_back(endpoint)
+    /// Specify how long to wait for the [`Endpoint`] connection to be established. If the
+    /// connection is still in progress after the timeout the attempt is aborted and the endpoint
+    /// will be recreated (subject to [`Endpoint::can_recreate`]).
+    pub fn with_connect_timeout(self, connect_timeout: Duration) -> IOService<S, E, C, R, T> {
+        Self {
+            connect_timeout: Some(connect_timeout),
+            ..self
+        }
+    }
+
+    /// Enable or disable catching the panics raised by the endpoints when polled (disabled by default).
+    /// The endpoint that has panicked is disconnected with [`DisconnectReason::Panic`] and is either
+    /// recreated or dropped (instead of panicking) depending on [`Endpoint::can_recreate_with_reason`],
+    /// while the remaining endpoints keep running.
+    pub fn with_panic_isolation(self, panic_isolation: bool) -> IOService<S, E, C, R, T> {
+        Self {
+            panic_isolation,
+            ..self
+        }
     }
 
-    fn resolve_dns(addr: &str) -> io::Result<SocketAddr> {
-        addr.to_socket_addrs()?
-            .next()
-            .ok_or_else(|| io::Error::other("unable to resolve dns address"))
+    /// Sample the kernel socket buffer occupancy (see [`SocketQueues`]) of each connection at the
+    /// specified interval during [`IOService::poll`]. Only supported on Linux.
+    pub fn with_socket_queues_sampling(self, interval: Duration) -> IOService<S, E, C, R, T> {
+        Self {
+            socket_queues_sample_interval: Some(interval),
+            ..self
+        }
     }
-}
 
-impl<S, E> IOService<S, E, ()>
-where
-    S: Selector,
-    E: Endpoint<Target = S::Target>,
-{
-    /// This method polls all registered endpoints for readiness and performs I/O operations based
-    /// on the ['Selector'] poll results. It then iterates through all endpoints, either
-    /// updating existing streams or creating and registering new ones. It uses [`Endpoint::can_recreate`]
-    /// to determine if the error that occurred during polling is recoverable (typically due to remote peer disconnect).
-    pub fn poll(&mut self) -> io::Result<()> {
-        // check for pending endpoints (one at a time & throttled)
-        if !self.pending_endpoints.is_empty() {
-            let current_time_ns = current_time_nanos();
-            if current_time_ns > self.next_endpoint_create_time_ns {
-                if let Some(mut endpoint) = self.pending_endpoints.pop_front() {
-                    let addr = Self::resolve_dns(&endpoint.connection_info()?.to_string())?;
-                    let stream = endpoint.create_target(addr)?;
-                    let mut io_node = IONode::new(stream, endpoint, self.auto_disconnect);
-                    let token = self.selector.register(&mut io_node)?;
-                    self.io_nodes.insert(token, io_node);
+    /// Check the pending socket error (see [`Selectable::take_socket_error`]) of each connection at
+    /// the specified interval during [`IOService::poll`], so that the failed connection is recreated
+    /// even if the stream does not track the failure itself and the endpoint is not reading.
+    pub fn with_socket_error_check(self, interval: Duration) -> IOService<S, E, C, R, T> {
+        Self {
+            socket_error_check_interval: Some(interval),
+            ..self
+        }
+    }
+
+    /// Registers a new [`Endpoint`] with the service and returns [`Handle`] that can be later
+    /// used to refer to this endpoint.
+    pub fn register(&mut self, endpoint: E) -> Handle {
+        let handle = self.next_handle;
+        self.next_handle += 1;
+        self.pending_endpoints.push_back(PendingEndpoint {
+            handle,
+            endpoint,
+            resume_token: None,
+            throttled: true,
+            queued_ns: self.time_source.current_time_nanos(),
+            attempts: 0,
+            retry_time_ns: 0,
+            dns_failures: 0,
+        });
+        handle
+    }
+
+    /// Registers a new [`Endpoint`] as member of the group identified by the `tag`, so that actions
+    /// can be dispatched to all the members with [`IOService::dispatch_group`].
+    pub fn register_with_tag(&mut self, endpoint: E, tag: impl Into<String>) -> Handle {
+        let handle = self.register(endpoint);
+        self.add_tag(handle, tag);
+        handle
+    }
+
+    /// Adds the endpoint to the group identified by the `tag`, endpoint can be member of multiple groups.
+    pub fn add_tag(&mut self, handle: Handle, tag: impl Into<String>) {
+        let handles = self.groups.entry(tag.into()).or_default();
+        if !handles.contains(&handle) {
+            handles.push(handle);
+        }
+    }
+
+    /// Removes the endpoint from the group identified by the `tag`.
+    pub fn remove_tag(&mut self, handle: Handle, tag: &str) {
+        if let Some(handles) = self.groups.get_mut(tag) {
+            handles.retain(|member| *member != handle);
+        }
+    }
+
+    /// Returns handles of the endpoints in the group identified by the `tag`.
+    pub fn group(&self, tag: &str) -> &[Handle] {
+        self.groups.get(tag).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    /// Registers multiple endpoints at once, typically during startup. Unlike [`IOService::register`]
+    /// the initial connections are not subject to the endpoint creation throttle and are all
+    /// initiated during the next [`IOService::poll`] cycle. Subsequent reconnects are throttled as usual.
+    pub fn register_all<I: IntoIterator<Item = E>>(&mut self, endpoints: I) -> Vec<Handle> {
+        let queued_ns = self.time_source.current_time_nanos();
+        endpoints
+            .into_iter()
+            .map(|endpoint| {
+                let handle = self.next_handle;
+                self.next_handle += 1;
+                self.pending_endpoints.push_back(PendingEndpoint {
+                    handle,
+                    endpoint,
+                    resume_token: None,
+                    throttled: false,
+                    queued_ns,
+                    attempts: 0,
+                    retry_time_ns: 0,
+                    dns_failures: 0,
+                });
+                handle
+            })
+            .collect()
+    }
+
+    /// Stops reading from the endpoint connection without disconnecting, so that the peer is
+    /// subject to TCP backpressure. While paused the endpoint is still polled and receives its
+    /// timers, so that it can keep sending (such as the heartbeats), but the stream does not read
+    /// from the socket (the data already buffered by the stream wrappers may still be returned).
+    /// Requires the selector that monitors the read readiness, such as the `MioSelector`. The pause
+    /// only applies to the current connection and is cleared if the connection is recreated.
+    /// Returns `false` if the endpoint is not currently connected.
+    pub fn pause_reading(&mut self, handle: Handle) -> io::Result<bool> {
+        match self.io_nodes.iter_mut().find(|(_, io_node)| io_node.handle == handle) {
+            Some((token, io_node)) => {
+                if !io_node.paused {
+                    self.selector.pause_reading(*token, io_node)?;
+                    io_node.paused = true;
                 }
-                self.next_endpoint_create_time_ns = current_time_ns + ENDPOINT_CREATION_THROTTLE_NS;
+                Ok(true)
             }
+            None => Ok(false),
         }
+    }
 
-        // check for readiness events
-        self.selector.poll(&mut self.io_nodes)?;
-
-        // check for auto disconnect if enabled
-        if self.auto_disconnect.is_some() {
-            let current_time_ns = current_time_nanos();
-            self.io_nodes.retain(|_token, io_node| {
-                let force_disconnect = current_time_ns > io_node.disconnect_time_ns;
-                if force_disconnect {
-                    // check if we really have to disconnect
-                    return if io_node.as_endpoint_mut().can_auto_disconnect() {
-                        warn!("endpoint auto disconnected after {:?}", self.auto_disconnect.unwrap());
-                        self.selector.unregister(io_node).unwrap();
-                        let mut endpoint = io_node.endpoint.take().unwrap();
-                        if endpoint.can_recreate() {
-                            self.pending_endpoints.push_back(endpoint);
-                        } else {
-                            panic!("unrecoverable error when polling endpoint");
-                        }
-                        false
-                    } else {
-                        // extend the endpoint TTL
-                        io_node.disconnect_time_ns += self.auto_disconnect.unwrap().as_nanos() as u64;
-                        true
-                    };
+    /// Resumes reading from the endpoint connection previously paused with [`IOService::pause_reading`].
+    /// Returns `false` if the endpoint is not currently connected.
+    pub fn resume_reading(&mut self, handle: Handle) -> io::Result<bool> {
+        match self.io_nodes.iter_mut().find(|(_, io_node)| io_node.handle == handle) {
+            Some((token, io_node)) => {
+                if io_node.paused {
+                    self.selector.resume_reading(*token, io_node)?;
+                    io_node.paused = false;
                 }
-                true
-            });
+                Ok(true)
+            }
+            None => Ok(false),
         }
+    }
 
-        // poll endpoints
-        self.io_nodes.retain(|_token, io_node| {
-            let (stream, endpoint) = io_node.as_parts_mut();
-            if let Err(err) = endpoint.poll(stream) {
-                error!("error when polling endpoint: {}", err);
-                self.selector.unregister(io_node).unwrap();
-                let mut endpoint = io_node.endpoint.take().unwrap();
-                if endpoint.can_recreate() {
-                    self.pending_endpoints.push_back(endpoint);
-                } else {
-                    panic!("unrecoverable error when polling endpoint");
+    /// Checks if reading from the endpoint connection is currently paused.
+    pub fn is_reading_paused(&self, handle: Handle) -> bool {
+        self.io_nodes
+            .values()
+            .any(|io_node| io_node.handle == handle && io_node.paused)
+    }
+
+    /// Returns the most recent kernel socket buffer occupancy sampled for the endpoint, or `None`
+    /// if sampling is not enabled, not supported by the stream or the endpoint is not connected.
+    pub fn socket_queues(&self, handle: Handle) -> Option<SocketQueues> {
+        self.io_nodes
+            .values()
+            .find(|io_node| io_node.handle == handle)
+            .and_then(|io_node| io_node.socket_queues)
+    }
+
+    /// Returns the endpoints that are not connected yet, either awaiting the DNS resolution (in the
+    /// order they will be connected) or with the connection in progress, for example so that an
+    /// operator console can display why the endpoint is not connected.
+    pub fn pending(&mut self) -> Vec<PendingInfo> {
+        let current_time_ns = self.time_source.current_time_nanos();
+        let mut pending = self
+            .pending_endpoints
+            .iter()
+            .map(|pending| PendingInfo {
+                handle: pending.handle,
+                state: PendingState::AwaitingDns,
+                pending_ns: current_time_ns.saturating_sub(pending.queued_ns),
+                attempts: pending.attempts,
+            })
+            .collect::<Vec<_>>();
+        let mut connecting = Vec::new();
+        for io_node in self.io_nodes.values_mut() {
+            if !io_node.connected && matches!(io_node.as_stream_mut().connected(), Ok(true)) {
+                io_node.mark_connected(current_time_ns);
+            }
+            if let (false, Some(addr)) = (io_node.connected, io_node.addr) {
+                connecting.push(PendingInfo {
+                    handle: io_node.handle,
+                    state: PendingState::Connecting {
+                        addr,
+                        remaining_addrs: io_node.remaining_addrs.len(),
+                    },
+                    pending_ns: current_time_ns.saturating_sub(io_node.pending_since_ns),
+                    attempts: io_node.connect_attempts,
+                });
+            }
+        }
+        connecting.sort_by_key(|info| info.handle);
+        pending.extend(connecting);
+        pending
+    }
+
+    /// Returns the current [`EndpointStatus`] of the endpoint identified by the `handle`. The endpoint
+    /// goes back to [`EndpointStatus::Pending`] each time its connection is dropped and recreated.
+    pub fn status(&mut self, handle: Handle) -> EndpointStatus {
+        if let Some(pending) = self.pending_endpoints.iter().find(|pending| pending.handle == handle) {
+            return match pending.dns_failures {
+                0 => EndpointStatus::Pending {
+                    attempts: pending.attempts,
+                },
+                failures => EndpointStatus::Resolving {
+                    attempts: pending.attempts,
+                    failures,
+                },
+            };
+        }
+        let current_time_ns = self.time_source.current_time_nanos();
+        let Some(io_node) = self.io_nodes.values_mut().find(|io_node| io_node.handle == handle) else {
+            return match self.failed.get(&handle) {
+                Some(reason) => EndpointStatus::Failed { reason: reason.clone() },
+                None => EndpointStatus::Unknown,
+            };
+        };
+        if !io_node.connected && matches!(io_node.as_stream_mut().connected(), Ok(true)) {
+            io_node.mark_connected(current_time_ns);
+        }
+        match (io_node.connected, io_node.addr) {
+            (false, Some(addr)) => EndpointStatus::Connecting {
+                addr,
+                attempts: io_node.connect_attempts,
+            },
+            _ => EndpointStatus::Active {
+                since_ns: io_node.connected_since_ns.unwrap_or(io_node.pending_since_ns),
+                paused: io_node.paused,
+            },
+        }
+    }
+
+    /// Returns snapshot of the service metrics collected since the service was created (or since
+    /// the last [`IOService::reset_metrics`]), including the bytes transferred by each connected
+    /// endpoint. Available with the `stats` feature.
+    #[cfg(feature = "stats")]
+    pub fn metrics(&self) -> ServiceMetrics {
+        let mut metrics = self.metrics.clone();
+        metrics.endpoint_io = self
+            .io_nodes
+            .values()
+            .filter_map(|io_node| {
+                io_node
+                    .as_stream()
+                    .io_counters()
+                    .map(|counters| (io_node.handle, counters))
+            })
+            .collect();
+        metrics.paused_endpoints = self
+            .io_nodes
+            .values()
+            .filter(|io_node| io_node.paused)
+            .map(|io_node| io_node.handle)
+            .collect();
+        metrics
+    }
+
+    /// Resets the service metrics, the endpoint byte counters are not affected.
+    #[cfg(feature = "stats")]
+    pub fn reset_metrics(&mut self) {
+        self.metrics = ServiceMetrics::default();
+    }
+
+    /// Limits the rate at which messages can be sent to the endpoint using [`IOService::dispatch`]
+    /// or [`IOService::send`]. Replaces any rate limit previously set for this endpoint.
+    pub fn set_rate_limit(&mut self, handle: Handle, bucket: TokenBucket) {
+        self.rate_limits.insert(
+            handle,
+            RateLimit {
+                bucket,
+                queue: VecDeque::new(),
+            },
+        );
+    }
+
+    /// Removes the rate limit for the endpoint discarding any queued messages.
+    pub fn clear_rate_limit(&mut self, handle: Handle) {
+        self.rate_limits.remove(&handle);
+    }
+
+    /// Limits the total weight of the messages sent to the endpoint using [`IOService::dispatch_weighted`]
+    /// within the sliding window, for venues that assign different weights to different request types.
+    /// Replaces any weight limit previously set for this endpoint.
+    pub fn set_weight_limit(&mut self, handle: Handle, window: SlidingWindow) {
+        self.weight_limits.insert(handle, window);
+    }
+
+    /// Removes the weight limit for the endpoint.
+    pub fn clear_weight_limit(&mut self, handle: Handle) {
+        self.weight_limits.remove(&handle);
+    }
+
+    /// Returns the weight that can still be sent to the endpoint, or `None` if no weight limit has
+    /// been set. Lets the strategy check the budget before attempting to send.
+    pub fn remaining_weight(&mut self, handle: Handle) -> Option<u32> {
+        let current_time_ns = self.time_source.current_time_nanos();
+        self.weight_limits
+            .get_mut(&handle)
+            .map(|window| window.remaining(current_time_ns))
+    }
+
+    /// Same as [`IOService::dispatch`] but the message of the given `weight` (typically assigned
+    /// by the [`CostModel`](crate::rate_limit::CostModel)) is also subject to the weight limit (if
+    /// set) for this endpoint. Returns [`ServiceError::RateLimited`] error if either limit has been
+    /// reached, in which case neither is consumed.
+    pub fn dispatch_weighted<F, O>(&mut self, handle: Handle, weight: u32, action: F) -> Result<Option<O>, ServiceError>
+    where
+        F: FnOnce(&mut S::Target, &mut E) -> io::Result<O>,
+    {
+        let current_time_ns = self.time_source.current_time_nanos();
+        if let Some(window) = self.weight_limits.get_mut(&handle) {
+            if weight > window.remaining(current_time_ns) {
+                return Err(ServiceError::RateLimited);
+            }
+        }
+        let output = self.dispatch(handle, action)?;
+        if output.is_some() {
+            if let Some(window) = self.weight_limits.get_mut(&handle) {
+                window.try_acquire(current_time_ns, weight);
+            }
+        }
+        Ok(output)
+    }
+
+    /// Invokes the `action` immediately with the endpoint stream, subject to the rate limit (if set)
+    /// for this endpoint. Returns [`ServiceError::RateLimited`] error if the limit has been reached,
+    /// or `None` if the endpoint is not currently connected.
+    pub fn dispatch<F, O>(&mut self, handle: Handle, action: F) -> Result<Option<O>, ServiceError>
+    where
+        F: FnOnce(&mut S::Target, &mut E) -> io::Result<O>,
+    {
+        let io_node = match self.io_nodes.values_mut().find(|io_node| io_node.handle == handle) {
+            Some(io_node) => io_node,
+            None => return Ok(None),
+        };
+        if let Some(rate_limit) = self.rate_limits.get_mut(&handle) {
+            if !rate_limit.queue.is_empty() || !rate_limit.bucket.try_acquire(self.time_source.current_time_nanos()) {
+                return Err(ServiceError::RateLimited);
+            }
+        }
+        let (stream, endpoint) = io_node.as_parts_mut();
+        action(stream, endpoint).map(Some).map_err(ServiceError::Endpoint)
+    }
+
+    /// Invokes the `action` immediately with the stream of each connected endpoint in the group
+    /// identified by the `tag`, skipping the endpoints whose rate limit (if set) has been reached.
+    /// Returns the number of endpoints the action has been invoked for, or the first error.
+    pub fn dispatch_group<F>(&mut self, tag: &str, mut action: F) -> Result<usize, ServiceError>
+    where
+        F: FnMut(&mut S::Target, &mut E) -> io::Result<()>,
+    {
+        let handles = match self.groups.get(tag) {
+            Some(handles) => handles,
+            None => return Ok(0),
+        };
+        let current_time_ns = self.time_source.current_time_nanos();
+        let mut dispatched = 0;
+        for io_node in self.io_nodes.values_mut() {
+            if !handles.contains(&io_node.handle) {
+                continue;
+            }
+            if let Some(rate_limit) = self.rate_limits.get_mut(&io_node.handle) {
+                if !rate_limit.queue.is_empty() || !rate_limit.bucket.try_acquire(current_time_ns) {
+                    continue;
                 }
-                return false;
             }
-            true
-        });
+            let (stream, endpoint) = io_node.as_parts_mut();
+            action(stream, endpoint).map_err(ServiceError::Endpoint)?;
+            dispatched += 1;
+        }
+        Ok(dispatched)
+    }
+
+    /// Invokes the `action` with the endpoint stream if permitted by the rate limit, otherwise
+    /// queues it to be invoked during subsequent [`IOService::poll`] once the tokens are available.
+    /// Returns `true` if the action was invoked immediately and `false` if it has been queued.
+    /// Queued actions are discarded if the endpoint disconnects.
+    pub fn send<F>(&mut self, handle: Handle, action: F) -> Result<bool, ServiceError>
+    where
+        F: FnOnce(&mut S::Target, &mut E) -> io::Result<()> + Send + 'static,
+    {
+        let io_node = match self.io_nodes.values_mut().find(|io_node| io_node.handle == handle) {
+            Some(io_node) => io_node,
+            None => return Err(ServiceError::NotConnected),
+        };
+        if let Some(rate_limit) = self.rate_limits.get_mut(&handle) {
+            if !rate_limit.queue.is_empty() || !rate_limit.bucket.try_acquire(self.time_source.current_time_nanos()) {
+                rate_limit.queue.push_back(Box::new(action));
+                return Ok(false);
+            }
+        }
+        let (stream, endpoint) = io_node.as_parts_mut();
+        action(stream, endpoint).map_err(ServiceError::Endpoint)?;
+        Ok(true)
+    }
 
-        self.idle_strategy.idle(0);
+    /// Returns [`ServiceMailbox`] that other threads can use to enqueue actions for the endpoints.
+    pub fn mailbox(&mut self) -> ServiceMailbox<S::Target, E> {
+        let waker = self.waker().unwrap_or_else(|err| {
+            warn!("unable to create selector waker: {}", err);
+            None
+        });
+        let (sender, _) = self.mailbox.get_or_insert_with(channel);
+        ServiceMailbox {
+            sender: sender.clone(),
+            waker,
+        }
+    }
 
-        Ok(())
+    /// Returns the [`Waker`] that other threads can use to interrupt the selector blocked waiting
+    /// for the events as well as the idle period of the [`IdlePolicy`], or `None` if neither of them
+    /// ever blocks.
+    pub fn waker(&mut self) -> io::Result<Option<Waker>> {
+        Ok(match (self.selector.waker()?, self.idle_policy.waker()) {
+            (Some(selector), Some(idle)) => Some(Waker::new(move || {
+                selector.wake()?;
+                idle.wake()
+            })),
+            (selector, idle) => selector.or(idle),
+        })
     }
-}
 
-impl<S, E, C> IOService<S, E, C>
-where
-    S: Selector,
-    C: Context,
-    E: EndpointWithContext<C, Target = S::Target>,
-{
-    /// This method polls all registered endpoints for readiness passing the [`Context`] and performs I/O operations based
-    /// on the `SelectService` poll results. It then iterates through all endpoints, either
-    /// updating existing streams or creating and registering new ones. It uses [`Endpoint::can_recreate`]
-    /// to determine if the error that occurred during polling is recoverable (typically due to remote peer disconnect).
-    pub fn poll(&mut self, context: &mut C) -> io::Result<()> {
-        // check for pending endpoints (one at a time & throttled)
-        if !self.pending_endpoints.is_empty() {
-            let current_time_ns = current_time_nanos();
-            if current_time_ns > self.next_endpoint_create_time_ns {
-                if let Some(mut endpoint) = self.pending_endpoints.pop_front() {
-                    let addr = Self::resolve_dns(&endpoint.connection_info()?.to_string())?;
-                    let stream = endpoint.create_target(addr, context)?;
-                    let mut io_node = IONode::new(stream, endpoint, self.auto_disconnect);
-                    let token = self.selector.register(&mut io_node)?;
-                    self.io_nodes.insert(token, io_node);
+    /// Invokes actions received with the [`ServiceMailbox`] and returns their number.
+    fn drain_mailbox(&mut self, current_time_ns: u64) -> usize {
+        let mut action_count = 0;
+        while let Some(Ok((handle, action))) = self.mailbox.as_ref().map(|(_, receiver)| receiver.try_recv()) {
+            action_count += 1;
+            let io_node = match self.io_nodes.values_mut().find(|io_node| io_node.handle == handle) {
+                Some(io_node) => io_node,
+                None => {
+                    warn!("discarding mailbox message for disconnected endpoint");
+                    continue;
+                }
+            };
+            if let Some(rate_limit) = self.rate_limits.get_mut(&handle) {
+                if !rate_limit.queue.is_empty() || !rate_limit.bucket.try_acquire(current_time_ns) {
+                    rate_limit.queue.push_back(action);
+                    continue;
                 }
-                self.next_endpoint_create_time_ns = current_time_ns + ENDPOINT_CREATION_THROTTLE_NS;
+            }
+            let (stream, endpoint) = io_node.as_parts_mut();
+            if let Err(err) = action(stream, endpoint) {
+                error!("error when sending mailbox message: {}", err);
             }
         }
+        action_count
+    }
 
-        // check for readiness events
-        self.selector.poll(&mut self.io_nodes)?;
-
-        // check for auto disconnect if enabled
-        if self.auto_disconnect.is_some() {
-            let current_time_ns = current_time_nanos();
-            self.io_nodes.retain(|_token, io_node| {
-                let force_disconnect = current_time_ns > io_node.disconnect_time_ns;
-                if force_disconnect {
-                    // check if we really have to disconnect
-                    return if io_node.as_endpoint_mut().can_auto_disconnect(context) {
-                        warn!("endpoint auto disconnected after {:?}", self.auto_disconnect.unwrap());
-                        self.selector.unregister(io_node).unwrap();
-                        let mut endpoint = io_node.endpoint.take().unwrap();
-                        if endpoint.can_recreate(context) {
-                            self.pending_endpoints.push_back(endpoint);
-                        } else {
-                            panic!("unrecoverable error when polling endpoint");
-                        }
-                        false
-                    } else {
-                        // extend the endpoint TTL
-                        io_node.disconnect_time_ns += self.auto_disconnect.unwrap().as_nanos() as u64;
-                        true
-                    };
+    /// Returns the number of actions queued by [`IOService::send`] for the endpoint.
+    pub fn queued_messages(&self, handle: Handle) -> usize {
+        self.rate_limits
+            .get(&handle)
+            .map(|rate_limit| rate_limit.queue.len())
+            .unwrap_or(0)
+    }
+
+    /// Schedules one-shot timer for the endpoint that will be delivered to [`Endpoint::on_timer`]
+    /// (or [`EndpointWithContext::on_timer`]) during [`IOService::poll`] once the `delay` has elapsed.
+    /// Timers have millisecond resolution and are not delivered if the endpoint is not connected
+    /// at the time of expiry.
+    pub fn schedule_timer(&mut self, handle: Handle, delay: Duration) -> TimerId {
+        let deadline_ns = self.time_source.current_time_nanos() + delay.as_nanos() as u64;
+        self.timers.schedule(deadline_ns, None, handle)
+    }
+
+    /// Schedules timer for the endpoint that will be delivered every `period` until cancelled,
+    /// see [`IOService::schedule_timer`].
+    pub fn schedule_periodic_timer(&mut self, handle: Handle, period: Duration) -> TimerId {
+        let deadline_ns = self.time_source.current_time_nanos() + period.as_nanos() as u64;
+        self.timers.schedule(deadline_ns, Some(period), handle)
+    }
+
+    /// Cancels the timer, returns `false` if the timer does not exist or has already fired.
+    pub fn cancel_timer(&mut self, timer_id: TimerId) -> bool {
+        self.timers.cancel(timer_id)
+    }
+
+    #[cfg(feature = "probe")]
+    #[inline]
+    fn probe_event(&mut self, event: ProbeEvent) {
+        if let Some(probe) = self.probe.as_mut() {
+            probe.on_event(event, current_time_nanos());
+        }
+    }
+
+    /// Collects the expired timers, which are kept until delivered to the endpoint during the sweep.
+    fn expire_timers(&mut self, current_time_ns: u64) {
+        let expired_timers = &mut self.expired_timers;
+        self.timers
+            .advance(current_time_ns, |timer_id, handle| expired_timers.push((*handle, timer_id)));
+    }
+
+    /// Invokes queued actions for which the tokens have become available.
+    fn drain_rate_limited(&mut self, current_time_ns: u64) {
+        if self.rate_limits.is_empty() {
+            return;
+        }
+        for (handle, rate_limit) in self.rate_limits.iter_mut() {
+            if rate_limit.queue.is_empty() {
+                continue;
+            }
+            let io_node = match self.io_nodes.values_mut().find(|io_node| io_node.handle == *handle) {
+                Some(io_node) => io_node,
+                None => {
+                    warn!("discarding {} queued message(s) for disconnected endpoint", rate_limit.queue.len());
+                    rate_limit.queue.clear();
+                    continue;
                 }
-                true
-            });
+            };
+            while !rate_limit.queue.is_empty() && rate_limit.bucket.try_acquire(current_time_ns) {
+                let action = rate_limit.queue.pop_front().unwrap();
+                let (stream, endpoint) = io_node.as_parts_mut();
+                if let Err(err) = action(stream, endpoint) {
+                    error!("error when sending queued message: {}", err);
+                }
+            }
         }
+    }
 
-        // poll endpoints
-        self.io_nodes.retain(|_token, io_node| {
-            let (stream, endpoint) = io_node.as_parts_mut();
-            if let Err(err) = endpoint.poll(stream, context) {
-                error!("error when polling endpoint: {}", err);
-                self.selector.unregister(io_node).unwrap();
-                let mut endpoint = io_node.endpoint.take().unwrap();
-                if endpoint.can_recreate(context) {
-                    self.pending_endpoints.push_back(endpoint);
-                } else {
-                    panic!("unrecoverable error when polling endpoint");
+    fn sample_socket_queues(&mut self, current_time_ns: u64) {
+        if let Some(interval) = self.socket_queues_sample_interval {
+            if current_time_ns > self.next_socket_queues_sample_time_ns {
+                for io_node in self.io_nodes.values_mut() {
+                    io_node.socket_queues = io_node.as_stream().socket_queues();
                 }
-                return false;
+                self.next_socket_queues_sample_time_ns = current_time_ns + interval.as_nanos() as u64;
             }
-            true
-        });
+        }
+    }
 
-        self.idle_strategy.idle(0);
+    /// Returns the [`SessionRecorder`] of the endpoint with the `handle` if the recording is enabled.
+    fn session_recorder(&mut self, handle: Handle) -> Option<&mut SessionRecorder> {
+        let dir = self.recording_dir.as_ref()?;
+        let recorder = self
+            .session_recorders
+            .entry(handle)
+            .or_insert_with(|| SessionRecorder::new(dir.join(RECORDING_FILE_NAME).to_string_lossy(), handle));
+        Some(recorder)
+    }
 
-        Ok(())
+    /// Lets the endpoint follow the redirect that caused the disconnect (if any) using `on_redirect`,
+    /// returns `true` if the connection should be recreated with the updated [`ConnectionInfo`].
+    fn follow_redirect<F>(&mut self, handle: Handle, reason: &DisconnectReason, on_redirect: F) -> bool
+    where
+        F: FnOnce(&Redirect) -> bool,
+    {
+        let redirect = match reason {
+            DisconnectReason::Error(ServiceError::Endpoint(err)) => Redirect::find(err),
+            _ => None,
+        };
+        let Some(redirect) = redirect else {
+            self.redirects.remove(&handle);
+            return false;
+        };
+        let redirects = self.redirects.entry(handle).or_default();
+        if *redirects >= self.max_redirects {
+            warn!("not following redirect after {} consecutive redirects: {}", redirects, redirect);
+            return false;
+        }
+        if !on_redirect(redirect) {
+            return false;
+        }
+        *redirects += 1;
+        info!("following redirect: {}", redirect);
+        true
+    }
+
+    /// Returns `true` if the pending socket errors should be checked during the current poll.
+    fn socket_error_check_due(&mut self, current_time_ns: u64) -> bool {
+        match self.socket_error_check_interval {
+            Some(interval) if current_time_ns > self.next_socket_error_check_time_ns => {
+                self.next_socket_error_check_time_ns = current_time_ns + interval.as_nanos() as u64;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn resolve_dns(&mut self, connection_info: &ConnectionInfo) -> Result<VecDeque<SocketAddr>, ServiceError> {
+        // static address bypasses the resolver entirely
+        if let Some(addr) = connection_info.addr {
+            return Ok(VecDeque::from([addr]));
+        }
+        #[cfg(feature = "stats")]
+        let start_time_ns = self.time_source.current_time_nanos();
+        let addrs = self.dns_resolver.resolve(&connection_info.host, connection_info.port);
+        #[cfg(feature = "stats")]
+        self.metrics
+            .dns_resolution
+            .record(self.time_source.current_time_nanos().saturating_sub(start_time_ns));
+        let mut addrs = addrs.map_err(ServiceError::Dns)?;
+        connection_info.address_family.apply(&mut addrs);
+        let mut addrs = VecDeque::from(addrs);
+        if addrs.is_empty() {
+            return Err(ServiceError::Dns(io::Error::other("unable to resolve dns address")));
+        }
+        if self.connect_strategy == ConnectStrategy::FirstAddress {
+            addrs.truncate(1);
+        }
+        Ok(addrs)
+    }
+
+    fn register_io_node(
+        &mut self,
+        mut io_node: IONode<S::Target, E>,
+        current_time_ns: u64,
+    ) -> Result<(), ServiceError> {
+        if let Some(connect_timeout) = self.connect_timeout {
+            if io_node.connect_deadline_ns == u64::MAX {
+                io_node.connect_deadline_ns = current_time_ns + connect_timeout.as_nanos() as u64;
+            }
+        }
+        if let ConnectStrategy::Sequential(attempt_timeout) = self.connect_strategy {
+            if !io_node.remaining_addrs.is_empty() {
+                io_node.connect_attempt_deadline_ns = current_time_ns + attempt_timeout.as_nanos() as u64;
+            }
+        }
+        let token = self.selector.register(&mut io_node).map_err(ServiceError::Selector)?;
+        self.io_nodes.insert(token, io_node);
+        Ok(())
+    }
+
+    /// Invokes `on_shutdown` for each connected endpoint, flushes the pending writes until the
+    /// `drain_timeout` elapses and returns all the endpoints ordered by the handle.
+    fn shutdown_with<F>(mut self, drain_timeout: Duration, mut on_shutdown: F) -> Vec<(Handle, E)>
+    where
+        F: FnMut(&mut E, &mut S::Target) -> io::Result<()>,
+    {
+        let current_time_ns = self.time_source.current_time_nanos();
+        for io_node in self.io_nodes.values_mut() {
+            if !io_node.connected && !matches!(io_node.as_stream_mut().connected(), Ok(true)) {
+                continue;
+            }
+            io_node.mark_connected(current_time_ns);
+            let (stream, endpoint) = io_node.as_parts_mut();
+            if let Err(err) = on_shutdown(endpoint, stream) {
+                warn!("error when shutting down endpoint: {}", err);
+            }
+        }
+
+        let deadline_ns = current_time_ns.saturating_add(drain_timeout.as_nanos() as u64);
+        let mut draining = self
+            .io_nodes
+            .iter()
+            .filter(|(_, io_node)| io_node.connected && io_node.as_stream().has_pending_writes())
+            .map(|(token, _)| *token)
+            .collect::<Vec<_>>();
+        while !draining.is_empty() && self.time_source.current_time_nanos() < deadline_ns {
+            draining.retain(|token| {
+                let stream = self.io_nodes.get_mut(token).unwrap().as_stream_mut();
+                match stream.flush_pending_writes() {
+                    Ok(()) => stream.has_pending_writes(),
+                    Err(err) => {
+                        warn!("unable to flush pending writes during shutdown: {}", err);
+                        false
+                    }
+                }
+            });
+        }
+        if !draining.is_empty() {
+            warn!("{} endpoint(s) still had pending writes after {:?}", draining.len(), drain_timeout);
+        }
+
+        let mut endpoints = Vec::with_capacity(self.io_nodes.len() + self.pending_endpoints.len());
+        for (_, mut io_node) in self.io_nodes.drain() {
+            if let Err(err) = self.selector.unregister(&mut io_node) {
+                warn!("unable to unregister endpoint during shutdown: {}", err);
+            }
+            endpoints.push((io_node.handle, io_node.endpoint.take().unwrap()));
+        }
+        endpoints.extend(
+            self.pending_endpoints
+                .drain(..)
+                .map(|pending| (pending.handle, pending.endpoint)),
+        );
+        endpoints.sort_by_key(|(handle, _)| *handle);
+        endpoints
+    }
+
+    const fn tracks_connect_progress(&self) -> bool {
+        self.connect_timeout.is_some() || matches!(self.connect_strategy, ConnectStrategy::Sequential(_))
+    }
+
+    /// Returns the connections that have timed out or failed as well as the connection attempts
+    /// that have not completed on time (or have failed) and can move on to the next address.
+    fn check_connect_progress(&mut self, current_time_ns: u64) -> Vec<(SelectorToken, ConnectProgress)> {
+        let mut progress = Vec::new();
+        for (token, io_node) in self.io_nodes.iter_mut() {
+            if io_node.connected {
+                continue;
+            }
+            let can_move_on = !io_node.remaining_addrs.is_empty();
+            match io_node.as_stream_mut().connected() {
+                Ok(true) => io_node.mark_connected(current_time_ns),
+                Ok(false) if current_time_ns > io_node.connect_deadline_ns => {
+                    progress.push((*token, ConnectProgress::TimedOut))
+                }
+                Ok(false) if can_move_on && current_time_ns > io_node.connect_attempt_deadline_ns => {
+                    progress.push((*token, ConnectProgress::Expired))
+                }
+                Ok(false) => {}
+                Err(err) if can_move_on => {
+                    warn!("connection attempt failed: {}", err);
+                    progress.push((*token, ConnectProgress::Expired))
+                }
+                Err(err) => progress.push((*token, ConnectProgress::Failed(err))),
+            }
+        }
+        progress
+    }
+}
+
+/// Registers `services.len()` identical endpoints, one with each service (shard), and returns the
+/// handles in the shard order. The `create_endpoint` is invoked with the shard index, typically to
+/// select the [`ConnectionInfo`] created with [`ConnectionInfo::shards`].
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::io;
+/// use std::net::{SocketAddr, TcpStream};
+/// use boomnet::endpoint::{ConnectionInfo, Endpoint};
+/// use boomnet::select::direct::DirectSelector;
+/// use boomnet::service::{register_sharded, IntoIOService};
+/// use boomnet::stream::BindAndConnect;
+/// use idle::IdleStrategy;
+///
+/// struct FeedEndpoint {
+///     connection_info: ConnectionInfo,
+/// }
+///
+/// impl Endpoint for FeedEndpoint {
+///     type Target = TcpStream;
+///
+///     fn connection_info(&self) -> io::Result<ConnectionInfo> {
+///         Ok(self.connection_info.clone())
+///     }
+///
+///     fn create_target(&mut self, addr: SocketAddr) -> io::Result<Self::Target> {
+///         TcpStream::bind_and_connect_with_options(addr, None, None, &self.connection_info.socket_options)
+///     }
+///
+///     fn poll(&mut self, _target: &mut Self::Target) -> io::Result<()> {
+///         Ok(())
+///     }
+/// }
+///
+/// let shards = ConnectionInfo::new("feed.example.com", 9000).shards([2, 3]);
+/// let mut services = [
+///     DirectSelector::new().unwrap().into_io_service(IdleStrategy::Sleep(std::time::Duration::from_millis(1))),
+///     DirectSelector::new().unwrap().into_io_service(IdleStrategy::Sleep(std::time::Duration::from_millis(1))),
+/// ];
+/// let handles = register_sharded(&mut services, |shard| FeedEndpoint {
+///     connection_info: shards[shard].clone(),
+/// });
+/// ```
+pub fn register_sharded<S, E, C, R, T, F>(
+    services: &mut [IOService<S, E, C, R, T>],
+    mut create_endpoint: F,
+) -> Vec<Handle>
+where
+    S: Selector,
+    R: DnsResolver,
+    T: TimeSource,
+    F: FnMut(usize) -> E,
+{
+    services
+        .iter_mut()
+        .enumerate()
+        .map(|(shard, service)| service.register(create_endpoint(shard)))
+        .collect()
+}
+
+fn apply_socket_options<T: Selectable>(stream: &mut T, connection_info: &ConnectionInfo) -> io::Result<()> {
+    if connection_info.socket_options.is_empty() {
+        return Ok(());
+    }
+    stream.apply_socket_options(&connection_info.socket_options)
+}
+
+/// Endpoint callbacks invoked by the poll cycle shared by the [`Endpoint`] and the [`EndpointWithContext`]
+/// services, so that the connection lifecycle is implemented once for both.
+trait EndpointCalls<E, T> {
+    fn connection_info(&mut self, endpoint: &E) -> io::Result<ConnectionInfo>;
+
+    fn create_target(&mut self, endpoint: &mut E, addr: SocketAddr, resume_token: Option<u64>) -> io::Result<T>;
+
+    fn poll(&mut self, endpoint: &mut E, target: &mut T) -> io::Result<()>;
+
+    fn on_timer(&mut self, endpoint: &mut E, target: &mut T, timer_id: TimerId) -> io::Result<()>;
+
+    fn resume_token(&mut self, endpoint: &E, target: &T) -> Option<u64>;
+
+    fn can_recreate_with_reason(&mut self, endpoint: &mut E, reason: &DisconnectReason) -> bool;
+
+    fn on_redirect(&mut self, endpoint: &mut E, redirect: &Redirect) -> bool;
+
+    fn on_session_recording(&mut self, endpoint: &mut E, recorder: &mut SessionRecorder);
+
+    fn can_auto_disconnect(&mut self, endpoint: &mut E) -> bool;
+
+    fn ttl(&self, endpoint: &E) -> Option<Duration>;
+}
+
+/// Invokes the [`Endpoint`] callbacks.
+struct NoContext;
+
+impl<E: Endpoint> EndpointCalls<E, E::Target> for NoContext {
+    #[inline]
+    fn connection_info(&mut self, endpoint: &E) -> io::Result<ConnectionInfo> {
+        endpoint.connection_info()
+    }
+
+    #[inline]
+    fn create_target(
+        &mut self,
+        endpoint: &mut E,
+        addr: SocketAddr,
+        resume_token: Option<u64>,
+    ) -> io::Result<E::Target> {
+        match resume_token {
+            Some(resume_token) => endpoint.create_target_with_resume(addr, resume_token),
+            None => endpoint.create_target(addr),
+        }
+    }
+
+    #[inline]
+    fn poll(&mut self, endpoint: &mut E, target: &mut E::Target) -> io::Result<()> {
+        endpoint.poll(target)
+    }
+
+    #[inline]
+    fn on_timer(&mut self, endpoint: &mut E, target: &mut E::Target, timer_id: TimerId) -> io::Result<()> {
+        endpoint.on_timer(target, timer_id)
+    }
+
+    #[inline]
+    fn resume_token(&mut self, endpoint: &E, target: &E::Target) -> Option<u64> {
+        endpoint.resume_token(target)
+    }
+
+    #[inline]
+    fn can_recreate_with_reason(&mut self, endpoint: &mut E, reason: &DisconnectReason) -> bool {
+        endpoint.can_recreate_with_reason(reason)
+    }
+
+    #[inline]
+    fn on_redirect(&mut self, endpoint: &mut E, redirect: &Redirect) -> bool {
+        endpoint.on_redirect(redirect)
+    }
+
+    #[inline]
+    fn on_session_recording(&mut self, endpoint: &mut E, recorder: &mut SessionRecorder) {
+        endpoint.on_session_recording(recorder)
+    }
+
+    #[inline]
+    fn can_auto_disconnect(&mut self, endpoint: &mut E) -> bool {
+        endpoint.can_auto_disconnect()
+    }
+
+    #[inline]
+    fn ttl(&self, endpoint: &E) -> Option<Duration> {
+        endpoint.ttl()
+    }
+}
+
+/// Invokes the [`EndpointWithContext`] callbacks with the user provided [`Context`].
+struct WithContext<'a, C>(&'a mut C);
+
+impl<C: Context, E: EndpointWithContext<C>> EndpointCalls<E, E::Target> for WithContext<'_, C> {
+    #[inline]
+    fn connection_info(&mut self, endpoint: &E) -> io::Result<ConnectionInfo> {
+        endpoint.connection_info()
+    }
+
+    #[inline]
+    fn create_target(
+        &mut self,
+        endpoint: &mut E,
+        addr: SocketAddr,
+        resume_token: Option<u64>,
+    ) -> io::Result<E::Target> {
+        match resume_token {
+            Some(resume_token) => endpoint.create_target_with_resume(addr, resume_token, self.0),
+            None => endpoint.create_target(addr, self.0),
+        }
+    }
+
+    #[inline]
+    fn poll(&mut self, endpoint: &mut E, target: &mut E::Target) -> io::Result<()> {
+        endpoint.poll(target, self.0)
+    }
+
+    #[inline]
+    fn on_timer(&mut self, endpoint: &mut E, target: &mut E::Target, timer_id: TimerId) -> io::Result<()> {
+        endpoint.on_timer(target, timer_id, self.0)
+    }
+
+    #[inline]
+    fn resume_token(&mut self, endpoint: &E, target: &E::Target) -> Option<u64> {
+        endpoint.resume_token(target, self.0)
+    }
+
+    #[inline]
+    fn can_recreate_with_reason(&mut self, endpoint: &mut E, reason: &DisconnectReason) -> bool {
+        endpoint.can_recreate_with_reason(reason, self.0)
+    }
+
+    #[inline]
+    fn on_redirect(&mut self, endpoint: &mut E, redirect: &Redirect) -> bool {
+        endpoint.on_redirect(redirect, self.0)
+    }
+
+    #[inline]
+    fn on_session_recording(&mut self, endpoint: &mut E, recorder: &mut SessionRecorder) {
+        endpoint.on_session_recording(recorder, self.0)
+    }
+
+    #[inline]
+    fn can_auto_disconnect(&mut self, endpoint: &mut E) -> bool {
+        endpoint.can_auto_disconnect(self.0)
+    }
+
+    #[inline]
+    fn ttl(&self, endpoint: &E) -> Option<Duration> {
+        endpoint.ttl()
+    }
+}
+
+impl<S, E, C, R, T> IOService<S, E, C, R, T>
+where
+    S: Selector,
+    R: DnsResolver,
+    T: TimeSource,
+{
+    fn poll_cycle<O>(
+        &mut self,
+        max_endpoints: usize,
+        budget: Option<Duration>,
+        calls: &mut O,
+    ) -> Result<bool, ServiceError>
+    where
+        O: EndpointCalls<E, S::Target>,
+    {
+        // read the clock once per poll cycle
+        let current_time_ns = self.time_source.current_time_nanos();
+
+        #[cfg(feature = "probe")]
+        self.probe_event(ProbeEvent::PollStart);
+
+        // invoke actions sent from other threads
+        let mut work_count = self.drain_mailbox(current_time_ns);
+
+        // check for pending endpoints (one at a time & throttled, unless registered with `register_all`)
+        while let Some(pending) = self.pending_endpoints.front() {
+            let throttled = pending.throttled;
+            if throttled && current_time_ns <= self.next_endpoint_create_time_ns {
+                break;
+            }
+            // the endpoints queued behind the one backing off wait for it, preserving the order
+            if current_time_ns < pending.retry_time_ns {
+                break;
+            }
+            let pending = self.pending_endpoints.pop_front().unwrap();
+            match calls.connection_info(&pending.endpoint) {
+                Ok(connection_info) => match self.resolve_dns(&connection_info) {
+                    Ok(addrs) => self.connect(pending, addrs, u64::MAX, &connection_info, current_time_ns, calls)?,
+                    Err(err) => {
+                        self.retry_dns(pending, err, current_time_ns, calls)?;
+                        continue;
+                    }
+                },
+                Err(err) => {
+                    let pending = PendingEndpoint {
+                        throttled: true,
+                        ..pending
+                    };
+                    self.recreate(pending, DisconnectReason::Error(ServiceError::Endpoint(err)), calls);
+                }
+            }
+            if throttled {
+                self.next_endpoint_create_time_ns = current_time_ns + self.endpoint_creation_throttle.as_nanos() as u64;
+                break;
+            }
+        }
+
+        // check for readiness events
+        work_count += self.selector.poll(&mut self.io_nodes).map_err(ServiceError::Selector)?;
+
+        // sample kernel socket buffers if enabled
+        self.sample_socket_queues(current_time_ns);
+
+        // abort connections that did not complete on time or move on to the next address
+        if self.tracks_connect_progress() {
+            for (token, progress) in self.check_connect_progress(current_time_ns) {
+                let io_node = self.io_nodes.remove(&token).unwrap();
+                match progress {
+                    ConnectProgress::TimedOut => {
+                        let connect_timeout = self.connect_timeout.unwrap();
+                        warn!("endpoint connection timed out after {:?}", connect_timeout);
+                        let reason = DisconnectReason::ConnectTimeout(connect_timeout);
+                        self.disconnect(io_node, reason, current_time_ns, calls)?;
+                    }
+                    ConnectProgress::Expired => self.connect_next_address(io_node, current_time_ns, calls)?,
+                    ConnectProgress::Failed(err) => {
+                        let reason = DisconnectReason::Error(ServiceError::Connect(err));
+                        self.disconnect(io_node, reason, current_time_ns, calls)?;
+                    }
+                }
+            }
+        }
+
+        // check for auto disconnect, either configured with the service or by the endpoint itself
+        let mut auto_disconnected = Vec::new();
+        for (token, io_node) in self.io_nodes.iter_mut() {
+            if current_time_ns <= io_node.disconnect_time_ns {
+                continue;
+            }
+            let ttl = io_node.ttl.unwrap();
+            // check if we really have to disconnect
+            match calls.can_auto_disconnect(io_node.as_endpoint_mut()) {
+                true => auto_disconnected.push((*token, ttl)),
+                // extend the endpoint TTL
+                false => io_node.disconnect_time_ns += ttl.as_nanos() as u64,
+            }
+        }
+        for (token, ttl) in auto_disconnected {
+            let io_node = self.io_nodes.remove(&token).unwrap();
+            warn!("endpoint auto disconnected after {:?}", ttl);
+            self.disconnect(io_node, DisconnectReason::AutoDisconnect(ttl), current_time_ns, calls)?;
+        }
+
+        // send rate limited messages
+        self.drain_rate_limited(current_time_ns);
+
+        // collect expired timers
+        self.expire_timers(current_time_ns);
+        work_count += self.expired_timers.len();
+
+        // deliver timers and poll endpoints, resuming the sweep where the previous call stopped
+        if self.poll_cursor == 0 {
+            self.poll_order.clear();
+            self.poll_order.extend(self.io_nodes.keys().copied());
+            self.poll_order.sort_unstable();
+        }
+        let check_socket_error = self.socket_error_check_due(current_time_ns);
+        let deadline_ns = budget.map_or(u64::MAX, |budget| current_time_ns + budget.as_nanos() as u64);
+        let mut polled = 0;
+        while self.poll_cursor < self.poll_order.len() && polled < max_endpoints {
+            // always make progress, even if the budget has been exhausted before the sweep
+            if polled > 0 && deadline_ns != u64::MAX && self.time_source.current_time_nanos() >= deadline_ns {
+                break;
+            }
+            let token = self.poll_order[self.poll_cursor];
+            self.poll_cursor += 1;
+            let Some(io_node) = self.io_nodes.get_mut(&token) else {
+                // removed since the sweep has started
+                continue;
+            };
+            polled += 1;
+            let handle = io_node.handle;
+            // failed attempts with the addresses left are handled by the connect progress check
+            let checkable = io_node.connected || io_node.remaining_addrs.is_empty();
+            let (stream, endpoint) = io_node.as_parts_mut();
+            let expired_timers = &self.expired_timers;
+            let result = poll_guarded(self.panic_isolation, || {
+                expired_timers
+                    .iter()
+                    .filter(|(timer_handle, _)| *timer_handle == handle)
+                    .try_for_each(|(_, timer_id)| calls.on_timer(endpoint, stream, *timer_id))
+                    .and_then(|_| calls.poll(endpoint, stream))
+            });
+            let result = result.and_then(|_| match checkable {
+                true => check_connection(stream, check_socket_error),
+                false => Ok(()),
+            });
+            self.expired_timers.retain(|(timer_handle, _)| *timer_handle != handle);
+            #[cfg(feature = "stats")]
+            {
+                self.metrics.endpoints_polled += 1;
+            }
+            if let Err(reason) = result {
+                error!("error when polling endpoint: {}", reason);
+                #[cfg(feature = "tracing")]
+                tracing::warn!(target: "boomnet", handle, %reason, "endpoint disconnected");
+                let io_node = self.io_nodes.remove(&token).unwrap();
+                self.disconnect(io_node, reason, current_time_ns, calls)?;
+            }
+        }
+        let complete = self.poll_cursor >= self.poll_order.len();
+        if complete {
+            self.poll_cursor = 0;
+            // discard the timers of the endpoints that are no longer connected
+            let io_nodes = &self.io_nodes;
+            self.expired_timers
+                .retain(|(handle, _)| io_nodes.values().any(|io_node| io_node.handle == *handle));
+        }
+
+        #[cfg(feature = "probe")]
+        self.probe_event(ProbeEvent::PollEnd);
+
+        #[cfg(feature = "stats")]
+        self.metrics
+            .poll_duration
+            .record(self.time_source.current_time_nanos().saturating_sub(current_time_ns));
+
+        self.idle_policy.idle(work_count);
+
+        Ok(complete)
+    }
+
+    /// Queues the endpoint whose DNS resolution has failed to be retried after the backoff, unless
+    /// the endpoint refuses it.
+    fn retry_dns<O>(
+        &mut self,
+        pending: PendingEndpoint<E>,
+        err: ServiceError,
+        current_time_ns: u64,
+        calls: &mut O,
+    ) -> Result<(), ServiceError>
+    where
+        O: EndpointCalls<E, S::Target>,
+    {
+        let PendingEndpoint { mut endpoint, .. } = pending;
+        let reason = DisconnectReason::Error(err);
+        if !calls.can_recreate_with_reason(&mut endpoint, &reason) {
+            self.failed.insert(pending.handle, reason.to_string());
+            let DisconnectReason::Error(err) = reason else {
+                unreachable!()
+            };
+            return Err(err);
+        }
+        warn!("{}, retrying in {:?}", reason, self.dns_retry_backoff);
+        self.pending_endpoints.push_back(PendingEndpoint {
+            endpoint,
+            retry_time_ns: current_time_ns + self.dns_retry_backoff.as_nanos() as u64,
+            dns_failures: pending.dns_failures + 1,
+            ..pending
+        });
+        Ok(())
+    }
+
+    /// Creates the connection to the first of the `addrs` and registers it with the selector, the
+    /// remaining addresses are tried if the connection does not complete (see [`ConnectStrategy::Sequential`]).
+    fn connect<O>(
+        &mut self,
+        pending: PendingEndpoint<E>,
+        mut addrs: VecDeque<SocketAddr>,
+        connect_deadline_ns: u64,
+        connection_info: &ConnectionInfo,
+        current_time_ns: u64,
+        calls: &mut O,
+    ) -> Result<(), ServiceError>
+    where
+        O: EndpointCalls<E, S::Target>,
+    {
+        let PendingEndpoint {
+            handle,
+            mut endpoint,
+            resume_token,
+            queued_ns,
+            attempts,
+            ..
+        } = pending;
+        let addr = addrs.pop_front().unwrap();
+        if let Some(recorder) = self.session_recorder(handle) {
+            calls.on_session_recording(&mut endpoint, recorder);
+        }
+        let stream = calls
+            .create_target(&mut endpoint, addr, resume_token)
+            .and_then(|mut stream| {
+                apply_socket_options(&mut stream, connection_info)?;
+                Ok(stream)
+            });
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(err) => {
+                warn!("unable to create connection to {}: {}", addr, err);
+                let pending = PendingEndpoint {
+                    handle,
+                    endpoint,
+                    resume_token,
+                    throttled: true,
+                    queued_ns,
+                    attempts: attempts + 1,
+                    retry_time_ns: 0,
+                    dns_failures: 0,
+                };
+                self.recreate(pending, DisconnectReason::Error(ServiceError::Connect(err)), calls);
+                return Ok(());
+            }
+        };
+        #[cfg(feature = "tracing")]
+        tracing::debug!(target: "boomnet", handle, attempt = attempts + 1, %addr, "endpoint connection created");
+        let ttl = calls.ttl(&endpoint).or(self.auto_disconnect);
+        let mut io_node = IONode::new(stream, handle, endpoint, ttl, current_time_ns);
+        io_node.remaining_addrs = addrs;
+        io_node.resume_token = resume_token;
+        io_node.connect_deadline_ns = connect_deadline_ns;
+        io_node.addr = Some(addr);
+        io_node.pending_since_ns = queued_ns;
+        io_node.connect_attempts = attempts + 1;
+        self.register_io_node(io_node, current_time_ns)
+    }
+
+    /// Abandons the connection attempt and moves on to the next of the remaining addresses.
+    fn connect_next_address<O>(
+        &mut self,
+        mut io_node: IONode<S::Target, E>,
+        current_time_ns: u64,
+        calls: &mut O,
+    ) -> Result<(), ServiceError>
+    where
+        O: EndpointCalls<E, S::Target>,
+    {
+        self.selector.unregister(&mut io_node).map_err(ServiceError::Selector)?;
+        let endpoint = io_node.endpoint.take().unwrap();
+        warn!("connection attempt did not complete, trying next address: {}", io_node.remaining_addrs[0]);
+        let pending = PendingEndpoint {
+            handle: io_node.handle,
+            endpoint,
+            resume_token: io_node.resume_token,
+            throttled: true,
+            queued_ns: io_node.pending_since_ns,
+            attempts: io_node.connect_attempts,
+            retry_time_ns: 0,
+            dns_failures: 0,
+        };
+        let connection_info = match calls.connection_info(&pending.endpoint) {
+            Ok(connection_info) => connection_info,
+            Err(err) => {
+                self.recreate(pending, DisconnectReason::Error(ServiceError::Endpoint(err)), calls);
+                return Ok(());
+            }
+        };
+        let addrs = std::mem::take(&mut io_node.remaining_addrs);
+        self.connect(pending, addrs, io_node.connect_deadline_ns, &connection_info, current_time_ns, calls)
+    }
+
+    /// Unregisters the connection that has been dropped for the `reason` and queues the endpoint
+    /// to be recreated, unless the endpoint refuses it (see [`Endpoint::can_recreate_with_reason`]).
+    fn disconnect<O>(
+        &mut self,
+        mut io_node: IONode<S::Target, E>,
+        reason: DisconnectReason,
+        current_time_ns: u64,
+        calls: &mut O,
+    ) -> Result<(), ServiceError>
+    where
+        O: EndpointCalls<E, S::Target>,
+    {
+        self.selector.unregister(&mut io_node).map_err(ServiceError::Selector)?;
+        let endpoint = io_node.endpoint.take().unwrap();
+        let resume_token = calls
+            .resume_token(&endpoint, io_node.as_stream())
+            .or(io_node.resume_token);
+        let pending = PendingEndpoint {
+            handle: io_node.handle,
+            endpoint,
+            resume_token,
+            throttled: true,
+            queued_ns: current_time_ns,
+            attempts: io_node.connect_attempts,
+            retry_time_ns: 0,
+            dns_failures: 0,
+        };
+        self.recreate(pending, reason, calls);
+        Ok(())
+    }
+
+    /// Queues the endpoint that has been disconnected (or could not be connected) for the `reason`
+    /// to be recreated, unless the endpoint refuses it (see [`Endpoint::can_recreate_with_reason`]).
+    fn recreate<O>(&mut self, mut pending: PendingEndpoint<E>, reason: DisconnectReason, calls: &mut O)
+    where
+        O: EndpointCalls<E, S::Target>,
+    {
+        let endpoint = &mut pending.endpoint;
+        let redirected =
+            self.follow_redirect(pending.handle, &reason, |redirect| calls.on_redirect(endpoint, redirect));
+        if redirected || calls.can_recreate_with_reason(&mut pending.endpoint, &reason) {
+            #[cfg(feature = "stats")]
+            {
+                self.metrics.reconnects += 1;
+            }
+            self.pending_endpoints.push_back(pending);
+        } else {
+            error!("dropping endpoint that cannot be recreated after: {}", reason);
+            self.failed.insert(pending.handle, reason.to_string());
+        }
+    }
+}
+
+impl<S, E, R, T> IOService<S, E, (), R, T>
+where
+    S: Selector,
+    R: DnsResolver,
+    T: TimeSource,
+    E: Endpoint<Target = S::Target>,
+{
+    /// This method polls all registered endpoints for readiness and performs I/O operations based
+    /// on the ['Selector'] poll results. It then iterates through all endpoints, either
+    /// updating existing streams or creating and registering new ones. It uses [`Endpoint::can_recreate`]
+    /// to determine if the error that occurred during polling is recoverable (typically due to remote peer disconnect).
+    pub fn poll(&mut self) -> Result<(), ServiceError> {
+        self.poll_with_limits(usize::MAX, None).map(|_| ())
+    }
+
+    /// Same as [`IOService::poll`] but polls at most `max_endpoints` endpoints, so that the caller
+    /// can interleave other work with the IO. The next call resumes the sweep with the endpoints
+    /// that have not been polled yet. Returns `true` once all the endpoints have been polled.
+    pub fn poll_n(&mut self, max_endpoints: usize) -> Result<bool, ServiceError> {
+        self.poll_with_limits(max_endpoints, None)
+    }
+
+    /// Same as [`IOService::poll`] but stops polling the endpoints once the `budget` has elapsed
+    /// (at least one endpoint is always polled). The next call resumes the sweep with the endpoints
+    /// that have not been polled yet. Returns `true` once all the endpoints have been polled.
+    pub fn poll_with_budget(&mut self, budget: Duration) -> Result<bool, ServiceError> {
+        self.poll_with_limits(usize::MAX, Some(budget))
+    }
+
+    /// Polls the service until the endpoint identified by the `handle` becomes [`EndpointStatus::Active`]
+    /// or the `timeout` (measured by the service [`TimeSource`]) elapses, returning `false` in the
+    /// latter case. Intended for the application setup phase, as it blocks the calling thread.
+    pub fn wait_connected(&mut self, handle: Handle, timeout: Duration) -> Result<bool, ServiceError> {
+        let deadline_ns = self.time_source.current_time_nanos() + timeout.as_nanos() as u64;
+        loop {
+            match self.status(handle) {
+                EndpointStatus::Active { .. } => return Ok(true),
+                EndpointStatus::Unknown | EndpointStatus::Failed { .. } => return Err(ServiceError::NotConnected),
+                _ if self.time_source.current_time_nanos() >= deadline_ns => return Ok(false),
+                _ => self.poll()?,
+            }
+        }
+    }
+
+    /// Shuts the service down in an orderly manner. Each connected endpoint is notified with
+    /// [`Endpoint::on_shutdown`] (websocket endpoints send the close frame by default), then the
+    /// pending writes are flushed for at most `drain_timeout` before the connections are unregistered
+    /// from the selector and dropped. Returns all the endpoints (including those not connected)
+    /// together with their handles, so that they can be registered with another service.
+    pub fn shutdown(self, drain_timeout: Duration) -> Vec<(Handle, E)> {
+        self.shutdown_with(drain_timeout, |endpoint, stream| endpoint.on_shutdown(stream))
+    }
+
+    fn poll_with_limits(&mut self, max_endpoints: usize, budget: Option<Duration>) -> Result<bool, ServiceError> {
+        self.poll_cycle(max_endpoints, budget, &mut NoContext)
+    }
+}
+
+impl<S, E, C, R, T> IOService<S, E, C, R, T>
+where
+    S: Selector,
+    C: Context,
+    R: DnsResolver,
+    T: TimeSource,
+    E: EndpointWithContext<C, Target = S::Target>,
+{
+    /// This method polls all registered endpoints for readiness passing the [`Context`] and performs I/O operations based
+    /// on the `SelectService` poll results. It then iterates through all endpoints, either
+    /// updating existing streams or creating and registering new ones. It uses [`Endpoint::can_recreate`]
+    /// to determine if the error that occurred during polling is recoverable (typically due to remote peer disconnect).
+    pub fn poll(&mut self, context: &mut C) -> Result<(), ServiceError> {
+        self.poll_with_limits(usize::MAX, None, context).map(|_| ())
+    }
+
+    /// Same as [`IOService::poll`] but polls at most `max_endpoints` endpoints, see `IOService::poll_n`.
+    pub fn poll_n(&mut self, max_endpoints: usize, context: &mut C) -> Result<bool, ServiceError> {
+        self.poll_with_limits(max_endpoints, None, context)
+    }
+
+    /// Same as [`IOService::poll`] but stops polling the endpoints once the `budget` has elapsed,
+    /// see `IOService::poll_with_budget`.
+    pub fn poll_with_budget(&mut self, budget: Duration, context: &mut C) -> Result<bool, ServiceError> {
+        self.poll_with_limits(usize::MAX, Some(budget), context)
+    }
+
+    /// Same as [`IOService::shutdown`] but passes the [`Context`] to [`EndpointWithContext::on_shutdown`].
+    pub fn shutdown(self, drain_timeout: Duration, context: &mut C) -> Vec<(Handle, E)> {
+        self.shutdown_with(drain_timeout, |endpoint, stream| endpoint.on_shutdown(stream, context))
+    }
+
+    fn poll_with_limits(
+        &mut self,
+        max_endpoints: usize,
+        budget: Option<Duration>,
+        context: &mut C,
+    ) -> Result<bool, ServiceError> {
+        self.poll_cycle(max_endpoints, budget, &mut WithContext(context))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::{Cell, RefCell};
+    #[cfg(feature = "mio")]
+    use std::io::Write;
+    use std::io::{Cursor, Read};
+    use std::net::{Ipv4Addr, TcpListener, TcpStream};
+    use std::rc::Rc;
+    use std::time::Instant;
+
+    use crate::select::direct::DirectSelector;
+    use crate::stream::file::FileStream;
+    use crate::stream::record::{RecordedStream, Recorder};
+    use crate::stream::replay::{RecordedSessions, SessionStream};
+    use crate::time::ManualTimeSource;
+
+    use super::*;
+
+    struct LocalResolver;
+
+    impl DnsResolver for LocalResolver {
+        fn resolve(&mut self, _host: &str, port: u16) -> io::Result<Vec<SocketAddr>> {
+            Ok(vec![SocketAddr::from((Ipv4Addr::LOCALHOST, port))])
+        }
+    }
+
+    struct CountingEndpoint {
+        id: usize,
+        polls: Rc<RefCell<Vec<usize>>>,
+    }
+
+    impl Endpoint for CountingEndpoint {
+        type Target = FileStream<Cursor<Vec<u8>>>;
+
+        fn connection_info(&self) -> io::Result<ConnectionInfo> {
+            Ok(ConnectionInfo::new("localhost", 9443))
+        }
+
+        fn create_target(&mut self, _addr: SocketAddr) -> io::Result<Self::Target> {
+            Ok(FileStream::from_bytes(Vec::new()))
+        }
+
+        fn poll(&mut self, _target: &mut Self::Target) -> io::Result<()> {
+            self.polls.borrow_mut().push(self.id);
+            Ok(())
+        }
+
+        fn on_shutdown(&mut self, _target: &mut Self::Target) -> io::Result<()> {
+            self.polls.borrow_mut().push(100 + self.id);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn should_resume_sweep_when_limited() {
+        let polls = Rc::new(RefCell::new(Vec::new()));
+        let mut service = DirectSelector::new()
+            .unwrap()
+            .into_io_service(IdleStrategy::NoOp)
+            .with_dns_resolver(LocalResolver);
+        service.register_all((0..3).map(|id| CountingEndpoint {
+            id,
+            polls: polls.clone(),
+        }));
+        assert_eq!(3, service.pending().len());
+
+        assert!(!service.poll_n(2).unwrap());
+        assert_eq!(2, polls.borrow().len());
+        assert!(service.poll_n(2).unwrap());
+        assert!(service.pending().is_empty());
+
+        let mut polled = polls.borrow().clone();
+        polled.sort_unstable();
+        assert_eq!(vec![0, 1, 2], polled);
+
+        service.poll().unwrap();
+        assert_eq!(6, polls.borrow().len());
+    }
+
+    #[test]
+    fn should_report_endpoint_status() {
+        let time_source = ManualTimeSource::new(0);
+        let mut service = DirectSelector::new()
+            .unwrap()
+            .into_io_service(IdleStrategy::NoOp)
+            .with_dns_resolver(LocalResolver)
+            .with_time_source(time_source.clone());
+        let handle = service.register(CountingEndpoint {
+            id: 0,
+            polls: Rc::new(RefCell::new(Vec::new())),
+        });
+        assert_eq!(EndpointStatus::Pending { attempts: 0 }, service.status(handle));
+        assert_eq!(EndpointStatus::Unknown, service.status(handle + 1));
+
+        time_source.advance(Duration::from_secs(2));
+        assert!(service.wait_connected(handle, Duration::from_secs(1)).unwrap());
+        let since_ns = Duration::from_secs(2).as_nanos() as u64;
+        assert_eq!(
+            EndpointStatus::Active {
+                since_ns,
+                paused: false
+            },
+            service.status(handle)
+        );
+        assert!(matches!(service.wait_connected(handle + 1, Duration::from_secs(1)), Err(ServiceError::NotConnected)));
+    }
+
+    #[test]
+    fn should_apply_weight_limit() {
+        let time_source = ManualTimeSource::new(0);
+        let mut service = DirectSelector::new()
+            .unwrap()
+            .into_io_service(IdleStrategy::NoOp)
+            .with_dns_resolver(LocalResolver)
+            .with_time_source(time_source.clone());
+        let handle = service.register(CountingEndpoint {
+            id: 0,
+            polls: Rc::new(RefCell::new(Vec::new())),
+        });
+        time_source.advance(Duration::from_secs(2));
+        assert!(service.wait_connected(handle, Duration::from_secs(1)).unwrap());
+
+        assert_eq!(None, service.remaining_weight(handle));
+        service.set_weight_limit(handle, SlidingWindow::new(10, Duration::from_secs(1)));
+        assert_eq!(Some(6), service.dispatch_weighted(handle, 6, |_, _| Ok(6)).unwrap());
+        assert!(matches!(service.dispatch_weighted(handle, 6, |_, _| Ok(6)), Err(ServiceError::RateLimited)));
+        assert_eq!(Some(4), service.remaining_weight(handle));
+        assert_eq!(Some(4), service.dispatch_weighted(handle, 4, |_, _| Ok(4)).unwrap());
+        assert_eq!(Some(0), service.remaining_weight(handle));
+
+        time_source.advance(Duration::from_secs(1));
+        assert_eq!(Some(10), service.remaining_weight(handle));
+        service.clear_weight_limit(handle);
+        assert_eq!(Some(()), service.dispatch_weighted(handle, 100, |_, _| Ok(())).unwrap());
+    }
+
+    #[test]
+    fn should_deliver_mailbox_action_sent_from_another_thread() {
+        let time_source = ManualTimeSource::new(0);
+        let polls = Rc::new(RefCell::new(Vec::new()));
+        let mut service = DirectSelector::new()
+            .unwrap()
+            .into_io_service(IdleStrategy::NoOp)
+            .with_dns_resolver(LocalResolver)
+            .with_time_source(time_source.clone())
+            .with_endpoint_creation_throttle(Duration::ZERO);
+        let handles = service.register_all((0..2).map(|id| CountingEndpoint {
+            id,
+            polls: polls.clone(),
+        }));
+        time_source.advance(Duration::from_secs(1));
+        for handle in &handles {
+            assert!(service.wait_connected(*handle, Duration::from_secs(1)).unwrap());
+        }
+
+        let mailbox = service.mailbox();
+        let unknown_handle = handles[1] + 1;
+        std::thread::spawn(move || {
+            mailbox.send(unknown_handle, |_, _| Ok(())).unwrap();
+            mailbox
+                .send(handles[1], |_, endpoint| {
+                    endpoint.polls.borrow_mut().push(200 + endpoint.id);
+                    Ok(())
+                })
+                .unwrap();
+        })
+        .join()
+        .unwrap();
+        assert!(!polls.borrow().contains(&201));
+
+        service.poll().unwrap();
+        assert_eq!(1, polls.borrow().iter().filter(|id| **id == 201).count());
+        assert!(!polls.borrow().contains(&200));
+    }
+
+    #[test]
+    #[cfg(feature = "mio")]
+    fn should_be_send() {
+        fn assert_send<T: Send>() {}
+        assert_send::<IOService<crate::select::mio::MioSelector<crate::stream::mio::MioStream>, u32, ()>>();
+    }
+
+    // fails the first `failures` queries, counting all of them
+    struct FlakyResolver {
+        failures: u32,
+        queries: Rc<Cell<u32>>,
+    }
+
+    impl DnsResolver for FlakyResolver {
+        fn resolve(&mut self, host: &str, port: u16) -> io::Result<Vec<SocketAddr>> {
+            self.queries.set(self.queries.get() + 1);
+            if self.queries.get() <= self.failures {
+                return Err(io::Error::other("temporary failure in name resolution"));
+            }
+            LocalResolver.resolve(host, port)
+        }
+    }
+
+    #[test]
+    fn should_retry_dns_resolution() {
+        let time_source = ManualTimeSource::new(0);
+        let queries = Rc::new(Cell::new(0));
+        let polls = Rc::new(RefCell::new(Vec::new()));
+        let mut service = DirectSelector::new()
+            .unwrap()
+            .into_io_service(IdleStrategy::NoOp)
+            .with_dns_resolver(FlakyResolver {
+                failures: 2,
+                queries: queries.clone(),
+            })
+            .with_time_source(time_source.clone())
+            .with_dns_retry_backoff(Duration::from_secs(5));
+        let handle = service.register(CountingEndpoint {
+            id: 0,
+            polls: polls.clone(),
+        });
+
+        time_source.advance(Duration::from_secs(2));
+        service.poll().unwrap();
+        assert_eq!(1, queries.get());
+        assert_eq!(
+            EndpointStatus::Resolving {
+                attempts: 0,
+                failures: 1
+            },
+            service.status(handle)
+        );
+
+        // backing off
+        time_source.advance(Duration::from_secs(4));
+        service.poll().unwrap();
+        assert_eq!(1, queries.get());
+
+        time_source.advance(Duration::from_secs(1));
+        service.poll().unwrap();
+        assert_eq!(2, queries.get());
+        assert_eq!(
+            EndpointStatus::Resolving {
+                attempts: 0,
+                failures: 2
+            },
+            service.status(handle)
+        );
+
+        time_source.advance(Duration::from_secs(5));
+        service.poll().unwrap();
+        assert_eq!(3, queries.get());
+        assert!(matches!(service.status(handle), EndpointStatus::Active { .. }));
+        assert_eq!(vec![0], *polls.borrow());
+    }
+
+    // plain TCP connection that is never read from, counting the connections made
+    struct IdleTcpEndpoint {
+        port: u16,
+        connections: Rc<Cell<u32>>,
+    }
+
+    impl Endpoint for IdleTcpEndpoint {
+        type Target = TcpStream;
+
+        fn connection_info(&self) -> io::Result<ConnectionInfo> {
+            Ok(ConnectionInfo::new("localhost", self.port))
+        }
+
+        fn create_target(&mut self, addr: SocketAddr) -> io::Result<Self::Target> {
+            self.connections.set(self.connections.get() + 1);
+            TcpStream::connect(addr)
+        }
+
+        fn poll(&mut self, _target: &mut Self::Target) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn should_recreate_connection_with_socket_error() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let connections = Rc::new(Cell::new(0));
+        let mut service = DirectSelector::new()
+            .unwrap()
+            .into_io_service(IdleStrategy::NoOp)
+            .with_dns_resolver(LocalResolver)
+            .with_endpoint_creation_throttle(Duration::ZERO)
+            .with_socket_error_check(Duration::ZERO);
+        service.register(IdleTcpEndpoint {
+            port: listener.local_addr().unwrap().port(),
+            connections: connections.clone(),
+        });
+        while connections.get() == 0 {
+            service.poll().unwrap();
+        }
+
+        // reset the connection, which the endpoint would only notice when reading
+        let (server, _) = listener.accept().unwrap();
+        socket2::SockRef::from(&server)
+            .set_linger(Some(Duration::ZERO))
+            .unwrap();
+        drop(server);
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while connections.get() == 1 && Instant::now() < deadline {
+            service.poll().unwrap();
+        }
+        assert_eq!(2, connections.get());
+    }
+
+    // reads everything available and sends the heartbeat on each poll once requested
+    #[cfg(feature = "mio")]
+    struct HeartbeatEndpoint {
+        port: u16,
+        received: Rc<RefCell<Vec<u8>>>,
+        heartbeat: Rc<Cell<bool>>,
+    }
+
+    #[cfg(feature = "mio")]
+    impl Endpoint for HeartbeatEndpoint {
+        type Target = crate::stream::mio::MioStream;
+
+        fn connection_info(&self) -> io::Result<ConnectionInfo> {
+            Ok(ConnectionInfo::new("localhost", self.port))
+        }
+
+        fn create_target(&mut self, addr: SocketAddr) -> io::Result<Self::Target> {
+            let stream = TcpStream::connect(addr)?;
+            stream.set_nonblocking(true)?;
+            Ok(mio::net::TcpStream::from_std(stream).into())
+        }
+
+        fn poll(&mut self, target: &mut Self::Target) -> io::Result<()> {
+            let mut buf = [0u8; 64];
+            loop {
+                match target.read(&mut buf) {
+                    Ok(0) => return Err(io::Error::from(ErrorKind::UnexpectedEof)),
+                    Ok(read) => self.received.borrow_mut().extend_from_slice(&buf[..read]),
+                    Err(err) if err.kind() == ErrorKind::WouldBlock => break,
+                    Err(err) => return Err(err),
+                }
+            }
+            if self.heartbeat.replace(false) {
+                target.write_all(b"h")?;
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "mio")]
+    fn should_keep_writing_while_reading_paused() {
+        use crate::select::mio::MioSelector;
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let received = Rc::new(RefCell::new(Vec::new()));
+        let heartbeat = Rc::new(Cell::new(false));
+        let mut service = MioSelector::new()
+            .unwrap()
+            .into_io_service(IdleStrategy::NoOp)
+            .with_dns_resolver(LocalResolver)
+            .with_endpoint_creation_throttle(Duration::ZERO);
+        let handle = service.register(HeartbeatEndpoint {
+            port: listener.local_addr().unwrap().port(),
+            received: received.clone(),
+            heartbeat: heartbeat.clone(),
+        });
+        assert!(service.wait_connected(handle, Duration::from_secs(5)).unwrap());
+        let (mut server, _) = listener.accept().unwrap();
+        server.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+
+        assert!(service.pause_reading(handle).unwrap());
+        assert!(service.is_reading_paused(handle));
+        assert!(matches!(service.status(handle), EndpointStatus::Active { paused: true, .. }));
+        #[cfg(feature = "stats")]
+        assert_eq!(vec![handle], service.metrics().paused_endpoints);
+
+        // nothing is read while paused, but the endpoint keeps sending
+        server.write_all(b"hello").unwrap();
+        heartbeat.set(true);
+        let deadline = Instant::now() + Duration::from_millis(100);
+        while Instant::now() < deadline {
+            service.poll().unwrap();
+        }
+        assert!(received.borrow().is_empty());
+        let mut buf = [0u8; 1];
+        server.read_exact(&mut buf).unwrap();
+        assert_eq!(b"h", &buf);
+
+        assert!(service.resume_reading(handle).unwrap());
+        assert!(matches!(service.status(handle), EndpointStatus::Active { paused: false, .. }));
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while received.borrow().is_empty() && Instant::now() < deadline {
+            service.poll().unwrap();
+        }
+        assert_eq!(b"hello", received.borrow().as_slice());
+    }
+
+    // redirected by every peer it connects to, following the redirects to the next host
+    struct RedirectedEndpoint {
+        host: String,
+        hosts: Rc<RefCell<Vec<String>>>,
+    }
+
+    impl Endpoint for RedirectedEndpoint {
+        type Target = FileStream<Cursor<Vec<u8>>>;
+
+        fn connection_info(&self) -> io::Result<ConnectionInfo> {
+            Ok(ConnectionInfo::new(&self.host, 9443))
+        }
+
+        fn create_target(&mut self, _addr: SocketAddr) -> io::Result<Self::Target> {
+            self.hosts.borrow_mut().push(self.host.clone());
+            Ok(FileStream::from_bytes(Vec::new()))
+        }
+
+        fn poll(&mut self, _target: &mut Self::Target) -> io::Result<()> {
+            let location = format!("{}.standby", self.host);
+            Err(io::Error::other(Redirect { status: 302, location }))
+        }
+
+        fn on_redirect(&mut self, redirect: &Redirect) -> bool {
+            self.host.clone_from(&redirect.location);
+            true
+        }
+    }
+
+    #[test]
+    fn should_follow_redirects() {
+        let time_source = ManualTimeSource::new(0);
+        let hosts = Rc::new(RefCell::new(Vec::new()));
+        let mut service = DirectSelector::new()
+            .unwrap()
+            .into_io_service(IdleStrategy::NoOp)
+            .with_dns_resolver(LocalResolver)
+            .with_time_source(time_source.clone())
+            .with_max_redirects(2);
+        service.register(RedirectedEndpoint {
+            host: String::from("a"),
+            hosts: hosts.clone(),
+        });
+        for _ in 0..5 {
+            time_source.advance(Duration::from_secs(2));
+            service.poll().unwrap();
+        }
+
+        // once the limit is reached the endpoint is recreated without following the redirect
+        assert_eq!(
+            vec![
+                "a",
+                "a.standby",
+                "a.standby.standby",
+                "a.standby.standby",
+                "a.standby.standby"
+            ],
+            *hosts.borrow()
+        );
+    }
+
+    struct RecordingEndpoint {
+        connections: u32,
+        recorder: Option<Recorder>,
+    }
+
+    impl Endpoint for RecordingEndpoint {
+        type Target = FileStream<RecordedStream<Cursor<Vec<u8>>>>;
+
+        fn connection_info(&self) -> io::Result<ConnectionInfo> {
+            Ok(ConnectionInfo::new("localhost", 9443))
+        }
+
+        fn create_target(&mut self, _addr: SocketAddr) -> io::Result<Self::Target> {
+            self.connections += 1;
+            let session = format!("session {}", self.connections).into_bytes();
+            let recorder = self.recorder.take().expect("recording not enabled");
+            Ok(FileStream::new(RecordedStream::new(Cursor::new(session), recorder)))
+        }
+
+        // fails once the session has been read to the end
+        fn poll(&mut self, target: &mut Self::Target) -> io::Result<()> {
+            let _ = target.read(&mut [0u8; 64])?;
+            Ok(())
+        }
+
+        fn on_session_recording(&mut self, recorder: &mut SessionRecorder) {
+            self.recorder = Some(recorder.next_session().unwrap());
+        }
+    }
+
+    struct ReplayedEndpoint(Vec<String>);
+
+    impl Endpoint for ReplayedEndpoint {
+        type Target = SessionStream;
+
+        fn connection_info(&self) -> io::Result<ConnectionInfo> {
+            unreachable!()
+        }
+
+        fn create_target(&mut self, _addr: SocketAddr) -> io::Result<Self::Target> {
+            unreachable!()
+        }
+
+        fn poll(&mut self, target: &mut Self::Target) -> io::Result<()> {
+            let mut buf = [0u8; 64];
+            match target.read(&mut buf)? {
+                0 => Err(io::Error::from(ErrorKind::UnexpectedEof)),
+                read => {
+                    self.0.push(String::from_utf8_lossy(&buf[..read]).into_owned());
+                    Ok(())
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn should_record_and_replay_sessions() {
+        let dir = std::env::temp_dir().join(format!("boomnet_recording_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let time_source = ManualTimeSource::new(0);
+        let mut service = DirectSelector::new()
+            .unwrap()
+            .into_io_service(IdleStrategy::NoOp)
+            .with_dns_resolver(LocalResolver)
+            .with_time_source(time_source.clone())
+            .with_recording(&dir);
+        let handle = service.register(RecordingEndpoint {
+            connections: 0,
+            recorder: None,
+        });
+        // each connection is polled once before it fails and is recreated
+        for _ in 0..4 {
+            time_source.advance(Duration::from_secs(2));
+            service.poll().unwrap();
+        }
+
+        let sessions = RecordedSessions::load(&dir, handle).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(2, sessions.len());
+        let mut endpoint = ReplayedEndpoint(Vec::new());
+        let replayed = sessions.replay(&mut endpoint, |_, stream| Ok(stream)).unwrap();
+        assert_eq!(2, replayed);
+        assert_eq!(vec!["session 1", "session 2"], endpoint.0);
+    }
+
+    struct StaticEndpoint(Rc<RefCell<Vec<SocketAddr>>>);
+
+    impl Endpoint for StaticEndpoint {
+        type Target = FileStream<Cursor<Vec<u8>>>;
+
+        fn connection_info(&self) -> io::Result<ConnectionInfo> {
+            Ok(ConnectionInfo::from_socket_addr(SocketAddr::from(([10, 0, 0, 1], 9443))))
+        }
+
+        fn create_target(&mut self, addr: SocketAddr) -> io::Result<Self::Target> {
+            self.0.borrow_mut().push(addr);
+            Ok(FileStream::from_bytes(Vec::new()))
+        }
+
+        fn poll(&mut self, _target: &mut Self::Target) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn should_connect_to_static_address_without_dns() {
+        struct UnreachableResolver;
+
+        impl DnsResolver for UnreachableResolver {
+            fn resolve(&mut self, host: &str, _port: u16) -> io::Result<Vec<SocketAddr>> {
+                unreachable!("static address resolved: {host}")
+            }
+        }
+
+        let addrs = Rc::new(RefCell::new(Vec::new()));
+        let mut service = DirectSelector::new()
+            .unwrap()
+            .into_io_service(IdleStrategy::NoOp)
+            .with_dns_resolver(UnreachableResolver);
+        service.register(StaticEndpoint(addrs.clone()));
+        service.poll().unwrap();
+        assert_eq!(vec![SocketAddr::from(([10, 0, 0, 1], 9443))], *addrs.borrow());
+    }
+
+    const BLACKHOLED_PORT: u16 = 1;
+    const REFUSED_PORT: u16 = 2;
+    const UNAVAILABLE_PORT: u16 = 3;
+
+    // connection outcome is determined by the port the stream has been created for
+    struct ConnectingStream {
+        port: u16,
+    }
+
+    impl Selectable for ConnectingStream {
+        fn connected(&mut self) -> io::Result<bool> {
+            match self.port {
+                BLACKHOLED_PORT => Ok(false),
+                REFUSED_PORT => Err(io::Error::from(ErrorKind::ConnectionRefused)),
+                _ => Ok(true),
+            }
+        }
+
+        fn make_writable(&mut self) {}
+
+        fn make_readable(&mut self) {}
+
+        fn connect_error(&self) -> Option<io::Error> {
+            (self.port == REFUSED_PORT).then(|| io::Error::from(ErrorKind::ConnectionRefused))
+        }
+    }
+
+    struct StaticResolver(Vec<SocketAddr>);
+
+    impl DnsResolver for StaticResolver {
+        fn resolve(&mut self, _host: &str, _port: u16) -> io::Result<Vec<SocketAddr>> {
+            Ok(self.0.clone())
+        }
+    }
+
+    #[derive(Default)]
+    struct ConnectLog {
+        // address and resume token of each connection created
+        attempts: Vec<(SocketAddr, Option<u64>)>,
+        reasons: Vec<String>,
+    }
+
+    // counts the polls once connected, using the count as the resume token
+    struct ConnectingEndpoint {
+        log: Rc<RefCell<ConnectLog>>,
+        polls: u64,
+        fail_after: Option<u64>,
+        recreate: bool,
+    }
+
+    impl ConnectingEndpoint {
+        fn new(log: Rc<RefCell<ConnectLog>>) -> ConnectingEndpoint {
+            Self {
+                log,
+                polls: 0,
+                fail_after: None,
+                recreate: true,
+            }
+        }
+    }
+
+    impl Endpoint for ConnectingEndpoint {
+        type Target = ConnectingStream;
+
+        fn connection_info(&self) -> io::Result<ConnectionInfo> {
+            Ok(ConnectionInfo::new("localhost", 9443))
+        }
+
+        fn create_target(&mut self, addr: SocketAddr) -> io::Result<Self::Target> {
+            self.log.borrow_mut().attempts.push((addr, None));
+            match addr.port() {
+                UNAVAILABLE_PORT => Err(io::Error::from(ErrorKind::AddrNotAvailable)),
+                port => Ok(ConnectingStream { port }),
+            }
+        }
+
+        fn poll(&mut self, target: &mut Self::Target) -> io::Result<()> {
+            if !matches!(target.connected(), Ok(true)) {
+                return Ok(());
+            }
+            self.polls += 1;
+            match self.fail_after == Some(self.polls) {
+                true => Err(io::Error::new(ErrorKind::ConnectionReset, "disconnected")),
+                false => Ok(()),
+            }
+        }
+
+        fn resume_token(&self, _target: &Self::Target) -> Option<u64> {
+            (self.polls > 0).then_some(self.polls)
+        }
+
+        fn create_target_with_resume(&mut self, addr: SocketAddr, resume_token: u64) -> io::Result<Self::Target> {
+            self.log.borrow_mut().attempts.push((addr, Some(resume_token)));
+            Ok(ConnectingStream { port: addr.port() })
+        }
+
+        fn can_recreate_with_reason(&mut self, reason: &DisconnectReason) -> bool {
+            let reason = match reason {
+                DisconnectReason::ConnectTimeout(_) => String::from("timeout"),
+                DisconnectReason::Error(ServiceError::Connect(err)) => format!("connect {:?}", err.kind()),
+                reason => reason.to_string(),
+            };
+            self.log.borrow_mut().reasons.push(reason);
+            self.recreate
+        }
+    }
+
+    #[test]
+    fn should_time_out_connection_across_addresses() {
+        let blackholed = [
+            SocketAddr::from(([10, 0, 0, 1], BLACKHOLED_PORT)),
+            SocketAddr::from(([10, 0, 0, 2], BLACKHOLED_PORT)),
+        ];
+        let log = Rc::new(RefCell::new(ConnectLog::default()));
+        let time_source = ManualTimeSource::new(1);
+        let mut service = DirectSelector::new()
+            .unwrap()
+            .into_io_service(IdleStrategy::NoOp)
+            .with_dns_resolver(StaticResolver(blackholed.to_vec()))
+            .with_time_source(time_source.clone())
+            .with_endpoint_creation_throttle(Duration::ZERO)
+            .with_connect_strategy(ConnectStrategy::Sequential(Duration::from_secs(1)))
+            .with_connect_timeout(Duration::from_secs(3));
+        let handle = service.register(ConnectingEndpoint::new(log.clone()));
+
+        service.poll().unwrap();
+        assert_eq!(
+            EndpointStatus::Connecting {
+                addr: blackholed[0],
+                attempts: 1
+            },
+            service.status(handle)
+        );
+
+        // the connect deadline is carried over to the next address
+        time_source.advance(Duration::from_millis(1500));
+        service.poll().unwrap();
+        assert_eq!(
+            EndpointStatus::Connecting {
+                addr: blackholed[1],
+                attempts: 2
+            },
+            service.status(handle)
+        );
+        time_source.advance(Duration::from_secs(1));
+        service.poll().unwrap();
+        assert_eq!(
+            EndpointStatus::Connecting {
+                addr: blackholed[1],
+                attempts: 2
+            },
+            service.status(handle)
+        );
+        assert!(log.borrow().reasons.is_empty());
+
+        time_source.advance(Duration::from_secs(1));
+        service.poll().unwrap();
+        assert_eq!(EndpointStatus::Pending { attempts: 2 }, service.status(handle));
+        assert_eq!(vec!["timeout"], log.borrow().reasons);
+
+        time_source.advance(Duration::from_millis(1));
+        service.poll().unwrap();
+        assert_eq!(
+            EndpointStatus::Connecting {
+                addr: blackholed[0],
+                attempts: 3
+            },
+            service.status(handle)
+        );
+        assert_eq!(vec![(blackholed[0], None), (blackholed[1], None), (blackholed[0], None)], log.borrow().attempts);
+    }
+
+    #[test]
+    fn should_report_refused_last_address_before_connect_timeout() {
+        let refused = SocketAddr::from(([10, 0, 0, 1], REFUSED_PORT));
+        let log = Rc::new(RefCell::new(ConnectLog::default()));
+        let time_source = ManualTimeSource::new(1);
+        let mut service = DirectSelector::new()
+            .unwrap()
+            .into_io_service(IdleStrategy::NoOp)
+            .with_dns_resolver(StaticResolver(vec![refused]))
+            .with_time_source(time_source.clone())
+            .with_endpoint_creation_throttle(Duration::ZERO)
+            .with_connect_timeout(Duration::from_secs(3));
+        let handle = service.register(ConnectingEndpoint::new(log.clone()));
+
+        // reported without the endpoint being polled
+        service.poll_n(0).unwrap();
+        assert_eq!(EndpointStatus::Pending { attempts: 1 }, service.status(handle));
+        assert_eq!(vec!["connect ConnectionRefused"], log.borrow().reasons);
+        assert_eq!(vec![(refused, None)], log.borrow().attempts);
+    }
+
+    #[test]
+    fn should_report_failed_endpoint_that_declines_recreation() {
+        let refused = SocketAddr::from(([10, 0, 0, 1], REFUSED_PORT));
+        let log = Rc::new(RefCell::new(ConnectLog::default()));
+        let time_source = ManualTimeSource::new(1);
+        let mut service = DirectSelector::new()
+            .unwrap()
+            .into_io_service(IdleStrategy::NoOp)
+            .with_dns_resolver(StaticResolver(vec![refused]))
+            .with_time_source(time_source.clone())
+            .with_endpoint_creation_throttle(Duration::ZERO);
+        let handle = service.register(ConnectingEndpoint {
+            recreate: false,
+            ..ConnectingEndpoint::new(log.clone())
+        });
+
+        service.poll().unwrap();
+        assert_eq!(vec!["connect ConnectionRefused"], log.borrow().reasons);
+        assert!(matches!(service.status(handle), EndpointStatus::Failed { .. }));
+        assert!(matches!(service.wait_connected(handle, Duration::ZERO), Err(ServiceError::NotConnected)));
+
+        // dropped, not attempted again
+        time_source.advance(Duration::from_secs(1));
+        service.poll().unwrap();
+        assert_eq!(vec![(refused, None)], log.borrow().attempts);
+        assert!(matches!(service.status(handle), EndpointStatus::Failed { .. }));
+    }
+
+    #[test]
+    fn should_report_failed_endpoint_when_dns_retry_declined() {
+        struct GivingUpEndpoint(CountingEndpoint);
+
+        impl Endpoint for GivingUpEndpoint {
+            type Target = FileStream<Cursor<Vec<u8>>>;
+
+            fn connection_info(&self) -> io::Result<ConnectionInfo> {
+                self.0.connection_info()
+            }
+
+            fn create_target(&mut self, addr: SocketAddr) -> io::Result<Self::Target> {
+                self.0.create_target(addr)
+            }
+
+            fn poll(&mut self, target: &mut Self::Target) -> io::Result<()> {
+                self.0.poll(target)
+            }
+
+            fn can_recreate_with_reason(&mut self, _reason: &DisconnectReason) -> bool {
+                false
+            }
+        }
+
+        let time_source = ManualTimeSource::new(1);
+        let queries = Rc::new(Cell::new(0));
+        let mut service = DirectSelector::new()
+            .unwrap()
+            .into_io_service(IdleStrategy::NoOp)
+            .with_dns_resolver(FlakyResolver {
+                failures: 1,
+                queries: queries.clone(),
+            })
+            .with_time_source(time_source.clone())
+            .with_endpoint_creation_throttle(Duration::ZERO);
+        let handle = service.register(GivingUpEndpoint(CountingEndpoint {
+            id: 0,
+            polls: Rc::new(RefCell::new(Vec::new())),
+        }));
+
+        assert!(matches!(service.poll(), Err(ServiceError::Dns(_))));
+        let EndpointStatus::Failed { reason } = service.status(handle) else {
+            panic!("endpoint should have failed")
+        };
+        assert!(reason.starts_with("dns resolution failed"), "{reason}");
+
+        time_source.advance(Duration::from_secs(1));
+        service.poll().unwrap();
+        assert_eq!(1, queries.get());
+    }
+
+    #[test]
+    fn should_try_next_address_when_attempt_expires_or_fails() {
+        let addrs = [
+            SocketAddr::from(([10, 0, 0, 1], BLACKHOLED_PORT)),
+            SocketAddr::from(([10, 0, 0, 2], REFUSED_PORT)),
+            SocketAddr::from(([10, 0, 0, 3], 9443)),
+        ];
+        let log = Rc::new(RefCell::new(ConnectLog::default()));
+        let time_source = ManualTimeSource::new(1);
+        let mut service = DirectSelector::new()
+            .unwrap()
+            .into_io_service(IdleStrategy::NoOp)
+            .with_dns_resolver(StaticResolver(addrs.to_vec()))
+            .with_time_source(time_source.clone())
+            .with_endpoint_creation_throttle(Duration::ZERO)
+            .with_connect_strategy(ConnectStrategy::Sequential(Duration::from_secs(1)));
+        let handle = service.register(ConnectingEndpoint::new(log.clone()));
+
+        service.poll().unwrap();
+        time_source.advance(Duration::from_millis(500));
+        service.poll().unwrap();
+        assert_eq!(
+            EndpointStatus::Connecting {
+                addr: addrs[0],
+                attempts: 1
+            },
+            service.status(handle)
+        );
+
+        // the attempt deadline has passed
+        time_source.advance(Duration::from_millis(600));
+        service.poll().unwrap();
+        assert_eq!(
+            EndpointStatus::Connecting {
+                addr: addrs[1],
+                attempts: 2
+            },
+            service.status(handle)
+        );
+
+        // the attempt has failed, no need to wait for its deadline
+        service.poll().unwrap();
+        assert!(matches!(service.status(handle), EndpointStatus::Active { .. }));
+        assert_eq!(addrs.iter().map(|addr| (*addr, None)).collect::<Vec<_>>(), log.borrow().attempts);
+        assert!(log.borrow().reasons.is_empty());
+    }
+
+    #[test]
+    fn should_recreate_endpoint_when_next_address_cannot_be_connected() {
+        let addrs = [
+            SocketAddr::from(([10, 0, 0, 1], BLACKHOLED_PORT)),
+            SocketAddr::from(([10, 0, 0, 2], UNAVAILABLE_PORT)),
+        ];
+        let log = Rc::new(RefCell::new(ConnectLog::default()));
+        let time_source = ManualTimeSource::new(1);
+        let mut service = DirectSelector::new()
+            .unwrap()
+            .into_io_service(IdleStrategy::NoOp)
+            .with_dns_resolver(StaticResolver(addrs.to_vec()))
+            .with_time_source(time_source.clone())
+            .with_endpoint_creation_throttle(Duration::ZERO)
+            .with_connect_strategy(ConnectStrategy::Sequential(Duration::from_secs(1)));
+        let handle = service.register(ConnectingEndpoint::new(log.clone()));
+
+        service.poll().unwrap();
+        time_source.advance(Duration::from_millis(1100));
+        service.poll().unwrap();
+        assert_eq!(EndpointStatus::Pending { attempts: 2 }, service.status(handle));
+        assert_eq!(vec!["connect AddrNotAvailable"], log.borrow().reasons);
+
+        time_source.advance(Duration::from_millis(1));
+        service.poll().unwrap();
+        assert_eq!(
+            EndpointStatus::Connecting {
+                addr: addrs[0],
+                attempts: 3
+            },
+            service.status(handle)
+        );
+        assert_eq!(vec![(addrs[0], None), (addrs[1], None), (addrs[0], None)], log.borrow().attempts);
+    }
+
+    #[test]
+    fn should_recreate_connection_with_resume_token() {
+        let addrs = [
+            SocketAddr::from(([10, 0, 0, 1], BLACKHOLED_PORT)),
+            SocketAddr::from(([10, 0, 0, 2], 9443)),
+        ];
+        let log = Rc::new(RefCell::new(ConnectLog::default()));
+        let time_source = ManualTimeSource::new(1);
+        let mut service = DirectSelector::new()
+            .unwrap()
+            .into_io_service(IdleStrategy::NoOp)
+            .with_dns_resolver(StaticResolver(addrs.to_vec()))
+            .with_time_source(time_source.clone())
+            .with_endpoint_creation_throttle(Duration::ZERO)
+            .with_connect_strategy(ConnectStrategy::Sequential(Duration::from_secs(1)));
+        let mut endpoint = ConnectingEndpoint::new(log.clone());
+        endpoint.fail_after = Some(3);
+        let handle = service.register(endpoint);
+
+        service.poll().unwrap();
+        time_source.advance(Duration::from_millis(1100));
+        for _ in 0..3 {
+            service.poll().unwrap();
+        }
+        assert_eq!(EndpointStatus::Pending { attempts: 2 }, service.status(handle));
+        assert_eq!(1, log.borrow().reasons.len());
+
+        // the token is passed to each address tried when recreating the connection
+        time_source.advance(Duration::from_millis(1));
+        service.poll().unwrap();
+        assert_eq!(
+            EndpointStatus::Connecting {
+                addr: addrs[0],
+                attempts: 3
+            },
+            service.status(handle)
+        );
+        time_source.advance(Duration::from_millis(1100));
+        service.poll().unwrap();
+        assert!(matches!(service.status(handle), EndpointStatus::Active { .. }));
+        assert_eq!(
+            vec![
+                (addrs[0], None),
+                (addrs[1], None),
+                (addrs[0], Some(3)),
+                (addrs[1], Some(3))
+            ],
+            log.borrow().attempts
+        );
+    }
+
+    struct TtlEndpoint(CountingEndpoint, Duration);
+
+    impl Endpoint for TtlEndpoint {
+        type Target = FileStream<Cursor<Vec<u8>>>;
+
+        fn connection_info(&self) -> io::Result<ConnectionInfo> {
+            self.0.connection_info()
+        }
+
+        fn create_target(&mut self, addr: SocketAddr) -> io::Result<Self::Target> {
+            self.0.create_target(addr)
+        }
+
+        fn poll(&mut self, target: &mut Self::Target) -> io::Result<()> {
+            self.0.poll(target)
+        }
+
+        fn ttl(&self) -> Option<Duration> {
+            Some(self.1)
+        }
+    }
+
+    #[test]
+    fn should_apply_endpoint_ttl() {
+        let time_source = ManualTimeSource::new(0);
+        let mut service = DirectSelector::new()
+            .unwrap()
+            .into_io_service(IdleStrategy::NoOp)
+            .with_dns_resolver(LocalResolver)
+            .with_time_source(time_source.clone())
+            .with_auto_disconnect(Duration::from_secs(10));
+        let polls = Rc::new(RefCell::new(Vec::new()));
+        let handles = service.register_all((0..2).map(|id| {
+            let ttl = Duration::from_secs(1 + id as u64 * 10);
+            TtlEndpoint(
+                CountingEndpoint {
+                    id,
+                    polls: polls.clone(),
+                },
+                ttl,
+            )
+        }));
+        service.poll().unwrap();
+
+        time_source.advance(Duration::from_secs(2));
+        service.poll().unwrap();
+        assert_eq!(EndpointStatus::Pending { attempts: 1 }, service.status(handles[0]));
+        assert!(matches!(service.status(handles[1]), EndpointStatus::Active { .. }));
+
+        // the endpoint TTL takes precedence over the service wide setting
+        time_source.advance(Duration::from_millis(8_500));
+        service.poll().unwrap();
+        assert!(matches!(service.status(handles[1]), EndpointStatus::Active { .. }));
+    }
+
+    struct PanickingEndpoint(CountingEndpoint);
+
+    impl Endpoint for PanickingEndpoint {
+        type Target = FileStream<Cursor<Vec<u8>>>;
+
+        fn connection_info(&self) -> io::Result<ConnectionInfo> {
+            self.0.connection_info()
+        }
+
+        fn create_target(&mut self, addr: SocketAddr) -> io::Result<Self::Target> {
+            self.0.create_target(addr)
+        }
+
+        fn poll(&mut self, target: &mut Self::Target) -> io::Result<()> {
+            self.0.poll(target)?;
+            if self.0.id == 0 {
+                panic!("endpoint {} failed", self.0.id);
+            }
+            Ok(())
+        }
+
+        fn can_recreate_with_reason(&mut self, reason: &DisconnectReason) -> bool {
+            !matches!(reason, DisconnectReason::Panic(message) if message == "endpoint 0 failed")
+        }
+    }
+
+    #[test]
+    fn should_isolate_endpoint_panic() {
+        let polls = Rc::new(RefCell::new(Vec::new()));
+        let mut service = DirectSelector::new()
+            .unwrap()
+            .into_io_service(IdleStrategy::NoOp)
+            .with_dns_resolver(LocalResolver)
+            .with_panic_isolation(true);
+        let handles = service.register_all((0..2).map(|id| {
+            PanickingEndpoint(CountingEndpoint {
+                id,
+                polls: polls.clone(),
+            })
+        }));
+
+        service.poll().unwrap();
+        service.poll().unwrap();
+        assert_eq!(
+            EndpointStatus::Failed {
+                reason: String::from("endpoint panicked: endpoint 0 failed")
+            },
+            service.status(handles[0])
+        );
+        assert!(matches!(service.status(handles[1]), EndpointStatus::Active { .. }));
+
+        let mut polled = polls.borrow().clone();
+        polled.sort_unstable();
+        assert_eq!(vec![0, 1, 1], polled);
+    }
+
+    #[test]
+    fn should_return_endpoints_on_shutdown() {
+        let polls = Rc::new(RefCell::new(Vec::new()));
+        let mut service = DirectSelector::new()
+            .unwrap()
+            .into_io_service(IdleStrategy::NoOp)
+            .with_dns_resolver(LocalResolver);
+        service.register_all((0..2).map(|id| CountingEndpoint {
+            id,
+            polls: polls.clone(),
+        }));
+        service.poll().unwrap();
+        // still pending as the creation is throttled
+        service.register(CountingEndpoint {
+            id: 2,
+            polls: polls.clone(),
+        });
+        polls.borrow_mut().clear();
+
+        let endpoints = service.shutdown(Duration::from_secs(1));
+        assert_eq!(vec![0, 1, 2], endpoints.iter().map(|(handle, _)| *handle).collect::<Vec<_>>());
+        let mut shutdowns = polls.borrow().clone();
+        shutdowns.sort_unstable();
+        assert_eq!(vec![100, 101], shutdowns);
+    }
+
+    #[test]
+    fn should_dispatch_to_group_members() {
+        let polls = Rc::new(RefCell::new(Vec::new()));
+        let mut service = DirectSelector::new()
+            .unwrap()
+            .into_io_service(IdleStrategy::NoOp)
+            .with_dns_resolver(LocalResolver)
+            .with_endpoint_creation_throttle(Duration::ZERO);
+        let a = service.register_with_tag(
+            CountingEndpoint {
+                id: 0,
+                polls: polls.clone(),
+            },
+            "feed",
+        );
+        let b = service.register(CountingEndpoint {
+            id: 1,
+            polls: polls.clone(),
+        });
+        let c = service.register_with_tag(
+            CountingEndpoint {
+                id: 2,
+                polls: polls.clone(),
+            },
+            "feed",
+        );
+        service.add_tag(b, "orders");
+        for _ in 0..3 {
+            service.poll().unwrap();
+        }
+        assert_eq!(&[a, c], service.group("feed"));
+
+        let mut ids = Vec::new();
+        let dispatched = service
+            .dispatch_group("feed", |_stream, endpoint| {
+                ids.push(endpoint.id);
+                Ok(())
+            })
+            .unwrap();
+        assert_eq!(2, dispatched);
+        ids.sort_unstable();
+        assert_eq!(vec![0, 2], ids);
+
+        service.remove_tag(a, "feed");
+        assert_eq!(&[c], service.group("feed"));
+        assert_eq!(0, service.dispatch_group("unknown", |_, _| Ok(())).unwrap());
+    }
+
+    #[test]
+    fn should_report_typed_errors() {
+        let mut service = DirectSelector::new()
+            .unwrap()
+            .into_io_service(IdleStrategy::NoOp)
+            .with_dns_resolver(LocalResolver);
+        let polls = Rc::new(RefCell::new(Vec::new()));
+        let handle = service.register(CountingEndpoint { id: 0, polls });
+
+        let err = service.send(handle, |_, _| Ok(())).unwrap_err();
+        assert!(matches!(err, ServiceError::NotConnected));
+
+        service.poll().unwrap();
+        service.set_rate_limit(handle, TokenBucket::new(1, Duration::from_secs(60)));
+        service.dispatch(handle, |_, _| Ok(())).unwrap();
+        let err = service.dispatch(handle, |_, _| Ok(())).unwrap_err();
+        assert!(matches!(err, ServiceError::RateLimited));
+        assert_eq!(ErrorKind::WouldBlock, io::Error::from(err).kind());
+    }
+
+    #[cfg(feature = "ws")]
+    #[test]
+    fn should_drive_plain_websocket_endpoint() {
+        use crate::endpoint::ws::WebsocketEndpoint;
+        use crate::ws::{Websocket, WebsocketFrame};
+
+        struct PlainEndpoint {
+            received: Rc<RefCell<Vec<Vec<u8>>>>,
+        }
+
+        impl WebsocketEndpoint for PlainEndpoint {
+            type Stream = FileStream<Cursor<Vec<u8>>>;
+
+            fn url(&self) -> &str {
+                "ws://localhost:9001/feed"
+            }
+
+            fn create_websocket(&mut self, _addr: SocketAddr) -> io::Result<Websocket<Self::Stream>> {
+                Ok(Websocket::new_connected(FileStream::from_bytes(b"\x81\x05hello".to_vec())))
+            }
+
+            fn poll(&mut self, ws: &mut Websocket<Self::Stream>) -> io::Result<()> {
+                while let Some(WebsocketFrame::Text(_, _, data)) = ws.receive_next()? {
+                    self.received.borrow_mut().push(data.to_vec());
+                }
+                Ok(())
+            }
+        }
+
+        let received = Rc::new(RefCell::new(Vec::new()));
+        let mut service = DirectSelector::new()
+            .unwrap()
+            .into_io_service(IdleStrategy::NoOp)
+            .with_dns_resolver(LocalResolver);
+        service.register(PlainEndpoint {
+            received: received.clone(),
+        });
+        for _ in 0..4 {
+            service.poll().unwrap();
+        }
+        assert_eq!(vec![b"hello".to_vec()], *received.borrow());
+    }
+
+    #[cfg(feature = "ws")]
+    #[test]
+    fn should_pass_disconnect_reason_to_websocket_endpoint() {
+        use crate::endpoint::ws::WebsocketEndpoint;
+        use crate::ws::Websocket;
+
+        struct PanickingWebsocketEndpoint {
+            reasons: Rc<RefCell<Vec<String>>>,
+        }
+
+        impl WebsocketEndpoint for PanickingWebsocketEndpoint {
+            type Stream = FileStream<Cursor<Vec<u8>>>;
+
+            fn url(&self) -> &str {
+                "ws://localhost:9001/feed"
+            }
+
+            fn create_websocket(&mut self, _addr: SocketAddr) -> io::Result<Websocket<Self::Stream>> {
+                Ok(Websocket::new_connected(FileStream::from_bytes(Vec::new())))
+            }
+
+            fn poll(&mut self, _ws: &mut Websocket<Self::Stream>) -> io::Result<()> {
+                panic!("boom")
+            }
+
+            fn can_recreate_with_reason(&mut self, reason: &DisconnectReason) -> bool {
+                self.reasons.borrow_mut().push(reason.to_string());
+                false
+            }
+        }
+
+        let reasons = Rc::new(RefCell::new(Vec::new()));
+        let mut service = DirectSelector::new()
+            .unwrap()
+            .into_io_service(IdleStrategy::NoOp)
+            .with_dns_resolver(LocalResolver)
+            .with_panic_isolation(true);
+        let handle = service.register(PanickingWebsocketEndpoint {
+            reasons: reasons.clone(),
+        });
+        for _ in 0..4 {
+            service.poll().unwrap();
+        }
+        assert_eq!(vec!["endpoint panicked: boom"], *reasons.borrow());
+        assert_eq!(
+            EndpointStatus::Failed {
+                reason: String::from("endpoint panicked: boom")
+            },
+            service.status(handle)
+        );
     }
 }
@@ -1,8 +1,11 @@
 //! Stream that is buffering data written to it.
 
 use std::io;
-use std::io::{ErrorKind, Read, Write};
-use std::mem::MaybeUninit;
+use std::io::{ErrorKind, IoSlice, Read, Write};
+
+use crate::endpoint::ConnectionInfo;
+use crate::stream::{ConnectionInfoProvider, ConnectionProperties, ConnectionPropertiesProvider, Cork};
+use crate::util::write_all_vectored;
 
 /// Default buffer size in bytes.
 pub const DEFAULT_BUFFER_SIZE: usize = 1024;
@@ -10,7 +13,13 @@ pub const DEFAULT_BUFFER_SIZE: usize = 1024;
 /// Buffers data written to it until explicitly flushed. Useful if you
 /// want to reduce the number of operating system calls when writing. If there
 /// is no more space in the buffer to accommodate the current write it
-/// will return [ErrorKind::WriteZero].
+/// will return [ErrorKind::WriteZero], unless a different [`BufferFullPolicy`] is specified.
+/// The buffer can also be flushed implicitly once it reaches the watermark (see
+/// [`BufferedStream::with_flush_watermark`]) or before each read (see [`BufferedStream::with_flush_on_read`]).
+/// The buffered data is flushed with single vectored write, so that the underlying stream (such
+/// as `TlsStream`) can send it in as few records and system calls as possible. While corked (see
+/// [`Cork`]) the explicit and implicit flushes are deferred until uncorked, only the buffer that is
+/// full is still flushed with [`BufferFullPolicy::Flush`].
 ///
 /// # Examples
 ///
@@ -41,42 +50,196 @@ pub const DEFAULT_BUFFER_SIZE: usize = 1024;
 ///  .into_buffered_stream::<512>()
 ///  .into_websocket("wss://stream.binance.com:9443/ws");
 /// ```
+///
+/// Flush once half of the buffer is used and before each read.
+///
+/// ``` no_run
+/// use std::net::TcpStream;
+/// use boomnet::stream::buffer::{BufferFullPolicy, IntoBufferedStream};
+///
+/// let stream = TcpStream::connect("127.0.0.1:9000").unwrap()
+///  .into_buffered_stream::<1024>()
+///  .with_flush_watermark(512)
+///  .with_flush_on_read(true)
+///  .with_full_buffer_policy(BufferFullPolicy::Flush);
+/// ```
 pub struct BufferedStream<S, const N: usize = DEFAULT_BUFFER_SIZE> {
     inner: S,
     buffer: [u8; N],
     cursor: usize,
+    // data written past the buffer capacity with `BufferFullPolicy::Grow`
+    spill: Vec<u8>,
+    flush_watermark: Option<usize>,
+    flush_on_read: bool,
+    full_buffer_policy: BufferFullPolicy,
+    corked: bool,
+}
+
+/// Defines what happens when the write does not fit into the [`BufferedStream`] buffer.
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq)]
+pub enum BufferFullPolicy {
+    /// Fail the write with [`ErrorKind::WriteZero`].
+    #[default]
+    Error,
+    /// Flush the buffered data to the underlying stream to make room, writes larger than the
+    /// buffer are passed directly to the underlying stream.
+    Flush,
+    /// Keep buffering on the heap until the next flush.
+    Grow,
+}
+
+impl<S, const N: usize> BufferedStream<S, N> {
+    /// Flush the buffer as soon as it holds at least `watermark` bytes.
+    pub fn with_flush_watermark(self, watermark: usize) -> BufferedStream<S, N> {
+        Self {
+            flush_watermark: Some(watermark),
+            ..self
+        }
+    }
+
+    /// Enable or disable flushing the buffer before each read, so that the request is always sent
+    /// before waiting for the response.
+    pub fn with_flush_on_read(self, flush_on_read: bool) -> BufferedStream<S, N> {
+        Self { flush_on_read, ..self }
+    }
+
+    /// Specify [`BufferFullPolicy`], by default the write that does not fit into the buffer fails.
+    pub fn with_full_buffer_policy(self, full_buffer_policy: BufferFullPolicy) -> BufferedStream<S, N> {
+        Self {
+            full_buffer_policy,
+            ..self
+        }
+    }
+
+    /// Number of bytes buffered and not yet flushed.
+    pub fn buffered_len(&self) -> usize {
+        self.cursor + self.spill.len()
+    }
 }
 
-impl<S: Read, const N: usize> Read for BufferedStream<S, N> {
+impl<S: Read + Write, const N: usize> Read for BufferedStream<S, N> {
     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.flush_on_read && !self.corked && self.buffered_len() > 0 {
+            self.flush()?;
+        }
         self.inner.read(buf)
     }
 }
 
+#[cold]
+fn handle_overflow() -> io::Result<()> {
+    Err(io::Error::new(ErrorKind::WriteZero, "unable to write the whole buffer"))
+}
+
 impl<S: Write, const N: usize> Write for BufferedStream<S, N> {
     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
-        #[cold]
-        fn handle_overflow() -> io::Result<()> {
-            Err(io::Error::new(ErrorKind::WriteZero, "unable to write the whole buffer"))
-        }
-
         let len = buf.len();
         let remaining = N - self.cursor;
-        if len > remaining {
-            handle_overflow()?
+        if len > remaining || !self.spill.is_empty() {
+            match self.full_buffer_policy {
+                BufferFullPolicy::Error => handle_overflow()?,
+                BufferFullPolicy::Flush => {
+                    self.flush_buffer()?;
+                    if len > N {
+                        self.inner.write_all(buf)?;
+                        return Ok(len);
+                    }
+                }
+                BufferFullPolicy::Grow => {
+                    let (head, tail) = buf.split_at(remaining.min(len));
+                    self.buffer[self.cursor..self.cursor + head.len()].copy_from_slice(head);
+                    self.cursor += head.len();
+                    self.spill.extend_from_slice(tail);
+                    self.flush_above_watermark()?;
+                    return Ok(len);
+                }
+            }
         }
         self.buffer[self.cursor..self.cursor + len].copy_from_slice(buf);
         self.cursor += len;
+        self.flush_above_watermark()?;
+        Ok(len)
+    }
+
+    fn write_vectored(&mut self, bufs: &[IoSlice<'_>]) -> io::Result<usize> {
+        let len = bufs.iter().map(|buf| buf.len()).sum();
+        if len <= N - self.cursor && self.spill.is_empty() {
+            for buf in bufs {
+                self.buffer[self.cursor..self.cursor + buf.len()].copy_from_slice(buf);
+                self.cursor += buf.len();
+            }
+            self.flush_above_watermark()?;
+            return Ok(len);
+        }
+        match self.full_buffer_policy {
+            // nothing is buffered if the whole write does not fit
+            BufferFullPolicy::Error => handle_overflow()?,
+            BufferFullPolicy::Flush if len > N => {
+                self.flush_buffer()?;
+                let mut bufs = bufs.iter().map(|buf| &**buf).collect::<Vec<_>>();
+                write_all_vectored(&mut self.inner, &mut bufs)?;
+            }
+            _ => {
+                for buf in bufs {
+                    self.write_all(buf)?;
+                }
+            }
+        }
         Ok(len)
     }
 
     fn flush(&mut self) -> io::Result<()> {
-        self.inner.write_all(&self.buffer[..self.cursor])?;
+        match self.corked {
+            true => Ok(()),
+            false => self.flush_buffer(),
+        }
+    }
+}
+
+impl<S: Write, const N: usize> BufferedStream<S, N> {
+    #[inline]
+    fn flush_above_watermark(&mut self) -> io::Result<()> {
+        match self.flush_watermark {
+            Some(watermark) if self.buffered_len() >= watermark => self.flush(),
+            _ => Ok(()),
+        }
+    }
+
+    fn flush_buffer(&mut self) -> io::Result<()> {
+        write_all_vectored(&mut self.inner, &mut [&self.buffer[..self.cursor], &self.spill])?;
         self.cursor = 0;
+        self.spill.clear();
         self.inner.flush()
     }
 }
 
+impl<S: Write, const N: usize> Cork for BufferedStream<S, N> {
+    fn cork(&mut self) {
+        self.corked = true;
+    }
+
+    fn uncork(&mut self) -> io::Result<()> {
+        self.corked = false;
+        self.flush_buffer()
+    }
+
+    fn is_corked(&self) -> bool {
+        self.corked
+    }
+}
+
+impl<S: ConnectionInfoProvider, const N: usize> ConnectionInfoProvider for BufferedStream<S, N> {
+    fn connection_info(&self) -> &ConnectionInfo {
+        self.inner.connection_info()
+    }
+}
+
+impl<S: ConnectionPropertiesProvider, const N: usize> ConnectionPropertiesProvider for BufferedStream<S, N> {
+    fn connection_properties(&self) -> ConnectionProperties {
+        self.inner.connection_properties()
+    }
+}
+
 /// Trait to convert any stream into `BufferedStream`.
 pub trait IntoBufferedStream<S> {
     /// Convert into `BufferedStream` and specify buffer length.
@@ -96,12 +259,160 @@ where
     T: Read + Write,
 {
     fn into_buffered_stream<const N: usize>(self) -> BufferedStream<T, N> {
-        unsafe {
-            BufferedStream {
-                inner: self,
-                buffer: MaybeUninit::uninit().assume_init(),
-                cursor: 0,
-            }
+        BufferedStream {
+            inner: self,
+            buffer: [0u8; N],
+            cursor: 0,
+            spill: Vec::new(),
+            flush_watermark: None,
+            flush_on_read: false,
+            full_buffer_policy: BufferFullPolicy::default(),
+            corked: false,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Cursor, ErrorKind, IoSlice, Read, Write};
+
+    use super::*;
+
+    #[test]
+    fn should_buffer_until_flushed() {
+        let mut stream = Cursor::new(Vec::new()).into_buffered_stream::<8>();
+
+        stream.write_all(b"hello").unwrap();
+        assert!(stream.inner.get_ref().is_empty());
+
+        stream.flush().unwrap();
+        assert_eq!(b"hello", stream.inner.get_ref().as_slice());
+
+        stream.write_all(b"12345678").unwrap();
+        let err = stream.write(b"9").unwrap_err();
+        assert_eq!(ErrorKind::WriteZero, err.kind());
+
+        stream.flush().unwrap();
+        assert_eq!(b"hello12345678", stream.inner.get_ref().as_slice());
+    }
+
+    #[test]
+    fn should_flush_implicitly() {
+        let mut stream = Cursor::new(Vec::new())
+            .into_buffered_stream::<8>()
+            .with_flush_watermark(4)
+            .with_full_buffer_policy(BufferFullPolicy::Flush);
+
+        stream.write_all(b"abc").unwrap();
+        assert!(stream.inner.get_ref().is_empty());
+        stream.write_all(b"d").unwrap();
+        assert_eq!(b"abcd", stream.inner.get_ref().as_slice());
+        stream.write_all(b"e").unwrap();
+        stream.write_all(b"0123456789").unwrap();
+        assert_eq!(b"abcde0123456789", stream.inner.get_ref().as_slice());
+        assert_eq!(0, stream.buffered_len());
+
+        let mut stream = Cursor::new(Vec::new())
+            .into_buffered_stream::<4>()
+            .with_full_buffer_policy(BufferFullPolicy::Grow)
+            .with_flush_on_read(true);
+        stream.write_all(b"abc").unwrap();
+        stream.write_all(b"defgh").unwrap();
+        assert_eq!(8, stream.buffered_len());
+        assert!(stream.inner.get_ref().is_empty());
+
+        // the flushed data is behind the cursor position so nothing is read back
+        assert_eq!(0, stream.read(&mut [0u8; 8]).unwrap());
+        assert_eq!(b"abcdefgh", stream.inner.get_ref().as_slice());
+        assert_eq!(0, stream.buffered_len());
+    }
+
+    // records each write call, consuming all the buffers of the vectored write
+    #[derive(Default)]
+    struct Writes(Vec<Vec<u8>>);
+
+    impl Read for Writes {
+        fn read(&mut self, _buf: &mut [u8]) -> io::Result<usize> {
+            Ok(0)
+        }
+    }
+
+    impl Write for Writes {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.push(buf.to_vec());
+            Ok(buf.len())
+        }
+
+        fn write_vectored(&mut self, bufs: &[IoSlice<'_>]) -> io::Result<usize> {
+            self.0.push(bufs.iter().flat_map(|buf| buf.iter().copied()).collect());
+            Ok(self.0.last().unwrap().len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn should_flush_with_single_vectored_write() {
+        let mut stream = Writes::default()
+            .into_buffered_stream::<4>()
+            .with_full_buffer_policy(BufferFullPolicy::Grow);
+        let written = stream
+            .write_vectored(&[IoSlice::new(b"ab"), IoSlice::new(b"cdef")])
+            .unwrap();
+        assert_eq!(6, written);
+        stream.write_all(b"g").unwrap();
+        assert!(stream.inner.0.is_empty());
+        // the buffer and the spill are written together
+        stream.flush().unwrap();
+        assert_eq!(vec![b"abcdefg".to_vec()], stream.inner.0);
+
+        let mut stream = Writes::default().into_buffered_stream::<4>();
+        assert_eq!(
+            3,
+            stream
+                .write_vectored(&[IoSlice::new(b"a"), IoSlice::new(b"bc")])
+                .unwrap()
+        );
+        let err = stream
+            .write_vectored(&[IoSlice::new(b"d"), IoSlice::new(b"e")])
+            .unwrap_err();
+        assert_eq!(ErrorKind::WriteZero, err.kind());
+        assert_eq!(3, stream.buffered_len());
+
+        // writes larger than the buffer are passed through
+        let mut stream = Writes::default()
+            .into_buffered_stream::<4>()
+            .with_full_buffer_policy(BufferFullPolicy::Flush);
+        stream.write_all(b"ab").unwrap();
+        let written = stream
+            .write_vectored(&[IoSlice::new(b"cde"), IoSlice::new(b"fgh")])
+            .unwrap();
+        assert_eq!(6, written);
+        assert_eq!(vec![b"ab".to_vec(), b"cdefgh".to_vec()], stream.inner.0);
+    }
+
+    #[test]
+    fn should_defer_flush_while_corked() {
+        let mut stream = Writes::default()
+            .into_buffered_stream::<8>()
+            .with_flush_watermark(2)
+            .with_flush_on_read(true)
+            .with_full_buffer_policy(BufferFullPolicy::Flush);
+        stream.cork();
+        stream.write_all(b"ab").unwrap();
+        stream.write_all(b"cd").unwrap();
+        stream.flush().unwrap();
+        assert_eq!(0, stream.read(&mut [0u8; 8]).unwrap());
+        assert!(stream.inner.0.is_empty());
+        // the full buffer is still flushed to make room
+        stream.write_all(b"efghi").unwrap();
+        assert_eq!(vec![b"abcd".to_vec()], stream.inner.0);
+
+        stream.uncork().unwrap();
+        assert!(!stream.is_corked());
+        assert_eq!(vec![b"abcd".to_vec(), b"efghi".to_vec()], stream.inner.0);
+        assert_eq!(0, stream.buffered_len());
+    }
+}
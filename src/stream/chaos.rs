@@ -0,0 +1,343 @@
+//! Stream wrapper that injects faults, used to exercise the reconnect logic of the endpoints and the
+//! robustness of the decoders. The faults are driven by a seedable pseudo random generator so that
+//! every run with the same seed (and the same sequence of calls) injects exactly the same faults.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use std::net::TcpStream;
+//! use boomnet::stream::chaos::IntoFaultyStream;
+//! use boomnet::stream::tls::IntoTlsStream;
+//! use boomnet::ws::IntoWebsocket;
+//!
+//! let ws = TcpStream::connect("stream.binance.com:9443")
+//!     .unwrap()
+//!     .into_faulty_stream(42)
+//!     .with_would_block_probability(0.1)
+//!     .with_short_io_probability(0.5)
+//!     .with_eof_after(64 * 1024)
+//!     .into_tls_stream("stream.binance.com")
+//!     .into_websocket("wss://stream.binance.com:9443/ws");
+//! ```
+
+#[cfg(feature = "mio")]
+use mio::{event::Source, Interest, Registry, Token};
+use std::io;
+use std::io::ErrorKind::{UnexpectedEof, WouldBlock};
+use std::io::{Read, Write};
+use std::time::Duration;
+
+use crate::endpoint::ConnectionInfo;
+use crate::select::Selectable;
+use crate::stream::{ConnectionInfoProvider, SocketOptions, SocketQueues};
+
+/// Wraps the stream and injects the configured faults. By default no faults are injected.
+pub struct FaultyStream<S> {
+    inner: S,
+    rng: XorShift,
+    would_block_probability: f64,
+    short_io_probability: f64,
+    corruption_probability: f64,
+    latency: Option<Duration>,
+    eof_after: Option<usize>,
+    bytes_read: usize,
+}
+
+impl<S> FaultyStream<S> {
+    /// Wraps the `inner` stream, `seed` determines the sequence of the injected faults.
+    pub fn new(inner: S, seed: u64) -> FaultyStream<S> {
+        Self {
+            inner,
+            rng: XorShift::new(seed),
+            would_block_probability: 0.0,
+            short_io_probability: 0.0,
+            corruption_probability: 0.0,
+            latency: None,
+            eof_after: None,
+            bytes_read: 0,
+        }
+    }
+
+    /// Probability (from `0.0` to `1.0`) of failing the read or write with [`WouldBlock`] without
+    /// touching the inner stream.
+    pub fn with_would_block_probability(self, probability: f64) -> FaultyStream<S> {
+        Self {
+            would_block_probability: probability,
+            ..self
+        }
+    }
+
+    /// Probability of limiting the read or write to a random number of bytes (at least one).
+    pub fn with_short_io_probability(self, probability: f64) -> FaultyStream<S> {
+        Self {
+            short_io_probability: probability,
+            ..self
+        }
+    }
+
+    /// Probability of flipping a random bit of the data returned by the read.
+    pub fn with_corruption_probability(self, probability: f64) -> FaultyStream<S> {
+        Self {
+            corruption_probability: probability,
+            ..self
+        }
+    }
+
+    /// Delay each read that returns data by the `latency` (the calling thread is put to sleep).
+    pub fn with_latency(self, latency: Duration) -> FaultyStream<S> {
+        Self {
+            latency: Some(latency),
+            ..self
+        }
+    }
+
+    /// Fail the reads with [`UnexpectedEof`] once `bytes` have been read, emulating the peer
+    /// closing the connection mid-stream.
+    pub fn with_eof_after(self, bytes: usize) -> FaultyStream<S> {
+        Self {
+            eof_after: Some(bytes),
+            ..self
+        }
+    }
+
+    pub fn inner(&self) -> &S {
+        &self.inner
+    }
+
+    pub fn inner_mut(&mut self) -> &mut S {
+        &mut self.inner
+    }
+
+    #[inline]
+    fn limit(&mut self, len: usize) -> usize {
+        if len > 1 && self.rng.chance(self.short_io_probability) {
+            1 + self.rng.below(len as u64 - 1) as usize
+        } else {
+            len
+        }
+    }
+}
+
+impl<S: Read> Read for FaultyStream<S> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut len = buf.len();
+        if let Some(eof_after) = self.eof_after {
+            let remaining = eof_after.saturating_sub(self.bytes_read);
+            if remaining == 0 {
+                return Err(io::Error::new(UnexpectedEof, "injected eof"));
+            }
+            len = len.min(remaining);
+        }
+        if self.rng.chance(self.would_block_probability) {
+            return Err(io::Error::from(WouldBlock));
+        }
+        let len = self.limit(len);
+        let read = self.inner.read(&mut buf[..len])?;
+        if read > 0 {
+            if let Some(latency) = self.latency {
+                std::thread::sleep(latency);
+            }
+            if self.rng.chance(self.corruption_probability) {
+                let index = self.rng.below(read as u64) as usize;
+                buf[index] ^= 1 << self.rng.below(8);
+            }
+        }
+        self.bytes_read += read;
+        Ok(read)
+    }
+}
+
+impl<S: Write> Write for FaultyStream<S> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.rng.chance(self.would_block_probability) {
+            return Err(io::Error::from(WouldBlock));
+        }
+        let len = self.limit(buf.len());
+        self.inner.write(&buf[..len])
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(feature = "mio")]
+impl<S: Source> Source for FaultyStream<S> {
+    fn register(&mut self, registry: &Registry, token: Token, interests: Interest) -> io::Result<()> {
+        registry.register(&mut self.inner, token, interests)
+    }
+
+    fn reregister(&mut self, registry: &Registry, token: Token, interests: Interest) -> io::Result<()> {
+        registry.reregister(&mut self.inner, token, interests)
+    }
+
+    fn deregister(&mut self, registry: &Registry) -> io::Result<()> {
+        registry.deregister(&mut self.inner)
+    }
+}
+
+impl<S: Selectable> Selectable for FaultyStream<S> {
+    fn connected(&mut self) -> io::Result<bool> {
+        self.inner.connected()
+    }
+
+    fn make_writable(&mut self) {
+        self.inner.make_writable()
+    }
+
+    fn make_readable(&mut self) {
+        self.inner.make_readable()
+    }
+
+    fn make_unreadable(&mut self) {
+        self.inner.make_unreadable()
+    }
+
+    fn socket_queues(&self) -> Option<SocketQueues> {
+        self.inner.socket_queues()
+    }
+
+    fn rx_timestamp_ns(&self) -> Option<u64> {
+        self.inner.rx_timestamp_ns()
+    }
+
+    #[cfg(feature = "stats")]
+    fn io_counters(&self) -> Option<crate::stream::IoCounters> {
+        self.inner.io_counters()
+    }
+
+    fn apply_socket_options(&mut self, options: &SocketOptions) -> io::Result<()> {
+        self.inner.apply_socket_options(options)
+    }
+
+    fn has_pending_writes(&self) -> bool {
+        self.inner.has_pending_writes()
+    }
+
+    fn flush_pending_writes(&mut self) -> io::Result<()> {
+        self.inner.flush_pending_writes()
+    }
+
+    fn peer_closed(&self) -> bool {
+        self.inner.peer_closed()
+    }
+
+    fn write_closed(&self) -> bool {
+        self.inner.write_closed()
+    }
+
+    fn make_write_closed(&mut self) {
+        self.inner.make_write_closed()
+    }
+
+    fn take_socket_error(&mut self) -> io::Result<Option<io::Error>> {
+        self.inner.take_socket_error()
+    }
+
+    fn connect_error(&self) -> Option<io::Error> {
+        self.inner.connect_error()
+    }
+}
+
+impl<S: ConnectionInfoProvider> ConnectionInfoProvider for FaultyStream<S> {
+    fn connection_info(&self) -> &ConnectionInfo {
+        self.inner.connection_info()
+    }
+}
+
+pub trait IntoFaultyStream {
+    fn into_faulty_stream(self, seed: u64) -> FaultyStream<Self>
+    where
+        Self: Sized;
+}
+
+impl<T> IntoFaultyStream for T
+where
+    T: Read + Write,
+{
+    fn into_faulty_stream(self, seed: u64) -> FaultyStream<Self>
+    where
+        Self: Sized,
+    {
+        FaultyStream::new(self, seed)
+    }
+}
+
+/// Small xorshift64* generator, good enough to drive the faults deterministically.
+struct XorShift(u64);
+
+impl XorShift {
+    const fn new(seed: u64) -> XorShift {
+        // state must not be zero
+        XorShift(seed ^ 0x9E37_79B9_7F4A_7C15)
+    }
+
+    #[inline]
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    #[inline]
+    fn below(&mut self, bound: u64) -> u64 {
+        self.next() % bound
+    }
+
+    #[inline]
+    fn chance(&mut self, probability: f64) -> bool {
+        // uniformly distributed in [0, 1) using the top 53 bits
+        let sample = (self.next() >> 11) as f64 / (1u64 << 53) as f64;
+        probability > 0.0 && sample < probability
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    fn read_all(stream: &mut FaultyStream<Cursor<Vec<u8>>>) -> (Vec<u8>, usize, io::Error) {
+        let mut data = Vec::new();
+        let mut would_block = 0;
+        let mut buf = [0u8; 16];
+        loop {
+            match stream.read(&mut buf) {
+                Ok(0) => return (data, would_block, io::Error::from(UnexpectedEof)),
+                Ok(n) => data.extend_from_slice(&buf[..n]),
+                Err(err) if err.kind() == WouldBlock => would_block += 1,
+                Err(err) => return (data, would_block, err),
+            }
+        }
+    }
+
+    #[test]
+    fn should_inject_faults_deterministically() {
+        let input = (0..=255u8).collect::<Vec<_>>();
+        let faulty = |seed| {
+            Cursor::new(input.clone())
+                .into_faulty_stream(seed)
+                .with_would_block_probability(0.3)
+                .with_short_io_probability(0.5)
+                .with_eof_after(200)
+        };
+
+        let (data, would_block, err) = read_all(&mut faulty(7));
+        assert_eq!(&input[..200], data.as_slice());
+        assert!(would_block > 0);
+        assert_eq!(UnexpectedEof, err.kind());
+        assert_eq!((data, would_block), {
+            let (data, would_block, _) = read_all(&mut faulty(7));
+            (data, would_block)
+        });
+
+        let mut corrupted = Cursor::new(input.clone())
+            .into_faulty_stream(7)
+            .with_corruption_probability(1.0);
+        let (data, _, _) = read_all(&mut corrupted);
+        assert_eq!(input.len(), data.len());
+        assert_ne!(input, data);
+    }
+}
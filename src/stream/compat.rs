@@ -0,0 +1,212 @@
+//! Adapters between the boomnet streams and the `tokio` [`AsyncRead`] and [`AsyncWrite`] traits,
+//! available with the `tokio-compat` feature. They let the buffering, TLS and recording layers be
+//! reused by the services running on the `tokio` runtime.
+//!
+//! [`AsyncStream`] drives the (non-blocking) boomnet stream with the readiness of the underlying
+//! socket reported by the `tokio` reactor, while [`SyncStream`] exposes the `tokio` stream as the
+//! non-blocking [`Read`] and [`Write`] that fails with [`WouldBlock`] until the data is ready.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use std::os::fd::AsRawFd;
+//! use boomnet::stream::compat::AsyncStream;
+//! use boomnet::stream::record::IntoRecordedStream;
+//! use tokio::io::AsyncWriteExt;
+//!
+//! async fn send() -> std::io::Result<()> {
+//!     let stream = std::net::TcpStream::connect("127.0.0.1:9000")?;
+//!     stream.set_nonblocking(true)?;
+//!     let fd = stream.as_raw_fd();
+//!     let mut stream = AsyncStream::new(stream.into_recorded_stream("aux"), fd)?;
+//!     stream.write_all(b"hello").await
+//! }
+//! ```
+
+use std::io;
+use std::io::ErrorKind::WouldBlock;
+use std::io::{Read, Write};
+use std::os::fd::RawFd;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll, RawWaker, RawWakerVTable, Waker};
+
+use tokio::io::unix::AsyncFd;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+/// Exposes the non-blocking stream `S` as [`AsyncRead`] and [`AsyncWrite`]. The readiness is
+/// tracked for the socket `fd` the stream (possibly through multiple layers) reads from and writes
+/// to, which must be in the non-blocking mode. Must be created within the `tokio` runtime.
+pub struct AsyncStream<S> {
+    stream: S,
+    fd: AsyncFd<RawFd>,
+}
+
+impl<S> AsyncStream<S> {
+    /// Registers the socket `fd` underlying the `stream` with the `tokio` reactor.
+    pub fn new(stream: S, fd: RawFd) -> io::Result<AsyncStream<S>> {
+        Ok(Self {
+            stream,
+            fd: AsyncFd::new(fd)?,
+        })
+    }
+
+    /// Returns reference to the wrapped stream.
+    pub const fn get_ref(&self) -> &S {
+        &self.stream
+    }
+
+    /// Returns the wrapped stream, deregistering the socket from the reactor.
+    pub fn into_inner(self) -> S {
+        self.stream
+    }
+}
+
+impl<S: Read + Unpin> AsyncRead for AsyncStream<S> {
+    fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        // the stream may have buffered the data already (such as the decrypted TLS records)
+        match this.stream.read(buf.initialize_unfilled()) {
+            Err(err) if err.kind() == WouldBlock => {}
+            result => return Poll::Ready(result.map(|read| buf.advance(read))),
+        }
+        loop {
+            let mut guard = ready!(this.fd.poll_read_ready(cx))?;
+            if let Ok(result) = guard.try_io(|_| this.stream.read(buf.initialize_unfilled())) {
+                return Poll::Ready(result.map(|read| buf.advance(read)));
+            }
+        }
+    }
+}
+
+impl<S: Write + Unpin> AsyncWrite for AsyncStream<S> {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        loop {
+            let mut guard = ready!(this.fd.poll_write_ready(cx))?;
+            if let Ok(result) = guard.try_io(|_| this.stream.write(buf)) {
+                return Poll::Ready(result);
+            }
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        loop {
+            let mut guard = ready!(this.fd.poll_write_ready(cx))?;
+            if let Ok(result) = guard.try_io(|_| this.stream.flush()) {
+                return Poll::Ready(result);
+            }
+        }
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.poll_flush(cx)
+    }
+}
+
+/// Exposes the `tokio` stream `S` as the non-blocking [`Read`] and [`Write`], so that it can be
+/// wrapped with the boomnet streams. The operations that cannot complete fail with [`WouldBlock`]
+/// and the [`Waker`] registered with [`SyncStream::register`] is notified once they can be retried.
+pub struct SyncStream<S> {
+    stream: S,
+    waker: Waker,
+}
+
+impl<S> SyncStream<S> {
+    pub fn new(stream: S) -> SyncStream<S> {
+        Self {
+            stream,
+            waker: noop_waker(),
+        }
+    }
+
+    /// Registers the `waker` of the task that drives the stream, typically `cx.waker()` before
+    /// performing the reads and writes from within the `poll` function.
+    pub fn register(&mut self, waker: &Waker) {
+        self.waker.clone_from(waker);
+    }
+
+    /// Returns the wrapped stream.
+    pub fn into_inner(self) -> S {
+        self.stream
+    }
+}
+
+impl<S> SyncStream<S> {
+    fn poll_io<T>(&mut self, op: impl FnOnce(Pin<&mut S>, &mut Context<'_>) -> Poll<io::Result<T>>) -> io::Result<T>
+    where
+        S: Unpin,
+    {
+        let waker = self.waker.clone();
+        match op(Pin::new(&mut self.stream), &mut Context::from_waker(&waker)) {
+            Poll::Ready(result) => result,
+            Poll::Pending => Err(io::Error::from(WouldBlock)),
+        }
+    }
+}
+
+impl<S: AsyncRead + Unpin> Read for SyncStream<S> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut buf = ReadBuf::new(buf);
+        self.poll_io(|stream, cx| stream.poll_read(cx, &mut buf))?;
+        Ok(buf.filled().len())
+    }
+}
+
+impl<S: AsyncWrite + Unpin> Write for SyncStream<S> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.poll_io(|stream, cx| stream.poll_write(cx, buf))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.poll_io(|stream, cx| stream.poll_flush(cx))
+    }
+}
+
+fn noop_waker() -> Waker {
+    const VTABLE: RawWakerVTable = RawWakerVTable::new(|_| RAW, |_| {}, |_| {}, |_| {});
+    const RAW: RawWaker = RawWaker::new(std::ptr::null(), &VTABLE);
+    // SAFETY: the vtable functions do not dereference the data pointer
+    unsafe { Waker::from_raw(RAW) }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{TcpListener, TcpStream};
+    use std::os::fd::AsRawFd;
+
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    use crate::stream::buffer::IntoBufferedStream;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn should_adapt_streams() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        client.set_nonblocking(true).unwrap();
+        let (server, _) = listener.accept().unwrap();
+        server.set_nonblocking(true).unwrap();
+
+        let fd = client.as_raw_fd();
+        let mut client = AsyncStream::new(client.into_default_buffered_stream(), fd).unwrap();
+        client.write_all(b"hello").await.unwrap();
+        client.flush().await.unwrap();
+
+        let mut server = SyncStream::new(tokio::net::TcpStream::from_std(server).unwrap());
+        let mut buf = [0u8; 5];
+        let read = loop {
+            match server.read(&mut buf) {
+                Err(err) if err.kind() == WouldBlock => tokio::task::yield_now().await,
+                result => break result.unwrap(),
+            }
+        };
+        assert_eq!(b"hello", &buf[..read]);
+
+        server.write_all(b"world").unwrap();
+        let mut buf = [0u8; 5];
+        client.read_exact(&mut buf).await.unwrap();
+        assert_eq!(b"world", &buf);
+    }
+}
@@ -1,25 +1,143 @@
-use std::cmp::min;
+//! Stream that serves canned bytes (typically from a file) and records the writes, intended for
+//! deterministic integration tests of the endpoints driven by the `IOService`.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use boomnet::endpoint::ConnectionInfo;
+//! use boomnet::stream::file::FileStream;
+//!
+//! let stream = FileStream::from_file("inbound.bin")
+//!     .unwrap()
+//!     .with_chunk_size(64)
+//!     .with_would_block(true)
+//!     .with_connection_info(ConnectionInfo::new("stream.binance.com", 9443));
+//! // keep the log to inspect the data written by the endpoint
+//! let writes = stream.write_log();
+//! ```
+
+use std::cell::RefCell;
 use std::fs::File;
 use std::io;
-use std::io::ErrorKind::UnexpectedEof;
-use std::io::{BufReader, Read, Write};
+use std::io::ErrorKind::{UnexpectedEof, WouldBlock};
+use std::io::{BufReader, Cursor, Read, Write};
+use std::path::Path;
+use std::rc::Rc;
 
-pub struct FileStream<const CHUNK_SIZE: usize = 256>(BufReader<File>);
+use crate::endpoint::ConnectionInfo;
+use crate::select::Selectable;
+use crate::stream::ConnectionInfoProvider;
 
-impl<const CHUNK_SIZE: usize> Read for FileStream<CHUNK_SIZE> {
-    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
-        let up_to = min(buf.len(), CHUNK_SIZE);
+/// Default number of bytes returned by a single read.
+pub const DEFAULT_CHUNK_SIZE: usize = 256;
+
+/// Serves the bytes from the `reader` at most `chunk_size` bytes per read, optionally signalling
+/// [`WouldBlock`] between the chunks to emulate the data arriving over multiple poll cycles. Once
+/// all the data has been served the read fails with [`UnexpectedEof`], which the `IOService`
+/// treats as disconnect. Everything written to the stream is kept in the [`WriteLog`].
+pub struct FileStream<R = BufReader<File>> {
+    reader: R,
+    chunk_size: usize,
+    would_block: bool,
+    blocked: bool,
+    write_log: WriteLog,
+    connection_info: ConnectionInfo,
+}
+
+/// Data written to the [`FileStream`], shared with the stream so that it stays accessible after the
+/// stream has been handed over to the endpoint.
+#[derive(Debug, Clone, Default)]
+pub struct WriteLog(Rc<RefCell<Vec<u8>>>);
+
+impl WriteLog {
+    /// Returns copy of the data written so far.
+    pub fn to_vec(&self) -> Vec<u8> {
+        self.0.borrow().clone()
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.borrow().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.borrow().is_empty()
+    }
+
+    pub fn clear(&self) {
+        self.0.borrow_mut().clear()
+    }
+}
+
+impl FileStream {
+    pub fn from_file(path: impl AsRef<Path>) -> io::Result<FileStream> {
+        Ok(Self::new(BufReader::new(File::open(path)?)))
+    }
+}
+
+impl FileStream<Cursor<Vec<u8>>> {
+    pub fn from_bytes(bytes: impl Into<Vec<u8>>) -> FileStream<Cursor<Vec<u8>>> {
+        Self::new(Cursor::new(bytes.into()))
+    }
+}
 
-        match self.0.read(&mut buf[..up_to]) {
-            Ok(0) => Err(io::Error::new(UnexpectedEof, "eof")),
-            Ok(n) => Ok(n),
-            Err(err) => Err(err),
+impl<R: Read> FileStream<R> {
+    pub fn new(reader: R) -> FileStream<R> {
+        Self {
+            reader,
+            chunk_size: DEFAULT_CHUNK_SIZE,
+            would_block: false,
+            blocked: false,
+            write_log: WriteLog::default(),
+            connection_info: ConnectionInfo::new("localhost", 0),
         }
     }
+
+    /// Specify the maximum number of bytes returned by a single read.
+    pub fn with_chunk_size(self, chunk_size: usize) -> FileStream<R> {
+        assert!(chunk_size > 0, "chunk size must be positive");
+        Self { chunk_size, ..self }
+    }
+
+    /// Enable or disable signalling [`WouldBlock`] after each chunk, so that every chunk is
+    /// delivered in a separate poll cycle.
+    pub fn with_would_block(self, would_block: bool) -> FileStream<R> {
+        Self { would_block, ..self }
+    }
+
+    /// Specify the [`ConnectionInfo`] reported by the stream.
+    pub fn with_connection_info(self, connection_info: ConnectionInfo) -> FileStream<R> {
+        Self {
+            connection_info,
+            ..self
+        }
+    }
+
+    /// Returns the log of the data written to the stream.
+    pub fn write_log(&self) -> WriteLog {
+        self.write_log.clone()
+    }
 }
 
-impl Write for FileStream {
+impl<R: Read> Read for FileStream<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.blocked {
+            self.blocked = false;
+            return Err(io::Error::from(WouldBlock));
+        }
+        let up_to = buf.len().min(self.chunk_size);
+        match self.reader.read(&mut buf[..up_to])? {
+            0 => Err(io::Error::new(UnexpectedEof, "eof")),
+            n => {
+                self.blocked = self.would_block;
+                Ok(n)
+            }
+        }
+    }
+}
+
+impl<R> Write for FileStream<R> {
     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.write_log.0.borrow_mut().extend_from_slice(buf);
         Ok(buf.len())
     }
 
@@ -28,12 +146,68 @@ impl Write for FileStream {
     }
 }
 
+impl<R> Selectable for FileStream<R> {
+    fn connected(&mut self) -> io::Result<bool> {
+        Ok(true)
+    }
+
+    fn make_writable(&mut self) {
+        // no-op
+    }
+
+    fn make_readable(&mut self) {
+        // no-op
+    }
+}
+
+impl<R> ConnectionInfoProvider for FileStream<R> {
+    fn connection_info(&self) -> &ConnectionInfo {
+        &self.connection_info
+    }
+}
+
 impl TryFrom<&str> for FileStream {
     type Error = io::Error;
 
     fn try_from(path: &str) -> Result<Self, Self::Error> {
-        let file = File::open(path)?;
-        let stream = FileStream(BufReader::new(file));
-        Ok(stream)
+        Self::from_file(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_serve_chunks_and_record_writes() {
+        let mut stream = FileStream::from_bytes(b"hello world".to_vec())
+            .with_chunk_size(4)
+            .with_would_block(true);
+        let writes = stream.write_log();
+
+        let mut buf = [0u8; 16];
+        let mut chunks = Vec::new();
+        let err = loop {
+            match stream.read(&mut buf) {
+                Ok(n) => chunks.push(buf[..n].to_vec()),
+                Err(err) if err.kind() == WouldBlock => chunks.push(Vec::new()),
+                Err(err) => break err,
+            }
+        };
+        assert_eq!(UnexpectedEof, err.kind());
+        assert_eq!(
+            vec![
+                b"hell".to_vec(),
+                vec![],
+                b"o wo".to_vec(),
+                vec![],
+                b"rld".to_vec(),
+                vec![]
+            ],
+            chunks
+        );
+
+        stream.write_all(b"ping").unwrap();
+        assert_eq!(b"ping".to_vec(), writes.to_vec());
     }
 }
@@ -1,18 +1,49 @@
 use std::io;
-use std::io::ErrorKind::{Interrupted, NotConnected, WouldBlock};
-use std::io::{Read, Write};
+use std::io::ErrorKind;
+use std::io::ErrorKind::{Interrupted, NotConnected, WouldBlock, WriteZero};
+use std::io::{IoSlice, Read, Write};
 
 use mio::event::Source;
 use mio::net::TcpStream;
 use mio::{Interest, Registry, Token};
 
 use crate::select::Selectable;
+#[cfg(feature = "stats")]
+use crate::stream::IoCounters;
+use crate::stream::{ConnectionProperties, ConnectionPropertiesProvider, SocketOptions, SocketQueues};
 
+/// Default limit of bytes that can be queued while the socket is not writable.
+pub const DEFAULT_MAX_PENDING_WRITE_BYTES: usize = 1024 * 1024;
+/// Default limit of bytes that can be queued before the connection has been established.
+pub const DEFAULT_MAX_PRE_CONNECT_BYTES: usize = 64 * 1024;
+
+/// Non-blocking TCP stream driven by the `MioSelector`. Data written while the socket is not
+/// writable (either the connection is still in progress or the kernel send buffer is full) is
+/// queued and sent once the socket becomes writable again. Once the socket reports [`WouldBlock`]
+/// no further writes are attempted until the selector signals write readiness, the selector keeps
+/// the node registered for write readiness for as long as there is queued data. The queue is bounded,
+/// once the number of pending bytes would exceed the limit the write fails with [`WriteZero`] error.
+///
+/// The failed connection attempt (such as refused by the peer) is detected from the pending socket
+/// error (`SO_ERROR`) once the socket is reported writable, after which [`Selectable::connected`],
+/// reads and writes fail with the connect error, also available from [`Selectable::connect_error`].
+/// The data queued before the connection has been established is subject to the separate (smaller)
+/// limit, see [`MioStream::with_max_pre_connect_bytes`].
 pub struct MioStream {
     inner: TcpStream,
     connected: bool,
     can_read: bool,
     can_write: bool,
+    read_closed: bool,
+    write_closed: bool,
+    connect_error: Option<(ErrorKind, String)>,
+    outbound: Vec<u8>,
+    max_pending_write_bytes: usize,
+    max_pre_connect_bytes: usize,
+    rx_timestamps: bool,
+    rx_timestamp_ns: Option<u64>,
+    #[cfg(feature = "stats")]
+    io_counters: IoCounters,
 }
 
 impl From<TcpStream> for MioStream {
@@ -22,16 +53,138 @@ impl From<TcpStream> for MioStream {
             connected: false,
             can_read: false,
             can_write: false,
+            read_closed: false,
+            write_closed: false,
+            connect_error: None,
+            outbound: Vec::new(),
+            max_pending_write_bytes: DEFAULT_MAX_PENDING_WRITE_BYTES,
+            max_pre_connect_bytes: DEFAULT_MAX_PRE_CONNECT_BYTES,
+            rx_timestamps: false,
+            rx_timestamp_ns: None,
+            #[cfg(feature = "stats")]
+            io_counters: Default::default(),
         }
     }
 }
 
+impl MioStream {
+    /// Specify the maximum number of bytes that can be queued while the socket is not writable.
+    pub fn with_max_pending_write_bytes(self, max_pending_write_bytes: usize) -> MioStream {
+        Self {
+            max_pending_write_bytes,
+            ..self
+        }
+    }
+
+    /// Specify the maximum number of bytes that can be queued before the connection has been
+    /// established, capped by the [`MioStream::with_max_pending_write_bytes`] limit.
+    pub fn with_max_pre_connect_bytes(self, max_pre_connect_bytes: usize) -> MioStream {
+        Self {
+            max_pre_connect_bytes,
+            ..self
+        }
+    }
+
+    /// Number of bytes queued and not yet written to the socket.
+    pub fn pending_write_bytes(&self) -> usize {
+        self.outbound.len()
+    }
+
+    /// Attempts to write the queued bytes to the socket, returns `true` if the queue is now empty.
+    fn write_pending(&mut self) -> io::Result<bool> {
+        if !self.can_write {
+            return Ok(self.outbound.is_empty());
+        }
+        let mut written = 0;
+        while written < self.outbound.len() {
+            match self.inner.write(&self.outbound[written..]) {
+                Ok(0) => return Err(io::Error::from(WriteZero)),
+                Ok(n) => {
+                    #[cfg(feature = "stats")]
+                    {
+                        self.io_counters.bytes_written += n as u64;
+                    }
+                    written += n
+                }
+                Err(err) if err.kind() == WouldBlock => {
+                    // wait for the selector to report write readiness
+                    self.can_write = false;
+                    break;
+                }
+                Err(err) if err.kind() == Interrupted => continue,
+                Err(err) => return Err(err),
+            }
+        }
+        self.outbound.drain(..written);
+        Ok(self.outbound.is_empty())
+    }
+
+    #[inline]
+    fn read_inner(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        #[cfg(target_os = "linux")]
+        if self.rx_timestamps {
+            use std::os::fd::AsRawFd;
+            let (read, rx_timestamp_ns) = crate::stream::timestamp::recv_timestamped(self.inner.as_raw_fd(), buf)?;
+            if rx_timestamp_ns.is_some() {
+                self.rx_timestamp_ns = rx_timestamp_ns;
+            }
+            return Ok(read);
+        }
+        self.inner.read(buf)
+    }
+
+    /// Records the failed connection attempt and returns the error.
+    #[cold]
+    fn fail_connect(&mut self, err: io::Error) -> io::Error {
+        self.connect_error = Some((err.kind(), err.to_string()));
+        self.outbound.clear();
+        err
+    }
+
+    #[inline]
+    fn enqueue(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.enqueue_vectored(&[IoSlice::new(buf)])
+    }
+
+    /// Queues all the `bufs` or none of them if the limit would be exceeded.
+    fn enqueue_vectored(&mut self, bufs: &[IoSlice<'_>]) -> io::Result<usize> {
+        #[cold]
+        fn handle_overflow(pending: usize, limit: usize) -> io::Result<usize> {
+            Err(io::Error::new(
+                WriteZero,
+                format!("outbound queue limit exceeded: {pending} bytes pending, limit is {limit} bytes"),
+            ))
+        }
+
+        // the stream may have been created from the already connected socket
+        let limit = match self.connected()? {
+            true => self.max_pending_write_bytes,
+            false => self.max_pre_connect_bytes.min(self.max_pending_write_bytes),
+        };
+        let len = bufs.iter().map(|buf| buf.len()).sum();
+        if self.outbound.len() + len > limit {
+            return handle_overflow(self.outbound.len(), limit);
+        }
+        for buf in bufs {
+            self.outbound.extend_from_slice(buf);
+        }
+        Ok(len)
+    }
+}
+
 impl Selectable for MioStream {
     fn connected(&mut self) -> io::Result<bool> {
         if self.connected {
             return Ok(true);
         }
+        if let Some(err) = self.connect_error() {
+            return Err(err);
+        }
 
+        // the failed attempt leaves the socket not connected, with the reason in SO_ERROR
+        if let Some(err) = self.inner.take_error()? {
+            return Err(self.fail_connect(err));
+        }
         match self.inner.peer_addr() {
             Ok(_) => {
                 self.connected = true;
@@ -39,7 +192,7 @@ impl Selectable for MioStream {
             }
             Err(err) if err.kind() == NotConnected => Ok(false),
             Err(err) if err.kind() == Interrupted => Ok(false),
-            Err(err) => Err(err),
+            Err(err) => Err(self.fail_connect(err)),
         }
     }
 
@@ -50,6 +203,76 @@ impl Selectable for MioStream {
     fn make_readable(&mut self) {
         self.can_read = true;
     }
+
+    fn make_unreadable(&mut self) {
+        self.can_read = false;
+    }
+
+    #[cfg(target_os = "linux")]
+    fn socket_queues(&self) -> Option<SocketQueues> {
+        crate::stream::socket_queues(&self.inner)
+    }
+
+    fn rx_timestamp_ns(&self) -> Option<u64> {
+        self.rx_timestamp_ns
+    }
+
+    #[cfg(feature = "stats")]
+    fn io_counters(&self) -> Option<IoCounters> {
+        Some(self.io_counters)
+    }
+
+    fn apply_socket_options(&mut self, options: &SocketOptions) -> io::Result<()> {
+        use std::os::fd::{AsRawFd, BorrowedFd};
+        // SAFETY: the file descriptor remains open for the lifetime of the borrow
+        let fd = unsafe { BorrowedFd::borrow_raw(self.inner.as_raw_fd()) };
+        options.apply(&socket2::SockRef::from(&fd))?;
+        // the timestamps are only delivered to recvmsg
+        self.rx_timestamps = cfg!(target_os = "linux") && options.rx_timestamps;
+        Ok(())
+    }
+
+    fn has_pending_writes(&self) -> bool {
+        !self.outbound.is_empty()
+    }
+
+    fn flush_pending_writes(&mut self) -> io::Result<()> {
+        self.write_pending()?;
+        Ok(())
+    }
+
+    fn peer_closed(&self) -> bool {
+        self.read_closed
+    }
+
+    fn write_closed(&self) -> bool {
+        self.write_closed
+    }
+
+    fn make_write_closed(&mut self) {
+        self.write_closed = true;
+    }
+
+    fn take_socket_error(&mut self) -> io::Result<Option<io::Error>> {
+        self.inner.take_error()
+    }
+
+    fn connect_error(&self) -> Option<io::Error> {
+        self.connect_error
+            .as_ref()
+            .map(|(kind, reason)| io::Error::new(*kind, reason.clone()))
+    }
+}
+
+impl ConnectionPropertiesProvider for MioStream {
+    fn connection_properties(&self) -> ConnectionProperties {
+        ConnectionProperties {
+            local_addr: self.inner.local_addr().ok(),
+            // not available until the connection has been established
+            peer_addr: self.inner.peer_addr().ok(),
+            tls: None,
+        }
+    }
 }
 
 impl Source for MioStream {
@@ -68,11 +291,25 @@ impl Source for MioStream {
 
 impl Read for MioStream {
     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if let Some(err) = self.connect_error() {
+            return Err(err);
+        }
+        // opportunistically send queued data
+        if !self.outbound.is_empty() {
+            self.write_pending()?;
+        }
         if self.can_read {
-            let read = self.inner.read(buf)?;
+            let read = self.read_inner(buf)?;
+            #[cfg(feature = "stats")]
+            {
+                self.io_counters.bytes_read += read as u64;
+            }
             if read < buf.len() {
                 self.can_read = false;
             }
+            if read == 0 && !buf.is_empty() {
+                self.read_closed = true;
+            }
             return Ok(read);
         }
         Err(io::Error::from(WouldBlock))
@@ -81,14 +318,60 @@ impl Read for MioStream {
 
 impl Write for MioStream {
     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
-        if !self.can_write {
-            return Ok(0);
+        if let Some(err) = self.connect_error() {
+            return Err(err);
+        }
+        // preserve ordering with respect to the already queued data
+        if !self.can_write || !self.write_pending()? {
+            return self.enqueue(buf);
+        }
+        match self.inner.write(buf) {
+            Ok(n) => {
+                #[cfg(feature = "stats")]
+                {
+                    self.io_counters.bytes_written += n as u64;
+                }
+                Ok(n)
+            }
+            Err(err) if err.kind() == WouldBlock => {
+                self.can_write = false;
+                self.enqueue(buf)
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    fn write_vectored(&mut self, bufs: &[IoSlice<'_>]) -> io::Result<usize> {
+        if let Some(err) = self.connect_error() {
+            return Err(err);
+        }
+        // preserve ordering with respect to the already queued data
+        if !self.can_write || !self.write_pending()? {
+            return self.enqueue_vectored(bufs);
+        }
+        match self.inner.write_vectored(bufs) {
+            Ok(n) => {
+                #[cfg(feature = "stats")]
+                {
+                    self.io_counters.bytes_written += n as u64;
+                }
+                Ok(n)
+            }
+            Err(err) if err.kind() == WouldBlock => {
+                self.can_write = false;
+                self.enqueue_vectored(bufs)
+            }
+            Err(err) => Err(err),
         }
-        self.inner.write(buf)
     }
 
     fn flush(&mut self) -> io::Result<()> {
-        self.inner.flush()
+        if self.write_pending()? {
+            self.inner.flush()
+        } else {
+            // remaining data will be sent once the socket is writable
+            Ok(())
+        }
     }
 }
 
@@ -101,3 +384,103 @@ impl IntoMioStream for std::net::TcpStream {
         TcpStream::from_std(self).into()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::net::TcpListener;
+
+    use super::*;
+
+    #[test]
+    fn should_queue_writes_until_socket_is_writable() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let client = std::net::TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        client.set_nonblocking(true).unwrap();
+        let (mut server, _) = listener.accept().unwrap();
+        let mut stream = client.into_mio_stream().with_max_pending_write_bytes(usize::MAX);
+        stream.make_writable();
+
+        // fill the kernel send buffer until the socket blocks
+        let chunk = vec![0u8; 64 * 1024];
+        while !stream.has_pending_writes() {
+            stream.write_all(&chunk).unwrap();
+        }
+        assert!(!stream.can_write);
+        let pending = stream.pending_write_bytes();
+        stream.write_all(&chunk).unwrap();
+        assert_eq!(pending + chunk.len(), stream.pending_write_bytes());
+
+        // drain the peer and simulate write readiness reported by the selector
+        server.set_nonblocking(true).unwrap();
+        let mut buf = vec![0u8; 1024 * 1024];
+        while stream.has_pending_writes() {
+            while server.read(&mut buf).map(|n| n > 0).unwrap_or(false) {}
+            stream.make_writable();
+            stream.flush_pending_writes().unwrap();
+        }
+    }
+
+    #[test]
+    fn should_report_peer_closed() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let client = std::net::TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        client.set_nonblocking(true).unwrap();
+        let (mut server, _) = listener.accept().unwrap();
+        let mut stream = client.into_mio_stream();
+
+        server.write_all(b"bye").unwrap();
+        server.shutdown(std::net::Shutdown::Write).unwrap();
+
+        // the data sent before the shutdown is still delivered
+        let mut buf = [0u8; 16];
+        let mut received = Vec::new();
+        while !stream.peer_closed() {
+            stream.make_readable();
+            match stream.read(&mut buf) {
+                Ok(read) => received.extend_from_slice(&buf[..read]),
+                Err(err) if err.kind() == WouldBlock => std::thread::yield_now(),
+                Err(err) => panic!("{err}"),
+            }
+        }
+        assert_eq!(b"bye", received.as_slice());
+        assert!(!stream.write_closed());
+        assert!(stream.take_socket_error().unwrap().is_none());
+    }
+
+    #[test]
+    fn should_report_refused_connection() {
+        // nothing listens on the port once the listener is dropped
+        let addr = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+        let mut stream = MioStream::from(TcpStream::connect(addr).unwrap());
+
+        let err = loop {
+            match stream.connected() {
+                Ok(true) => panic!("connection should have been refused"),
+                Ok(false) => std::thread::yield_now(),
+                Err(err) => break err,
+            }
+        };
+        assert_eq!(ErrorKind::ConnectionRefused, err.kind());
+        // the failure is sticky and reported by all the operations
+        assert_eq!(ErrorKind::ConnectionRefused, stream.connect_error().unwrap().kind());
+        assert_eq!(ErrorKind::ConnectionRefused, stream.connected().unwrap_err().kind());
+        assert_eq!(ErrorKind::ConnectionRefused, stream.write(b"hello").unwrap_err().kind());
+        assert_eq!(ErrorKind::ConnectionRefused, stream.read(&mut [0u8; 16]).unwrap_err().kind());
+        assert!(!stream.has_pending_writes());
+    }
+
+    #[test]
+    fn should_limit_writes_queued_before_connected() {
+        // socket that has not been connected yet
+        let socket = socket2::Socket::new(socket2::Domain::IPV4, socket2::Type::STREAM, None).unwrap();
+        socket.set_nonblocking(true).unwrap();
+        let mut stream = MioStream::from(TcpStream::from_std(socket.into()))
+            .with_max_pre_connect_bytes(8)
+            .with_max_pending_write_bytes(16);
+
+        assert!(!stream.connected().unwrap());
+        stream.write_all(b"12345678").unwrap();
+        assert_eq!(WriteZero, stream.write(b"9").unwrap_err().kind());
+        assert_eq!(8, stream.pending_write_bytes());
+    }
+}
@@ -1,18 +1,29 @@
 //! Various stream implementations on top of which protocol can be applied.
 
 use std::io;
-use std::net::{SocketAddr, TcpStream, ToSocketAddrs};
+use std::io::ErrorKind::AddrInUse;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, TcpStream, ToSocketAddrs};
+use std::time::Duration;
 
-use socket2::{Domain, Protocol, Socket, Type};
+use socket2::{Domain, Protocol, SockRef, Socket, Type};
 
+use crate::endpoint::ConnectionInfo;
 use crate::select::Selectable;
 
 pub mod buffer;
+pub mod chaos;
+#[cfg(all(feature = "tokio-compat", unix))]
+pub mod compat;
 pub mod file;
 #[cfg(feature = "mio")]
 pub mod mio;
+pub mod pcap;
+#[cfg(feature = "proxy")]
+pub mod proxy;
 pub mod record;
 pub mod replay;
+#[cfg(target_os = "linux")]
+pub mod timestamp;
 #[cfg(any(feature = "tls-webpki", feature = "tls-native"))]
 pub mod tls;
 
@@ -21,6 +32,242 @@ const EINPROGRESS: i32 = 115;
 #[cfg(target_os = "macos")]
 const EINPROGRESS: i32 = 36;
 
+/// Number of bytes held in the kernel socket buffers.
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq)]
+pub struct SocketQueues {
+    /// Bytes received but not yet read by the application (`SIOCINQ`).
+    pub receive_queue: usize,
+    /// Bytes written but not yet acknowledged by the peer (`SIOCOUTQ`).
+    pub send_queue: usize,
+}
+
+/// Number of bytes transferred over the connection, available with the `stats` feature.
+#[cfg(feature = "stats")]
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq)]
+pub struct IoCounters {
+    /// Bytes read from the socket.
+    pub bytes_read: u64,
+    /// Bytes written to the socket.
+    pub bytes_written: u64,
+}
+
+#[cfg(target_os = "linux")]
+pub(crate) fn socket_queues<F: std::os::fd::AsRawFd>(socket: &F) -> Option<SocketQueues> {
+    let fd = socket.as_raw_fd();
+    let mut receive_queue: libc::c_int = 0;
+    let mut send_queue: libc::c_int = 0;
+    // SAFETY: both requests write single c_int to the provided pointer
+    unsafe {
+        if libc::ioctl(fd, libc::FIONREAD, &mut receive_queue) < 0 {
+            return None;
+        }
+        if libc::ioctl(fd, libc::TIOCOUTQ, &mut send_queue) < 0 {
+            return None;
+        }
+    }
+    Some(SocketQueues {
+        receive_queue: receive_queue as usize,
+        send_queue: send_queue as usize,
+    })
+}
+
+/// Socket options applied when the connection is established (and re-applied every time the
+/// connection is recreated by the `IOService`).
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::net::TcpStream;
+/// use std::time::Duration;
+/// use boomnet::stream::{BindAndConnect, SocketOptions};
+///
+/// let options = SocketOptions::default()
+///     .with_recv_buffer_size(4 * 1024 * 1024)
+///     .with_quick_ack(true)
+///     .with_busy_poll(Duration::from_micros(50));
+/// let stream = TcpStream::bind_and_connect_with_options("stream.binance.com:9443", None, None, &options).unwrap();
+/// ```
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq)]
+pub struct SocketOptions {
+    /// Size of the kernel receive buffer (`SO_RCVBUF`).
+    pub recv_buffer_size: Option<usize>,
+    /// Size of the kernel send buffer (`SO_SNDBUF`).
+    pub send_buffer_size: Option<usize>,
+    /// Enables `TCP_QUICKACK` (Linux only).
+    pub quick_ack: bool,
+    /// Busy poll timeout for blocking receives (`SO_BUSY_POLL`, Linux only).
+    pub busy_poll: Option<Duration>,
+    /// Enables `SO_REUSEPORT` so that multiple sockets can bind the same local address (Unix only).
+    pub reuse_port: bool,
+    /// CPU whose receive queue should process the packets of this socket (`SO_INCOMING_CPU`, Linux only).
+    pub incoming_cpu: Option<usize>,
+    /// Enables kernel receive timestamps (`SO_TIMESTAMPNS`, Linux only), see [`Selectable::rx_timestamp_ns`].
+    pub rx_timestamps: bool,
+}
+
+impl SocketOptions {
+    /// Specify size of the kernel receive buffer.
+    pub fn with_recv_buffer_size(self, recv_buffer_size: usize) -> SocketOptions {
+        Self {
+            recv_buffer_size: Some(recv_buffer_size),
+            ..self
+        }
+    }
+
+    /// Specify size of the kernel send buffer.
+    pub fn with_send_buffer_size(self, send_buffer_size: usize) -> SocketOptions {
+        Self {
+            send_buffer_size: Some(send_buffer_size),
+            ..self
+        }
+    }
+
+    /// Enable or disable `TCP_QUICKACK`.
+    pub fn with_quick_ack(self, quick_ack: bool) -> SocketOptions {
+        Self { quick_ack, ..self }
+    }
+
+    /// Specify `SO_BUSY_POLL` timeout (with microsecond resolution).
+    pub fn with_busy_poll(self, busy_poll: Duration) -> SocketOptions {
+        Self {
+            busy_poll: Some(busy_poll),
+            ..self
+        }
+    }
+
+    /// Enable or disable `SO_REUSEPORT`. It only takes effect when applied before the socket is bound,
+    /// such as with [`BindAndConnect::bind_and_connect_with_options`].
+    pub fn with_reuse_port(self, reuse_port: bool) -> SocketOptions {
+        Self { reuse_port, ..self }
+    }
+
+    /// Specify `SO_INCOMING_CPU` affinity, used to steer each connection of the sharded feed to the
+    /// receive queue of the CPU that processes it.
+    pub fn with_incoming_cpu(self, cpu: usize) -> SocketOptions {
+        Self {
+            incoming_cpu: Some(cpu),
+            ..self
+        }
+    }
+
+    /// Enable or disable kernel receive timestamps. Only the streams that read with `recvmsg` (such as
+    /// `MioStream` or [`TimestampedStream`](timestamp::TimestampedStream)) report them.
+    pub fn with_rx_timestamps(self, rx_timestamps: bool) -> SocketOptions {
+        Self { rx_timestamps, ..self }
+    }
+
+    /// Checks if any option has been set.
+    pub fn is_empty(&self) -> bool {
+        *self == SocketOptions::default()
+    }
+
+    /// Applies the options to the socket.
+    #[allow(unused_variables)]
+    pub fn apply(&self, socket: &Socket) -> io::Result<()> {
+        if let Some(size) = self.recv_buffer_size {
+            socket.set_recv_buffer_size(size)?;
+        }
+        if let Some(size) = self.send_buffer_size {
+            socket.set_send_buffer_size(size)?;
+        }
+        #[cfg(unix)]
+        if self.reuse_port {
+            socket.set_reuse_port(true)?;
+        }
+        #[cfg(target_os = "linux")]
+        if let Some(cpu) = self.incoming_cpu {
+            socket.set_cpu_affinity(cpu)?;
+        }
+        #[cfg(target_os = "linux")]
+        if self.rx_timestamps {
+            use std::os::fd::AsRawFd;
+            timestamp::enable_rx_timestamps(socket.as_raw_fd())?;
+        }
+        #[cfg(target_os = "linux")]
+        if self.quick_ack {
+            socket.set_quickack(true)?;
+        }
+        #[cfg(target_os = "linux")]
+        if let Some(busy_poll) = self.busy_poll {
+            use std::os::fd::AsRawFd;
+            let micros = busy_poll.as_micros().min(libc::c_int::MAX as u128) as libc::c_int;
+            // SAFETY: option value points to a valid c_int for the duration of the call
+            let res = unsafe {
+                libc::setsockopt(
+                    socket.as_raw_fd(),
+                    libc::SOL_SOCKET,
+                    libc::SO_BUSY_POLL,
+                    &micros as *const libc::c_int as *const libc::c_void,
+                    std::mem::size_of::<libc::c_int>() as libc::socklen_t,
+                )
+            };
+            if res < 0 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Provides information about the remote peer the stream communicates with.
+pub trait ConnectionInfoProvider {
+    /// Returns connection info of the remote peer.
+    fn connection_info(&self) -> &ConnectionInfo;
+}
+
+/// Properties of the established connection, as opposed to the [`ConnectionInfo`] that describes
+/// where to connect. Useful for the audit logs.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct ConnectionProperties {
+    /// Local address of the socket.
+    pub local_addr: Option<SocketAddr>,
+    /// Address of the peer the socket is actually connected to.
+    pub peer_addr: Option<SocketAddr>,
+    /// Negotiated TLS parameters, `None` for plain connections or until the TLS handshake completes.
+    pub tls: Option<TlsProperties>,
+}
+
+/// Parameters negotiated during the TLS handshake.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct TlsProperties {
+    /// Protocol version, such as `TLSv1_3`.
+    pub version: String,
+    /// Cipher suite, such as `TLS13_AES_256_GCM_SHA384`.
+    pub cipher_suite: String,
+    /// Application protocol selected with ALPN, if any.
+    pub alpn_protocol: Option<Vec<u8>>,
+}
+
+/// Provides the [`ConnectionProperties`] of the established connection.
+pub trait ConnectionPropertiesProvider {
+    /// Returns the properties of the connection, the fields not known (yet) are `None`.
+    fn connection_properties(&self) -> ConnectionProperties;
+}
+
+/// Stream that can hold back the data written to it (cork) and release it at once (uncork), so that
+/// many small writes, such as the websocket frames sent in response to the batch of received ones,
+/// are sent together (for the `TlsStream` encrypted into single record) instead of one by one.
+pub trait Cork {
+    /// Holds back the data written (and makes the flush no-op) until [`Cork::uncork`].
+    fn cork(&mut self);
+
+    /// Releases the data held back since [`Cork::cork`] and flushes the stream.
+    fn uncork(&mut self) -> io::Result<()>;
+
+    /// Returns `true` if the stream is currently corked.
+    fn is_corked(&self) -> bool;
+}
+
+impl ConnectionPropertiesProvider for TcpStream {
+    fn connection_properties(&self) -> ConnectionProperties {
+        ConnectionProperties {
+            local_addr: self.local_addr().ok(),
+            peer_addr: self.peer_addr().ok(),
+            tls: None,
+        }
+    }
+}
+
 /// Trait to create `TcpStream` and optionally bind it to a specific network interface and/or cpu
 /// before connecting.
 ///
@@ -77,6 +324,66 @@ pub trait BindAndConnect {
         Self::bind_and_connect_with_socket_config(addr, net_iface, cpu, |_| Ok(()))
     }
 
+    /// Creates `TcpStream` and optionally binds it to network interface and/or CPU before
+    /// connecting, applying the [`SocketOptions`] to the socket.
+    fn bind_and_connect_with_options<A>(
+        addr: A,
+        net_iface: Option<SocketAddr>,
+        cpu: Option<usize>,
+        options: &SocketOptions,
+    ) -> io::Result<TcpStream>
+    where
+        A: ToSocketAddrs,
+    {
+        Self::bind_and_connect_with_socket_config(addr, net_iface, cpu, |socket| options.apply(socket))
+    }
+
+    /// Creates `TcpStream` as described by the [`ConnectionInfo`], applying its [`SocketOptions`]
+    /// and binding it to the [`ConnectionInfo::bind_addr`] before connecting. If the
+    /// [`ConnectionInfo::local_port_range`] is specified, each port is tried in turn until one that
+    /// is not taken is found.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use std::net::TcpStream;
+    /// use boomnet::endpoint::ConnectionInfo;
+    /// use boomnet::stream::BindAndConnect;
+    ///
+    /// let connection_info = ConnectionInfo::new("stream.binance.com", 9443)
+    ///     .with_bind_addr("10.0.0.5:0".parse().unwrap())
+    ///     .with_local_port_range(40000..40100);
+    /// let stream = TcpStream::bind_and_connect_with_connection_info("stream.binance.com:9443", &connection_info, None).unwrap();
+    /// ```
+    fn bind_and_connect_with_connection_info<A>(
+        addr: A,
+        connection_info: &ConnectionInfo,
+        cpu: Option<usize>,
+    ) -> io::Result<TcpStream>
+    where
+        A: ToSocketAddrs,
+    {
+        let options = &connection_info.socket_options;
+        let Some(ports) = connection_info.local_port_range.clone() else {
+            return Self::bind_and_connect_with_options(addr, connection_info.bind_addr, cpu, options);
+        };
+        // resolve once rather than for every port attempted
+        let addr = addr
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| io::Error::other("unable to resolve socket address"))?;
+        let ip = connection_info
+            .bind_addr
+            .map_or(IpAddr::V4(Ipv4Addr::UNSPECIFIED), |bind_addr| bind_addr.ip());
+        for port in ports.clone() {
+            match Self::bind_and_connect_with_options(addr, Some(SocketAddr::new(ip, port)), cpu, options) {
+                Err(err) if err.kind() == AddrInUse => continue,
+                result => return result,
+            }
+        }
+        Err(io::Error::new(AddrInUse, format!("no local port available in range {}..{}", ports.start, ports.end)))
+    }
+
     /// Creates `TcpStream` and optionally binds it to network interface and/or CPU before
     /// connecting. This also accepts user defined `socket_config` closure that will be applied
     /// to the socket.
@@ -186,4 +493,117 @@ impl Selectable for TcpStream {
     fn make_readable(&mut self) {
         // no-op
     }
+
+    #[cfg(target_os = "linux")]
+    fn socket_queues(&self) -> Option<SocketQueues> {
+        socket_queues(self)
+    }
+
+    fn apply_socket_options(&mut self, options: &SocketOptions) -> io::Result<()> {
+        options.apply(&SockRef::from(&*self))
+    }
+
+    fn take_socket_error(&mut self) -> io::Result<Option<io::Error>> {
+        self.take_error()
+    }
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use std::io::Write;
+    use std::net::TcpListener;
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn should_report_receive_queue() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (server, _) = listener.accept().unwrap();
+
+        client.write_all(b"hello").unwrap();
+        let mut queues = SocketQueues::default();
+        for _ in 0..100 {
+            queues = server.socket_queues().unwrap();
+            if queues.receive_queue == 5 {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        assert_eq!(5, queues.receive_queue);
+    }
+
+    #[test]
+    fn should_apply_socket_options() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut stream = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+
+        let options = SocketOptions::default()
+            .with_send_buffer_size(64 * 1024)
+            .with_quick_ack(true);
+        stream.apply_socket_options(&options).unwrap();
+
+        let socket = SockRef::from(&stream);
+        // kernel doubles the requested value to allow for bookkeeping overhead
+        assert_eq!(128 * 1024, socket.send_buffer_size().unwrap());
+        assert!(socket.quickack().unwrap());
+    }
+
+    #[test]
+    fn should_report_connection_properties() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let stream = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+
+        let properties = stream.connection_properties();
+        assert_eq!(Some(listener.local_addr().unwrap()), properties.peer_addr);
+        assert_eq!(stream.local_addr().ok(), properties.local_addr);
+        assert_eq!(None, properties.tls);
+
+        #[cfg(any(feature = "tls-webpki", feature = "tls-native"))]
+        {
+            use crate::stream::tls::TlsStream;
+
+            // the handshake has not completed yet
+            let stream = TlsStream::wrap(stream, "localhost");
+            assert_eq!(properties, stream.connection_properties());
+        }
+    }
+
+    #[test]
+    fn should_apply_shard_socket_options() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut stream = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+
+        let shards = ConnectionInfo::new("127.0.0.1", 9000).shards([0]);
+        stream.apply_socket_options(&shards[0].socket_options).unwrap();
+
+        let socket = SockRef::from(&stream);
+        assert!(socket.reuse_port().unwrap());
+        assert_eq!(Some(0), socket.cpu_affinity().ok());
+    }
+
+    #[test]
+    fn should_bind_within_local_port_range() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let taken = TcpListener::bind("127.0.0.1:0").unwrap();
+        let taken_port = taken.local_addr().unwrap().port();
+
+        let connection_info = ConnectionInfo::new("127.0.0.1", listener.local_addr().unwrap().port())
+            .with_bind_addr("127.0.0.1:0".parse().unwrap())
+            .with_local_port_range(taken_port..taken_port.saturating_add(16));
+        let stream =
+            TcpStream::bind_and_connect_with_connection_info(listener.local_addr().unwrap(), &connection_info, None)
+                .unwrap();
+
+        let local_addr = SockRef::from(&stream).local_addr().unwrap().as_socket().unwrap();
+        assert_eq!(IpAddr::V4(Ipv4Addr::LOCALHOST), local_addr.ip());
+        assert!(local_addr.port() > taken_port && local_addr.port() < taken_port.saturating_add(16));
+
+        let connection_info = connection_info.with_local_port_range(taken_port..taken_port + 1);
+        let err =
+            TcpStream::bind_and_connect_with_connection_info(listener.local_addr().unwrap(), &connection_info, None)
+                .unwrap_err();
+        assert_eq!(AddrInUse, err.kind());
+    }
 }
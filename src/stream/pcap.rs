@@ -0,0 +1,601 @@
+//! Export and import of the recordings in the [pcap](https://wiki.wireshark.org/Development/LibpcapFileFormat)
+//! format, so that they can be inspected with Wireshark or `tcpdump`.
+//!
+//! The [`PcapWriter`] synthesizes the IP and TCP headers around the recorded payload (both
+//! directions are written into the single file), use [`Recorder::with_pcap`](crate::stream::record::Recorder::with_pcap)
+//! to capture the `RecordedStream`. The [`PcapReader`] accepts both pcap and pcapng captures and
+//! extracts the payload of the single TCP flow, use [`ReplayStream::from_pcap`](crate::stream::replay::ReplayStream::from_pcap)
+//! to replay it.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use std::net::TcpStream;
+//! use boomnet::stream::pcap::FiveTuple;
+//! use boomnet::stream::record::{RecordedStream, Recorder};
+//! use boomnet::stream::replay::ReplayStream;
+//!
+//! let stream = TcpStream::connect("127.0.0.1:8080").unwrap();
+//! let (local, remote) = (stream.local_addr().unwrap(), stream.peer_addr().unwrap());
+//! let stream = RecordedStream::new(stream, Recorder::with_pcap("session", local, remote).unwrap());
+//!
+//! // replay the data received from the server
+//! let replay = ReplayStream::from_pcap("session.pcap", FiveTuple::new(remote, local)).unwrap();
+//! ```
+
+use std::io;
+use std::io::ErrorKind::{InvalidData, InvalidInput, UnexpectedEof};
+use std::io::{Read, Write};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::ops::Range;
+
+use crate::stream::record::RECORDING_V2_MAGIC;
+use crate::util::current_time_nanos;
+
+const PCAP_MAGIC_MICROS: u32 = 0xa1b2c3d4;
+const PCAP_MAGIC_NANOS: u32 = 0xa1b23c4d;
+const PCAPNG_SECTION_HEADER: u32 = 0x0a0d0d0a;
+const PCAPNG_BYTE_ORDER_MAGIC: u32 = 0x1a2b3c4d;
+const PCAPNG_INTERFACE_DESCRIPTION: u32 = 1;
+const PCAPNG_ENHANCED_PACKET: u32 = 6;
+
+const LINKTYPE_NULL: u32 = 0;
+const LINKTYPE_ETHERNET: u32 = 1;
+const LINKTYPE_RAW: u32 = 101;
+const LINKTYPE_LINUX_SLL: u32 = 113;
+const LINKTYPE_IPV4: u32 = 228;
+const LINKTYPE_IPV6: u32 = 229;
+const LINKTYPE_LINUX_SLL2: u32 = 276;
+
+const TCP_FIN: u8 = 0x01;
+const TCP_SYN: u8 = 0x02;
+const TCP_PSH: u8 = 0x08;
+const TCP_ACK: u8 = 0x10;
+const TCP_HEADER_LEN: usize = 20;
+const IP_PROTOCOL_TCP: u8 = 6;
+
+// keeps the IPv4 total length within u16
+const MAX_SEGMENT_LEN: usize = 65_000;
+
+/// TCP connection identified by the source and destination address, the protocol is always TCP.
+/// Only the segments sent from `src` to `dst` match the tuple.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct FiveTuple {
+    pub src: SocketAddr,
+    pub dst: SocketAddr,
+}
+
+impl FiveTuple {
+    pub const fn new(src: SocketAddr, dst: SocketAddr) -> FiveTuple {
+        Self { src, dst }
+    }
+
+    /// Tuple matching the opposite direction of the same connection.
+    pub const fn reversed(&self) -> FiveTuple {
+        Self {
+            src: self.dst,
+            dst: self.src,
+        }
+    }
+}
+
+/// Writes the connection payload as the nanosecond resolution pcap file with the raw IP link
+/// type. The connection starts with the synthetic three-way handshake so that the tools can follow
+/// the TCP stream.
+pub struct PcapWriter<W> {
+    inner: W,
+    local: SocketAddr,
+    remote: SocketAddr,
+    // next sequence number of the (local, remote) side
+    seq: [u32; 2],
+    packet: Vec<u8>,
+}
+
+impl<W: Write> PcapWriter<W> {
+    /// Creates writer for the connection between the `local` and `remote` address, both addresses
+    /// must be of the same IP version.
+    pub fn new(mut inner: W, local: SocketAddr, remote: SocketAddr) -> io::Result<PcapWriter<W>> {
+        if local.is_ipv4() != remote.is_ipv4() {
+            return Err(io::Error::new(InvalidInput, "local and remote address must be of the same IP version"));
+        }
+        inner.write_all(&PCAP_MAGIC_NANOS.to_le_bytes())?;
+        inner.write_all(&2u16.to_le_bytes())?;
+        inner.write_all(&4u16.to_le_bytes())?;
+        inner.write_all(&0i32.to_le_bytes())?;
+        inner.write_all(&0u32.to_le_bytes())?;
+        inner.write_all(&(u16::MAX as u32 * 4).to_le_bytes())?;
+        inner.write_all(&LINKTYPE_RAW.to_le_bytes())?;
+        let mut writer = Self {
+            inner,
+            local,
+            remote,
+            seq: [0, 0],
+            packet: Vec::with_capacity(1024),
+        };
+        let ts = current_time_nanos();
+        writer.write_segment(ts, true, TCP_SYN, &[])?;
+        writer.write_segment(ts, false, TCP_SYN | TCP_ACK, &[])?;
+        writer.write_segment(ts, true, TCP_ACK, &[])?;
+        Ok(writer)
+    }
+
+    /// Writes data received from the remote side.
+    pub fn write_inbound(&mut self, ts: u64, payload: &[u8]) -> io::Result<()> {
+        for segment in payload.chunks(MAX_SEGMENT_LEN) {
+            self.write_segment(ts, false, TCP_PSH | TCP_ACK, segment)?;
+        }
+        Ok(())
+    }
+
+    /// Writes data sent to the remote side.
+    pub fn write_outbound(&mut self, ts: u64, payload: &[u8]) -> io::Result<()> {
+        for segment in payload.chunks(MAX_SEGMENT_LEN) {
+            self.write_segment(ts, true, TCP_PSH | TCP_ACK, segment)?;
+        }
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+
+    fn write_segment(&mut self, ts: u64, outbound: bool, flags: u8, payload: &[u8]) -> io::Result<()> {
+        let (src, dst, side) = match outbound {
+            true => (self.local, self.remote, 0),
+            false => (self.remote, self.local, 1),
+        };
+        let seq = self.seq[side];
+        let ack = if flags & TCP_ACK != 0 { self.seq[1 - side] } else { 0 };
+        let tcp_len = TCP_HEADER_LEN + payload.len();
+
+        let packet = &mut self.packet;
+        packet.clear();
+        let mut pseudo_sum = 0;
+        match (src.ip(), dst.ip()) {
+            (IpAddr::V4(src_ip), IpAddr::V4(dst_ip)) => {
+                packet.extend_from_slice(&[0x45, 0]);
+                packet.extend_from_slice(&((20 + tcp_len) as u16).to_be_bytes());
+                // identification, don't fragment, ttl, protocol and checksum
+                packet.extend_from_slice(&[0, 0, 0x40, 0, 64, IP_PROTOCOL_TCP, 0, 0]);
+                packet.extend_from_slice(&src_ip.octets());
+                packet.extend_from_slice(&dst_ip.octets());
+                let checksum = fold_checksum(sum_words(0, packet));
+                packet[10..12].copy_from_slice(&checksum.to_be_bytes());
+                pseudo_sum = sum_words(pseudo_sum, &packet[12..20]);
+                pseudo_sum += IP_PROTOCOL_TCP as u32 + tcp_len as u32;
+            }
+            (IpAddr::V6(src_ip), IpAddr::V6(dst_ip)) => {
+                packet.extend_from_slice(&[0x60, 0, 0, 0]);
+                packet.extend_from_slice(&(tcp_len as u16).to_be_bytes());
+                packet.extend_from_slice(&[IP_PROTOCOL_TCP, 64]);
+                packet.extend_from_slice(&src_ip.octets());
+                packet.extend_from_slice(&dst_ip.octets());
+                pseudo_sum = sum_words(pseudo_sum, &packet[8..40]);
+                pseudo_sum += IP_PROTOCOL_TCP as u32 + tcp_len as u32;
+            }
+            _ => unreachable!("address versions are validated on creation"),
+        }
+
+        let tcp_start = packet.len();
+        packet.extend_from_slice(&src.port().to_be_bytes());
+        packet.extend_from_slice(&dst.port().to_be_bytes());
+        packet.extend_from_slice(&seq.to_be_bytes());
+        packet.extend_from_slice(&ack.to_be_bytes());
+        packet.extend_from_slice(&[0x50, flags, 0xff, 0xff, 0, 0, 0, 0]);
+        packet.extend_from_slice(payload);
+        let checksum = fold_checksum(sum_words(pseudo_sum, &packet[tcp_start..]));
+        packet[tcp_start + 16..tcp_start + 18].copy_from_slice(&checksum.to_be_bytes());
+
+        self.inner.write_all(&((ts / 1_000_000_000) as u32).to_le_bytes())?;
+        self.inner.write_all(&((ts % 1_000_000_000) as u32).to_le_bytes())?;
+        self.inner.write_all(&(packet.len() as u32).to_le_bytes())?;
+        self.inner.write_all(&(packet.len() as u32).to_le_bytes())?;
+        self.inner.write_all(packet)?;
+
+        let flag_len = (flags & (TCP_SYN | TCP_FIN) != 0) as u32;
+        self.seq[side] = seq.wrapping_add(payload.len() as u32 + flag_len);
+        Ok(())
+    }
+}
+
+fn sum_words(mut sum: u32, data: &[u8]) -> u32 {
+    let mut words = data.chunks_exact(2);
+    for word in words.by_ref() {
+        sum += u16::from_be_bytes([word[0], word[1]]) as u32;
+    }
+    if let [last] = words.remainder() {
+        sum += (*last as u32) << 8;
+    }
+    sum
+}
+
+fn fold_checksum(mut sum: u32) -> u16 {
+    while sum > 0xffff {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
+#[derive(Debug)]
+enum Format {
+    Pcap {
+        big_endian: bool,
+        nanos: bool,
+        link_type: u32,
+    },
+    PcapNg {
+        big_endian: bool,
+        // (link type, timestamp ticks per second) of each interface
+        interfaces: Vec<(u32, u64)>,
+    },
+}
+
+/// Extracts the payload of the single direction of the TCP connection from the pcap or pcapng
+/// capture. The payload is produced in the timestamped recording format, so that the reader can
+/// be consumed by the `ReplayStream`. Retransmitted segments are skipped, IP fragments are not
+/// supported.
+pub struct PcapReader<R> {
+    inner: R,
+    format: Format,
+    flow: FiveTuple,
+    next_seq: Option<u32>,
+    packet: Vec<u8>,
+    output: Vec<u8>,
+    output_offset: usize,
+}
+
+impl<R: Read> PcapReader<R> {
+    /// Creates reader of the capture, only the segments matching the `flow` are extracted.
+    pub fn new(mut inner: R, flow: FiveTuple) -> io::Result<PcapReader<R>> {
+        let mut magic = [0u8; 4];
+        inner.read_exact(&mut magic)?;
+        let format = match (u32::from_le_bytes(magic), u32::from_be_bytes(magic)) {
+            (PCAPNG_SECTION_HEADER, _) => PcapNg {
+                big_endian: read_section_header(&mut inner)?,
+                interfaces: Vec::new(),
+            },
+            (magic_le, magic_be) => {
+                let (big_endian, nanos) = match (magic_le, magic_be) {
+                    (PCAP_MAGIC_MICROS, _) => (false, false),
+                    (PCAP_MAGIC_NANOS, _) => (false, true),
+                    (_, PCAP_MAGIC_MICROS) => (true, false),
+                    (_, PCAP_MAGIC_NANOS) => (true, true),
+                    _ => return Err(io::Error::new(InvalidData, "not a pcap or pcapng file")),
+                };
+                let mut header = [0u8; 20];
+                inner.read_exact(&mut header)?;
+                Pcap {
+                    big_endian,
+                    nanos,
+                    link_type: read_u32(&header[16..20], big_endian) & 0xffff,
+                }
+            }
+        };
+        let mut output = Vec::with_capacity(1024);
+        output.extend_from_slice(RECORDING_V2_MAGIC);
+        Ok(Self {
+            inner,
+            format,
+            flow,
+            next_seq: None,
+            packet: Vec::with_capacity(1024),
+            output,
+            output_offset: 0,
+        })
+    }
+
+    /// Reads the next packet into the `packet` buffer, returns the link type and the timestamp or
+    /// `None` at the end of the capture.
+    fn next_packet(&mut self) -> io::Result<Option<(u32, u64)>> {
+        match &mut self.format {
+            Pcap {
+                big_endian,
+                nanos,
+                link_type,
+            } => {
+                let mut header = [0u8; 16];
+                if !read_or_eof(&mut self.inner, &mut header)? {
+                    return Ok(None);
+                }
+                let seconds = read_u32(&header[0..4], *big_endian) as u64;
+                let fraction = read_u32(&header[4..8], *big_endian) as u64;
+                let ts = seconds * 1_000_000_000 + if *nanos { fraction } else { fraction * 1_000 };
+                self.packet.resize(read_u32(&header[8..12], *big_endian) as usize, 0);
+                self.inner.read_exact(&mut self.packet)?;
+                Ok(Some((*link_type, ts)))
+            }
+            PcapNg { big_endian, interfaces } => loop {
+                let mut header = [0u8; 8];
+                if !read_or_eof(&mut self.inner, &mut header)? {
+                    return Ok(None);
+                }
+                let block_type = read_u32(&header[0..4], *big_endian);
+                if block_type == PCAPNG_SECTION_HEADER {
+                    // the length is read again once the byte order of the new section is known
+                    *big_endian = read_section_header_after(&mut self.inner, &header[4..8])?;
+                    interfaces.clear();
+                    continue;
+                }
+                let block_len = read_u32(&header[4..8], *big_endian) as usize;
+                if block_len < 12 {
+                    return Err(io::Error::new(InvalidData, "invalid pcapng block length"));
+                }
+                self.packet.resize(block_len - 8, 0);
+                self.inner.read_exact(&mut self.packet)?;
+                let body = &self.packet[..block_len - 12];
+                match block_type {
+                    PCAPNG_INTERFACE_DESCRIPTION if body.len() >= 8 => {
+                        let link_type = read_u16(&body[0..2], *big_endian) as u32;
+                        interfaces.push((link_type, ts_resolution(&body[8..], *big_endian)));
+                    }
+                    PCAPNG_ENHANCED_PACKET if body.len() >= 20 => {
+                        let interface = read_u32(&body[0..4], *big_endian) as usize;
+                        let &(link_type, ticks_per_sec) = interfaces
+                            .get(interface)
+                            .ok_or_else(|| io::Error::new(InvalidData, "unknown pcapng interface"))?;
+                        let ticks = (read_u32(&body[4..8], *big_endian) as u64) << 32
+                            | read_u32(&body[8..12], *big_endian) as u64;
+                        let ts = (ticks as u128 * 1_000_000_000 / ticks_per_sec as u128) as u64;
+                        let captured = (read_u32(&body[12..16], *big_endian) as usize).min(body.len() - 20);
+                        self.packet.copy_within(20..20 + captured, 0);
+                        self.packet.truncate(captured);
+                        return Ok(Some((link_type, ts)));
+                    }
+                    _ => {}
+                }
+            },
+        }
+    }
+
+    /// Appends the payload of the next matching segment to the output, returns `false` at the
+    /// end of the capture.
+    fn next_segment(&mut self) -> io::Result<bool> {
+        while let Some((link_type, ts)) = self.next_packet()? {
+            let Some((src, dst, seq, flags, payload)) = parse_tcp(link_type, &self.packet) else {
+                continue;
+            };
+            if src != self.flow.src || dst != self.flow.dst {
+                continue;
+            }
+            let mut payload = payload;
+            if flags & TCP_SYN != 0 {
+                self.next_seq = Some(seq.wrapping_add(1));
+            }
+            if payload.is_empty() {
+                continue;
+            }
+            let end = seq.wrapping_add(payload.len() as u32);
+            if let Some(next_seq) = self.next_seq {
+                // retransmission of the data that has already been delivered
+                if end.wrapping_sub(next_seq) as i32 <= 0 {
+                    continue;
+                }
+                let overlap = next_seq.wrapping_sub(seq) as i32;
+                if overlap > 0 {
+                    payload.start += overlap as usize;
+                }
+            }
+            self.next_seq = Some(end);
+            self.output.extend_from_slice(&ts.to_le_bytes());
+            self.output.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+            self.output.extend_from_slice(&self.packet[payload]);
+            return Ok(true);
+        }
+        Ok(false)
+    }
+}
+
+use Format::{Pcap, PcapNg};
+
+impl<R: Read> Read for PcapReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.output_offset == self.output.len() {
+            self.output.clear();
+            self.output_offset = 0;
+            if !self.next_segment()? {
+                return Ok(0);
+            }
+        }
+        let len = buf.len().min(self.output.len() - self.output_offset);
+        buf[..len].copy_from_slice(&self.output[self.output_offset..self.output_offset + len]);
+        self.output_offset += len;
+        Ok(len)
+    }
+}
+
+/// Parses the TCP segment, returns the (source, destination, sequence number, flags, payload range).
+fn parse_tcp(link_type: u32, packet: &[u8]) -> Option<(SocketAddr, SocketAddr, u32, u8, Range<usize>)> {
+    let ip_start = match link_type {
+        LINKTYPE_RAW | LINKTYPE_IPV4 | LINKTYPE_IPV6 => 0,
+        LINKTYPE_NULL => 4,
+        LINKTYPE_LINUX_SLL => 16,
+        LINKTYPE_LINUX_SLL2 => 20,
+        LINKTYPE_ETHERNET => match packet.get(12..14)? {
+            // 802.1Q and 802.1ad tags
+            [0x81, 0x00] | [0x88, 0xa8] => 18,
+            _ => 14,
+        },
+        _ => return None,
+    };
+    let ip = packet.get(ip_start..)?;
+    let (src_ip, dst_ip, tcp) = match ip.first()? >> 4 {
+        4 => {
+            let header_len = (ip[0] & 0x0f) as usize * 4;
+            let total_len = u16::from_be_bytes(ip.get(2..4)?.try_into().ok()?) as usize;
+            let fragmented = u16::from_be_bytes(ip.get(6..8)?.try_into().ok()?) & 0x3fff != 0;
+            if *ip.get(9)? != IP_PROTOCOL_TCP || fragmented {
+                return None;
+            }
+            let src: [u8; 4] = ip.get(12..16)?.try_into().ok()?;
+            let dst: [u8; 4] = ip.get(16..20)?.try_into().ok()?;
+            // the total length excludes the ethernet padding
+            let tcp = ip_start + header_len..ip_start + total_len.min(ip.len());
+            (IpAddr::V4(Ipv4Addr::from(src)), IpAddr::V4(Ipv4Addr::from(dst)), tcp)
+        }
+        6 => {
+            if *ip.get(6)? != IP_PROTOCOL_TCP {
+                return None;
+            }
+            let payload_len = u16::from_be_bytes(ip.get(4..6)?.try_into().ok()?) as usize;
+            let src: [u8; 16] = ip.get(8..24)?.try_into().ok()?;
+            let dst: [u8; 16] = ip.get(24..40)?.try_into().ok()?;
+            let tcp = ip_start + 40..ip_start + (40 + payload_len).min(ip.len());
+            (IpAddr::V6(Ipv6Addr::from(src)), IpAddr::V6(Ipv6Addr::from(dst)), tcp)
+        }
+        _ => return None,
+    };
+    let header = packet.get(tcp.clone())?;
+    if header.len() < TCP_HEADER_LEN {
+        return None;
+    }
+    let src_port = u16::from_be_bytes([header[0], header[1]]);
+    let dst_port = u16::from_be_bytes([header[2], header[3]]);
+    let seq = u32::from_be_bytes(header[4..8].try_into().ok()?);
+    let data_offset = (header[12] >> 4) as usize * 4;
+    let payload = (tcp.start + data_offset).min(tcp.end)..tcp.end;
+    Some((SocketAddr::new(src_ip, src_port), SocketAddr::new(dst_ip, dst_port), seq, header[13], payload))
+}
+
+/// Reads the section header block that follows the block type, returns `true` if the section is
+/// big endian.
+fn read_section_header<R: Read>(inner: &mut R) -> io::Result<bool> {
+    let mut block_len = [0u8; 4];
+    inner.read_exact(&mut block_len)?;
+    read_section_header_after(inner, &block_len)
+}
+
+fn read_section_header_after<R: Read>(inner: &mut R, block_len: &[u8]) -> io::Result<bool> {
+    let mut byte_order = [0u8; 4];
+    inner.read_exact(&mut byte_order)?;
+    let big_endian = match u32::from_le_bytes(byte_order) {
+        PCAPNG_BYTE_ORDER_MAGIC => false,
+        _ if u32::from_be_bytes(byte_order) == PCAPNG_BYTE_ORDER_MAGIC => true,
+        _ => return Err(io::Error::new(InvalidData, "invalid pcapng byte order magic")),
+    };
+    let block_len = read_u32(block_len, big_endian) as usize;
+    if block_len < 16 {
+        return Err(io::Error::new(InvalidData, "invalid pcapng block length"));
+    }
+    // skip the version, section length, options and the trailing length
+    io::copy(&mut inner.take((block_len - 12) as u64), &mut io::sink())?;
+    Ok(big_endian)
+}
+
+/// Timestamp ticks per second from the `if_tsresol` option, microseconds by default.
+fn ts_resolution(mut options: &[u8], big_endian: bool) -> u64 {
+    while options.len() >= 4 {
+        let code = read_u16(&options[0..2], big_endian);
+        let len = read_u16(&options[2..4], big_endian) as usize;
+        match code {
+            0 => break,
+            9 if len == 1 && options.len() > 4 => {
+                let resolution = options[4];
+                return match resolution & 0x80 {
+                    0 => 10u64.saturating_pow(resolution as u32),
+                    _ => 2u64.saturating_pow((resolution & 0x7f) as u32),
+                };
+            }
+            _ => {}
+        }
+        options = options.get(4 + len.next_multiple_of(4)..).unwrap_or_default();
+    }
+    1_000_000
+}
+
+fn read_or_eof<R: Read>(inner: &mut R, buf: &mut [u8]) -> io::Result<bool> {
+    match inner.read_exact(buf) {
+        Ok(()) => Ok(true),
+        Err(err) if err.kind() == UnexpectedEof => Ok(false),
+        Err(err) => Err(err),
+    }
+}
+
+fn read_u32(bytes: &[u8], big_endian: bool) -> u32 {
+    let bytes = bytes.try_into().unwrap();
+    match big_endian {
+        true => u32::from_be_bytes(bytes),
+        false => u32::from_le_bytes(bytes),
+    }
+}
+
+fn read_u16(bytes: &[u8], big_endian: bool) -> u16 {
+    let bytes = bytes.try_into().unwrap();
+    match big_endian {
+        true => u16::from_be_bytes(bytes),
+        false => u16::from_le_bytes(bytes),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    #[test]
+    fn should_extract_flow_from_capture() {
+        let local: SocketAddr = "10.0.0.1:50000".parse().unwrap();
+        let remote: SocketAddr = "10.0.0.2:443".parse().unwrap();
+        let mut writer = PcapWriter::new(Vec::new(), local, remote).unwrap();
+        writer.write_outbound(1_000, b"request").unwrap();
+        writer.write_inbound(2_000, b"hello").unwrap();
+        writer.write_inbound(3_000, b"world").unwrap();
+        let capture = writer.into_inner();
+
+        // the synthetic packets carry valid checksums
+        let first = &capture[24 + 16..24 + 16 + 40];
+        assert_eq!(0, fold_checksum(sum_words(0, &first[..20])));
+
+        let mut inbound = Vec::new();
+        PcapReader::new(Cursor::new(capture.clone()), FiveTuple::new(remote, local))
+            .unwrap()
+            .read_to_end(&mut inbound)
+            .unwrap();
+        let mut expected = RECORDING_V2_MAGIC.to_vec();
+        for (ts, payload) in [(2_000u64, b"hello"), (3_000, b"world")] {
+            expected.extend_from_slice(&ts.to_le_bytes());
+            expected.extend_from_slice(&5u32.to_le_bytes());
+            expected.extend_from_slice(payload);
+        }
+        assert_eq!(expected, inbound);
+
+        // the same packets wrapped in pcapng blocks with the nanosecond resolution
+        let mut pcapng = Vec::new();
+        let mut block = |block_type: u32, body: &[u8]| {
+            let len = 12 + body.len().next_multiple_of(4) as u32;
+            pcapng.extend_from_slice(&block_type.to_le_bytes());
+            pcapng.extend_from_slice(&len.to_le_bytes());
+            pcapng.extend_from_slice(body);
+            pcapng.resize(pcapng.len() + body.len().next_multiple_of(4) - body.len(), 0);
+            pcapng.extend_from_slice(&len.to_le_bytes());
+        };
+        block(PCAPNG_SECTION_HEADER, &[&PCAPNG_BYTE_ORDER_MAGIC.to_le_bytes()[..], &[1, 0, 0, 0], &[0xff; 8]].concat());
+        block(
+            PCAPNG_INTERFACE_DESCRIPTION,
+            &[&[101, 0, 0, 0, 0, 0, 0, 0][..], &[9, 0, 1, 0, 9, 0, 0, 0], &[0; 4]].concat(),
+        );
+        let mut offset = 24;
+        while offset < capture.len() {
+            let len = u32::from_le_bytes(capture[offset + 8..offset + 12].try_into().unwrap()) as usize;
+            let ts = u32::from_le_bytes(capture[offset + 4..offset + 8].try_into().unwrap()) as u64;
+            let mut body = vec![0, 0, 0, 0];
+            body.extend_from_slice(&((ts >> 32) as u32).to_le_bytes());
+            body.extend_from_slice(&(ts as u32).to_le_bytes());
+            body.extend_from_slice(&(len as u32).to_le_bytes());
+            body.extend_from_slice(&(len as u32).to_le_bytes());
+            body.extend_from_slice(&capture[offset + 16..offset + 16 + len]);
+            block(PCAPNG_ENHANCED_PACKET, &body);
+            offset += 16 + len;
+        }
+        let mut outbound = Vec::new();
+        PcapReader::new(Cursor::new(pcapng), FiveTuple::new(local, remote))
+            .unwrap()
+            .read_to_end(&mut outbound)
+            .unwrap();
+        assert_eq!(b"request", &outbound[RECORDING_V2_MAGIC.len() + 12..]);
+    }
+}
@@ -0,0 +1,827 @@
+//! Streams that tunnel the connection through a proxy server.
+//!
+//! Both [`HttpProxyStream`] and [`Socks5Stream`] wrap a stream that is connected to the proxy
+//! and negotiate the tunnel to the target host in a non-blocking manner. Any data written before
+//! the tunnel has been established is buffered and sent once the proxy has accepted the request.
+//!
+//! # Examples
+//!
+//! Connect to the websocket server through HTTP `CONNECT` proxy.
+//!
+//! ```no_run
+//! use std::net::TcpStream;
+//! use boomnet::stream::proxy::IntoHttpProxyStream;
+//! use boomnet::stream::tls::IntoTlsStream;
+//! use boomnet::stream::BindAndConnect;
+//! use boomnet::ws::IntoWebsocket;
+//!
+//! let mut ws = TcpStream::bind_and_connect("proxy.internal:3128", None, None).unwrap()
+//!  .into_http_proxy_stream("stream.binance.com", 9443)
+//!  .with_basic_auth("user", "password")
+//!  .into_tls_stream("stream.binance.com")
+//!  .into_websocket("wss://stream.binance.com:9443/ws");
+//! ```
+//!
+//! Connect to the websocket server through SOCKS5 proxy.
+//!
+//! ```no_run
+//! use std::net::TcpStream;
+//! use boomnet::stream::proxy::IntoSocks5Stream;
+//! use boomnet::stream::tls::IntoTlsStream;
+//! use boomnet::stream::BindAndConnect;
+//! use boomnet::ws::IntoWebsocket;
+//!
+//! let mut ws = TcpStream::bind_and_connect("proxy.internal:1080", None, None).unwrap()
+//!  .into_socks5_stream("stream.binance.com", 9443)
+//!  .into_tls_stream("stream.binance.com")
+//!  .into_websocket("wss://stream.binance.com:9443/ws");
+//! ```
+
+use std::io;
+use std::io::ErrorKind::WouldBlock;
+use std::io::{Read, Write};
+use std::net::IpAddr;
+
+use base64::engine::general_purpose;
+use base64::Engine;
+#[cfg(feature = "mio")]
+use mio::{event::Source, Interest, Registry, Token};
+
+use crate::buffer::ReadBuffer;
+use crate::endpoint::ConnectionInfo;
+use crate::select::Selectable;
+use crate::stream::{ConnectionInfoProvider, SocketOptions, SocketQueues};
+
+const SOCKS5_VERSION: u8 = 0x05;
+const SOCKS5_AUTH_VERSION: u8 = 0x01;
+const SOCKS5_NO_AUTH: u8 = 0x00;
+const SOCKS5_USERNAME_PASSWORD: u8 = 0x02;
+const SOCKS5_NO_ACCEPTABLE_METHODS: u8 = 0xFF;
+const SOCKS5_CMD_CONNECT: u8 = 0x01;
+const SOCKS5_ATYP_IPV4: u8 = 0x01;
+const SOCKS5_ATYP_DOMAIN: u8 = 0x03;
+const SOCKS5_ATYP_IPV6: u8 = 0x04;
+const SOCKS5_SUCCEEDED: u8 = 0x00;
+
+/// Tunnels the connection through HTTP proxy using the `CONNECT` method, optionally
+/// authenticating with the proxy using basic auth.
+pub struct HttpProxyStream<S> {
+    inner: S,
+    target: ConnectionInfo,
+    credentials: Option<(String, String)>,
+    state: HttpProxyState,
+    tunnel: Tunnel,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum HttpProxyState {
+    NotStarted,
+    AwaitingResponse,
+    Established,
+}
+
+impl<S> HttpProxyStream<S> {
+    /// Wraps stream connected to the proxy and requests tunnel to the target `host` and `port`.
+    pub fn new(stream: S, host: &str, port: u16) -> HttpProxyStream<S> {
+        Self {
+            inner: stream,
+            target: ConnectionInfo::new(host, port),
+            credentials: None,
+            state: HttpProxyState::NotStarted,
+            tunnel: Tunnel::new(),
+        }
+    }
+
+    /// Authenticate with the proxy using `Proxy-Authorization: Basic` header.
+    pub fn with_basic_auth(self, username: &str, password: &str) -> HttpProxyStream<S> {
+        Self {
+            credentials: Some((username.to_owned(), password.to_owned())),
+            ..self
+        }
+    }
+
+    /// Checks if the proxy has accepted the tunnel request.
+    pub fn tunnel_established(&self) -> bool {
+        self.state == HttpProxyState::Established
+    }
+
+    fn connect_request(&self) -> Vec<u8> {
+        let authority = authority(&self.target);
+        let mut request = format!("CONNECT {authority} HTTP/1.1\r\nHost: {authority}\r\n");
+        if let Some((username, password)) = &self.credentials {
+            let token = general_purpose::STANDARD.encode(format!("{username}:{password}"));
+            request.push_str(&format!("Proxy-Authorization: Basic {token}\r\n"));
+        }
+        request.push_str("\r\n");
+        request.into_bytes()
+    }
+}
+
+impl<S: Read + Write> HttpProxyStream<S> {
+    fn negotiate(&mut self) -> io::Result<bool> {
+        loop {
+            match self.state {
+                HttpProxyState::NotStarted => {
+                    let request = self.connect_request();
+                    self.tunnel.queue_request(request);
+                    self.state = HttpProxyState::AwaitingResponse;
+                }
+                HttpProxyState::AwaitingResponse => {
+                    if !self.tunnel.send_request(&mut self.inner)? {
+                        return Ok(false);
+                    }
+                    if !self.tunnel.read_until_end_of_headers(&mut self.inner)? {
+                        return Ok(false);
+                    }
+                    let mut headers = [httparse::EMPTY_HEADER; 64];
+                    let mut response = httparse::Response::new(&mut headers);
+                    response.parse(self.tunnel.buffer.view()).map_err(io::Error::other)?;
+                    match response.code {
+                        Some(200..=299) => {}
+                        Some(code) => {
+                            return Err(io::Error::other(format!(
+                                "proxy refused to establish tunnel: {} {}",
+                                code,
+                                response.reason.unwrap_or_default()
+                            )))
+                        }
+                        None => return Err(io::Error::other("invalid proxy response")),
+                    }
+                    self.tunnel.complete();
+                    self.state = HttpProxyState::Established;
+                }
+                HttpProxyState::Established => return Ok(true),
+            }
+        }
+    }
+}
+
+impl<S: Read + Write> Read for HttpProxyStream<S> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if !self.tunnel_established() && !self.negotiate()? {
+            return Err(io::Error::from(WouldBlock));
+        }
+        self.tunnel.drain_outbound(&mut self.inner)?;
+        self.inner.read(buf)
+    }
+}
+
+impl<S: Read + Write> Write for HttpProxyStream<S> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if !self.tunnel_established() && !self.negotiate()? {
+            return Ok(self.tunnel.buffer_outbound(buf));
+        }
+        self.tunnel.write(&mut self.inner, buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if !self.tunnel_established() && !self.negotiate()? {
+            return Ok(());
+        }
+        self.tunnel.drain_outbound(&mut self.inner)?;
+        self.inner.flush()
+    }
+}
+
+impl<S: Selectable> Selectable for HttpProxyStream<S> {
+    fn connected(&mut self) -> io::Result<bool> {
+        self.inner.connected()
+    }
+
+    fn make_writable(&mut self) {
+        self.inner.make_writable()
+    }
+
+    fn make_readable(&mut self) {
+        self.inner.make_readable()
+    }
+
+    fn make_unreadable(&mut self) {
+        self.inner.make_unreadable()
+    }
+
+    fn socket_queues(&self) -> Option<SocketQueues> {
+        self.inner.socket_queues()
+    }
+
+    fn rx_timestamp_ns(&self) -> Option<u64> {
+        self.inner.rx_timestamp_ns()
+    }
+
+    #[cfg(feature = "stats")]
+    fn io_counters(&self) -> Option<crate::stream::IoCounters> {
+        self.inner.io_counters()
+    }
+
+    fn apply_socket_options(&mut self, options: &SocketOptions) -> io::Result<()> {
+        self.inner.apply_socket_options(options)
+    }
+
+    fn has_pending_writes(&self) -> bool {
+        self.inner.has_pending_writes()
+    }
+
+    fn flush_pending_writes(&mut self) -> io::Result<()> {
+        self.inner.flush_pending_writes()
+    }
+
+    fn peer_closed(&self) -> bool {
+        self.inner.peer_closed()
+    }
+
+    fn write_closed(&self) -> bool {
+        self.inner.write_closed()
+    }
+
+    fn make_write_closed(&mut self) {
+        self.inner.make_write_closed()
+    }
+
+    fn take_socket_error(&mut self) -> io::Result<Option<io::Error>> {
+        self.inner.take_socket_error()
+    }
+
+    fn connect_error(&self) -> Option<io::Error> {
+        self.inner.connect_error()
+    }
+}
+
+impl<S> ConnectionInfoProvider for HttpProxyStream<S> {
+    fn connection_info(&self) -> &ConnectionInfo {
+        &self.target
+    }
+}
+
+#[cfg(feature = "mio")]
+impl<S: Source> Source for HttpProxyStream<S> {
+    fn register(&mut self, registry: &Registry, token: Token, interests: Interest) -> io::Result<()> {
+        registry.register(&mut self.inner, token, interests)
+    }
+
+    fn reregister(&mut self, registry: &Registry, token: Token, interests: Interest) -> io::Result<()> {
+        registry.reregister(&mut self.inner, token, interests)
+    }
+
+    fn deregister(&mut self, registry: &Registry) -> io::Result<()> {
+        registry.deregister(&mut self.inner)
+    }
+}
+
+/// Tunnels the connection through SOCKS5 proxy using the `CONNECT` command, optionally
+/// authenticating with the proxy using username and password (RFC 1929).
+pub struct Socks5Stream<S> {
+    inner: S,
+    target: ConnectionInfo,
+    credentials: Option<(String, String)>,
+    state: Socks5State,
+    tunnel: Tunnel,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Socks5State {
+    NotStarted,
+    AwaitingMethod,
+    AwaitingAuth,
+    AwaitingReply,
+    Established,
+}
+
+impl<S> Socks5Stream<S> {
+    /// Wraps stream connected to the proxy and requests tunnel to the target `host` and `port`.
+    /// If the `host` is not an ip address it will be resolved by the proxy.
+    pub fn new(stream: S, host: &str, port: u16) -> Socks5Stream<S> {
+        Self {
+            inner: stream,
+            target: ConnectionInfo::new(host, port),
+            credentials: None,
+            state: Socks5State::NotStarted,
+            tunnel: Tunnel::new(),
+        }
+    }
+
+    /// Authenticate with the proxy using username and password.
+    pub fn with_credentials(self, username: &str, password: &str) -> Socks5Stream<S> {
+        Self {
+            credentials: Some((username.to_owned(), password.to_owned())),
+            ..self
+        }
+    }
+
+    /// Checks if the proxy has accepted the tunnel request.
+    pub fn tunnel_established(&self) -> bool {
+        self.state == Socks5State::Established
+    }
+
+    fn greeting(&self) -> Vec<u8> {
+        match self.credentials {
+            Some(_) => vec![SOCKS5_VERSION, 2, SOCKS5_NO_AUTH, SOCKS5_USERNAME_PASSWORD],
+            None => vec![SOCKS5_VERSION, 1, SOCKS5_NO_AUTH],
+        }
+    }
+
+    fn auth_request(&self) -> io::Result<Vec<u8>> {
+        let (username, password) = self
+            .credentials
+            .as_ref()
+            .ok_or_else(|| io::Error::other("proxy requested authentication but no credentials provided"))?;
+        if username.len() > u8::MAX as usize || password.len() > u8::MAX as usize {
+            return Err(io::Error::other("proxy credentials too long"));
+        }
+        let mut request = Vec::with_capacity(3 + username.len() + password.len());
+        request.push(SOCKS5_AUTH_VERSION);
+        request.push(username.len() as u8);
+        request.extend_from_slice(username.as_bytes());
+        request.push(password.len() as u8);
+        request.extend_from_slice(password.as_bytes());
+        Ok(request)
+    }
+
+    fn connect_request(&self) -> io::Result<Vec<u8>> {
+        let mut request = vec![SOCKS5_VERSION, SOCKS5_CMD_CONNECT, 0x00];
+        match self.target.host.parse::<IpAddr>() {
+            Ok(IpAddr::V4(ip)) => {
+                request.push(SOCKS5_ATYP_IPV4);
+                request.extend_from_slice(&ip.octets());
+            }
+            Ok(IpAddr::V6(ip)) => {
+                request.push(SOCKS5_ATYP_IPV6);
+                request.extend_from_slice(&ip.octets());
+            }
+            Err(_) => {
+                let host = self.target.host.as_bytes();
+                if host.len() > u8::MAX as usize {
+                    return Err(io::Error::other("target host name too long"));
+                }
+                request.push(SOCKS5_ATYP_DOMAIN);
+                request.push(host.len() as u8);
+                request.extend_from_slice(host);
+            }
+        }
+        request.extend_from_slice(&self.target.port.to_be_bytes());
+        Ok(request)
+    }
+}
+
+impl<S: Read + Write> Socks5Stream<S> {
+    fn negotiate(&mut self) -> io::Result<bool> {
+        loop {
+            match self.state {
+                Socks5State::NotStarted => {
+                    let greeting = self.greeting();
+                    self.tunnel.queue_request(greeting);
+                    self.state = Socks5State::AwaitingMethod;
+                }
+                Socks5State::AwaitingMethod => {
+                    if !self.tunnel.send_request(&mut self.inner)? || !self.tunnel.read_exact(&mut self.inner, 2)? {
+                        return Ok(false);
+                    }
+                    let reply = self.tunnel.buffer.consume_next(2);
+                    if reply[0] != SOCKS5_VERSION {
+                        return Err(io::Error::other("invalid socks version in proxy reply"));
+                    }
+                    match reply[1] {
+                        SOCKS5_NO_AUTH => {
+                            let request = self.connect_request()?;
+                            self.tunnel.queue_request(request);
+                            self.state = Socks5State::AwaitingReply;
+                        }
+                        SOCKS5_USERNAME_PASSWORD => {
+                            let request = self.auth_request()?;
+                            self.tunnel.queue_request(request);
+                            self.state = Socks5State::AwaitingAuth;
+                        }
+                        SOCKS5_NO_ACCEPTABLE_METHODS => {
+                            return Err(io::Error::other("proxy did not accept any authentication method"))
+                        }
+                        method => return Err(io::Error::other(format!("unsupported proxy auth method: {method}"))),
+                    }
+                }
+                Socks5State::AwaitingAuth => {
+                    if !self.tunnel.send_request(&mut self.inner)? || !self.tunnel.read_exact(&mut self.inner, 2)? {
+                        return Ok(false);
+                    }
+                    let reply = self.tunnel.buffer.consume_next(2);
+                    if reply[1] != SOCKS5_SUCCEEDED {
+                        return Err(io::Error::other("proxy authentication failed"));
+                    }
+                    let request = self.connect_request()?;
+                    self.tunnel.queue_request(request);
+                    self.state = Socks5State::AwaitingReply;
+                }
+                Socks5State::AwaitingReply => {
+                    // VER, REP, RSV, ATYP followed by the bound address and port
+                    if !self.tunnel.send_request(&mut self.inner)? || !self.tunnel.read_exact(&mut self.inner, 5)? {
+                        return Ok(false);
+                    }
+                    let view = self.tunnel.buffer.view();
+                    if view[0] != SOCKS5_VERSION {
+                        return Err(io::Error::other("invalid socks version in proxy reply"));
+                    }
+                    if view[1] != SOCKS5_SUCCEEDED {
+                        return Err(io::Error::other(format!(
+                            "proxy refused to establish tunnel: {}",
+                            socks5_reply_reason(view[1])
+                        )));
+                    }
+                    let reply_len = match view[3] {
+                        SOCKS5_ATYP_IPV4 => 4 + 4 + 2,
+                        SOCKS5_ATYP_IPV6 => 4 + 16 + 2,
+                        SOCKS5_ATYP_DOMAIN => 4 + 1 + view[4] as usize + 2,
+                        atyp => return Err(io::Error::other(format!("invalid address type in proxy reply: {atyp}"))),
+                    };
+                    if !self.tunnel.read_exact(&mut self.inner, reply_len)? {
+                        return Ok(false);
+                    }
+                    self.tunnel.complete();
+                    self.state = Socks5State::Established;
+                }
+                Socks5State::Established => return Ok(true),
+            }
+        }
+    }
+}
+
+impl<S: Read + Write> Read for Socks5Stream<S> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if !self.tunnel_established() && !self.negotiate()? {
+            return Err(io::Error::from(WouldBlock));
+        }
+        self.tunnel.drain_outbound(&mut self.inner)?;
+        self.inner.read(buf)
+    }
+}
+
+impl<S: Read + Write> Write for Socks5Stream<S> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if !self.tunnel_established() && !self.negotiate()? {
+            return Ok(self.tunnel.buffer_outbound(buf));
+        }
+        self.tunnel.write(&mut self.inner, buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if !self.tunnel_established() && !self.negotiate()? {
+            return Ok(());
+        }
+        self.tunnel.drain_outbound(&mut self.inner)?;
+        self.inner.flush()
+    }
+}
+
+impl<S: Selectable> Selectable for Socks5Stream<S> {
+    fn connected(&mut self) -> io::Result<bool> {
+        self.inner.connected()
+    }
+
+    fn make_writable(&mut self) {
+        self.inner.make_writable()
+    }
+
+    fn make_readable(&mut self) {
+        self.inner.make_readable()
+    }
+
+    fn make_unreadable(&mut self) {
+        self.inner.make_unreadable()
+    }
+
+    fn socket_queues(&self) -> Option<SocketQueues> {
+        self.inner.socket_queues()
+    }
+
+    fn rx_timestamp_ns(&self) -> Option<u64> {
+        self.inner.rx_timestamp_ns()
+    }
+
+    #[cfg(feature = "stats")]
+    fn io_counters(&self) -> Option<crate::stream::IoCounters> {
+        self.inner.io_counters()
+    }
+
+    fn apply_socket_options(&mut self, options: &SocketOptions) -> io::Result<()> {
+        self.inner.apply_socket_options(options)
+    }
+
+    fn has_pending_writes(&self) -> bool {
+        self.inner.has_pending_writes()
+    }
+
+    fn flush_pending_writes(&mut self) -> io::Result<()> {
+        self.inner.flush_pending_writes()
+    }
+
+    fn peer_closed(&self) -> bool {
+        self.inner.peer_closed()
+    }
+
+    fn write_closed(&self) -> bool {
+        self.inner.write_closed()
+    }
+
+    fn make_write_closed(&mut self) {
+        self.inner.make_write_closed()
+    }
+
+    fn take_socket_error(&mut self) -> io::Result<Option<io::Error>> {
+        self.inner.take_socket_error()
+    }
+
+    fn connect_error(&self) -> Option<io::Error> {
+        self.inner.connect_error()
+    }
+}
+
+impl<S> ConnectionInfoProvider for Socks5Stream<S> {
+    fn connection_info(&self) -> &ConnectionInfo {
+        &self.target
+    }
+}
+
+#[cfg(feature = "mio")]
+impl<S: Source> Source for Socks5Stream<S> {
+    fn register(&mut self, registry: &Registry, token: Token, interests: Interest) -> io::Result<()> {
+        registry.register(&mut self.inner, token, interests)
+    }
+
+    fn reregister(&mut self, registry: &Registry, token: Token, interests: Interest) -> io::Result<()> {
+        registry.reregister(&mut self.inner, token, interests)
+    }
+
+    fn deregister(&mut self, registry: &Registry) -> io::Result<()> {
+        registry.deregister(&mut self.inner)
+    }
+}
+
+/// Trait to convert any stream connected to the proxy into `HttpProxyStream`.
+pub trait IntoHttpProxyStream {
+    fn into_http_proxy_stream(self, host: &str, port: u16) -> HttpProxyStream<Self>
+    where
+        Self: Sized;
+}
+
+impl<T> IntoHttpProxyStream for T
+where
+    T: Read + Write,
+{
+    fn into_http_proxy_stream(self, host: &str, port: u16) -> HttpProxyStream<Self>
+    where
+        Self: Sized,
+    {
+        HttpProxyStream::new(self, host, port)
+    }
+}
+
+/// Trait to convert any stream connected to the proxy into `Socks5Stream`.
+pub trait IntoSocks5Stream {
+    fn into_socks5_stream(self, host: &str, port: u16) -> Socks5Stream<Self>
+    where
+        Self: Sized;
+}
+
+impl<T> IntoSocks5Stream for T
+where
+    T: Read + Write,
+{
+    fn into_socks5_stream(self, host: &str, port: u16) -> Socks5Stream<Self>
+    where
+        Self: Sized,
+    {
+        Socks5Stream::new(self, host, port)
+    }
+}
+
+/// Handles non-blocking exchange with the proxy while the tunnel is being negotiated.
+struct Tunnel {
+    buffer: ReadBuffer<1, 512>,
+    request: Vec<u8>,
+    request_offset: usize,
+    outbound: Vec<u8>,
+    outbound_offset: usize,
+}
+
+impl Tunnel {
+    fn new() -> Self {
+        Self {
+            buffer: ReadBuffer::new(),
+            request: Vec::new(),
+            request_offset: 0,
+            outbound: Vec::new(),
+            outbound_offset: 0,
+        }
+    }
+
+    fn queue_request(&mut self, request: Vec<u8>) {
+        self.request = request;
+        self.request_offset = 0;
+    }
+
+    /// Returns `true` once the whole request has been written to the proxy.
+    fn send_request<S: Write>(&mut self, stream: &mut S) -> io::Result<bool> {
+        write_pending(stream, &self.request, &mut self.request_offset)
+    }
+
+    /// Returns `true` once at least `len` bytes have been read from the proxy.
+    fn read_exact<S: Read>(&mut self, stream: &mut S, len: usize) -> io::Result<bool> {
+        while self.buffer.available() < len {
+            if !self.read_next(stream)? {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    /// Returns `true` once the end of http headers has been read from the proxy.
+    fn read_until_end_of_headers<S: Read>(&mut self, stream: &mut S) -> io::Result<bool> {
+        loop {
+            if self.buffer.available() >= 4 && self.buffer.view_last(4) == b"\r\n\r\n" {
+                return Ok(true);
+            }
+            if !self.read_next(stream)? {
+                return Ok(false);
+            }
+        }
+    }
+
+    fn read_next<S: Read>(&mut self, stream: &mut S) -> io::Result<bool> {
+        let available = self.buffer.available();
+        self.buffer.read_from(stream)?;
+        Ok(self.buffer.available() > available)
+    }
+
+    fn complete(&mut self) {
+        self.request = Vec::new();
+        self.request_offset = 0;
+    }
+
+    fn buffer_outbound(&mut self, buf: &[u8]) -> usize {
+        self.outbound.extend_from_slice(buf);
+        buf.len()
+    }
+
+    /// Returns `true` if there is no more outbound data buffered.
+    fn drain_outbound<S: Write>(&mut self, stream: &mut S) -> io::Result<bool> {
+        if self.outbound.is_empty() {
+            return Ok(true);
+        }
+        if write_pending(stream, &self.outbound, &mut self.outbound_offset)? {
+            self.outbound.clear();
+            self.outbound_offset = 0;
+            return Ok(true);
+        }
+        Ok(false)
+    }
+
+    fn write<S: Write>(&mut self, stream: &mut S, buf: &[u8]) -> io::Result<usize> {
+        // preserve ordering with any data buffered before the tunnel was established
+        if !self.drain_outbound(stream)? {
+            return Ok(self.buffer_outbound(buf));
+        }
+        stream.write(buf)
+    }
+}
+
+fn write_pending<S: Write>(stream: &mut S, buf: &[u8], offset: &mut usize) -> io::Result<bool> {
+    while *offset < buf.len() {
+        match stream.write(&buf[*offset..]) {
+            Ok(0) => return Ok(false),
+            Ok(n) => *offset += n,
+            Err(err) if err.kind() == WouldBlock => return Ok(false),
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(true)
+}
+
+fn authority(target: &ConnectionInfo) -> String {
+    match target.host.parse::<IpAddr>() {
+        Ok(IpAddr::V6(ip)) => format!("[{}]:{}", ip, target.port),
+        _ => target.to_string(),
+    }
+}
+
+fn socks5_reply_reason(rep: u8) -> &'static str {
+    match rep {
+        0x01 => "general socks server failure",
+        0x02 => "connection not allowed by ruleset",
+        0x03 => "network unreachable",
+        0x04 => "host unreachable",
+        0x05 => "connection refused",
+        0x06 => "ttl expired",
+        0x07 => "command not supported",
+        0x08 => "address type not supported",
+        _ => "unknown error",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+
+    use super::*;
+
+    /// Stream that returns `WouldBlock` once all scripted input has been consumed.
+    #[derive(Default)]
+    struct MockStream {
+        input: VecDeque<u8>,
+        output: Vec<u8>,
+    }
+
+    impl MockStream {
+        fn respond(&mut self, bytes: &[u8]) {
+            self.input.extend(bytes);
+        }
+    }
+
+    impl Read for MockStream {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.input.is_empty() {
+                return Err(io::Error::from(WouldBlock));
+            }
+            let len = buf.len().min(self.input.len());
+            for (i, b) in self.input.drain(..len).enumerate() {
+                buf[i] = b;
+            }
+            Ok(len)
+        }
+    }
+
+    impl Write for MockStream {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.output.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn should_establish_http_tunnel_and_flush_buffered_data() {
+        let mut stream = MockStream::default().into_http_proxy_stream("example.com", 443);
+        stream = stream.with_basic_auth("user", "pass");
+
+        stream.write_all(b"hello").unwrap();
+        assert!(!stream.tunnel_established());
+        assert_eq!(
+            b"CONNECT example.com:443 HTTP/1.1\r\nHost: example.com:443\r\nProxy-Authorization: Basic dXNlcjpwYXNz\r\n\r\n",
+            stream.inner.output.as_slice()
+        );
+
+        stream
+            .inner
+            .respond(b"HTTP/1.1 200 Connection established\r\n\r\nworld");
+        let mut buf = [0u8; 16];
+        let read = stream.read(&mut buf).unwrap();
+        assert!(stream.tunnel_established());
+        assert_eq!(b"world", &buf[..read]);
+        assert!(stream.inner.output.ends_with(b"\r\n\r\nhello"));
+        assert_eq!("example.com:443", stream.connection_info().to_string());
+    }
+
+    #[test]
+    fn should_fail_if_http_proxy_refuses_tunnel() {
+        let mut stream = MockStream::default().into_http_proxy_stream("example.com", 443);
+        stream
+            .inner
+            .respond(b"HTTP/1.1 407 Proxy Authentication Required\r\n\r\n");
+        let mut buf = [0u8; 16];
+        let err = stream.read(&mut buf).unwrap_err();
+        assert_eq!("proxy refused to establish tunnel: 407 Proxy Authentication Required", err.to_string());
+    }
+
+    #[test]
+    fn should_establish_socks5_tunnel_with_credentials() {
+        let mut stream = MockStream::default()
+            .into_socks5_stream("example.com", 443)
+            .with_credentials("user", "pass");
+
+        let mut buf = [0u8; 16];
+        assert_eq!(WouldBlock, stream.read(&mut buf).unwrap_err().kind());
+        assert_eq!(&[5, 2, 0, 2], stream.inner.output.as_slice());
+
+        stream.inner.respond(&[5, 2]);
+        assert_eq!(WouldBlock, stream.read(&mut buf).unwrap_err().kind());
+        assert_eq!(b"\x01\x04user\x04pass", &stream.inner.output[4..]);
+
+        stream.inner.respond(&[1, 0]);
+        assert_eq!(WouldBlock, stream.read(&mut buf).unwrap_err().kind());
+        assert_eq!(b"\x05\x01\x00\x03\x0bexample.com\x01\xbb", &stream.inner.output[15..]);
+
+        stream.inner.respond(&[5, 0, 0, 1, 127, 0, 0, 1, 0x1f, 0x90]);
+        stream.inner.respond(b"data");
+        let read = stream.read(&mut buf).unwrap();
+        assert!(stream.tunnel_established());
+        assert_eq!(b"data", &buf[..read]);
+    }
+
+    #[test]
+    fn should_fail_if_socks5_proxy_refuses_connection() {
+        let mut stream = MockStream::default().into_socks5_stream("10.0.0.1", 443);
+        stream.inner.respond(&[5, 0, 5, 5, 0, 1, 0, 0, 0, 0, 0, 0]);
+        let mut buf = [0u8; 16];
+        let err = stream.read(&mut buf).unwrap_err();
+        assert_eq!("proxy refused to establish tunnel: connection refused", err.to_string());
+        assert_eq!(&[5, 1, 0, 5, 1, 0, 1, 10, 0, 0, 1, 0x01, 0xbb], stream.inner.output.as_slice());
+    }
+}
@@ -1,29 +1,255 @@
-use std::fs::File;
+use std::fmt::Display;
+use std::fs::{File, OpenOptions};
 use std::io;
 use std::io::{BufWriter, Read, Write};
+use std::net::SocketAddr;
+use std::path::Path;
+
+use crate::stream::pcap::PcapWriter;
+use crate::util::current_time_nanos;
 
 const DEFAULT_RECORDING_NAME: &str = "plain";
 
+/// Magic header identifying the timestamped (v2) recording format.
+pub const RECORDING_V2_MAGIC: &[u8; 8] = b"BNREC\x00\x00\x02";
+
+/// Length marking the session boundary record in the timestamped recording, see [`SessionRecorder`].
+pub const SESSION_BOUNDARY: u32 = u32::MAX;
+
+/// Layout of the recording files.
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq)]
+pub enum RecordingFormat {
+    /// Raw bytes exactly as received from (or sent to) the stream.
+    #[default]
+    Raw,
+    /// Each chunk is prefixed with the nanosecond timestamp (`u64`) and length (`u32`), both
+    /// little endian. The file starts with the [`RECORDING_V2_MAGIC`] header. Allows the
+    /// `ReplayStream` to pace the delivery according to the original inter-arrival times. The chunk
+    /// with the [`SESSION_BOUNDARY`] length and no payload marks the start of the new connection.
+    Timestamped,
+}
+
 pub struct Recorder {
     inbound: Box<dyn Write>,
     outbound: Box<dyn Write>,
+    format: RecordingFormat,
+    handshake: Option<HandshakeRecording>,
+    pcap: Option<PcapWriter<BufWriter<File>>>,
+}
+
+/// Destination of the data exchanged before the end of the HTTP upgrade response.
+struct HandshakeRecording {
+    inbound: Box<dyn Write>,
+    outbound: Box<dyn Write>,
+    // last bytes of the response seen so far, to detect the terminator split across reads
+    tail: Vec<u8>,
 }
 
 impl Recorder {
     pub fn new(recording_name: impl AsRef<str>) -> io::Result<Self> {
+        Self::with_format(recording_name, RecordingFormat::Raw)
+    }
+
+    /// Creates recorder that writes the files using the specified [`RecordingFormat`].
+    pub fn with_format(recording_name: impl AsRef<str>, format: RecordingFormat) -> io::Result<Self> {
         let file_in = format!("{}_inbound.rec", recording_name.as_ref());
         let file_out = format!("{}_outbound.rec", recording_name.as_ref());
-        let inbound = Box::new(BufWriter::new(File::create(file_in)?));
-        let outbound = Box::new(BufWriter::new(File::create(file_out)?));
-        Ok(Self { inbound, outbound })
+        let mut inbound: Box<dyn Write> = Box::new(BufWriter::new(File::create(file_in)?));
+        let mut outbound: Box<dyn Write> = Box::new(BufWriter::new(File::create(file_out)?));
+        if format == RecordingFormat::Timestamped {
+            inbound.write_all(RECORDING_V2_MAGIC)?;
+            outbound.write_all(RECORDING_V2_MAGIC)?;
+        }
+        Ok(Self {
+            inbound,
+            outbound,
+            format,
+            handshake: None,
+            pcap: None,
+        })
     }
+
+    /// Creates recorder that writes both directions of the connection into the single
+    /// `{recording_name}.pcap` file that can be opened with Wireshark, see [`PcapWriter`].
+    pub fn with_pcap(recording_name: impl AsRef<str>, local: SocketAddr, remote: SocketAddr) -> io::Result<Self> {
+        let file = BufWriter::new(File::create(format!("{}.pcap", recording_name.as_ref()))?);
+        Ok(Self {
+            inbound: Box::new(io::sink()),
+            outbound: Box::new(io::sink()),
+            format: RecordingFormat::Raw,
+            handshake: None,
+            pcap: Some(PcapWriter::new(file, local, remote)?),
+        })
+    }
+
     fn record_inbound(&mut self, buf: &[u8]) -> io::Result<()> {
-        self.inbound.write_all(buf)?;
-        self.inbound.flush()
+        if let Some(pcap) = self.pcap.as_mut() {
+            if !buf.is_empty() {
+                pcap.write_inbound(current_time_nanos(), buf)?;
+            }
+            return pcap.flush();
+        }
+        let Some(handshake) = self.handshake.as_mut() else {
+            return Self::record(&mut self.inbound, self.format, buf);
+        };
+        let seen = handshake.tail.len();
+        handshake.tail.extend_from_slice(buf);
+        match handshake.tail.windows(4).position(|window| window == b"\r\n\r\n") {
+            Some(position) => {
+                let end = position + 4 - seen;
+                Self::record(&mut handshake.inbound, self.format, &buf[..end])?;
+                self.handshake = None;
+                Self::record(&mut self.inbound, self.format, &buf[end..])
+            }
+            None => {
+                let keep = handshake.tail.len().saturating_sub(3);
+                handshake.tail.drain(..keep);
+                Self::record(&mut handshake.inbound, self.format, buf)
+            }
+        }
     }
+
     fn record_outbound(&mut self, buf: &[u8]) -> io::Result<()> {
-        self.outbound.write_all(buf)?;
-        self.outbound.flush()
+        if let Some(pcap) = self.pcap.as_mut() {
+            if !buf.is_empty() {
+                pcap.write_outbound(current_time_nanos(), buf)?;
+            }
+            return pcap.flush();
+        }
+        match self.handshake.as_mut() {
+            Some(handshake) => Self::record(&mut handshake.outbound, self.format, buf),
+            None => Self::record(&mut self.outbound, self.format, buf),
+        }
+    }
+
+    fn record(writer: &mut Box<dyn Write>, format: RecordingFormat, buf: &[u8]) -> io::Result<()> {
+        if format == RecordingFormat::Timestamped {
+            if buf.is_empty() {
+                return Ok(());
+            }
+            writer.write_all(&current_time_nanos().to_le_bytes())?;
+            writer.write_all(&(buf.len() as u32).to_le_bytes())?;
+        }
+        writer.write_all(buf)?;
+        writer.flush()
+    }
+}
+
+/// Records all the connections made by the endpoint into the same set of files, so that the
+/// full day can be reconstructed including the disconnects. The files are named after the
+/// endpoint `id` (such as the `IOService` handle) and the time the recorder has been created, each
+/// connection appends to them starting with the [`SESSION_BOUNDARY`] record. Optionally the HTTP
+/// upgrade request and response are recorded into the separate `_handshake` files.
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::io;
+/// use std::net::{SocketAddr, TcpStream};
+/// use boomnet::endpoint::ws::{TlsWebsocket, TlsWebsocketEndpoint};
+/// use boomnet::stream::record::{RecordedStream, SessionRecorder};
+/// use boomnet::ws::IntoTlsWebsocket;
+///
+/// struct TradeEndpoint {
+///     recorder: SessionRecorder,
+/// }
+///
+/// impl TlsWebsocketEndpoint for TradeEndpoint {
+///     type Stream = RecordedStream<TcpStream>;
+///
+///     fn url(&self) -> &str {
+///         "wss://stream.binance.com:9443/ws/btcusdt@trade"
+///     }
+///
+///     fn create_websocket(&mut self, addr: SocketAddr) -> io::Result<TlsWebsocket<Self::Stream>> {
+///         let stream = RecordedStream::new(TcpStream::connect(addr)?, self.recorder.next_session()?);
+///         stream.into_tls_websocket(self.url())
+///     }
+///
+///     fn poll(&mut self, ws: &mut TlsWebsocket<Self::Stream>) -> io::Result<()> {
+///         while ws.receive_next()?.is_some() {}
+///         Ok(())
+///     }
+/// }
+///
+/// let endpoint = TradeEndpoint {
+///     recorder: SessionRecorder::new("recordings/binance", 0),
+/// };
+/// ```
+#[derive(Debug, Clone)]
+pub struct SessionRecorder {
+    file_prefix: String,
+    sessions: u64,
+    record_handshake: bool,
+}
+
+impl SessionRecorder {
+    /// Creates recorder writing into the `{name}_{id}_{unix_seconds}_inbound.rec` (and `_outbound.rec`)
+    /// files, the `name` may include the directory.
+    pub fn new(name: impl AsRef<str>, id: impl Display) -> SessionRecorder {
+        let start_secs = current_time_nanos() / 1_000_000_000;
+        Self {
+            file_prefix: format!("{}_{}_{}", name.as_ref(), id, start_secs),
+            sessions: 0,
+            record_handshake: false,
+        }
+    }
+
+    /// Enable or disable recording the HTTP upgrade exchange into the separate `_handshake_inbound.rec`
+    /// and `_handshake_outbound.rec` files. The record stream must carry the plaintext, for TLS
+    /// connections it has to wrap the `TlsStream`.
+    pub fn with_handshake_recording(self, record_handshake: bool) -> SessionRecorder {
+        Self {
+            record_handshake,
+            ..self
+        }
+    }
+
+    /// Common prefix of the recording files.
+    pub fn file_prefix(&self) -> &str {
+        &self.file_prefix
+    }
+
+    /// Number of sessions started so far.
+    pub const fn sessions(&self) -> u64 {
+        self.sessions
+    }
+
+    /// Starts the new session, to be called every time the connection is created.
+    pub fn next_session(&mut self) -> io::Result<Recorder> {
+        let open = |suffix: &str| -> io::Result<Box<dyn Write>> {
+            let path = format!("{}_{}.rec", self.file_prefix, suffix);
+            let file = OpenOptions::new().create(true).append(true).open(&path)?;
+            let mut writer: Box<dyn Write> = Box::new(BufWriter::new(file));
+            if std::fs::metadata(Path::new(&path))?.len() == 0 {
+                writer.write_all(RECORDING_V2_MAGIC)?;
+            }
+            Self::write_boundary(&mut writer)?;
+            Ok(writer)
+        };
+        let handshake = match self.record_handshake {
+            true => Some(HandshakeRecording {
+                inbound: open("handshake_inbound")?,
+                outbound: open("handshake_outbound")?,
+                tail: Vec::with_capacity(8),
+            }),
+            false => None,
+        };
+        let recorder = Recorder {
+            inbound: open("inbound")?,
+            outbound: open("outbound")?,
+            format: RecordingFormat::Timestamped,
+            handshake,
+            pcap: None,
+        };
+        self.sessions += 1;
+        Ok(recorder)
+    }
+
+    fn write_boundary(writer: &mut Box<dyn Write>) -> io::Result<()> {
+        writer.write_all(&current_time_nanos().to_le_bytes())?;
+        writer.write_all(&SESSION_BOUNDARY.to_le_bytes())?;
+        writer.flush()
     }
 }
 
@@ -72,6 +298,14 @@ pub trait IntoRecordedStream {
     {
         self.into_recorded_stream(DEFAULT_RECORDING_NAME)
     }
+
+    /// Records the stream using the [`RecordingFormat::Timestamped`] format.
+    fn into_timestamped_recorded_stream(self, recording_name: impl AsRef<str>) -> RecordedStream<Self>
+    where
+        Self: Sized,
+    {
+        RecordedStream::new(self, Recorder::with_format(recording_name, RecordingFormat::Timestamped).unwrap())
+    }
 }
 
 impl<T> IntoRecordedStream for T
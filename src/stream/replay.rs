@@ -1,23 +1,244 @@
+use std::cell::Cell;
 use std::fs::File;
 use std::io;
-use std::io::{BufReader, Read, Write};
+use std::io::ErrorKind::{InvalidData, UnexpectedEof, WouldBlock};
+use std::io::{BufRead, BufReader, Cursor, Read, Seek, SeekFrom, Write};
 use std::path::Path;
+use std::rc::Rc;
 
+use crate::endpoint::Endpoint;
+use crate::service::{Handle, RECORDING_FILE_NAME};
+use crate::stream::pcap::{FiveTuple, PcapReader};
+use crate::stream::record::{RECORDING_V2_MAGIC, SESSION_BOUNDARY};
+use crate::util::current_time_nanos;
+
+/// Replays data previously captured with the `RecordedStream`. Both raw and timestamped
+/// recordings are supported, the format is detected automatically. Timestamped recordings
+/// are by default replayed as fast as possible, use [`ReplayStream::with_pacing`] to deliver
+/// the data according to the recorded inter-arrival times.
+///
+/// # Examples
+///
+/// ```no_run
+/// use boomnet::stream::replay::{Pacing, ReplayStream};
+///
+/// // replay at twice the recorded speed
+/// let stream = ReplayStream::from_file("plain_inbound.rec").unwrap().with_pacing(Pacing::Recorded(2.0));
+/// ```
+///
+/// The recording can also be replayed in the loop, for example to soak test the endpoint:
+///
+/// ```no_run
+/// use boomnet::stream::replay::{EndOfData, ReplayStream};
+///
+/// let stream = ReplayStream::from_file("plain_inbound.rec")
+///     .unwrap()
+///     .with_looping(true)
+///     .with_end_of_data(EndOfData::WouldBlock);
+/// ```
 pub struct ReplayStream<S> {
     inner: S,
+    timestamped: Option<TimestampedReplay>,
+    end_of_data: EndOfData,
+    rewind: Option<fn(&mut S) -> io::Result<()>>,
+}
+
+/// Defines what the [`ReplayStream`] reports once the whole recording has been delivered.
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq)]
+pub enum EndOfData {
+    /// Return `0` from the read, which the websocket (and most other readers) treat as the
+    /// connection closed by the peer.
+    #[default]
+    Eof,
+    /// Keep failing with [`WouldBlock`], as if the peer stopped sending but kept the connection open.
+    WouldBlock,
+}
+
+/// Defines how the timestamped recording is replayed.
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub enum Pacing {
+    /// Deliver the data as fast as possible.
+    #[default]
+    AsFastAsPossible,
+    /// Deliver the data according to the recorded inter-arrival times divided by the speed
+    /// multiplier (e.g. `2.0` replays twice as fast as recorded).
+    Recorded(f64),
+}
+
+struct TimestampedReplay {
+    pacing: Pacing,
+    chunk: Vec<u8>,
+    chunk_offset: usize,
+    chunk_time_ns: u64,
+    // (recording, replay) start times
+    start_time_ns: Option<(u64, u64)>,
 }
 
 impl ReplayStream<BufReader<File>> {
     pub fn from_file(path: impl AsRef<Path>) -> io::Result<ReplayStream<BufReader<File>>> {
+        Self::new(BufReader::new(File::open(path)?))
+    }
+}
+
+impl ReplayStream<BufReader<PcapReader<BufReader<File>>>> {
+    /// Replays the payload of the TCP segments matching the `flow` from the pcap or pcapng
+    /// capture, preserving the capture timestamps for the [`Pacing`].
+    pub fn from_pcap(
+        path: impl AsRef<Path>,
+        flow: FiveTuple,
+    ) -> io::Result<ReplayStream<BufReader<PcapReader<BufReader<File>>>>> {
+        Self::new(BufReader::new(PcapReader::new(BufReader::new(File::open(path)?), flow)?))
+    }
+}
+
+impl<S: BufRead> ReplayStream<S> {
+    /// Creates replay stream from the recording, detecting whether it is timestamped.
+    pub fn new(mut inner: S) -> io::Result<ReplayStream<S>> {
+        let timestamped = if inner.fill_buf()?.starts_with(RECORDING_V2_MAGIC) {
+            inner.consume(RECORDING_V2_MAGIC.len());
+            Some(TimestampedReplay {
+                pacing: Pacing::default(),
+                chunk: Vec::new(),
+                chunk_offset: 0,
+                chunk_time_ns: 0,
+                start_time_ns: None,
+            })
+        } else {
+            None
+        };
         Ok(Self {
-            inner: BufReader::new(File::open(path)?),
+            inner,
+            timestamped,
+            end_of_data: EndOfData::default(),
+            rewind: None,
         })
     }
 }
 
+impl<S: BufRead + Seek> ReplayStream<S> {
+    /// Enable or disable replaying the recording again from the start once the end has been
+    /// reached, the recording must start at the beginning of the stream `S`. The pacing restarts
+    /// with each loop.
+    pub fn with_looping(self, looping: bool) -> ReplayStream<S> {
+        let rewind: fn(&mut S) -> io::Result<()> = match self.timestamped {
+            Some(_) => |stream| {
+                stream
+                    .seek(SeekFrom::Start(RECORDING_V2_MAGIC.len() as u64))
+                    .map(|_| ())
+            },
+            None => |stream| stream.rewind(),
+        };
+        Self {
+            rewind: looping.then_some(rewind),
+            ..self
+        }
+    }
+}
+
+impl<S> ReplayStream<S> {
+    /// Specify [`Pacing`] for the timestamped recording, ignored for raw recordings.
+    pub fn with_pacing(mut self, pacing: Pacing) -> ReplayStream<S> {
+        if let Some(timestamped) = self.timestamped.as_mut() {
+            timestamped.pacing = pacing;
+        }
+        self
+    }
+
+    /// Specify what is reported once the whole recording has been delivered, see [`EndOfData`].
+    pub fn with_end_of_data(self, end_of_data: EndOfData) -> ReplayStream<S> {
+        Self { end_of_data, ..self }
+    }
+}
+
+impl TimestampedReplay {
+    fn read<S: Read>(&mut self, stream: &mut S, buf: &mut [u8]) -> io::Result<usize> {
+        if self.chunk_offset == self.chunk.len() && !self.next_chunk(stream)? {
+            return Ok(0);
+        }
+
+        if let Pacing::Recorded(speed) = self.pacing {
+            let current_time_ns = current_time_nanos();
+            let (recording_start_ns, replay_start_ns) =
+                *self.start_time_ns.get_or_insert((self.chunk_time_ns, current_time_ns));
+            let due_ns = ((self.chunk_time_ns - recording_start_ns) as f64 / speed) as u64;
+            if current_time_ns - replay_start_ns < due_ns {
+                return Err(io::Error::from(WouldBlock));
+            }
+        }
+
+        let len = buf.len().min(self.chunk.len() - self.chunk_offset);
+        buf[..len].copy_from_slice(&self.chunk[self.chunk_offset..self.chunk_offset + len]);
+        self.chunk_offset += len;
+        Ok(len)
+    }
+
+    fn restart(&mut self) {
+        self.chunk.clear();
+        self.chunk_offset = 0;
+        self.start_time_ns = None;
+    }
+
+    fn next_chunk<S: Read>(&mut self, stream: &mut S) -> io::Result<bool> {
+        let mut header = [0u8; 12];
+        let len = loop {
+            match stream.read_exact(&mut header) {
+                Ok(()) => {}
+                // also covers the header truncated when the recording has been interrupted
+                Err(err) if err.kind() == UnexpectedEof => return Ok(false),
+                Err(err) => return Err(err),
+            }
+            let (timestamp, len) = header.split_at(8);
+            self.chunk_time_ns = u64::from_le_bytes(timestamp.try_into().unwrap());
+            match u32::from_le_bytes(len.try_into().unwrap()) {
+                // the sessions are replayed back to back
+                SESSION_BOUNDARY => continue,
+                len => break len as usize,
+            }
+        };
+        self.chunk.resize(len, 0);
+        self.chunk_offset = 0;
+        match stream.read_exact(&mut self.chunk) {
+            Ok(()) => Ok(true),
+            // the payload of the last chunk is incomplete, treat as the end of the recording
+            Err(err) if err.kind() == UnexpectedEof => {
+                self.chunk.clear();
+                Ok(false)
+            }
+            Err(err) => Err(err),
+        }
+    }
+}
+
+impl<S: Read> ReplayStream<S> {
+    fn read_recording(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self.timestamped.as_mut() {
+            Some(timestamped) => timestamped.read(&mut self.inner, buf),
+            None => self.inner.read(buf),
+        }
+    }
+}
+
 impl<S: Read> Read for ReplayStream<S> {
     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
-        self.inner.read(buf)
+        let read = self.read_recording(buf)?;
+        if read > 0 || buf.is_empty() {
+            return Ok(read);
+        }
+        if let Some(rewind) = self.rewind {
+            rewind(&mut self.inner)?;
+            if let Some(timestamped) = self.timestamped.as_mut() {
+                timestamped.restart();
+            }
+            // the recording may be empty
+            let read = self.read_recording(buf)?;
+            if read > 0 {
+                return Ok(read);
+            }
+        }
+        match self.end_of_data {
+            EndOfData::Eof => Ok(0),
+            EndOfData::WouldBlock => Err(io::Error::from(WouldBlock)),
+        }
     }
 }
 
@@ -30,3 +251,339 @@ impl<S> Write for ReplayStream<S> {
         Ok(())
     }
 }
+
+/// Sessions recorded by the `IOService` for the endpoint (see `IOService::with_recording`), so that
+/// the incident can be replayed offline through the same endpoint code. Each session is replayed as
+/// the separate connection created by the endpoint from the [`SessionStream`].
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::io;
+/// use std::net::SocketAddr;
+/// use boomnet::endpoint::Endpoint;
+/// use boomnet::stream::replay::{RecordedSessions, SessionStream};
+/// use boomnet::ws::{IntoWebsocket, Websocket};
+///
+/// struct TradeEndpoint;
+///
+/// impl TradeEndpoint {
+///     // strategy code shared by the live and the replayed connections
+///     fn on_message<S: io::Read + io::Write>(&mut self, ws: &mut Websocket<S>) -> io::Result<()> {
+///         while ws.receive_next()?.is_some() {}
+///         Ok(())
+///     }
+/// }
+///
+/// impl Endpoint for TradeEndpoint {
+///     type Target = Websocket<SessionStream>;
+///
+///     fn connection_info(&self) -> io::Result<boomnet::endpoint::ConnectionInfo> {
+///         unreachable!("replayed endpoint does not connect")
+///     }
+///
+///     fn create_target(&mut self, _addr: SocketAddr) -> io::Result<Self::Target> {
+///         unreachable!("replayed endpoint does not connect")
+///     }
+///
+///     fn poll(&mut self, ws: &mut Self::Target) -> io::Result<()> {
+///         self.on_message(ws)
+///     }
+/// }
+///
+/// let sessions = RecordedSessions::load("recordings", 0).unwrap();
+/// let replayed = sessions
+///     .replay(&mut TradeEndpoint, |_, stream| Ok(Websocket::new_connected(stream)))
+///     .unwrap();
+/// println!("replayed {replayed} session(s)");
+/// ```
+#[derive(Debug, Default)]
+pub struct RecordedSessions {
+    sessions: Vec<Vec<u8>>,
+}
+
+/// Stream replaying single recorded session, see [`RecordedSessions`].
+pub type SessionStream = ReplayStream<SessionReader>;
+
+/// Recording of single session, tracks whether it has been read to the end.
+#[derive(Debug)]
+pub struct SessionReader {
+    inner: Cursor<Vec<u8>>,
+    exhausted: Rc<Cell<bool>>,
+}
+
+impl Read for SessionReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        if read == 0 && !buf.is_empty() {
+            self.exhausted.set(true);
+        }
+        Ok(read)
+    }
+}
+
+impl BufRead for SessionReader {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        self.inner.fill_buf()
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.inner.consume(amt)
+    }
+}
+
+impl RecordedSessions {
+    /// Number of times the endpoint is polled once its session has been read to the end, for
+    /// the endpoints that do not fail when the stream is closed.
+    const DRAIN_POLLS: usize = 16;
+
+    /// Loads the inbound sessions recorded into the `dir` for the endpoint with the `handle`, oldest
+    /// first (including those recorded by the previous runs of the service).
+    pub fn load(dir: impl AsRef<Path>, handle: Handle) -> io::Result<RecordedSessions> {
+        let prefix = format!("{}_{}_", RECORDING_FILE_NAME, handle);
+        let mut recordings = Vec::new();
+        for entry in std::fs::read_dir(dir)? {
+            let path = entry?.path();
+            let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+                continue;
+            };
+            // {prefix}{unix_seconds}_inbound.rec
+            let start_secs = name
+                .strip_prefix(&prefix)
+                .and_then(|name| name.strip_suffix("_inbound.rec"))
+                .and_then(|start_secs| start_secs.parse::<u64>().ok());
+            if let Some(start_secs) = start_secs {
+                recordings.push((start_secs, path));
+            }
+        }
+        recordings.sort();
+        let mut sessions = RecordedSessions::default();
+        for (_, path) in recordings {
+            sessions.sessions.extend(Self::split(&std::fs::read(path)?)?);
+        }
+        Ok(sessions)
+    }
+
+    /// Splits the timestamped recording written by the `SessionRecorder` into the sessions.
+    pub fn from_recording(recording: &[u8]) -> io::Result<RecordedSessions> {
+        Ok(Self {
+            sessions: Self::split(recording)?,
+        })
+    }
+
+    fn split(recording: &[u8]) -> io::Result<Vec<Vec<u8>>> {
+        let Some(mut chunks) = recording.strip_prefix(RECORDING_V2_MAGIC.as_slice()) else {
+            return Err(io::Error::new(InvalidData, "not a session recording"));
+        };
+        let mut sessions: Vec<Vec<u8>> = Vec::new();
+        // the header truncated when the recording has been interrupted is ignored
+        while chunks.len() >= 12 {
+            let len = u32::from_le_bytes(chunks[8..12].try_into().unwrap());
+            if len == SESSION_BOUNDARY {
+                sessions.push(RECORDING_V2_MAGIC.to_vec());
+                chunks = &chunks[12..];
+                continue;
+            }
+            let Some(chunk) = chunks.get(..12 + len as usize) else {
+                break;
+            };
+            match sessions.last_mut() {
+                Some(session) => session.extend_from_slice(chunk),
+                None => return Err(io::Error::new(InvalidData, "data recorded outside of session")),
+            }
+            chunks = &chunks[chunk.len()..];
+        }
+        Ok(sessions)
+    }
+
+    /// Number of the recorded sessions.
+    pub fn len(&self) -> usize {
+        self.sessions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sessions.is_empty()
+    }
+
+    /// Streams replaying each session, in the order they were recorded.
+    pub fn into_streams(self) -> impl Iterator<Item = io::Result<SessionStream>> {
+        self.sessions
+            .into_iter()
+            .map(|session| Self::stream(session, Rc::default()))
+    }
+
+    /// Replays each session through the `endpoint`: the target is created from the [`SessionStream`]
+    /// with `create_target` (in place of [`Endpoint::create_target`]) and polled until the endpoint
+    /// fails, which is how the session recorded up to the disconnect usually ends, or has consumed
+    /// the whole session. Returns the number of sessions replayed.
+    pub fn replay<E, F>(self, endpoint: &mut E, mut create_target: F) -> io::Result<usize>
+    where
+        E: Endpoint,
+        F: FnMut(&mut E, SessionStream) -> io::Result<E::Target>,
+    {
+        let mut replayed = 0;
+        for session in self.sessions {
+            let exhausted = Rc::new(Cell::new(false));
+            let mut target = create_target(endpoint, Self::stream(session, exhausted.clone())?)?;
+            let mut drain_polls = 0;
+            while drain_polls < Self::DRAIN_POLLS && endpoint.poll(&mut target).is_ok() {
+                if exhausted.get() {
+                    drain_polls += 1;
+                }
+            }
+            replayed += 1;
+        }
+        Ok(replayed)
+    }
+
+    fn stream(session: Vec<u8>, exhausted: Rc<Cell<bool>>) -> io::Result<SessionStream> {
+        ReplayStream::new(SessionReader {
+            inner: Cursor::new(session),
+            exhausted,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::stream::record::{RecordedStream, SessionRecorder};
+
+    use super::*;
+
+    fn chunk(timestamp_ns: u64, payload: &[u8]) -> Vec<u8> {
+        let mut chunk = timestamp_ns.to_le_bytes().to_vec();
+        chunk.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+        chunk.extend_from_slice(payload);
+        chunk
+    }
+
+    #[test]
+    fn should_replay_raw_recording() {
+        let mut stream = ReplayStream::new(Cursor::new(b"hello".to_vec())).unwrap();
+        let mut buf = [0u8; 16];
+        assert_eq!(5, stream.read(&mut buf).unwrap());
+        assert_eq!(b"hello", &buf[..5]);
+        assert_eq!(0, stream.read(&mut buf).unwrap());
+    }
+
+    #[test]
+    fn should_replay_timestamped_recording() {
+        let mut recording = RECORDING_V2_MAGIC.to_vec();
+        recording.extend(chunk(1_000, b"hello"));
+        recording.extend(chunk(2_000, b"world!"));
+
+        let mut stream = ReplayStream::new(Cursor::new(recording)).unwrap();
+        let mut buf = [0u8; 4];
+        assert_eq!(4, stream.read(&mut buf).unwrap());
+        assert_eq!(b"hell", &buf);
+        assert_eq!(1, stream.read(&mut buf).unwrap());
+        assert_eq!(b"o", &buf[..1]);
+        assert_eq!(4, stream.read(&mut buf).unwrap());
+        assert_eq!(b"worl", &buf);
+        assert_eq!(2, stream.read(&mut buf).unwrap());
+        assert_eq!(0, stream.read(&mut buf).unwrap());
+    }
+
+    struct Duplex(Cursor<Vec<u8>>, Vec<u8>);
+
+    impl Read for Duplex {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.0.read(buf)
+        }
+    }
+
+    impl Write for Duplex {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.1.write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn should_replay_sessions_back_to_back() {
+        let name = std::env::temp_dir().join(format!("boomnet_session_{}", std::process::id()));
+        let mut recorder = SessionRecorder::new(name.to_str().unwrap(), 7).with_handshake_recording(true);
+        for body in [b"first".as_slice(), b"second".as_slice()] {
+            let mut response = b"HTTP/1.1 101 Switching Protocols\r\n\r\n".to_vec();
+            response.extend_from_slice(body);
+            let duplex = Duplex(Cursor::new(response), Vec::new());
+            let mut stream = RecordedStream::new(duplex, recorder.next_session().unwrap());
+            stream.write_all(b"GET / HTTP/1.1\r\n\r\n").unwrap();
+            // the terminator is split across the reads
+            let mut buf = [0u8; 34];
+            while stream.read(&mut buf).unwrap() > 0 {}
+        }
+        assert_eq!(2, recorder.sessions());
+
+        let read_all = |suffix: &str| {
+            let path = format!("{}_{}.rec", recorder.file_prefix(), suffix);
+            let mut replay = Vec::new();
+            ReplayStream::from_file(&path)
+                .unwrap()
+                .read_to_end(&mut replay)
+                .unwrap();
+            std::fs::remove_file(path).unwrap();
+            replay
+        };
+        assert_eq!(b"firstsecond".to_vec(), read_all("inbound"));
+        assert!(read_all("outbound").is_empty());
+        assert_eq!(b"HTTP/1.1 101 Switching Protocols\r\n\r\n".repeat(2), read_all("handshake_inbound"));
+        assert_eq!(b"GET / HTTP/1.1\r\n\r\n".repeat(2), read_all("handshake_outbound"));
+    }
+
+    #[test]
+    fn should_loop_recording_and_report_end_of_data() {
+        let mut recording = RECORDING_V2_MAGIC.to_vec();
+        recording.extend(chunk(1_000, b"hello"));
+        // interrupted while writing the last chunk
+        recording.extend(&chunk(2_000, b"world")[..14]);
+
+        let mut stream = ReplayStream::new(Cursor::new(recording.clone()))
+            .unwrap()
+            .with_end_of_data(EndOfData::WouldBlock);
+        let mut buf = [0u8; 3];
+        assert_eq!(3, stream.read(&mut buf).unwrap());
+        assert_eq!(2, stream.read(&mut buf).unwrap());
+        assert_eq!(WouldBlock, stream.read(&mut buf).unwrap_err().kind());
+        assert_eq!(WouldBlock, stream.read(&mut buf).unwrap_err().kind());
+
+        let mut stream = ReplayStream::new(Cursor::new(recording)).unwrap().with_looping(true);
+        let mut replay = Vec::new();
+        for _ in 0..4 {
+            let read = stream.read(&mut buf).unwrap();
+            replay.extend_from_slice(&buf[..read]);
+        }
+        assert_eq!(b"hellohello", replay.as_slice());
+
+        let mut stream = ReplayStream::new(Cursor::new(b"abc".to_vec()))
+            .unwrap()
+            .with_looping(true);
+        let mut buf = [0u8; 2];
+        let mut replay = Vec::new();
+        for _ in 0..3 {
+            let read = stream.read(&mut buf).unwrap();
+            replay.extend_from_slice(&buf[..read]);
+        }
+        assert_eq!(b"abcab", replay.as_slice());
+
+        let mut stream = ReplayStream::new(Cursor::new(Vec::new())).unwrap().with_looping(true);
+        assert_eq!(0, stream.read(&mut buf).unwrap());
+    }
+
+    #[test]
+    fn should_pace_timestamped_recording() {
+        let mut recording = RECORDING_V2_MAGIC.to_vec();
+        recording.extend(chunk(0, b"a"));
+        recording.extend(chunk(60_000_000_000, b"b"));
+
+        let mut stream = ReplayStream::new(Cursor::new(recording))
+            .unwrap()
+            .with_pacing(Pacing::Recorded(1.0));
+        let mut buf = [0u8; 4];
+        assert_eq!(1, stream.read(&mut buf).unwrap());
+        assert_eq!(WouldBlock, stream.read(&mut buf).unwrap_err().kind());
+    }
+}
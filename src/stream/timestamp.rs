@@ -0,0 +1,313 @@
+//! Kernel receive timestamps (`SO_TIMESTAMPNS`, Linux only). The timestamp of the packet that
+//! carried the data is reported with [`Selectable::rx_timestamp_ns`], in nanoseconds since the UNIX
+//! epoch, so that it can be compared with `Websocket` frame timestamps.
+//!
+//! The `MioStream` reports the timestamps once the [`SocketOptions::with_rx_timestamps`] are applied,
+//! for the plain `TcpStream` use the [`TimestampedStream`] wrapper.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use std::net::TcpStream;
+//! use boomnet::select::Selectable;
+//! use boomnet::stream::timestamp::IntoTimestampedStream;
+//! use boomnet::stream::tls::IntoTlsStream;
+//! use boomnet::ws::{IntoWebsocket, ReadBudget};
+//!
+//! let mut ws = TcpStream::connect("stream.binance.com:9443")
+//!     .unwrap()
+//!     .into_timestamped_stream()
+//!     .unwrap()
+//!     .into_tls_stream("stream.binance.com")
+//!     .into_websocket("wss://stream.binance.com:9443/ws");
+//!
+//! ws.receive_batch_with_rx_timestamp(ReadBudget::default(), |frame, rx_timestamp_ns| {
+//!     println!("{} bytes received at {rx_timestamp_ns:?}", frame.payload().len());
+//!     Ok(())
+//! })
+//! .unwrap();
+//! ```
+
+#[cfg(feature = "mio")]
+use mio::{event::Source, Interest, Registry, Token};
+use std::io;
+use std::io::{Read, Write};
+use std::os::fd::{AsRawFd, RawFd};
+
+use crate::select::Selectable;
+use crate::stream::{SocketOptions, SocketQueues};
+
+/// Enables `SO_TIMESTAMPNS` on the socket.
+pub(crate) fn enable_rx_timestamps(fd: RawFd) -> io::Result<()> {
+    let enabled: libc::c_int = 1;
+    // SAFETY: option value points to a valid c_int for the duration of the call
+    let res = unsafe {
+        libc::setsockopt(
+            fd,
+            libc::SOL_SOCKET,
+            libc::SO_TIMESTAMPNS,
+            &enabled as *const libc::c_int as *const libc::c_void,
+            std::mem::size_of::<libc::c_int>() as libc::socklen_t,
+        )
+    };
+    if res < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Receives into the `buf` returning the number of bytes read together with the kernel receive
+/// timestamp, if present.
+pub(crate) fn recv_timestamped(fd: RawFd, buf: &mut [u8]) -> io::Result<(usize, Option<u64>)> {
+    // large enough for the single timespec control message
+    let mut control = [0u64; 8];
+    let mut iov = libc::iovec {
+        iov_base: buf.as_mut_ptr() as *mut libc::c_void,
+        iov_len: buf.len(),
+    };
+    // SAFETY: zeroed msghdr is valid, the pointers set below outlive the call
+    let mut msg: libc::msghdr = unsafe { std::mem::zeroed() };
+    msg.msg_iov = &mut iov;
+    msg.msg_iovlen = 1;
+    msg.msg_control = control.as_mut_ptr() as *mut libc::c_void;
+    msg.msg_controllen = std::mem::size_of_val(&control) as _;
+
+    // SAFETY: msghdr points to the valid buffers
+    let read = unsafe { libc::recvmsg(fd, &mut msg, 0) };
+    if read < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    let mut rx_timestamp_ns = None;
+    // SAFETY: control messages are walked within the bounds reported by the kernel
+    unsafe {
+        let mut cmsg = libc::CMSG_FIRSTHDR(&msg);
+        while !cmsg.is_null() {
+            if (*cmsg).cmsg_level == libc::SOL_SOCKET && (*cmsg).cmsg_type == libc::SCM_TIMESTAMPNS {
+                let ts = std::ptr::read_unaligned(libc::CMSG_DATA(cmsg) as *const libc::timespec);
+                rx_timestamp_ns = Some(ts.tv_sec as u64 * 1_000_000_000 + ts.tv_nsec as u64);
+            }
+            cmsg = libc::CMSG_NXTHDR(&msg, cmsg);
+        }
+    }
+    Ok((read as usize, rx_timestamp_ns))
+}
+
+/// Wraps the socket and records the kernel receive timestamp of the last read.
+pub struct TimestampedStream<S> {
+    inner: S,
+    rx_timestamp_ns: Option<u64>,
+}
+
+impl<S: AsRawFd> TimestampedStream<S> {
+    /// Enables the receive timestamps on the `inner` socket.
+    pub fn new(inner: S) -> io::Result<TimestampedStream<S>> {
+        enable_rx_timestamps(inner.as_raw_fd())?;
+        Ok(Self {
+            inner,
+            rx_timestamp_ns: None,
+        })
+    }
+}
+
+impl<S> TimestampedStream<S> {
+    pub fn inner(&self) -> &S {
+        &self.inner
+    }
+
+    pub fn inner_mut(&mut self) -> &mut S {
+        &mut self.inner
+    }
+}
+
+impl<S: AsRawFd> Read for TimestampedStream<S> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let (read, rx_timestamp_ns) = recv_timestamped(self.inner.as_raw_fd(), buf)?;
+        if rx_timestamp_ns.is_some() {
+            self.rx_timestamp_ns = rx_timestamp_ns;
+        }
+        Ok(read)
+    }
+}
+
+impl<S: Write> Write for TimestampedStream<S> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.inner.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(feature = "mio")]
+impl<S: Source> Source for TimestampedStream<S> {
+    fn register(&mut self, registry: &Registry, token: Token, interests: Interest) -> io::Result<()> {
+        registry.register(&mut self.inner, token, interests)
+    }
+
+    fn reregister(&mut self, registry: &Registry, token: Token, interests: Interest) -> io::Result<()> {
+        registry.reregister(&mut self.inner, token, interests)
+    }
+
+    fn deregister(&mut self, registry: &Registry) -> io::Result<()> {
+        registry.deregister(&mut self.inner)
+    }
+}
+
+impl<S: Selectable> Selectable for TimestampedStream<S> {
+    fn connected(&mut self) -> io::Result<bool> {
+        self.inner.connected()
+    }
+
+    fn make_writable(&mut self) {
+        self.inner.make_writable()
+    }
+
+    fn make_readable(&mut self) {
+        self.inner.make_readable()
+    }
+
+    fn make_unreadable(&mut self) {
+        self.inner.make_unreadable()
+    }
+
+    fn socket_queues(&self) -> Option<SocketQueues> {
+        self.inner.socket_queues()
+    }
+
+    fn rx_timestamp_ns(&self) -> Option<u64> {
+        self.rx_timestamp_ns
+    }
+
+    #[cfg(feature = "stats")]
+    fn io_counters(&self) -> Option<crate::stream::IoCounters> {
+        self.inner.io_counters()
+    }
+
+    fn apply_socket_options(&mut self, options: &SocketOptions) -> io::Result<()> {
+        self.inner.apply_socket_options(options)
+    }
+
+    fn has_pending_writes(&self) -> bool {
+        self.inner.has_pending_writes()
+    }
+
+    fn flush_pending_writes(&mut self) -> io::Result<()> {
+        self.inner.flush_pending_writes()
+    }
+
+    fn peer_closed(&self) -> bool {
+        self.inner.peer_closed()
+    }
+
+    fn write_closed(&self) -> bool {
+        self.inner.write_closed()
+    }
+
+    fn make_write_closed(&mut self) {
+        self.inner.make_write_closed()
+    }
+
+    fn take_socket_error(&mut self) -> io::Result<Option<io::Error>> {
+        self.inner.take_socket_error()
+    }
+
+    fn connect_error(&self) -> Option<io::Error> {
+        self.inner.connect_error()
+    }
+}
+
+pub trait IntoTimestampedStream {
+    fn into_timestamped_stream(self) -> io::Result<TimestampedStream<Self>>
+    where
+        Self: Sized;
+}
+
+impl<T: AsRawFd> IntoTimestampedStream for T {
+    fn into_timestamped_stream(self) -> io::Result<TimestampedStream<Self>>
+    where
+        Self: Sized,
+    {
+        TimestampedStream::new(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{TcpListener, TcpStream};
+    use std::time::{Duration, Instant};
+
+    use crate::util::current_time_nanos;
+
+    use super::*;
+
+    #[test]
+    fn should_report_rx_timestamp() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let before_ns = current_time_nanos();
+        let mut client = TcpStream::connect(listener.local_addr().unwrap())
+            .unwrap()
+            .into_timestamped_stream()
+            .unwrap();
+        let (mut server, _) = listener.accept().unwrap();
+        assert_eq!(None, client.rx_timestamp_ns());
+
+        // the kernel enables the timestamps lazily, so the first packets may arrive without one
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let mut buf = [0u8; 16];
+        let rx_timestamp_ns = loop {
+            server.write_all(b"hello").unwrap();
+            assert_eq!(5, client.read(&mut buf).unwrap());
+            match client.rx_timestamp_ns() {
+                Some(rx_timestamp_ns) => break rx_timestamp_ns,
+                None if Instant::now() < deadline => std::thread::sleep(Duration::from_millis(1)),
+                None => panic!("no rx timestamp reported"),
+            }
+        };
+        assert!(rx_timestamp_ns >= before_ns && rx_timestamp_ns <= current_time_nanos());
+    }
+
+    #[test]
+    fn should_forward_pending_writes() {
+        // socket that queues the writes until flushed by the selector
+        struct QueuedStream {
+            socket: TcpStream,
+            pending: bool,
+        }
+
+        impl AsRawFd for QueuedStream {
+            fn as_raw_fd(&self) -> RawFd {
+                self.socket.as_raw_fd()
+            }
+        }
+
+        impl Selectable for QueuedStream {
+            fn connected(&mut self) -> io::Result<bool> {
+                Ok(true)
+            }
+
+            fn make_writable(&mut self) {}
+
+            fn make_readable(&mut self) {}
+
+            fn has_pending_writes(&self) -> bool {
+                self.pending
+            }
+
+            fn flush_pending_writes(&mut self) -> io::Result<()> {
+                self.pending = false;
+                Ok(())
+            }
+        }
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let socket = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let mut stream = QueuedStream { socket, pending: true }
+            .into_timestamped_stream()
+            .unwrap();
+        assert!(stream.has_pending_writes());
+        stream.flush_pending_writes().unwrap();
+        assert!(!stream.has_pending_writes());
+    }
+}
@@ -1,23 +1,43 @@
 use std::io;
-use std::io::ErrorKind::Other;
-use std::io::{Read, Write};
+use std::io::ErrorKind::{InvalidInput, Other};
+use std::io::{IoSlice, Read, Write};
 use std::net::TcpStream;
 use std::sync::Arc;
 
 #[cfg(feature = "mio")]
 use mio::{event::Source, Interest, Registry, Token};
-use rustls::{ClientConnection, RootCertStore};
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::client::WebPkiServerVerifier;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName, UnixTime};
+use rustls::{CertificateError, ClientConfig, ClientConnection, DigitallySignedStruct, RootCertStore, SignatureScheme};
 
+use crate::endpoint::ConnectionInfo;
 use crate::select::Selectable;
 use crate::stream::buffer::BufferedStream;
+use crate::stream::chaos::FaultyStream;
+use crate::stream::file::FileStream;
 #[cfg(feature = "mio")]
 use crate::stream::mio::MioStream;
+#[cfg(feature = "proxy")]
+use crate::stream::proxy::{HttpProxyStream, Socks5Stream};
 use crate::stream::record::RecordedStream;
+#[cfg(target_os = "linux")]
+use crate::stream::timestamp::TimestampedStream;
+use crate::stream::{
+    ConnectionInfoProvider, ConnectionProperties, ConnectionPropertiesProvider, Cork, SocketOptions, SocketQueues,
+    TlsProperties,
+};
 use crate::util::NoBlock;
 
+/// Maximum plaintext length of single TLS record, the corked data is released once it fills the record.
+const MAX_RECORD_PLAINTEXT: usize = 16 * 1024;
+
 pub struct TlsStream<S> {
     stream: S,
     tls: ClientConnection,
+    corked: bool,
+    // plaintext held back while corked, see `Cork`
+    corked_data: Vec<u8>,
 }
 
 #[cfg(feature = "mio")]
@@ -47,6 +67,78 @@ impl<S: Selectable> Selectable for TlsStream<S> {
     fn make_readable(&mut self) {
         self.stream.make_readable()
     }
+
+    fn make_unreadable(&mut self) {
+        self.stream.make_unreadable()
+    }
+
+    fn socket_queues(&self) -> Option<SocketQueues> {
+        self.stream.socket_queues()
+    }
+
+    fn rx_timestamp_ns(&self) -> Option<u64> {
+        self.stream.rx_timestamp_ns()
+    }
+
+    #[cfg(feature = "stats")]
+    fn io_counters(&self) -> Option<crate::stream::IoCounters> {
+        self.stream.io_counters()
+    }
+
+    fn apply_socket_options(&mut self, options: &SocketOptions) -> io::Result<()> {
+        self.stream.apply_socket_options(options)
+    }
+
+    fn has_pending_writes(&self) -> bool {
+        self.stream.has_pending_writes()
+    }
+
+    fn flush_pending_writes(&mut self) -> io::Result<()> {
+        self.stream.flush_pending_writes()
+    }
+
+    fn peer_closed(&self) -> bool {
+        self.stream.peer_closed()
+    }
+
+    fn write_closed(&self) -> bool {
+        self.stream.write_closed()
+    }
+
+    fn make_write_closed(&mut self) {
+        self.stream.make_write_closed()
+    }
+
+    fn take_socket_error(&mut self) -> io::Result<Option<io::Error>> {
+        self.stream.take_socket_error()
+    }
+
+    fn connect_error(&self) -> Option<io::Error> {
+        self.stream.connect_error()
+    }
+}
+
+impl<S: ConnectionInfoProvider> ConnectionInfoProvider for TlsStream<S> {
+    fn connection_info(&self) -> &ConnectionInfo {
+        self.stream.connection_info()
+    }
+}
+
+impl<S: ConnectionPropertiesProvider> ConnectionPropertiesProvider for TlsStream<S> {
+    fn connection_properties(&self) -> ConnectionProperties {
+        let tls = match (self.tls.is_handshaking(), self.tls.protocol_version(), self.tls.negotiated_cipher_suite()) {
+            (false, Some(version), Some(cipher_suite)) => Some(TlsProperties {
+                version: format!("{:?}", version),
+                cipher_suite: format!("{:?}", cipher_suite.suite()),
+                alpn_protocol: self.tls.alpn_protocol().map(|protocol| protocol.to_vec()),
+            }),
+            _ => None,
+        };
+        ConnectionProperties {
+            tls,
+            ..self.stream.connection_properties()
+        }
+    }
 }
 
 impl<S: Read + Write> Read for TlsStream<S> {
@@ -58,39 +150,86 @@ impl<S: Read + Write> Read for TlsStream<S> {
 
 impl<S: Read + Write> Write for TlsStream<S> {
     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.corked {
+            self.corked_data.extend_from_slice(buf);
+            self.release_full_record()?;
+            return Ok(buf.len());
+        }
         self.tls.writer().write(buf)
     }
 
+    /// The buffers are encrypted together, so that small writes (such as the frame header and its
+    /// payload) do not produce separate records.
+    fn write_vectored(&mut self, bufs: &[IoSlice<'_>]) -> io::Result<usize> {
+        if self.corked {
+            let mut len = 0;
+            for buf in bufs {
+                self.corked_data.extend_from_slice(buf);
+                len += buf.len();
+            }
+            self.release_full_record()?;
+            return Ok(len);
+        }
+        self.tls.writer().write_vectored(bufs)
+    }
+
     fn flush(&mut self) -> io::Result<()> {
+        if self.corked {
+            return Ok(());
+        }
         self.tls.writer().flush()
     }
 }
 
-impl<S: Read + Write> TlsStream<S> {
-    pub fn wrap(stream: S, server_name: &str) -> TlsStream<S> {
-        #[cfg(not(all(feature = "rustls-native-certs", feature = "webpki-roots")))]
-        let mut root_store = RootCertStore::empty();
-
-        #[cfg(all(feature = "rustls-native-certs", feature = "webpki-roots"))]
-        let root_store = RootCertStore::empty();
-
-        #[cfg(all(feature = "webpki-roots", not(feature = "rustls-native-certs")))]
-        root_store.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+/// While corked the plaintext is held back and encrypted on [`Cork::uncork`], so that the frames
+/// written in the meantime share the TLS records (and their overhead) instead of producing one record
+/// each. The data is also released as soon as it fills the whole record.
+impl<S: Read + Write> Cork for TlsStream<S> {
+    fn cork(&mut self) {
+        self.corked = true;
+    }
 
-        #[cfg(all(feature = "rustls-native-certs", not(feature = "webpki-roots")))]
-        {
-            for cert in rustls_native_certs::load_native_certs().expect("could not load platform certs") {
-                root_store.add(cert).unwrap();
-            }
+    fn uncork(&mut self) -> io::Result<()> {
+        self.corked = false;
+        if !self.corked_data.is_empty() {
+            self.tls.writer().write_all(&self.corked_data)?;
+            self.corked_data.clear();
         }
+        self.flush()
+    }
 
-        let config = rustls::ClientConfig::builder()
-            .with_root_certificates(root_store)
-            .with_no_client_auth();
+    fn is_corked(&self) -> bool {
+        self.corked
+    }
+}
 
-        let tls = ClientConnection::new(Arc::new(config), server_name.to_owned().try_into().unwrap()).unwrap();
+impl<S: Read + Write> TlsStream<S> {
+    pub fn wrap(stream: S, server_name: &str) -> TlsStream<S> {
+        Self::wrap_with_config(stream, server_name, &TlsConfig::default()).unwrap()
+    }
 
-        Self { stream, tls }
+    /// Wraps the `stream` using additional settings provided by the [`TlsConfig`].
+    pub fn wrap_with_config(stream: S, server_name: &str, config: &TlsConfig) -> io::Result<TlsStream<S>> {
+        let server_name = server_name
+            .to_owned()
+            .try_into()
+            .map_err(|err| io::Error::new(InvalidInput, err))?;
+        let tls = ClientConnection::new(Arc::new(config.client_config()?), server_name).map_err(io::Error::other)?;
+        Ok(Self {
+            stream,
+            tls,
+            corked: false,
+            corked_data: Vec::new(),
+        })
+    }
+
+    #[inline]
+    fn release_full_record(&mut self) -> io::Result<()> {
+        if self.corked_data.len() >= MAX_RECORD_PLAINTEXT {
+            self.tls.writer().write_all(&self.corked_data)?;
+            self.corked_data.clear();
+        }
+        Ok(())
     }
 
     fn complete_io(&mut self) -> io::Result<(usize, usize)> {
@@ -116,6 +255,150 @@ impl<S: Read + Write> TlsStream<S> {
     }
 }
 
+/// Additional TLS client settings such as certificate pinning and client authentication (mTLS).
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::net::TcpStream;
+/// use boomnet::stream::tls::{IntoTlsStream, TlsConfig};
+///
+/// let config = TlsConfig::new()
+///     .with_pinned_cert(std::fs::read("exchange.der").unwrap())
+///     .with_client_cert(std::fs::read("client.der").unwrap(), std::fs::read("client.key.der").unwrap());
+/// let stream = TcpStream::connect("gateway.example.com:443")
+///     .unwrap()
+///     .into_tls_stream_with_config("gateway.example.com", &config)
+///     .unwrap();
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct TlsConfig {
+    pinned_certs: Vec<Vec<u8>>,
+    client_cert: Option<(Vec<u8>, Vec<u8>)>,
+}
+
+impl TlsConfig {
+    pub fn new() -> TlsConfig {
+        Self::default()
+    }
+
+    /// Pin the DER encoded server certificate. The chain presented by the server is still verified
+    /// against the root certificates, in addition the end entity certificate must match one of the
+    /// pinned certificates. Can be called multiple times to allow certificate rotation.
+    pub fn with_pinned_cert(self, der: impl Into<Vec<u8>>) -> TlsConfig {
+        let mut pinned_certs = self.pinned_certs;
+        pinned_certs.push(der.into());
+        Self { pinned_certs, ..self }
+    }
+
+    /// Authenticate with the DER encoded client certificate and its private key (PKCS#1, PKCS#8 or SEC1).
+    pub fn with_client_cert(self, cert: impl Into<Vec<u8>>, key: impl Into<Vec<u8>>) -> TlsConfig {
+        Self {
+            client_cert: Some((cert.into(), key.into())),
+            ..self
+        }
+    }
+
+    fn client_config(&self) -> io::Result<ClientConfig> {
+        let root_store = Arc::new(root_store());
+        let builder = if self.pinned_certs.is_empty() {
+            ClientConfig::builder().with_root_certificates(root_store)
+        } else {
+            let verifier = PinnedCertVerifier {
+                inner: WebPkiServerVerifier::builder(root_store)
+                    .build()
+                    .map_err(io::Error::other)?,
+                pinned_certs: self.pinned_certs.clone(),
+            };
+            ClientConfig::builder()
+                .dangerous()
+                .with_custom_certificate_verifier(Arc::new(verifier))
+        };
+        match &self.client_cert {
+            Some((cert, key)) => {
+                let key = PrivateKeyDer::try_from(key.clone()).map_err(|err| io::Error::new(InvalidInput, err))?;
+                builder
+                    .with_client_auth_cert(vec![CertificateDer::from(cert.clone())], key)
+                    .map_err(|err| io::Error::new(InvalidInput, err))
+            }
+            None => Ok(builder.with_no_client_auth()),
+        }
+    }
+}
+
+fn root_store() -> RootCertStore {
+    #[cfg(not(all(feature = "rustls-native-certs", feature = "webpki-roots")))]
+    let mut root_store = RootCertStore::empty();
+
+    #[cfg(all(feature = "rustls-native-certs", feature = "webpki-roots"))]
+    let root_store = RootCertStore::empty();
+
+    #[cfg(all(feature = "webpki-roots", not(feature = "rustls-native-certs")))]
+    root_store.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+
+    #[cfg(all(feature = "rustls-native-certs", not(feature = "webpki-roots")))]
+    {
+        for cert in rustls_native_certs::load_native_certs().expect("could not load platform certs") {
+            root_store.add(cert).unwrap();
+        }
+    }
+
+    root_store
+}
+
+/// Verifies the server certificate with the webpki verifier and then checks the end entity
+/// certificate against the pinned certificates.
+#[derive(Debug)]
+struct PinnedCertVerifier {
+    inner: Arc<WebPkiServerVerifier>,
+    pinned_certs: Vec<Vec<u8>>,
+}
+
+impl ServerCertVerifier for PinnedCertVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        ocsp_response: &[u8],
+        now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        let verified = self
+            .inner
+            .verify_server_cert(end_entity, intermediates, server_name, ocsp_response, now)?;
+        match self
+            .pinned_certs
+            .iter()
+            .any(|pinned| pinned.as_slice() == end_entity.as_ref())
+        {
+            true => Ok(verified),
+            false => Err(rustls::Error::InvalidCertificate(CertificateError::ApplicationVerificationFailure)),
+        }
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls12_signature(message, cert, dss)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls13_signature(message, cert, dss)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.inner.supported_verify_schemes()
+    }
+}
+
 #[allow(clippy::large_enum_variant)]
 pub enum TlsReadyStream<S> {
     Plain(S),
@@ -139,6 +422,13 @@ impl<S: Read + Write> Write for TlsReadyStream<S> {
         }
     }
 
+    fn write_vectored(&mut self, bufs: &[IoSlice<'_>]) -> io::Result<usize> {
+        match self {
+            TlsReadyStream::Plain(stream) => stream.write_vectored(bufs),
+            TlsReadyStream::Tls(stream) => stream.write_vectored(bufs),
+        }
+    }
+
     fn flush(&mut self) -> io::Result<()> {
         match self {
             TlsReadyStream::Plain(stream) => stream.flush(),
@@ -192,6 +482,100 @@ impl<S: Selectable> Selectable for TlsReadyStream<S> {
             TlsReadyStream::Tls(stream) => stream.make_readable(),
         }
     }
+
+    fn make_unreadable(&mut self) {
+        match self {
+            TlsReadyStream::Plain(stream) => stream.make_unreadable(),
+            TlsReadyStream::Tls(stream) => stream.make_unreadable(),
+        }
+    }
+
+    fn socket_queues(&self) -> Option<SocketQueues> {
+        match self {
+            TlsReadyStream::Plain(stream) => stream.socket_queues(),
+            TlsReadyStream::Tls(stream) => stream.socket_queues(),
+        }
+    }
+
+    fn rx_timestamp_ns(&self) -> Option<u64> {
+        match self {
+            TlsReadyStream::Plain(stream) => stream.rx_timestamp_ns(),
+            TlsReadyStream::Tls(stream) => stream.rx_timestamp_ns(),
+        }
+    }
+
+    #[cfg(feature = "stats")]
+    fn io_counters(&self) -> Option<crate::stream::IoCounters> {
+        match self {
+            TlsReadyStream::Plain(stream) => stream.io_counters(),
+            TlsReadyStream::Tls(stream) => stream.io_counters(),
+        }
+    }
+
+    fn apply_socket_options(&mut self, options: &SocketOptions) -> io::Result<()> {
+        match self {
+            TlsReadyStream::Plain(stream) => stream.apply_socket_options(options),
+            TlsReadyStream::Tls(stream) => stream.apply_socket_options(options),
+        }
+    }
+
+    fn has_pending_writes(&self) -> bool {
+        match self {
+            TlsReadyStream::Plain(stream) => stream.has_pending_writes(),
+            TlsReadyStream::Tls(stream) => stream.has_pending_writes(),
+        }
+    }
+
+    fn flush_pending_writes(&mut self) -> io::Result<()> {
+        match self {
+            TlsReadyStream::Plain(stream) => stream.flush_pending_writes(),
+            TlsReadyStream::Tls(stream) => stream.flush_pending_writes(),
+        }
+    }
+
+    fn peer_closed(&self) -> bool {
+        match self {
+            TlsReadyStream::Plain(stream) => stream.peer_closed(),
+            TlsReadyStream::Tls(stream) => stream.peer_closed(),
+        }
+    }
+
+    fn write_closed(&self) -> bool {
+        match self {
+            TlsReadyStream::Plain(stream) => stream.write_closed(),
+            TlsReadyStream::Tls(stream) => stream.write_closed(),
+        }
+    }
+
+    fn make_write_closed(&mut self) {
+        match self {
+            TlsReadyStream::Plain(stream) => stream.make_write_closed(),
+            TlsReadyStream::Tls(stream) => stream.make_write_closed(),
+        }
+    }
+
+    fn take_socket_error(&mut self) -> io::Result<Option<io::Error>> {
+        match self {
+            TlsReadyStream::Plain(stream) => stream.take_socket_error(),
+            TlsReadyStream::Tls(stream) => stream.take_socket_error(),
+        }
+    }
+
+    fn connect_error(&self) -> Option<io::Error> {
+        match self {
+            TlsReadyStream::Plain(stream) => stream.connect_error(),
+            TlsReadyStream::Tls(stream) => stream.connect_error(),
+        }
+    }
+}
+
+impl<S: ConnectionPropertiesProvider> ConnectionPropertiesProvider for TlsReadyStream<S> {
+    fn connection_properties(&self) -> ConnectionProperties {
+        match self {
+            TlsReadyStream::Plain(stream) => stream.connection_properties(),
+            TlsReadyStream::Tls(stream) => stream.connection_properties(),
+        }
+    }
 }
 
 pub trait NotTlsStream {}
@@ -202,13 +586,30 @@ impl<S> NotTlsStream for RecordedStream<S> {}
 
 impl<S> NotTlsStream for BufferedStream<S> {}
 
+impl<S> NotTlsStream for FaultyStream<S> {}
+
+impl<R> NotTlsStream for FileStream<R> {}
+
+#[cfg(target_os = "linux")]
+impl<S> NotTlsStream for TimestampedStream<S> {}
+
 #[cfg(feature = "mio")]
 impl NotTlsStream for MioStream {}
 
+#[cfg(feature = "proxy")]
+impl<S> NotTlsStream for HttpProxyStream<S> {}
+
+#[cfg(feature = "proxy")]
+impl<S> NotTlsStream for Socks5Stream<S> {}
+
 pub trait IntoTlsStream {
     fn into_tls_stream(self, server_name: &str) -> TlsStream<Self>
     where
         Self: Sized;
+
+    fn into_tls_stream_with_config(self, server_name: &str, config: &TlsConfig) -> io::Result<TlsStream<Self>>
+    where
+        Self: Sized;
 }
 
 impl<T> IntoTlsStream for T
@@ -221,4 +622,46 @@ where
     {
         TlsStream::wrap(self, server_name)
     }
+
+    fn into_tls_stream_with_config(self, server_name: &str, config: &TlsConfig) -> io::Result<TlsStream<Self>>
+    where
+        Self: Sized,
+    {
+        TlsStream::wrap_with_config(self, server_name, config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    #[test]
+    fn should_reject_invalid_client_key() {
+        let config = TlsConfig::new().with_client_cert(vec![0x30, 0x00], vec![0x01, 0x02]);
+        let err = config.client_config().unwrap_err();
+        assert_eq!(InvalidInput, err.kind());
+    }
+
+    #[test]
+    fn should_hold_back_data_while_corked() {
+        let mut stream = TlsStream::wrap(Cursor::new(Vec::new()), "example.com");
+        stream.cork();
+        stream.write_all(b"hello").unwrap();
+        let written = stream
+            .write_vectored(&[IoSlice::new(b" "), IoSlice::new(b"world")])
+            .unwrap();
+        assert_eq!(6, written);
+        stream.flush().unwrap();
+        assert_eq!(b"hello world", stream.corked_data.as_slice());
+
+        // the data that fills the whole record is released
+        stream.write_all(&[0u8; MAX_RECORD_PLAINTEXT]).unwrap();
+        assert!(stream.corked_data.is_empty());
+        stream.write_all(b"hello").unwrap();
+        stream.uncork().unwrap();
+        assert!(!stream.is_corked());
+        assert!(stream.corked_data.is_empty());
+    }
 }
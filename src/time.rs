@@ -0,0 +1,100 @@
+//! Time sources used by the `IOService` to drive timeouts and deadlines.
+
+use std::cell::Cell;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+use crate::util::current_time_nanos;
+
+/// Source of the current time in nanoseconds.
+pub trait TimeSource {
+    /// Returns current time in nanoseconds.
+    fn current_time_nanos(&self) -> u64;
+}
+
+/// Wall clock time since the UNIX epoch. Subject to clock adjustments (e.g. NTP steps).
+#[derive(Debug, Default, Copy, Clone)]
+pub struct SystemTimeClockSource;
+
+impl TimeSource for SystemTimeClockSource {
+    #[inline]
+    fn current_time_nanos(&self) -> u64 {
+        current_time_nanos()
+    }
+}
+
+/// Monotonic clock that is not affected by the wall clock adjustments. The returned value is
+/// anchored to the wall clock time at the moment of creation, so it remains comparable with
+/// the UNIX epoch based timestamps (for as long as the wall clock does not drift).
+#[derive(Debug, Copy, Clone)]
+pub struct MonotonicClockSource {
+    origin: Instant,
+    origin_time_ns: u64,
+}
+
+impl MonotonicClockSource {
+    pub fn new() -> MonotonicClockSource {
+        Self {
+            origin: Instant::now(),
+            origin_time_ns: current_time_nanos(),
+        }
+    }
+}
+
+impl Default for MonotonicClockSource {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TimeSource for MonotonicClockSource {
+    #[inline]
+    fn current_time_nanos(&self) -> u64 {
+        self.origin_time_ns + self.origin.elapsed().as_nanos() as u64
+    }
+}
+
+/// Time source controlled programmatically, intended for tests. Clones share the same time so
+/// one can be handed over to the `IOService` while the other is used to advance the time.
+///
+/// # Examples
+///
+/// ```
+/// use std::time::Duration;
+/// use boomnet::time::{ManualTimeSource, TimeSource};
+///
+/// let time_source = ManualTimeSource::new(1_000);
+/// let handle = time_source.clone();
+/// handle.advance(Duration::from_nanos(500));
+/// assert_eq!(1_500, time_source.current_time_nanos());
+/// ```
+#[derive(Debug, Default, Clone)]
+pub struct ManualTimeSource {
+    time_ns: Rc<Cell<u64>>,
+}
+
+impl ManualTimeSource {
+    /// Creates time source starting at `time_ns`.
+    pub fn new(time_ns: u64) -> ManualTimeSource {
+        Self {
+            time_ns: Rc::new(Cell::new(time_ns)),
+        }
+    }
+
+    /// Sets the current time.
+    pub fn set(&self, time_ns: u64) {
+        self.time_ns.set(time_ns);
+    }
+
+    /// Moves the current time forward by `duration`.
+    pub fn advance(&self, duration: Duration) {
+        self.time_ns.set(self.time_ns.get() + duration.as_nanos() as u64);
+    }
+}
+
+impl TimeSource for ManualTimeSource {
+    #[inline]
+    fn current_time_nanos(&self) -> u64 {
+        self.time_ns.get()
+    }
+}
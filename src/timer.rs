@@ -0,0 +1,216 @@
+//! Hierarchical timer wheel used by the `IOService` to deliver user timers.
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+const SLOT_BITS: u32 = 6;
+const SLOTS: usize = 1 << SLOT_BITS;
+const SLOT_MASK: u64 = SLOTS as u64 - 1;
+const LEVELS: usize = 4;
+
+/// Default resolution of the timer wheel.
+pub const DEFAULT_TICK: Duration = Duration::from_millis(1);
+
+/// Identifies timer scheduled with the [`TimerWheel`].
+pub type TimerId = u64;
+
+/// Hierarchical timer wheel with four levels of 64 slots each, so that both
+/// scheduling and expiring a timer is `O(1)` regardless of the number of timers. Timers due
+/// beyond the wheel horizon are kept in the overflow list and moved into the wheel as the time
+/// advances. Timers fire on the tick boundary, so the resolution is determined by the tick length.
+///
+/// # Examples
+///
+/// ```
+/// use std::time::Duration;
+/// use boomnet::timer::TimerWheel;
+///
+/// let mut wheel = TimerWheel::new(Duration::from_millis(1), 0);
+/// let id = wheel.schedule(5_000_000, None, "one-shot");
+///
+/// let mut fired = Vec::new();
+/// wheel.advance(4_000_000, |id, value| fired.push((id, *value)));
+/// assert!(fired.is_empty());
+/// wheel.advance(5_000_000, |id, value| fired.push((id, *value)));
+/// assert_eq!(vec![(id, "one-shot")], fired);
+/// ```
+#[derive(Debug)]
+pub struct TimerWheel<T> {
+    tick_ns: u64,
+    current_tick: u64,
+    levels: [Vec<Vec<TimerId>>; LEVELS],
+    overflow: Vec<TimerId>,
+    timers: HashMap<TimerId, Timer<T>>,
+    next_id: TimerId,
+}
+
+#[derive(Debug)]
+struct Timer<T> {
+    deadline_tick: u64,
+    period_ticks: Option<u64>,
+    value: T,
+}
+
+impl<T> TimerWheel<T> {
+    /// Creates new timer wheel with the given `tick` resolution, starting at `current_time_ns`.
+    pub fn new(tick: Duration, current_time_ns: u64) -> TimerWheel<T> {
+        let tick_ns = (tick.as_nanos() as u64).max(1);
+        Self {
+            tick_ns,
+            current_tick: current_time_ns / tick_ns,
+            levels: std::array::from_fn(|_| vec![Vec::new(); SLOTS]),
+            overflow: Vec::new(),
+            timers: HashMap::new(),
+            next_id: 0,
+        }
+    }
+
+    /// Number of active timers.
+    pub fn len(&self) -> usize {
+        self.timers.len()
+    }
+
+    /// Checks if there are no active timers.
+    pub fn is_empty(&self) -> bool {
+        self.timers.is_empty()
+    }
+
+    /// Schedules timer to fire at `deadline_ns` and then (optionally) every `period`.
+    pub fn schedule(&mut self, deadline_ns: u64, period: Option<Duration>, value: T) -> TimerId {
+        let id = self.next_id;
+        self.next_id += 1;
+        let deadline_tick = (deadline_ns / self.tick_ns).max(self.current_tick + 1);
+        let period_ticks = period.map(|period| (period.as_nanos() as u64 / self.tick_ns).max(1));
+        self.timers.insert(
+            id,
+            Timer {
+                deadline_tick,
+                period_ticks,
+                value,
+            },
+        );
+        self.place(id, deadline_tick);
+        id
+    }
+
+    /// Cancels the timer, returns `false` if the timer does not exist (or has already fired).
+    pub fn cancel(&mut self, id: TimerId) -> bool {
+        // slot entry is discarded lazily
+        self.timers.remove(&id).is_some()
+    }
+
+    /// Advances the wheel to `current_time_ns` invoking `on_expired` for every timer that is due.
+    /// Periodic timers are rescheduled automatically.
+    pub fn advance<F: FnMut(TimerId, &T)>(&mut self, current_time_ns: u64, mut on_expired: F) {
+        let target_tick = current_time_ns / self.tick_ns;
+        while self.current_tick < target_tick {
+            if self.timers.is_empty() {
+                self.current_tick = target_tick;
+                break;
+            }
+            self.current_tick += 1;
+            let tick = self.current_tick;
+
+            // move timers from the higher levels down as their slots come into range
+            for level in 1..LEVELS {
+                if tick & ((1 << (SLOT_BITS * level as u32)) - 1) != 0 {
+                    break;
+                }
+                let slot = ((tick >> (SLOT_BITS * level as u32)) & SLOT_MASK) as usize;
+                let ids = std::mem::take(&mut self.levels[level][slot]);
+                self.replace(ids);
+                if level == LEVELS - 1 && slot == 0 {
+                    let ids = std::mem::take(&mut self.overflow);
+                    self.replace(ids);
+                }
+            }
+
+            let slot = (tick & SLOT_MASK) as usize;
+            for id in std::mem::take(&mut self.levels[0][slot]) {
+                let timer = match self.timers.get_mut(&id) {
+                    Some(timer) => timer,
+                    None => continue,
+                };
+                on_expired(id, &timer.value);
+                match timer.period_ticks {
+                    Some(period_ticks) => {
+                        timer.deadline_tick = tick + period_ticks;
+                        let deadline_tick = timer.deadline_tick;
+                        self.place(id, deadline_tick);
+                    }
+                    None => {
+                        self.timers.remove(&id);
+                    }
+                }
+            }
+        }
+    }
+
+    fn replace(&mut self, ids: Vec<TimerId>) {
+        for id in ids {
+            if let Some(timer) = self.timers.get(&id) {
+                let deadline_tick = timer.deadline_tick;
+                self.place(id, deadline_tick);
+            }
+        }
+    }
+
+    fn place(&mut self, id: TimerId, deadline_tick: u64) {
+        let delta = deadline_tick.saturating_sub(self.current_tick).max(1);
+        for level in 0..LEVELS {
+            if delta < 1 << (SLOT_BITS * (level as u32 + 1)) {
+                let slot = ((deadline_tick >> (SLOT_BITS * level as u32)) & SLOT_MASK) as usize;
+                self.levels[level][slot].push(id);
+                return;
+            }
+        }
+        self.overflow.push(id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MS: u64 = 1_000_000;
+
+    fn advance(wheel: &mut TimerWheel<u32>, time_ns: u64) -> Vec<u32> {
+        let mut fired = Vec::new();
+        wheel.advance(time_ns, |_, value| fired.push(*value));
+        fired
+    }
+
+    #[test]
+    fn should_fire_timers_across_levels() {
+        let mut wheel = TimerWheel::new(DEFAULT_TICK, 0);
+        wheel.schedule(10 * MS, None, 1);
+        wheel.schedule(100 * MS, None, 2);
+        wheel.schedule(5_000 * MS, None, 3);
+        wheel.schedule(300_000 * MS, None, 4);
+        wheel.schedule(20_000_000 * MS, None, 5);
+
+        assert_eq!(Vec::<u32>::new(), advance(&mut wheel, 9 * MS));
+        assert_eq!(vec![1], advance(&mut wheel, 10 * MS));
+        assert_eq!(Vec::<u32>::new(), advance(&mut wheel, 99 * MS));
+        assert_eq!(vec![2], advance(&mut wheel, 4_999 * MS));
+        assert_eq!(vec![3], advance(&mut wheel, 5_000 * MS));
+        assert_eq!(vec![4], advance(&mut wheel, 300_000 * MS));
+        assert_eq!(Vec::<u32>::new(), advance(&mut wheel, 19_999_999 * MS));
+        assert_eq!(vec![5], advance(&mut wheel, 20_000_000 * MS));
+        assert!(wheel.is_empty());
+    }
+
+    #[test]
+    fn should_reschedule_periodic_timer() {
+        let mut wheel = TimerWheel::new(DEFAULT_TICK, 0);
+        let id = wheel.schedule(10 * MS, Some(Duration::from_millis(10)), 1);
+
+        assert_eq!(vec![1], advance(&mut wheel, 10 * MS));
+        assert_eq!(Vec::<u32>::new(), advance(&mut wheel, 19 * MS));
+        assert_eq!(vec![1, 1, 1], advance(&mut wheel, 40 * MS));
+
+        assert!(wheel.cancel(id));
+        assert!(!wheel.cancel(id));
+        assert_eq!(Vec::<u32>::new(), advance(&mut wheel, 100 * MS));
+    }
+}
@@ -0,0 +1,81 @@
+//! Integration with the [`tracing`] crate, available with the `tracing` feature. The websocket
+//! handshake, the decoded and sent frames as well as the connections created by the `IOService`
+//! are reported as events with the `boomnet` target, so the subscriber decides what is recorded
+//! and where. Frame events are emitted at the `TRACE` level and are sampled, as formatting every
+//! frame on the hot path is rarely affordable in production.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! // report every 1000th frame on each thread
+//! boomnet::trace::set_frame_sampling(1000);
+//! ```
+
+#[cfg(feature = "ws")]
+use std::cell::Cell;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Frame sampling used unless specified otherwise, see [`set_frame_sampling`].
+pub const DEFAULT_FRAME_SAMPLING: u64 = 1;
+
+static FRAME_SAMPLING: AtomicU64 = AtomicU64::new(DEFAULT_FRAME_SAMPLING);
+
+#[cfg(feature = "ws")]
+thread_local! {
+    static FRAME_COUNTER: Cell<u64> = const { Cell::new(0) };
+}
+
+/// Report one in `every` decoded or sent frames, the frames are counted per thread. Zero disables
+/// the frame events entirely while the handshake and connection events are still reported.
+pub fn set_frame_sampling(every: u64) {
+    FRAME_SAMPLING.store(every, Ordering::Relaxed);
+}
+
+/// Returns the current frame sampling, see [`set_frame_sampling`].
+pub fn frame_sampling() -> u64 {
+    FRAME_SAMPLING.load(Ordering::Relaxed)
+}
+
+/// Checks if the next frame should be reported, the sampling is consulted only if the `TRACE`
+/// level is enabled for the `boomnet` target.
+#[cfg(feature = "ws")]
+#[inline]
+pub(crate) fn sample_frame() -> bool {
+    if !tracing::enabled!(target: "boomnet", tracing::Level::TRACE) {
+        return false;
+    }
+    match frame_sampling() {
+        0 => false,
+        every => FRAME_COUNTER.with(|counter| {
+            let count = counter.get();
+            counter.set(count.wrapping_add(1));
+            count % every == 0
+        }),
+    }
+}
+
+/// Name of the frame type reported with the frame events.
+#[cfg(feature = "ws")]
+pub(crate) const fn frame_kind(frame: &crate::ws::WebsocketFrame) -> &'static str {
+    use crate::ws::WebsocketFrame;
+    match frame {
+        WebsocketFrame::Ping(..) => "ping",
+        WebsocketFrame::Pong(..) => "pong",
+        WebsocketFrame::Text(..) => "text",
+        WebsocketFrame::Binary(..) => "binary",
+        WebsocketFrame::Continuation(..) => "continuation",
+        WebsocketFrame::Close(..) => "close",
+    }
+}
+
+#[cfg(all(test, feature = "ws"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_not_sample_without_subscriber() {
+        set_frame_sampling(1);
+        assert!(!sample_frame());
+        assert_eq!(1, frame_sampling());
+    }
+}
@@ -1,5 +1,6 @@
 use std::io;
-use std::io::ErrorKind::{UnexpectedEof, WouldBlock};
+use std::io::ErrorKind::{Interrupted, UnexpectedEof, WouldBlock, WriteZero};
+use std::io::{IoSlice, Write};
 use std::time::{SystemTime, UNIX_EPOCH};
 
 pub trait NoBlock {
@@ -33,6 +34,42 @@ impl NoBlock for io::Result<()> {
     }
 }
 
+/// Maximum number of buffers passed to single `write_vectored` call by [`write_all_vectored`].
+const MAX_WRITE_SLICES: usize = 8;
+
+/// Writes all the `bufs` using the vectored writes, same as the unstable `Write::write_all_vectored`.
+pub fn write_all_vectored<W: Write + ?Sized>(writer: &mut W, bufs: &mut [&[u8]]) -> io::Result<()> {
+    let mut first = 0;
+    loop {
+        while bufs.get(first).is_some_and(|buf| buf.is_empty()) {
+            first += 1;
+        }
+        if first == bufs.len() {
+            return Ok(());
+        }
+        let mut slices = [IoSlice::new(&[]); MAX_WRITE_SLICES];
+        let count = (bufs.len() - first).min(MAX_WRITE_SLICES);
+        for (slice, buf) in slices.iter_mut().zip(&bufs[first..]) {
+            *slice = IoSlice::new(buf);
+        }
+        match writer.write_vectored(&slices[..count]) {
+            Ok(0) => return Err(io::Error::new(WriteZero, "failed to write whole buffer")),
+            Ok(mut written) => {
+                for buf in &mut bufs[first..] {
+                    let advance = written.min(buf.len());
+                    *buf = &buf[advance..];
+                    written -= advance;
+                    if written == 0 {
+                        break;
+                    }
+                }
+            }
+            Err(err) if err.kind() == Interrupted => {}
+            Err(err) => return Err(err),
+        }
+    }
+}
+
 #[inline]
 pub fn current_time_nanos() -> u64 {
     SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_nanos() as u64
@@ -1,17 +1,29 @@
-use std::io;
 use std::io::{Read, Write};
 
+use crate::buffer::{ReadBuffer, DEFAULT_INITIAL_CAPACITY};
 use crate::util::current_time_nanos;
-use crate::ws::{protocol, ReadBuffer, WebsocketFrame};
+use crate::ws::error::ProtocolError;
+#[cfg(feature = "stats")]
+use crate::ws::WebsocketStats;
+use crate::ws::{protocol, raw, Error, WebsocketFrame, DEFAULT_READ_CHUNK_SIZE};
 
 #[derive(Debug)]
-pub struct Decoder {
-    buffer: ReadBuffer,
+pub struct Decoder<
+    const CHUNK_SIZE: usize = DEFAULT_READ_CHUNK_SIZE,
+    const INITIAL_CAPACITY: usize = DEFAULT_INITIAL_CAPACITY,
+> {
+    buffer: ReadBuffer<CHUNK_SIZE, INITIAL_CAPACITY>,
     timestamp_ns: Option<u64>,
     decode_state: DecodeState,
     fin: bool,
     payload_length: usize,
     op_code: u8,
+    strict: bool,
+    // op code of the fragmented message awaiting the continuation frames
+    message_op_code: Option<u8>,
+    read_performed: bool,
+    #[cfg(feature = "stats")]
+    stats: WebsocketStats,
 }
 
 #[derive(Debug)]
@@ -23,7 +35,7 @@ enum DecodeState {
     ReadingPayload,
 }
 
-impl Decoder {
+impl<const CHUNK_SIZE: usize, const INITIAL_CAPACITY: usize> Decoder<CHUNK_SIZE, INITIAL_CAPACITY> {
     pub fn new() -> Self {
         Self {
             buffer: ReadBuffer::new(),
@@ -32,27 +44,102 @@ impl Decoder {
             fin: false,
             op_code: 0,
             payload_length: 0,
+            strict: true,
+            message_op_code: None,
+            read_performed: false,
+            #[cfg(feature = "stats")]
+            stats: WebsocketStats::default(),
         }
     }
 
+    /// Enable or disable RFC 6455 conformance checks.
+    pub fn set_strict(&mut self, strict: bool) {
+        self.strict = strict;
+    }
+
+    /// Returns the op code (text or binary) of the fragmented message in progress, if any. Control
+    /// frames may be interleaved with the fragments and do not affect it.
+    pub const fn in_progress_message_op_code(&self) -> Option<u8> {
+        self.message_op_code
+    }
+
+    /// Number of bytes received from the stream that have not been decoded yet.
+    pub const fn buffered(&self) -> usize {
+        self.buffer.available()
+    }
+
+    /// Number of complete frames that can be decoded from the buffered bytes without reading from
+    /// the stream. Reports zero while the frame header has been only partially received.
+    pub fn buffered_frames(&self) -> usize {
+        let mut view = self.buffer.view();
+        let mut frames = 0;
+        match self.decode_state {
+            DecodeState::ReadingHeader => {}
+            DecodeState::ReadingPayload if view.len() >= self.payload_length => {
+                view = &view[self.payload_length..];
+                frames += 1;
+            }
+            _ => return 0,
+        }
+        while let Some((_, _, len)) = raw::decode_frame(view) {
+            view = &view[len..];
+            frames += 1;
+        }
+        frames
+    }
+
+    /// Checks if any data has been read from the stream since the last `clear_read_performed`.
+    pub const fn read_performed(&self) -> bool {
+        self.read_performed
+    }
+
+    pub fn clear_read_performed(&mut self) {
+        self.read_performed = false;
+    }
+
+    #[cfg(feature = "stats")]
+    pub const fn stats(&self) -> &WebsocketStats {
+        &self.stats
+    }
+
+    #[cfg(feature = "stats")]
+    pub fn reset_stats(&mut self) {
+        self.stats = WebsocketStats::default();
+    }
+
     #[inline]
-    pub fn decode_next<S: Read + Write>(&mut self, stream: &mut S) -> io::Result<Option<WebsocketFrame>> {
+    pub fn decode_next<S: Read + Write>(&mut self, stream: &mut S) -> Result<Option<WebsocketFrame<'static>>, Error> {
         loop {
             let available = self.buffer.available();
             match self.decode_state {
                 DecodeState::ReadingHeader => {
+                    // fast path, decode the whole header at once when it is fully available
+                    if available >= 2 {
+                        let view = self.buffer.view();
+                        let (b0, b1) = (view[0], view[1]);
+                        let header_length = match b1 & protocol::PAYLOAD_LENGTH_MASK {
+                            0..=125 => 2,
+                            126 => 4,
+                            _ => 10,
+                        };
+                        if available >= header_length {
+                            let payload_length = match header_length {
+                                2 => (b1 & protocol::PAYLOAD_LENGTH_MASK) as usize,
+                                4 => u16::from_be_bytes([view[2], view[3]]) as usize,
+                                _ => u64::from_be_bytes(view[2..10].try_into().expect("incorrect length")) as usize,
+                            };
+                            self.buffer.consume_next(header_length);
+                            self.decode_first_byte(b0)?;
+                            Self::check_mask(b1)?;
+                            self.payload_length = payload_length;
+                            self.validate_frame()?;
+                            self.decode_state = DecodeState::ReadingPayload;
+                            continue;
+                        }
+                    }
                     if available > 0 {
                         let b = self.buffer.consume_next(1)[0];
-                        let fin = ((b & protocol::FIN_MASK) >> 7) == 1;
-                        let rsv1 = (b & protocol::RSV1_MASK) >> 6;
-                        let rsv2 = (b & protocol::RSV2_MASK) >> 5;
-                        let rsv3 = (b & protocol::RSV3_MASK) >> 4;
-                        if rsv1 + rsv2 + rsv3 > 0 {
-                            panic!("non zero RSV value received")
-                        }
-                        self.fin = fin;
-                        let op_code = b & protocol::OP_CODE_MASK;
-                        self.op_code = op_code;
+                        self.decode_first_byte(b)?;
                         self.decode_state = DecodeState::ReadingPayloadLength
                     } else {
                         break;
@@ -61,14 +148,14 @@ impl Decoder {
                 DecodeState::ReadingPayloadLength => {
                     if available > 0 {
                         let b = self.buffer.consume_next(1)[0];
-                        let mask = (b & protocol::MASK_MASK) >> 7;
-                        if mask == 1 {
-                            panic!("masking bit set on the server frame")
-                        }
+                        Self::check_mask(b)?;
                         let payload_length = b & protocol::PAYLOAD_LENGTH_MASK;
                         self.payload_length = payload_length as usize;
                         match payload_length {
-                            0..=125 => self.decode_state = DecodeState::ReadingPayload,
+                            0..=125 => {
+                                self.validate_frame()?;
+                                self.decode_state = DecodeState::ReadingPayload
+                            }
                             126 => self.decode_state = DecodeState::ReadingExtendedPayloadLength2,
                             127 => self.decode_state = DecodeState::ReadingExtendedPayloadLength8,
                             _ => {}
@@ -82,6 +169,7 @@ impl Decoder {
                         let bytes = self.buffer.consume_next(2);
                         let payload_length = u16::from_be_bytes(bytes.try_into().expect("incorrect length"));
                         self.payload_length = payload_length as usize;
+                        self.validate_frame()?;
                         self.decode_state = DecodeState::ReadingPayload;
                     } else {
                         break;
@@ -92,6 +180,7 @@ impl Decoder {
                         let bytes = self.buffer.consume_next(8);
                         let payload_length = u64::from_be_bytes(bytes.try_into().expect("incorrect length"));
                         self.payload_length = payload_length as usize;
+                        self.validate_frame()?;
                         self.decode_state = DecodeState::ReadingPayload;
                     } else {
                         break;
@@ -107,10 +196,21 @@ impl Decoder {
                             protocol::op::BINARY_FRAME => WebsocketFrame::Binary(ts, self.fin, payload),
                             protocol::op::CONTINUATION_FRAME => WebsocketFrame::Continuation(ts, self.fin, payload),
                             protocol::op::PING => WebsocketFrame::Ping(ts, payload),
-                            protocol::op::CONNECTION_CLOSE => WebsocketFrame::Close(ts, payload),
-                            _ => panic!("unknown op code: {}", self.op_code),
+                            protocol::op::PONG => WebsocketFrame::Pong(ts, payload),
+                            protocol::op::CONNECTION_CLOSE => {
+                                if self.strict {
+                                    Self::validate_close(payload)?;
+                                }
+                                WebsocketFrame::Close(ts, payload)
+                            }
+                            _ => unreachable!("op code is validated with the header"),
                         };
                         self.decode_state = DecodeState::ReadingHeader;
+                        #[cfg(feature = "stats")]
+                        {
+                            self.stats.frames_decoded += 1;
+                            self.stats.largest_frame = self.stats.largest_frame.max(payload_length);
+                        }
                         return Ok(Some(frame));
                     } else {
                         break;
@@ -120,8 +220,200 @@ impl Decoder {
         }
 
         // await for more data
+        let available = self.buffer.available();
         self.buffer.read_from(stream)?;
+        self.read_performed |= self.buffer.available() > available;
+        #[cfg(feature = "stats")]
+        {
+            self.stats.reads += 1;
+            self.stats.bytes_read += (self.buffer.available() - available) as u64;
+        }
         self.timestamp_ns.take();
         Ok(None)
     }
+
+    #[inline]
+    fn decode_first_byte(&mut self, b: u8) -> Result<(), ProtocolError> {
+        // no extensions are negotiated so the reserved bits must not be set
+        let rsv = b & (protocol::RSV1_MASK | protocol::RSV2_MASK | protocol::RSV3_MASK);
+        if rsv != 0 {
+            return Err(ProtocolError::ReservedBitsSet(rsv));
+        }
+        self.fin = b & protocol::FIN_MASK != 0;
+        self.op_code = b & protocol::OP_CODE_MASK;
+        match self.op_code {
+            protocol::op::CONTINUATION_FRAME
+            | protocol::op::TEXT_FRAME
+            | protocol::op::BINARY_FRAME
+            | protocol::op::CONNECTION_CLOSE
+            | protocol::op::PING
+            | protocol::op::PONG => Ok(()),
+            op_code => Err(ProtocolError::UnknownOpCode(op_code)),
+        }
+    }
+
+    #[inline]
+    fn check_mask(b: u8) -> Result<(), ProtocolError> {
+        if b & protocol::MASK_MASK != 0 {
+            return Err(ProtocolError::MaskedFrame);
+        }
+        Ok(())
+    }
+
+    /// Checks the close frame carries either no payload or the valid status code followed by
+    /// the UTF-8 reason (RFC 6455 section 5.5.1 and 7.4).
+    #[cold]
+    fn validate_close(payload: &[u8]) -> Result<(), ProtocolError> {
+        match payload {
+            [] => Ok(()),
+            [_] => Err(ProtocolError::InvalidClosePayload),
+            [b0, b1, reason @ ..] => {
+                let status_code = u16::from_be_bytes([*b0, *b1]);
+                if !protocol::status::is_valid(status_code) {
+                    return Err(ProtocolError::InvalidCloseStatus(status_code));
+                }
+                if std::str::from_utf8(reason).is_err() {
+                    return Err(ProtocolError::InvalidCloseReason);
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Checks the frame header against RFC 6455 once the payload length is known. The fragmented
+    /// message in progress is tracked regardless of the strict checks.
+    #[inline]
+    fn validate_frame(&mut self) -> Result<(), ProtocolError> {
+        match self.op_code {
+            protocol::op::CONNECTION_CLOSE | protocol::op::PING | protocol::op::PONG => {
+                if !self.strict {
+                    return Ok(());
+                }
+                if !self.fin {
+                    return Err(ProtocolError::FragmentedControlFrame);
+                }
+                if self.payload_length > 125 {
+                    return Err(ProtocolError::ControlFrameTooLarge(self.payload_length));
+                }
+            }
+            protocol::op::CONTINUATION_FRAME => {
+                if self.strict && self.message_op_code.is_none() {
+                    return Err(ProtocolError::UnexpectedContinuation);
+                }
+                if self.fin {
+                    self.message_op_code = None;
+                }
+            }
+            op_code => {
+                if self.strict && self.message_op_code.is_some() {
+                    return Err(ProtocolError::ExpectedContinuation);
+                }
+                self.message_op_code = (!self.fin).then_some(op_code);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    // delivers at most one byte per read so that the headers arrive split across reads
+    struct Trickle(Cursor<Vec<u8>>);
+
+    impl Read for Trickle {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let len = buf.len().min(1);
+            self.0.read(&mut buf[..len])
+        }
+    }
+
+    impl Write for Trickle {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn frames() -> Vec<u8> {
+        let mut data = Vec::new();
+        for len in [5usize, 300, 70000] {
+            data.push(0x82);
+            match len {
+                0..=125 => data.push(len as u8),
+                126..=0xFFFF => {
+                    data.push(126);
+                    data.extend_from_slice(&(len as u16).to_be_bytes());
+                }
+                _ => {
+                    data.push(127);
+                    data.extend_from_slice(&(len as u64).to_be_bytes());
+                }
+            }
+            data.extend(std::iter::repeat(len as u8).take(len));
+        }
+        data
+    }
+
+    fn decode_all<S: Read + Write>(stream: &mut S) -> Vec<(bool, usize)> {
+        let mut decoder: Decoder = Decoder::new();
+        let mut decoded = Vec::new();
+        while decoded.len() < 3 {
+            if let Some(WebsocketFrame::Binary(_, fin, payload)) = decoder.decode_next(stream).unwrap() {
+                assert!(payload.iter().all(|b| *b == payload.len() as u8));
+                decoded.push((fin, payload.len()));
+            }
+        }
+        decoded
+    }
+
+    #[test]
+    fn should_decode_whole_and_split_headers() {
+        let expected = vec![(true, 5), (true, 300), (true, 70000)];
+        assert_eq!(expected, decode_all(&mut Cursor::new(frames())));
+        assert_eq!(expected, decode_all(&mut Trickle(Cursor::new(frames()))));
+    }
+
+    #[test]
+    fn should_track_message_interleaved_with_control_frames() {
+        // text message fragmented into three frames with ping and pong in between
+        let data = b"\x01\x03abc\x89\x01p\x00\x03def\x8a\x00\x80\x03ghi\x82\x01x".to_vec();
+        for strict in [true, false] {
+            let mut decoder: Decoder = Decoder::new();
+            decoder.set_strict(strict);
+            let mut stream = Trickle(Cursor::new(data.clone()));
+            let mut decoded = Vec::new();
+            while decoded.len() < 6 {
+                if let Some(frame) = decoder.decode_next(&mut stream).unwrap() {
+                    let kind = match frame {
+                        WebsocketFrame::Text(..) => "text",
+                        WebsocketFrame::Continuation(..) => "continuation",
+                        WebsocketFrame::Ping(..) => "ping",
+                        WebsocketFrame::Pong(..) => "pong",
+                        WebsocketFrame::Binary(..) => "binary",
+                        WebsocketFrame::Close(..) => "close",
+                    };
+                    decoded.push((kind, frame.payload().to_vec(), decoder.in_progress_message_op_code()));
+                }
+            }
+            let text = Some(protocol::op::TEXT_FRAME);
+            assert_eq!(
+                vec![
+                    ("text", b"abc".to_vec(), text),
+                    ("ping", b"p".to_vec(), text),
+                    ("continuation", b"def".to_vec(), text),
+                    ("pong", b"".to_vec(), text),
+                    ("continuation", b"ghi".to_vec(), None),
+                    ("binary", b"x".to_vec(), None),
+                ],
+                decoded
+            );
+        }
+    }
 }
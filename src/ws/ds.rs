@@ -2,7 +2,7 @@ use crate::ws::{Error, State, Websocket, WebsocketFrame};
 use std::io;
 
 pub trait DataSource {
-    fn next(&self) -> Result<Option<WebsocketFrame>, Error>;
+    fn next(&self) -> Result<Option<WebsocketFrame<'_>>, Error>;
 
     fn into_stream(self) -> DataSourceStream<Self>
     where
@@ -17,7 +17,7 @@ pub struct DataSourceStream<D> {
 }
 
 impl<D: DataSource> Websocket<DataSourceStream<D>> {
-    pub fn receive_next(&mut self) -> Result<Option<WebsocketFrame>, Error> {
+    pub fn receive_next(&mut self) -> Result<Option<WebsocketFrame<'_>>, Error> {
         self.stream.data_source.next()
     }
 }
@@ -27,7 +27,14 @@ impl<D: DataSource> Websocket<D> {
         Ok(Websocket {
             stream: data_source.into_stream(),
             closed: false,
+            last_error: None,
+            strict: true,
+            heartbeats: false,
+            handshake_response: None,
             state: State::connection(),
+            rtt: None,
+            #[cfg(feature = "probe")]
+            probe: None,
         })
     }
 }
@@ -41,7 +48,7 @@ mod tests {
         struct CustomDataSource;
 
         impl DataSource for CustomDataSource {
-            fn next(&self) -> Result<Option<WebsocketFrame>, Error> {
+            fn next(&self) -> Result<Option<WebsocketFrame<'_>>, Error> {
                 Ok(Some(WebsocketFrame::Text(1, true, b"foo")))
             }
         }
@@ -1,44 +1,73 @@
 use std::io;
-use std::io::Write;
+use std::io::{IoSlice, Write};
 
+use crate::util::write_all_vectored;
 use crate::ws::protocol;
 
+/// Maximum length of the frame header (with the 64-bit payload length and the masking key).
+const MAX_HEADER_LEN: usize = 14;
+/// Number of buffers (the header and the payload segments) written without the allocation.
+const MAX_INLINE_BUFS: usize = 8;
+
 #[inline]
 pub fn send<S: Write>(stream: &mut S, fin: bool, op_code: u8, body: Option<&[u8]>) -> io::Result<()> {
-    let mut header = 0u8;
+    let body = body.unwrap_or_default();
+    let (header, len) = encode_header(fin, op_code, body.len());
+    // we can send plain text as masking key is set to zero on purpose
+    // this is done for performance reason as it will make XOR no-op
+    write_all_vectored(stream, &mut [&header[..len], body])?;
+    stream.flush()?;
+    Ok(())
+}
+
+/// Sends single frame whose payload is made of multiple `segments`, without copying them
+/// into an intermediate buffer.
+#[inline]
+pub fn send_vectored<S: Write>(stream: &mut S, fin: bool, op_code: u8, segments: &[IoSlice]) -> io::Result<()> {
+    let len = segments.iter().map(|segment| segment.len()).sum();
+    let (header, len) = encode_header(fin, op_code, len);
+    // payload is not masked, see `send`
+    if segments.len() < MAX_INLINE_BUFS {
+        let mut bufs: [&[u8]; MAX_INLINE_BUFS] = [&[]; MAX_INLINE_BUFS];
+        bufs[0] = &header[..len];
+        for (buf, segment) in bufs[1..].iter_mut().zip(segments) {
+            *buf = segment;
+        }
+        write_all_vectored(stream, &mut bufs[..segments.len() + 1])?;
+    } else {
+        let mut bufs = Vec::with_capacity(segments.len() + 1);
+        bufs.push(&header[..len]);
+        bufs.extend(segments.iter().map(|segment| &**segment));
+        write_all_vectored(stream, &mut bufs)?;
+    }
+    stream.flush()?;
+    Ok(())
+}
+
+/// Encodes the frame header, so that it can be written together with the payload. Returns the
+/// header buffer and the length of the header.
+#[inline]
+fn encode_header(fin: bool, op_code: u8, len: usize) -> ([u8; MAX_HEADER_LEN], usize) {
+    let mut header = [0u8; MAX_HEADER_LEN];
+    header[0] = op_code;
     if fin {
-        header |= protocol::FIN_MASK;
+        header[0] |= protocol::FIN_MASK;
     }
-    header |= op_code;
-    stream.write_all(&header.to_be_bytes())?;
     let mut payload_length = 0u8;
     payload_length |= protocol::MASK_MASK;
-    if let Some(body) = body {
-        let len = body.len();
-        if len <= 125 {
-            payload_length |= len as u8;
-            stream.write_all(&payload_length.to_be_bytes())?;
-        } else if len <= u16::MAX as usize {
-            payload_length |= 126;
-            let extended_payload_length = len as u16;
-            stream.write_all(&payload_length.to_be_bytes())?;
-            stream.write_all(&extended_payload_length.to_be_bytes())?;
-        } else if len <= u64::MAX as usize {
-            payload_length |= 127;
-            let extended_payload_length = len as u64;
-            stream.write_all(&payload_length.to_be_bytes())?;
-            stream.write_all(&extended_payload_length.to_be_bytes())?;
-        }
+    let masking_key_offset = if len <= 125 {
+        header[1] = payload_length | len as u8;
+        2
+    } else if len <= u16::MAX as usize {
+        header[1] = payload_length | 126;
+        header[2..4].copy_from_slice(&(len as u16).to_be_bytes());
+        4
     } else {
-        stream.write_all(&payload_length.to_be_bytes())?;
-    }
+        header[1] = payload_length | 127;
+        header[2..10].copy_from_slice(&(len as u64).to_be_bytes());
+        10
+    };
     let masking_key = 0u32;
-    stream.write_all(&masking_key.to_be_bytes())?;
-    if let Some(body) = body {
-        // we can send plain text as masking key is set to zero on purpose
-        // this is done for performance reason as it will make XOR no-op
-        stream.write_all(body)?;
-    }
-    stream.flush()?;
-    Ok(())
+    header[masking_key_offset..masking_key_offset + 4].copy_from_slice(&masking_key.to_be_bytes());
+    (header, masking_key_offset + 4)
 }
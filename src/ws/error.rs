@@ -1,9 +1,13 @@
 use std::array::TryFromSliceError;
 use std::io;
 use std::io::ErrorKind::Other;
+use std::time::Duration;
 use thiserror::Error;
 use url::ParseError;
 
+use crate::endpoint::Redirect;
+use crate::ws::{protocol, PendingMessage};
+
 #[derive(Error, Debug)]
 pub enum Error {
     #[error("the peer has sent the close frame: status code {0}, body: {1}")]
@@ -14,8 +18,55 @@ pub enum Error {
     IO(#[from] io::Error),
     #[error("url parse error: {0}")]
     InvalidUrl(#[from] ParseError),
+    #[error("unsupported url: {0}")]
+    UnsupportedUrl(String),
+    #[error("handshake failed with {} pending message(s): {0}", .1.len())]
+    HandshakeFailed(io::Error, Vec<PendingMessage>),
+    #[error("handshake {0} with {} pending message(s)", .1.len())]
+    Redirected(#[source] Redirect, Vec<PendingMessage>),
+    #[error("pending message buffer limit of {0} bytes exceeded")]
+    PendingBufferFull(usize),
+    #[error("handshake did not complete within {0:?}")]
+    HandshakeTimeout(Duration),
     #[error("slice error: {0}")]
     SliceError(#[from] TryFromSliceError),
+    #[error("protocol error: {0}")]
+    Protocol(#[from] ProtocolError),
+}
+
+/// Violations of RFC 6455 detected by the decoder, see `Websocket::with_strict_protocol`.
+#[derive(Error, Debug, Copy, Clone, Eq, PartialEq)]
+pub enum ProtocolError {
+    #[error("control frame payload of {0} bytes exceeds 125 bytes")]
+    ControlFrameTooLarge(usize),
+    #[error("control frame is fragmented")]
+    FragmentedControlFrame,
+    #[error("continuation frame received without a fragmented message in progress")]
+    UnexpectedContinuation,
+    #[error("data frame received while the fragmented message is still in progress")]
+    ExpectedContinuation,
+    #[error("reserved bits {0:#04x} set without negotiated extension")]
+    ReservedBitsSet(u8),
+    #[error("unknown op code: {0:#x}")]
+    UnknownOpCode(u8),
+    #[error("server frame is masked")]
+    MaskedFrame,
+    #[error("close frame payload of 1 byte is missing the status code")]
+    InvalidClosePayload,
+    #[error("invalid close status code: {0}")]
+    InvalidCloseStatus(u16),
+    #[error("close reason is not valid UTF-8")]
+    InvalidCloseReason,
+}
+
+impl ProtocolError {
+    /// Status code sent to the peer in the close frame when failing the connection.
+    pub const fn close_status_code(&self) -> u16 {
+        match self {
+            ProtocolError::InvalidCloseReason => protocol::status::INVALID_PAYLOAD,
+            _ => protocol::status::PROTOCOL_ERROR,
+        }
+    }
 }
 
 impl From<Error> for io::Error {
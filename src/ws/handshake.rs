@@ -1,25 +1,146 @@
-use std::collections::VecDeque;
-use std::io;
 use std::io::ErrorKind::{Other, WouldBlock};
-use std::io::{Read, Write};
+use std::io::{IoSlice, Read, Write};
+use std::time::Duration;
+use std::{fmt, io};
 
 use base64::engine::general_purpose;
 use base64::Engine;
 use http::StatusCode;
-use httparse::Response;
+use httparse::{Response, Status};
 use rand::{thread_rng, Rng};
-use url::Url;
+use sha1::{Digest, Sha1};
 
 use crate::buffer::ReadBuffer;
+use crate::endpoint::Redirect;
+use crate::time::TimeSource;
 use crate::ws::handshake::HandshakeState::{Completed, NotStarted, Pending};
-use crate::ws::Error;
+use crate::ws::pending::{OverflowPolicy, PendingBuffer, PendingBufferLimit};
+use crate::ws::util::parse_url;
+use crate::ws::{protocol, Error};
 
 #[derive(Debug)]
 pub struct Handshaker {
     buffer: ReadBuffer<1>,
     state: HandshakeState,
-    url: Url,
-    pending_msg_buffer: VecDeque<(u8, bool, Option<Vec<u8>>)>,
+    host_header: String,
+    path_with_query: String,
+    options: HandshakeOptions,
+    nonce: String,
+    response: Option<HandshakeResponse>,
+    timeout: Option<HandshakeTimeout>,
+    pending: PendingBuffer,
+}
+
+/// Deadline for the handshake to complete, started when the upgrade request is sent.
+struct HandshakeTimeout {
+    timeout: Duration,
+    deadline_ns: Option<u64>,
+    time_source: Box<dyn TimeSource>,
+}
+
+impl fmt::Debug for HandshakeTimeout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HandshakeTimeout")
+            .field("timeout", &self.timeout)
+            .field("deadline_ns", &self.deadline_ns)
+            .finish()
+    }
+}
+
+/// Additional headers and subprotocols sent with the websocket upgrade request.
+///
+/// # Examples
+///
+/// ```
+/// use boomnet::ws::HandshakeOptions;
+///
+/// let options = HandshakeOptions::default()
+///     .with_header("Authorization", "Bearer token")
+///     .with_subprotocol("v2.feed")
+///     .with_subprotocol("v1.feed");
+/// ```
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct HandshakeOptions {
+    headers: Vec<(String, String)>,
+    subprotocols: Vec<String>,
+    pending_buffer_limit: Option<PendingBufferLimit>,
+}
+
+impl HandshakeOptions {
+    /// Adds header to the upgrade request, such as `Authorization` or venue specific API key.
+    pub fn with_header(mut self, name: &str, value: &str) -> HandshakeOptions {
+        self.headers.push((name.to_owned(), value.to_owned()));
+        self
+    }
+
+    /// Adds subprotocol to the `Sec-WebSocket-Protocol` header, in the order of preference.
+    pub fn with_subprotocol(mut self, subprotocol: &str) -> HandshakeOptions {
+        self.subprotocols.push(subprotocol.to_owned());
+        self
+    }
+
+    /// Limit the total size (in bytes) of the messages sent while the handshake is pending, the
+    /// `policy` decides what happens to the message that does not fit. Unlimited by default.
+    pub fn with_pending_buffer_limit(self, max_bytes: usize, policy: OverflowPolicy) -> HandshakeOptions {
+        Self {
+            pending_buffer_limit: Some(PendingBufferLimit { max_bytes, policy }),
+            ..self
+        }
+    }
+}
+
+/// Checks if the response status is one of the `3xx` redirects.
+fn is_redirect(code: Option<u16>) -> bool {
+    code.and_then(|code| StatusCode::from_u16(code).ok())
+        .is_some_and(|code| code.is_redirection())
+}
+
+/// GUID appended to the nonce when deriving `Sec-WebSocket-Accept`, as per RFC 6455.
+const ACCEPT_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+/// Response to the websocket upgrade request, retained once the handshake has completed so that
+/// the negotiated extensions, cookies or any venue specific headers can be inspected.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct HandshakeResponse {
+    headers: Vec<(String, Vec<u8>)>,
+}
+
+impl HandshakeResponse {
+    /// Returns all response headers in the order they were received.
+    pub fn headers(&self) -> impl Iterator<Item = (&str, &[u8])> {
+        self.headers
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_slice()))
+    }
+
+    /// Returns the value of the first header with the given `name` (case-insensitive).
+    pub fn header(&self, name: &str) -> Option<&[u8]> {
+        self.headers
+            .iter()
+            .find(|(header, _)| header.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_slice())
+    }
+}
+
+/// Message sent while the handshake was still in progress and that has not yet been
+/// dispatched to the peer.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct PendingMessage {
+    pub fin: bool,
+    pub op_code: u8,
+    pub body: Option<Vec<u8>>,
+}
+
+impl PendingMessage {
+    /// Checks if this is a text frame.
+    pub const fn is_text(&self) -> bool {
+        self.op_code == protocol::op::TEXT_FRAME
+    }
+
+    /// Checks if this is a binary frame.
+    pub const fn is_binary(&self) -> bool {
+        self.op_code == protocol::op::BINARY_FRAME
+    }
 }
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
@@ -30,16 +151,51 @@ pub enum HandshakeState {
 }
 
 impl Handshaker {
-    pub fn new(url: &str) -> Result<Self, Error> {
-        let url = Url::parse(url)?;
+    pub fn new(url: &str, options: HandshakeOptions) -> Result<Self, Error> {
+        let url = parse_url(url)?;
+        let pending = PendingBuffer::new(options.pending_buffer_limit);
         Ok(Self {
             buffer: ReadBuffer::new(),
             state: NotStarted,
-            url,
-            pending_msg_buffer: VecDeque::with_capacity(256),
+            host_header: url.host_header,
+            path_with_query: url.path_with_query,
+            options,
+            nonce: String::new(),
+            response: None,
+            timeout: None,
+            pending,
         })
     }
 
+    pub fn not_started(&self) -> bool {
+        self.state == NotStarted
+    }
+
+    pub fn set_timeout<T: TimeSource + 'static>(&mut self, timeout: Duration, time_source: T) {
+        self.timeout = Some(HandshakeTimeout {
+            timeout,
+            deadline_ns: None,
+            time_source: Box::new(time_source),
+        });
+    }
+
+    /// Returns the configured timeout if the handshake has not completed before the deadline.
+    pub fn timed_out(&mut self) -> Option<Duration> {
+        if self.state == Completed {
+            return None;
+        }
+        let timeout = self.timeout.as_mut()?;
+        let current_time_ns = timeout.time_source.current_time_nanos();
+        let deadline_ns = *timeout
+            .deadline_ns
+            .get_or_insert(current_time_ns + timeout.timeout.as_nanos() as u64);
+        if current_time_ns > deadline_ns {
+            Some(timeout.timeout)
+        } else {
+            None
+        }
+    }
+
     #[cold]
     pub fn perform_handshake<S: Read + Write>(&mut self, stream: &mut S) -> io::Result<()> {
         match self.state {
@@ -49,17 +205,50 @@ impl Handshaker {
             }
             Pending => {
                 self.buffer.read_from(stream)?;
-                let available = self.buffer.available();
-                if available >= 4 && self.buffer.view_last(4) == b"\r\n\r\n" {
-                    // decode http response
-                    let mut headers = [httparse::EMPTY_HEADER; 64];
-                    let mut response = Response::new(&mut headers);
-                    response
-                        .parse(self.buffer.view())
-                        .map_err(|err| io::Error::new(Other, err))?;
+                // decode http response
+                let mut headers = [httparse::EMPTY_HEADER; 64];
+                let mut response = Response::new(&mut headers);
+                let status = response
+                    .parse(self.buffer.view())
+                    .map_err(|err| io::Error::new(Other, err))?;
+                let complete = match status {
+                    // unlike the upgrade response, the redirect may carry a body (such as html page)
+                    Status::Complete(_) if is_redirect(response.code) => {
+                        let location = response
+                            .headers
+                            .iter()
+                            .find(|header| header.name.eq_ignore_ascii_case("Location"))
+                            .ok_or_else(|| io::Error::new(Other, "redirect without location"))?;
+                        let redirect = Redirect {
+                            status: response.code.unwrap(),
+                            location: String::from_utf8_lossy(location.value).into_owned(),
+                        };
+                        return Err(io::Error::new(Other, redirect));
+                    }
+                    Status::Complete(len) => len == self.buffer.available(),
+                    Status::Partial => false,
+                };
+                if complete {
                     if response.code.unwrap() != StatusCode::SWITCHING_PROTOCOLS.as_u16() {
                         return Err(io::Error::new(Other, "unable to switch protocols"));
                     }
+                    let response = HandshakeResponse {
+                        headers: response
+                            .headers
+                            .iter()
+                            .map(|header| (header.name.to_owned(), header.value.to_vec()))
+                            .collect(),
+                    };
+                    let expected_accept = derive_accept(&self.nonce);
+                    if response.header("Sec-WebSocket-Accept") != Some(expected_accept.as_bytes()) {
+                        return Err(io::Error::new(Other, "invalid Sec-WebSocket-Accept"));
+                    }
+                    if let Some(subprotocol) = response.header("Sec-WebSocket-Protocol") {
+                        if !self.options.subprotocols.iter().any(|s| s.as_bytes() == subprotocol) {
+                            return Err(io::Error::new(Other, "server selected subprotocol that was not requested"));
+                        }
+                    }
+                    self.response = Some(response);
                     self.state = Completed;
                 }
                 Err(io::Error::from(WouldBlock))
@@ -69,30 +258,52 @@ impl Handshaker {
     }
 
     #[cold]
-    pub fn buffer_message(&mut self, fin: bool, op: u8, body: Option<&[u8]>) {
-        let body = body.map(|body| body.to_vec());
-        self.pending_msg_buffer.push_back((op, fin, body))
+    pub fn buffer_message(&mut self, fin: bool, op: u8, body: Option<&[u8]>) -> Result<(), Error> {
+        self.pending.push(fin, op, body)
     }
 
     #[cold]
-    pub fn drain_pending_message_buffer<S, F>(&mut self, stream: &mut S, mut send: F) -> Result<(), Error>
-    where
-        S: Write,
-        F: FnMut(&mut S, bool, u8, Option<&[u8]>) -> io::Result<()>,
-    {
-        while let Some((op, fin, body)) = self.pending_msg_buffer.pop_front() {
-            send(stream, fin, op, body.as_deref())?;
-        }
-        Ok(())
+    pub fn buffer_message_vectored(&mut self, fin: bool, op: u8, segments: &[IoSlice]) -> Result<(), Error> {
+        self.pending.push_vectored(fin, op, Some(segments))
+    }
+
+    /// Takes the response once the handshake has completed.
+    pub fn take_response(&mut self) -> Option<HandshakeResponse> {
+        self.response.take()
+    }
+
+    pub fn pending_message_count(&self) -> usize {
+        self.pending.len()
+    }
+
+    #[cold]
+    pub fn take_pending_messages(&mut self) -> Vec<PendingMessage> {
+        self.pending.take()
+    }
+
+    /// Sends the messages buffered while the handshake was pending, returns `true` once all of them
+    /// have been written or `false` if the stream would block and the drain has to be resumed.
+    #[cold]
+    pub fn drain_pending_message_buffer<S: Write>(&mut self, stream: &mut S) -> Result<bool, Error> {
+        Ok(self.pending.drain(stream)?)
     }
 
     fn send_handshake_request<S: Write>(&mut self, stream: &mut S) -> io::Result<()> {
-        stream.write_all(format!("GET {} HTTP/1.1\r\n", self.url.path()).as_bytes())?;
-        stream.write_all(format!("Host: {}\r\n", self.url.host_str().unwrap()).as_bytes())?;
+        stream.write_all(format!("GET {} HTTP/1.1\r\n", self.path_with_query).as_bytes())?;
+        stream.write_all(format!("Host: {}\r\n", self.host_header).as_bytes())?;
         stream.write_all(b"Upgrade: websocket\r\n")?;
         stream.write_all(b"Connection: upgrade\r\n")?;
-        stream.write_all(format!("Sec-WebSocket-Key: {}\r\n", generate_nonce()).as_bytes())?;
+        self.nonce = generate_nonce();
+        stream.write_all(format!("Sec-WebSocket-Key: {}\r\n", self.nonce).as_bytes())?;
         stream.write_all(b"Sec-WebSocket-Version: 13\r\n")?;
+        if !self.options.subprotocols.is_empty() {
+            stream.write_all(
+                format!("Sec-WebSocket-Protocol: {}\r\n", self.options.subprotocols.join(", ")).as_bytes(),
+            )?;
+        }
+        for (name, value) in &self.options.headers {
+            stream.write_all(format!("{}: {}\r\n", name, value).as_bytes())?;
+        }
         stream.write_all(b"\r\n")?;
         stream.flush()?;
         self.state = Pending;
@@ -105,3 +316,21 @@ fn generate_nonce() -> String {
     let nonce_bytes: [u8; 16] = rng.gen();
     general_purpose::STANDARD.encode(nonce_bytes)
 }
+
+pub(crate) fn derive_accept(nonce: &str) -> String {
+    let mut sha1 = Sha1::new();
+    sha1.update(nonce.as_bytes());
+    sha1.update(ACCEPT_GUID.as_bytes());
+    general_purpose::STANDARD.encode(sha1.finalize())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_derive_accept_key() {
+        // example from RFC 6455 section 1.3
+        assert_eq!("s3pPLMBiTxaQ9kYGzzhZRbK+xOo=", derive_accept("dGhlIHNhbXBsZSBub25jZQ=="));
+    }
+}
@@ -0,0 +1,224 @@
+//! Per-connection interceptors that observe or transform the payloads of the data frames received
+//! from and sent to the [`Websocket`], such as decompressing the venue specific compressed payloads
+//! (for example OKX `deflate-raw`), decoding the JSON into the application structs or tagging the
+//! metrics. Interceptors are composed into a chain with tuples and dispatched statically, so the
+//! chain costs no more than calling each interceptor directly.
+//!
+//! The interceptor is owned by the application (typically the endpoint) and passed to the websocket
+//! on each call, which keeps the websocket type unchanged. Interceptors see the individual frames,
+//! the payload of the fragmented message is delivered in multiple calls with the `fin` flag set on
+//! the last one. Control frames are never intercepted.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use std::net::TcpStream;
+//! use boomnet::ws::intercept::Interceptor;
+//! use boomnet::ws::{Error, IntoWebsocket, WebsocketFrame};
+//!
+//! /// Counts the inbound bytes.
+//! #[derive(Default)]
+//! struct ByteCounter(usize);
+//!
+//! impl Interceptor for ByteCounter {
+//!     fn on_inbound<'a>(&'a mut self, _op_code: u8, payload: &'a [u8]) -> Result<&'a [u8], Error> {
+//!         self.0 += payload.len();
+//!         Ok(payload)
+//!     }
+//! }
+//!
+//! /// Strips the envelope the venue prefixes the messages with.
+//! struct StripPrefix;
+//!
+//! impl Interceptor for StripPrefix {
+//!     fn on_inbound<'a>(&'a mut self, _op_code: u8, payload: &'a [u8]) -> Result<&'a [u8], Error> {
+//!         Ok(payload.strip_prefix(b"data:").unwrap_or(payload))
+//!     }
+//! }
+//!
+//! let mut ws = TcpStream::connect("127.0.0.1:9001").unwrap().into_websocket("ws://127.0.0.1:9001/ws");
+//! // the bytes are counted before the prefix is stripped
+//! let mut interceptors = (ByteCounter::default(), StripPrefix);
+//! while let Some(WebsocketFrame::Text(_, _, data)) = ws.receive_next_intercepted(&mut interceptors).unwrap() {
+//!     println!("{}", String::from_utf8_lossy(data));
+//! }
+//! ```
+
+use std::io::{Read, Write};
+
+use crate::ws::{protocol, Error, Websocket, WebsocketFrame};
+
+/// Observes or transforms the payload of the data frames, see the [module](self) documentation.
+/// The returned payload either borrows the original one (when it is passed through or narrowed)
+/// or the buffer owned by the interceptor (when it is transformed).
+pub trait Interceptor {
+    /// Called with the payload of each received data frame, returns the payload delivered to the
+    /// application. The `op_code` is the one of the frame (text, binary or continuation).
+    fn on_inbound<'a>(&'a mut self, _op_code: u8, payload: &'a [u8]) -> Result<&'a [u8], Error> {
+        Ok(payload)
+    }
+
+    /// Called with the payload of each data frame sent by the application, returns the payload
+    /// written to the stream.
+    fn on_outbound<'a>(&'a mut self, _op_code: u8, payload: &'a [u8]) -> Result<&'a [u8], Error> {
+        Ok(payload)
+    }
+}
+
+/// Interceptor that passes the payloads through unchanged.
+impl Interceptor for () {}
+
+/// Chain of two interceptors where the first one is closest to the wire: the inbound payload is
+/// passed to the first and then to the second interceptor, the outbound payload in reverse order.
+/// Longer chains are built by nesting, such as `(a, (b, c))`.
+impl<A: Interceptor, B: Interceptor> Interceptor for (A, B) {
+    #[inline]
+    fn on_inbound<'a>(&'a mut self, op_code: u8, payload: &'a [u8]) -> Result<&'a [u8], Error> {
+        let (first, second) = self;
+        let payload = first.on_inbound(op_code, payload)?;
+        second.on_inbound(op_code, payload)
+    }
+
+    #[inline]
+    fn on_outbound<'a>(&'a mut self, op_code: u8, payload: &'a [u8]) -> Result<&'a [u8], Error> {
+        let (first, second) = self;
+        let payload = second.on_outbound(op_code, payload)?;
+        first.on_outbound(op_code, payload)
+    }
+}
+
+impl<S: Read + Write, const CHUNK_SIZE: usize, const INITIAL_CAPACITY: usize>
+    Websocket<S, CHUNK_SIZE, INITIAL_CAPACITY>
+{
+    /// Same as [`Websocket::receive_next`] but the payload of the data frame is passed through the
+    /// `interceptor` before it is returned.
+    #[inline]
+    pub fn receive_next_intercepted<'a, I: Interceptor>(
+        &'a mut self,
+        interceptor: &'a mut I,
+    ) -> Result<Option<WebsocketFrame<'a>>, Error> {
+        let frame = match self.receive_next()? {
+            Some(WebsocketFrame::Text(ts, fin, payload)) => {
+                WebsocketFrame::Text(ts, fin, interceptor.on_inbound(protocol::op::TEXT_FRAME, payload)?)
+            }
+            Some(WebsocketFrame::Binary(ts, fin, payload)) => {
+                WebsocketFrame::Binary(ts, fin, interceptor.on_inbound(protocol::op::BINARY_FRAME, payload)?)
+            }
+            Some(WebsocketFrame::Continuation(ts, fin, payload)) => WebsocketFrame::Continuation(
+                ts,
+                fin,
+                interceptor.on_inbound(protocol::op::CONTINUATION_FRAME, payload)?,
+            ),
+            frame => return Ok(frame),
+        };
+        Ok(Some(frame))
+    }
+
+    /// Same as [`Websocket::send_text`] but the `body` is passed through the `interceptor` first.
+    #[inline]
+    #[must_use = "the websocket is closed if the send fails"]
+    pub fn send_text_intercepted<I: Interceptor>(
+        &mut self,
+        interceptor: &mut I,
+        fin: bool,
+        body: Option<&[u8]>,
+    ) -> Result<(), Error> {
+        self.send_intercepted(interceptor, fin, protocol::op::TEXT_FRAME, body)
+    }
+
+    /// Same as [`Websocket::send_binary`] but the `body` is passed through the `interceptor` first.
+    #[inline]
+    #[must_use = "the websocket is closed if the send fails"]
+    pub fn send_binary_intercepted<I: Interceptor>(
+        &mut self,
+        interceptor: &mut I,
+        fin: bool,
+        body: Option<&[u8]>,
+    ) -> Result<(), Error> {
+        self.send_intercepted(interceptor, fin, protocol::op::BINARY_FRAME, body)
+    }
+
+    #[inline]
+    fn send_intercepted<I: Interceptor>(
+        &mut self,
+        interceptor: &mut I,
+        fin: bool,
+        op_code: u8,
+        body: Option<&[u8]>,
+    ) -> Result<(), Error> {
+        let body = match body {
+            Some(body) => Some(interceptor.on_outbound(op_code, body)?),
+            None => None,
+        };
+        self.send(fin, op_code, body)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use crate::ws::raw::{self, op};
+
+    use super::*;
+
+    // replaces the payload with its uppercase copy
+    #[derive(Default)]
+    struct Uppercase(Vec<u8>);
+
+    impl Interceptor for Uppercase {
+        fn on_inbound<'a>(&'a mut self, _op_code: u8, payload: &'a [u8]) -> Result<&'a [u8], Error> {
+            self.0.clear();
+            self.0.extend(payload.iter().map(u8::to_ascii_uppercase));
+            Ok(&self.0)
+        }
+
+        fn on_outbound<'a>(&'a mut self, op_code: u8, payload: &'a [u8]) -> Result<&'a [u8], Error> {
+            self.on_inbound(op_code, payload)
+        }
+    }
+
+    // records the payloads it has seen
+    #[derive(Default)]
+    struct Recorder(Vec<Vec<u8>>);
+
+    impl Interceptor for Recorder {
+        fn on_inbound<'a>(&'a mut self, _op_code: u8, payload: &'a [u8]) -> Result<&'a [u8], Error> {
+            self.0.push(payload.to_vec());
+            Ok(payload)
+        }
+
+        fn on_outbound<'a>(&'a mut self, _op_code: u8, payload: &'a [u8]) -> Result<&'a [u8], Error> {
+            self.0.push(payload.to_vec());
+            Ok(payload)
+        }
+    }
+
+    #[test]
+    fn should_chain_interceptors() {
+        let mut inbound = Vec::new();
+        raw::encode_frame(&mut inbound, false, op::TEXT_FRAME, None, b"hello ");
+        raw::encode_frame(&mut inbound, true, op::PING, None, b"ping");
+        raw::encode_frame(&mut inbound, true, op::CONTINUATION_FRAME, None, b"world");
+        let mut ws = Websocket::new_connected(Cursor::new(inbound)).with_heartbeat_frames(true);
+
+        let mut chain = (Recorder::default(), Uppercase::default());
+        let mut payloads = Vec::new();
+        while payloads.len() < 3 {
+            if let Some(frame) = ws.receive_next_intercepted(&mut chain).unwrap() {
+                payloads.push(frame.payload().to_vec());
+            }
+        }
+        // control frames are not intercepted
+        assert_eq!(vec![b"HELLO ".to_vec(), b"ping".to_vec(), b"WORLD".to_vec()], payloads);
+        assert_eq!(vec![b"hello ".to_vec(), b"world".to_vec()], chain.0 .0);
+
+        // outbound payload passes through the chain in reverse order
+        let mut ws = Websocket::new_connected(Cursor::new(Vec::new()));
+        let mut chain = (Recorder::default(), Uppercase::default());
+        ws.send_text_intercepted(&mut chain, true, Some(b"sub")).unwrap();
+        ws.send_binary_intercepted(&mut (), true, None).unwrap();
+        assert_eq!(vec![b"SUB".to_vec()], chain.0 .0);
+        assert_eq!(b"\x81\x83\x00\x00\x00\x00SUB\x82\x80\x00\x00\x00\x00", ws.stream().get_ref().as_slice());
+    }
+}
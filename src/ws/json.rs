@@ -0,0 +1,300 @@
+//! Zero allocation extraction of the fields from the JSON payloads, for the strategies that only need
+//! a couple of fields (such as the price, quantity and timestamp) out of each message and would rather
+//! avoid the full parse. The payload is scanned once and only until all the requested fields have
+//! been found, the nested objects are only entered if some of the fields point into them and the
+//! long strings are skipped using `memchr`.
+//!
+//! The values are returned as the raw slices of the payload: strings without the quotes (the escape
+//! sequences are not processed), numbers and literals as they appear, objects and arrays including
+//! their brackets. The payload is expected to be well-formed, the malformed one yields `None` (or the
+//! fields found before the malformed part) but never panics.
+//!
+//! # Examples
+//!
+//! ```
+//! use boomnet::ws::json;
+//!
+//! let payload = br#"{"stream":"btcusdt@trade","data":{"e":"trade","p":"37000.01","q":"0.001","T":1700000000000}}"#;
+//! let [price, qty, ts] = json::find_all(payload, &["data.p", "data.q", "data.T"]);
+//! assert_eq!(Some(37000.01), price.and_then(json::parse_f64));
+//! assert_eq!(Some(b"0.001".as_slice()), qty);
+//! assert_eq!(Some(1700000000000), ts.and_then(json::parse_u64));
+//! ```
+
+use memchr::memchr2;
+
+/// Strings up to this length (most of the keys and values) are scanned byte by byte as `memchr`
+/// only pays off for the longer ones.
+const SHORT_STRING: usize = 16;
+
+/// Returns the value of the `key` in the top level object of the `payload`, or `None` if the key is
+/// not present. The fields of the nested objects are addressed with the dot separated path (such
+/// as `data.p`), keys containing the escape sequences are not matched.
+pub fn find<'a>(payload: &'a [u8], key: &str) -> Option<&'a [u8]> {
+    let [value] = find_all(payload, &[key]);
+    value
+}
+
+/// Returns the values of the `keys` (see [`find`]) in the `payload`, in the same order as the keys.
+/// The payload is scanned once and only until all the keys have been found, the nested objects are
+/// only entered if some of the keys point into them.
+pub fn find_all<'a, const N: usize>(payload: &'a [u8], keys: &[&str; N]) -> [Option<&'a [u8]>; N] {
+    let mut values = [None; N];
+    let mut remaining = N;
+    let pos = skip_whitespace(payload, 0);
+    if payload.get(pos) == Some(&b'{') {
+        scan_object(payload, pos, keys, &[], &mut values, &mut remaining);
+    }
+    values
+}
+
+/// Parses the unsigned integer value (such as the timestamp or the sequence number).
+pub fn parse_u64(value: &[u8]) -> Option<u64> {
+    if value.is_empty() {
+        return None;
+    }
+    value.iter().try_fold(0u64, |acc, byte| match byte {
+        b'0'..=b'9' => acc.checked_mul(10)?.checked_add((byte - b'0') as u64),
+        _ => None,
+    })
+}
+
+/// Parses the floating point value, either the number or the string (such as the price quoted by
+/// the exchanges to preserve the precision).
+pub fn parse_f64(value: &[u8]) -> Option<f64> {
+    std::str::from_utf8(value).ok()?.parse().ok()
+}
+
+/// Relation of the key to the field of the object at the current path.
+enum Matched {
+    Value,
+    Nested,
+    None,
+}
+
+/// Matches the `key` against the field `name` of the object at the `path` (such as `data.`).
+#[inline]
+fn match_key(key: &str, path: &[u8], name: &[u8]) -> Matched {
+    let key = key.as_bytes();
+    let len = path.len() + name.len();
+    // compare the lengths first, most of the fields do not match
+    let matched = match key.get(len) {
+        None if key.len() == len => Matched::Value,
+        Some(b'.') => Matched::Nested,
+        _ => return Matched::None,
+    };
+    match eq(&key[path.len()..len], name) && eq(&key[..path.len()], path) {
+        true => matched,
+        false => Matched::None,
+    }
+}
+
+/// Byte by byte comparison, the keys are too short for `memcmp` to pay off.
+#[inline]
+fn eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).all(|(a, b)| a == b)
+}
+
+/// Scans the object starting at `pos` for the fields of the `keys` not found yet, where `path` is the
+/// path to this object. Returns the position just after the object (or any position once all the
+/// keys have been found), or `None` if the payload is malformed.
+fn scan_object<'a, 'k, const N: usize>(
+    payload: &'a [u8],
+    pos: usize,
+    keys: &[&'k str; N],
+    path: &'k [u8],
+    values: &mut [Option<&'a [u8]>; N],
+    remaining: &mut usize,
+) -> Option<usize> {
+    let mut pos = skip_whitespace(payload, pos + 1);
+    if payload.get(pos) == Some(&b'}') {
+        return Some(pos + 1);
+    }
+    loop {
+        if payload.get(pos) != Some(&b'"') {
+            return None;
+        }
+        let end = string_end(payload, pos + 1)?;
+        let name = &payload[pos + 1..end];
+        pos = skip_whitespace(payload, end + 1);
+        if payload.get(pos) != Some(&b':') {
+            return None;
+        }
+        pos = skip_whitespace(payload, pos + 1);
+
+        let mut end = None;
+        let mut nested_path = None;
+        for (key, value) in keys.iter().zip(values.iter_mut()) {
+            if value.is_some() {
+                continue;
+            }
+            match match_key(key, path, name) {
+                Matched::Value => {
+                    let value_end = match end {
+                        Some(end) => end,
+                        None => *end.insert(value_end(payload, pos)?),
+                    };
+                    *value = Some(match payload[pos] {
+                        b'"' => &payload[pos + 1..value_end - 1],
+                        _ => &payload[pos..value_end],
+                    });
+                    *remaining -= 1;
+                }
+                Matched::Nested => nested_path = Some(&key.as_bytes()[..path.len() + name.len() + 1]),
+                Matched::None => {}
+            }
+        }
+        if *remaining == 0 {
+            return Some(pos);
+        }
+        pos = match (end, nested_path) {
+            (_, Some(nested_path)) if payload[pos] == b'{' => {
+                let end = scan_object(payload, pos, keys, nested_path, values, remaining)?;
+                if *remaining == 0 {
+                    return Some(end);
+                }
+                end
+            }
+            (Some(end), _) => end,
+            (None, _) => value_end(payload, pos)?,
+        };
+
+        pos = skip_whitespace(payload, pos);
+        match payload.get(pos)? {
+            b',' => pos = skip_whitespace(payload, pos + 1),
+            b'}' => return Some(pos + 1),
+            _ => return None,
+        }
+    }
+}
+
+#[inline]
+fn skip_whitespace(payload: &[u8], mut pos: usize) -> usize {
+    while payload.get(pos).is_some_and(u8::is_ascii_whitespace) {
+        pos += 1;
+    }
+    pos
+}
+
+/// Returns the position of the closing quote of the string starting at `pos` (after the opening quote).
+#[inline]
+fn string_end(payload: &[u8], mut pos: usize) -> Option<usize> {
+    loop {
+        let rest = payload.get(pos..)?;
+        let short = &rest[..rest.len().min(SHORT_STRING)];
+        pos += match short.iter().position(|&byte| byte == b'"' || byte == b'\\') {
+            Some(len) => len,
+            None => SHORT_STRING + memchr2(b'"', b'\\', rest.get(SHORT_STRING..)?)?,
+        };
+        match payload[pos] {
+            b'"' => return Some(pos),
+            // skip the escaped character
+            _ => pos += 2,
+        }
+    }
+}
+
+/// Returns the position just after the value starting at `pos`.
+#[inline]
+fn value_end(payload: &[u8], pos: usize) -> Option<usize> {
+    match *payload.get(pos)? {
+        b'"' => Some(string_end(payload, pos + 1)? + 1),
+        b'{' => container_end(payload, pos, b'{', b'}'),
+        b'[' => container_end(payload, pos, b'[', b']'),
+        _ => {
+            let len = payload[pos..]
+                .iter()
+                .position(|byte| matches!(byte, b',' | b'}' | b']') || byte.is_ascii_whitespace())
+                .unwrap_or(payload.len() - pos);
+            match len {
+                0 => None,
+                len => Some(pos + len),
+            }
+        }
+    }
+}
+
+/// Returns the position just after the object or array starting at `pos`. Only the brackets of the
+/// same kind need to be balanced as the nested containers of the other kind are balanced themselves.
+#[inline]
+fn container_end(payload: &[u8], mut pos: usize, open: u8, close: u8) -> Option<usize> {
+    let mut depth = 0usize;
+    while let Some(&byte) = payload.get(pos) {
+        if byte == b'"' {
+            pos = string_end(payload, pos + 1)?;
+        } else if byte == open {
+            depth += 1;
+        } else if byte == close {
+            depth -= 1;
+            if depth == 0 {
+                return Some(pos + 1);
+            }
+        }
+        pos += 1;
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_find_top_level_fields() {
+        let payload = br#" { "id" : 42, "nested": {"id": 1, "list": [{"p": "}"}, "]"]}, "msg": "say \"p\"",
+            "p":"37000.01", "ok":true, "none":null, "levels":[["1.0", "2.0"]] } "#;
+        assert_eq!(Some(b"42".as_slice()), find(payload, "id"));
+        assert_eq!(Some(br#"{"id": 1, "list": [{"p": "}"}, "]"]}"#.as_slice()), find(payload, "nested"));
+        assert_eq!(Some(br#"say \"p\""#.as_slice()), find(payload, "msg"));
+        assert_eq!(Some(b"37000.01".as_slice()), find(payload, "p"));
+        assert_eq!(Some(b"true".as_slice()), find(payload, "ok"));
+        assert_eq!(Some(b"null".as_slice()), find(payload, "none"));
+        assert_eq!(Some(br#"[["1.0", "2.0"]]"#.as_slice()), find(payload, "levels"));
+        assert_eq!(None, find(payload, "list"));
+
+        let [id, price, missing] = find_all(payload, &["id", "p", "q"]);
+        assert_eq!(Some(42), id.and_then(parse_u64));
+        assert_eq!(Some(37000.01), price.and_then(parse_f64));
+        assert_eq!(None, missing);
+    }
+
+    #[test]
+    fn should_find_nested_fields() {
+        let payload = br#"{"stream":"btcusdt@trade","msg":"long message with the \"quoted\" words and \\",
+            "data":{"e":"trade","p":"1.5","E":{"x":1}},"other":{"p":"9"},"T":7}"#;
+        let [price, other, x, data, time] = find_all(payload, &["data.p", "other.p", "data.E.x", "data", "T"]);
+        assert_eq!(Some(b"1.5".as_slice()), price);
+        assert_eq!(Some(b"9".as_slice()), other);
+        assert_eq!(Some(b"1".as_slice()), x);
+        assert_eq!(Some(br#"{"e":"trade","p":"1.5","E":{"x":1}}"#.as_slice()), data);
+        assert_eq!(Some(7), time.and_then(parse_u64));
+        assert_eq!(Some(br#"long message with the \"quoted\" words and \\"#.as_slice()), find(payload, "msg"));
+        // the whole path has to match
+        assert_eq!(None, find(payload, "dxta.p"));
+        assert_eq!(None, find(payload, "data.p.x"));
+        assert_eq!(None, find(payload, "stream.p"));
+    }
+
+    #[test]
+    fn should_not_panic_on_malformed_payload() {
+        for payload in [
+            b"".as_slice(),
+            b"[1, 2]",
+            b"{",
+            b"{}",
+            br#"{"p""#,
+            br#"{"p":"#,
+            br#"{"p":"1"#,
+            br#"{"p":{"q":1"#,
+            br#"{"p":1 "q":2}"#,
+            br#"{"\"#,
+        ] {
+            assert_eq!(None, find(payload, "q"));
+        }
+        // the fields before the malformed part are still found
+        assert_eq!(Some(b"1".as_slice()), find(br#"{"p":1,"q"#, "p"));
+        assert_eq!(None, parse_u64(b""));
+        assert_eq!(None, parse_u64(b"18446744073709551616"));
+        assert_eq!(None, parse_u64(b"-1"));
+    }
+}
@@ -1,57 +1,236 @@
 //! Websocket protocol.
 
+use log::warn;
 #[cfg(feature = "mio")]
 use mio::{event::Source, Interest, Registry, Token};
 use std::io;
 use std::io::ErrorKind::WouldBlock;
-use std::io::{Read, Write};
+use std::io::{IoSlice, Read, Write};
 use std::net::TcpStream;
+use std::time::Duration;
 use thiserror::Error;
-use url::Url;
 
-use crate::buffer;
+use crate::buffer::DEFAULT_INITIAL_CAPACITY;
+use crate::endpoint::{ConnectionInfo, Redirect};
+#[cfg(feature = "probe")]
+use crate::probe::{IoProbe, ProbeEvent, ProbedStream};
 use crate::select::Selectable;
 #[cfg(any(feature = "tls-webpki", feature = "tls-native"))]
 use crate::stream::tls::{IntoTlsStream, NotTlsStream, TlsReadyStream, TlsStream};
+use crate::stream::{
+    ConnectionInfoProvider, ConnectionProperties, ConnectionPropertiesProvider, Cork, SocketOptions, SocketQueues,
+};
+use crate::time::TimeSource;
+use crate::util::current_time_nanos;
 use crate::ws::decoder::Decoder;
 use crate::ws::handshake::Handshaker;
+use crate::ws::rtt::RttTracker;
 use crate::ws::Error::{Closed, ReceivedCloseFrame};
 
 // re-export
-pub use crate::ws::error::Error;
+pub use crate::ws::error::{Error, ProtocolError};
+pub use crate::ws::handshake::{HandshakeOptions, HandshakeResponse, PendingMessage};
+pub use crate::ws::pending::OverflowPolicy;
+pub use crate::ws::rtt::RttEstimate;
+pub use crate::ws::split::{WsReader, WsWriter};
 
 mod decoder;
 pub mod ds;
 mod encoder;
 mod error;
 mod handshake;
+pub mod intercept;
+pub mod json;
+mod pending;
 mod protocol;
+pub mod raw;
+mod rtt;
+mod split;
+pub mod subscription;
+pub mod testing;
+pub mod util;
 
-type ReadBuffer = buffer::ReadBuffer<4096>;
+/// Number of bytes the websocket attempts to read from the stream at once unless specified
+/// otherwise, see [`Websocket::with_read_buffer`].
+pub const DEFAULT_READ_CHUNK_SIZE: usize = 4096;
 
-pub enum WebsocketFrame {
-    Ping(u64, &'static [u8]),
-    Pong(u64, &'static [u8]),
-    Text(u64, bool, &'static [u8]),
-    Binary(u64, bool, &'static [u8]),
-    Continuation(u64, bool, &'static [u8]),
-    Close(u64, &'static [u8]),
+/// Evaluates `$call` with the websocket stream bound to `$stream`, the stream is wrapped with
+/// [`ProbedStream`] if the probe has been installed.
+macro_rules! with_stream {
+    ($self:ident, |$stream:ident| $call:expr) => {{
+        #[cfg(feature = "probe")]
+        let result = match $self.probe.as_deref_mut() {
+            Some(probe) => {
+                let $stream = &mut ProbedStream::new(&mut $self.stream, probe);
+                $call
+            }
+            None => {
+                let $stream = &mut $self.stream;
+                $call
+            }
+        };
+        #[cfg(not(feature = "probe"))]
+        let result = {
+            let $stream = &mut $self.stream;
+            $call
+        };
+        result
+    }};
+}
+
+/// Frame received from the websocket. The payload borrows the websocket read buffer (without
+/// any copy) and the borrow checker ensures it cannot outlive the next call to the websocket.
+pub enum WebsocketFrame<'a> {
+    Ping(u64, &'a [u8]),
+    Pong(u64, &'a [u8]),
+    Text(u64, bool, &'a [u8]),
+    Binary(u64, bool, &'a [u8]),
+    Continuation(u64, bool, &'a [u8]),
+    Close(u64, &'a [u8]),
+}
+
+/// Counters collected by the websocket decoder, useful when tuning the read path. Available
+/// with the `stats` feature.
+#[cfg(feature = "stats")]
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq)]
+pub struct WebsocketStats {
+    /// Number of frames decoded.
+    pub frames_decoded: u64,
+    /// Number of bytes read from the underlying stream.
+    pub bytes_read: u64,
+    /// Number of reads performed on the underlying stream.
+    pub reads: u64,
+    /// Largest frame payload seen so far (in bytes).
+    pub largest_frame: usize,
+}
+
+impl<'a> WebsocketFrame<'a> {
+    /// Returns the frame payload.
+    pub const fn payload(&self) -> &'a [u8] {
+        match self {
+            WebsocketFrame::Ping(_, payload)
+            | WebsocketFrame::Pong(_, payload)
+            | WebsocketFrame::Text(_, _, payload)
+            | WebsocketFrame::Binary(_, _, payload)
+            | WebsocketFrame::Continuation(_, _, payload)
+            | WebsocketFrame::Close(_, payload) => payload,
+        }
+    }
+
+    /// Returns the round trip time if this is the pong sent in response to
+    /// [`Websocket::send_timestamped_ping`], measured from the ping send to the pong receive time.
+    pub fn round_trip_time(&self) -> Option<Duration> {
+        match self {
+            WebsocketFrame::Pong(ts, payload) => {
+                let sent_ns = u64::from_be_bytes((*payload).try_into().ok()?);
+                Some(Duration::from_nanos(ts.saturating_sub(sent_ns)))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Bounds the amount of work performed by [`Websocket::receive_batch`] within a single call, so
+/// that an endpoint receiving a burst cannot starve other endpoints polled by the same `IOService`.
+/// Whatever remains buffered is processed during the next poll cycle.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct ReadBudget {
+    max_frames: usize,
+    max_bytes: usize,
+}
+
+impl Default for ReadBudget {
+    /// Unbounded budget.
+    fn default() -> Self {
+        Self {
+            max_frames: usize::MAX,
+            max_bytes: usize::MAX,
+        }
+    }
 }
 
+impl ReadBudget {
+    /// Creates budget limited to `max_frames` per call.
+    pub const fn new(max_frames: usize) -> ReadBudget {
+        Self {
+            max_frames,
+            max_bytes: usize::MAX,
+        }
+    }
+
+    /// Additionally limit the total payload size (in bytes) per call. The frame that crosses
+    /// the limit is still delivered.
+    pub const fn with_max_bytes(self, max_bytes: usize) -> ReadBudget {
+        Self { max_bytes, ..self }
+    }
+}
+
+/// Websocket over the stream `S`. The `CHUNK_SIZE` (number of bytes read from the stream at once)
+/// and the `INITIAL_CAPACITY` of the read buffer can be tuned with [`Websocket::with_read_buffer`],
+/// small chunks favour latency while large chunks favour throughput.
 #[derive(Debug)]
-pub struct Websocket<S> {
+pub struct Websocket<
+    S,
+    const CHUNK_SIZE: usize = DEFAULT_READ_CHUNK_SIZE,
+    const INITIAL_CAPACITY: usize = DEFAULT_INITIAL_CAPACITY,
+> {
     stream: S,
     closed: bool,
-    state: State,
+    last_error: Option<String>,
+    strict: bool,
+    heartbeats: bool,
+    handshake_response: Option<HandshakeResponse>,
+    state: State<CHUNK_SIZE, INITIAL_CAPACITY>,
+    rtt: Option<Box<RttTracker>>,
+    #[cfg(feature = "probe")]
+    probe: Option<Box<dyn IoProbe + Send>>,
 }
 
-impl<S> Websocket<S> {
+impl<S, const CHUNK_SIZE: usize, const INITIAL_CAPACITY: usize> Websocket<S, CHUNK_SIZE, INITIAL_CAPACITY> {
     /// Checks if the websocket is closed. This can be result of an IO error or the other side
     /// sending `WebsocketFrame::Closed`.
     pub const fn closed(&self) -> bool {
         self.closed
     }
 
+    /// Returns reference to the underlying stream.
+    pub const fn stream(&self) -> &S {
+        &self.stream
+    }
+
+    /// Returns mutable reference to the underlying stream. Reading from or writing to the stream
+    /// directly will corrupt the websocket framing.
+    pub fn stream_mut(&mut self) -> &mut S {
+        &mut self.stream
+    }
+
+    /// Returns the underlying stream if the websocket is closed or the handshake has not started
+    /// yet, as only then there is no partially read or written frame. Otherwise the websocket
+    /// is returned back unchanged.
+    #[allow(clippy::result_large_err)]
+    pub fn into_inner(self) -> Result<S, Self> {
+        let handshake_not_started = matches!(&self.state, State::Handshake(handshake) if handshake.not_started());
+        if self.closed || handshake_not_started {
+            Ok(self.stream)
+        } else {
+            Err(self)
+        }
+    }
+
+    /// Returns description of the error that caused the websocket to be closed, if any. Useful
+    /// to diagnose failures of the send operations whose result has been discarded.
+    pub fn last_error(&self) -> Option<&str> {
+        self.last_error.as_deref()
+    }
+
+    #[cold]
+    #[inline(never)]
+    fn close_with_error(&mut self, err: &Error) {
+        warn!("websocket closed due to error: {}", err);
+        self.closed = true;
+        self.last_error = Some(err.to_string());
+    }
+
     /// Checks if the handshake has completed successfully. If attempt is made to send a message
     /// while the handshake is pending the message will be buffered and dispatched once handshake
     /// has finished.
@@ -62,56 +241,447 @@ impl<S> Websocket<S> {
             State::Connection(_) => true,
         }
     }
+
+    /// Returns the response to the upgrade request once the handshake has completed, or `None` if
+    /// the handshake is still pending or the websocket has been created without the handshake.
+    pub const fn handshake_response(&self) -> Option<&HandshakeResponse> {
+        self.handshake_response.as_ref()
+    }
+
+    /// Returns the subprotocol selected by the server, if any.
+    pub fn subprotocol(&self) -> Option<&str> {
+        self.handshake_response
+            .as_ref()
+            .and_then(|response| response.header("Sec-WebSocket-Protocol"))
+            .and_then(|subprotocol| std::str::from_utf8(subprotocol).ok())
+    }
+
+    /// Returns the number of messages sent while the handshake is pending that have not yet been
+    /// dispatched. If the handshake fails these messages are returned with [`Error::HandshakeFailed`].
+    pub fn pending_message_count(&self) -> usize {
+        match &self.state {
+            State::Handshake(handshake) => handshake.pending_message_count(),
+            State::Connection(_) => 0,
+        }
+    }
+
+    /// Fail with [`Error::HandshakeTimeout`] if the handshake has not completed within the `timeout`,
+    /// measured by the `time_source` from the first attempt to receive. This lets the `IOService`
+    /// recreate the endpoint when the server never responds to the upgrade request.
+    pub fn with_handshake_timeout<T: TimeSource + 'static>(mut self, timeout: Duration, time_source: T) -> Self {
+        if let State::Handshake(handshake) = &mut self.state {
+            handshake.set_timeout(timeout, time_source);
+        }
+        self
+    }
+
+    /// Enable or disable the RFC 6455 conformance checks of the received frames (enabled by default).
+    /// When enabled, oversized or fragmented control frames as well as out of order continuation
+    /// frames are rejected with [`Error::Protocol`]. Disable for tolerant operation with peers that
+    /// are known to deviate from the specification.
+    pub fn with_strict_protocol(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        if let State::Connection(decoder) = &mut self.state {
+            decoder.set_strict(strict);
+        }
+        self
+    }
+
+    /// Enable or disable delivering the ping and pong frames to the caller (disabled by default).
+    /// The pings are answered with the pong regardless, so enabling this only lets the endpoint
+    /// observe the heartbeats, for example to track the connection liveness or the round trip time
+    /// with [`Websocket::send_timestamped_ping`].
+    pub fn with_heartbeat_frames(self, heartbeats: bool) -> Self {
+        Self { heartbeats, ..self }
+    }
+
+    /// Track the round trip time of the pings sent with [`Websocket::send_latency_ping`], measured
+    /// by the `time_source`. The matching pongs are processed by the websocket whether or not the
+    /// [`Websocket::with_heartbeat_frames`] are enabled, and the estimate is available with [`Websocket::rtt`].
+    pub fn with_rtt_tracking<T: TimeSource + 'static>(self, time_source: T) -> Self {
+        Self {
+            rtt: Some(Box::new(RttTracker::new(time_source))),
+            ..self
+        }
+    }
+
+    /// Returns the round trip time estimate, or `None` if the tracking has not been enabled with
+    /// [`Websocket::with_rtt_tracking`] or no pong has been matched yet.
+    pub fn rtt(&self) -> Option<RttEstimate> {
+        self.rtt.as_ref().and_then(|rtt| rtt.estimate())
+    }
+
+    /// Install the [`IoProbe`] that is notified about the reads, decoded frames, sends and flushes
+    /// performed by this websocket. Available with the `probe` feature.
+    #[cfg(feature = "probe")]
+    pub fn with_probe(self, probe: impl IoProbe + Send + 'static) -> Self {
+        Self {
+            probe: Some(Box::new(probe)),
+            ..self
+        }
+    }
+
+    /// Change the read buffer `CHUNK_SIZE` and `INITIAL_CAPACITY`, for example
+    /// `ws.with_read_buffer::<65536, 262144>()` for bulk transfers. Must be called before
+    /// anything has been received, as the data already buffered by the decoder is discarded.
+    pub fn with_read_buffer<const NEW_CHUNK_SIZE: usize, const NEW_INITIAL_CAPACITY: usize>(
+        self,
+    ) -> Websocket<S, NEW_CHUNK_SIZE, NEW_INITIAL_CAPACITY> {
+        let state = match self.state {
+            State::Handshake(handshake) => State::Handshake(handshake),
+            State::Connection(_) => {
+                let mut decoder = Decoder::new();
+                decoder.set_strict(self.strict);
+                State::Connection(decoder)
+            }
+        };
+        Websocket {
+            stream: self.stream,
+            closed: self.closed,
+            last_error: self.last_error,
+            strict: self.strict,
+            heartbeats: self.heartbeats,
+            handshake_response: self.handshake_response,
+            state,
+            rtt: self.rtt,
+            #[cfg(feature = "probe")]
+            probe: self.probe,
+        }
+    }
+
+    /// Returns the op code ([`raw::op::TEXT_FRAME`] or [`raw::op::BINARY_FRAME`]) of the
+    /// fragmented message that is still awaiting the continuation frames, useful for diagnostics.
+    /// Control frames (ping, pong and close) may arrive between the fragments without affecting it.
+    pub fn in_progress_message_opcode(&self) -> Option<u8> {
+        match &self.state {
+            State::Handshake(_) => None,
+            State::Connection(decoder) => decoder.in_progress_message_op_code(),
+        }
+    }
+
+    /// Number of complete frames already received that can be processed without reading from
+    /// the stream, for example to decide whether to skip the strategy work while the backlog is
+    /// drained. It is a hint as more data may be buffered by the stream itself (such as TLS).
+    pub fn frames_remaining_hint(&self) -> usize {
+        match &self.state {
+            State::Handshake(_) => 0,
+            State::Connection(decoder) => decoder.buffered_frames(),
+        }
+    }
+
+    /// Number of bytes received from the stream that have not been decoded into frames yet.
+    pub const fn bytes_buffered(&self) -> usize {
+        match &self.state {
+            State::Handshake(_) => 0,
+            State::Connection(decoder) => decoder.buffered(),
+        }
+    }
+
+    /// Checks if any data has been read from the stream since the last [`Websocket::receive_batch`]
+    /// started, as opposed to only decoding the frames that had been received before.
+    pub const fn was_network_read_performed(&self) -> bool {
+        match &self.state {
+            State::Handshake(_) => false,
+            State::Connection(decoder) => decoder.read_performed(),
+        }
+    }
+
+    /// Returns decoder statistics collected since the handshake completed (or since the last
+    /// [`Websocket::reset_stats`]).
+    #[cfg(feature = "stats")]
+    pub fn stats(&self) -> WebsocketStats {
+        match &self.state {
+            State::Handshake(_) => WebsocketStats::default(),
+            State::Connection(decoder) => *decoder.stats(),
+        }
+    }
+
+    /// Resets decoder statistics.
+    #[cfg(feature = "stats")]
+    pub fn reset_stats(&mut self) {
+        if let State::Connection(decoder) = &mut self.state {
+            decoder.reset_stats();
+        }
+    }
 }
 
 impl<S: Read + Write> Websocket<S> {
     pub fn new(stream: S, url: &str) -> io::Result<Self> {
+        Self::new_with_options(stream, url, HandshakeOptions::default())
+    }
+
+    /// Creates websocket that sends additional headers and subprotocols with the upgrade request.
+    pub fn new_with_options(stream: S, url: &str, options: HandshakeOptions) -> io::Result<Self> {
         Ok(Self {
             stream,
             closed: false,
-            state: State::handshake(url)?,
+            last_error: None,
+            strict: true,
+            heartbeats: false,
+            handshake_response: None,
+            state: State::handshake(url, options)?,
+            rtt: None,
+            #[cfg(feature = "probe")]
+            probe: None,
         })
     }
 
+    /// Creates websocket over the `stream` that has already been upgraded (or that speaks the
+    /// websocket framing directly), skipping the handshake entirely. Useful when reusing the
+    /// framing layer for private protocols over plain TCP as well as for replay testing.
+    pub fn new_connected(stream: S) -> Self {
+        Self {
+            stream,
+            closed: false,
+            last_error: None,
+            strict: true,
+            heartbeats: false,
+            handshake_response: None,
+            state: State::connection(),
+            rtt: None,
+            #[cfg(feature = "probe")]
+            probe: None,
+        }
+    }
+}
+
+impl<S: Read + Write, const CHUNK_SIZE: usize, const INITIAL_CAPACITY: usize>
+    Websocket<S, CHUNK_SIZE, INITIAL_CAPACITY>
+{
     #[inline]
-    pub fn receive_next(&mut self) -> Result<Option<WebsocketFrame>, Error> {
+    pub fn receive_next(&mut self) -> Result<Option<WebsocketFrame<'_>>, Error> {
+        self.receive_next_unbound()
+    }
+
+    /// Same as [`Websocket::receive_next`] but the frame is not tied to the websocket borrow,
+    /// for the crate internal callers that need to return the frame from within a loop.
+    #[inline]
+    pub(crate) fn receive_next_unbound(&mut self) -> Result<Option<WebsocketFrame<'static>>, Error> {
         self.ensure_not_closed()?;
-        match self.state.receive_next(&mut self.stream) {
-            Ok(frame) => Ok(frame),
+        let result = with_stream!(self, |stream| self.state.receive_next(
+            stream,
+            self.strict,
+            self.heartbeats,
+            self.rtt.as_deref_mut(),
+            &mut self.handshake_response
+        ));
+        match result {
+            Ok(frame) => {
+                #[cfg(feature = "probe")]
+                if let (Some(probe), Some(_)) = (self.probe.as_mut(), &frame) {
+                    probe.on_event(ProbeEvent::FrameDecoded, current_time_nanos());
+                }
+                #[cfg(feature = "tracing")]
+                if let Some(frame) = &frame {
+                    if crate::trace::sample_frame() {
+                        let kind = crate::trace::frame_kind(frame);
+                        tracing::trace!(target: "boomnet", kind, len = frame.payload().len(), "websocket frame decoded");
+                    }
+                }
+                Ok(frame)
+            }
             Err(err) => {
-                self.closed = true;
+                self.close_with_error(&err);
                 Err(err)?
             }
         }
     }
 
+    /// Receives frames invoking `on_frame` for each one until no more data is available or the
+    /// `budget` has been exhausted. Returns `true` if the budget has been exhausted, in which case
+    /// more frames may be ready and the call should be repeated during the next poll cycle. See
+    /// [`Websocket::frames_remaining_hint`], [`Websocket::bytes_buffered`] and
+    /// [`Websocket::was_network_read_performed`] for the state the batch has left behind.
+    pub fn receive_batch<F>(&mut self, budget: ReadBudget, mut on_frame: F) -> Result<bool, Error>
+    where
+        F: FnMut(WebsocketFrame<'_>) -> Result<(), Error>,
+    {
+        self.receive_batch_with(budget, |_| None, |frame, _| on_frame(frame))
+    }
+
+    #[inline]
+    fn receive_batch_with<F>(
+        &mut self,
+        budget: ReadBudget,
+        rx_timestamp_ns: fn(&S) -> Option<u64>,
+        mut on_frame: F,
+    ) -> Result<bool, Error>
+    where
+        F: FnMut(WebsocketFrame<'_>, Option<u64>) -> Result<(), Error>,
+    {
+        if let State::Connection(decoder) = &mut self.state {
+            decoder.clear_read_performed();
+        }
+        let mut frames = 0;
+        let mut bytes = 0;
+        // the decoder returns `None` after each read, so only stop once nothing new has arrived
+        let mut idle = false;
+        while frames < budget.max_frames && bytes < budget.max_bytes {
+            match self.receive_next_unbound()? {
+                Some(frame) => {
+                    idle = false;
+                    frames += 1;
+                    bytes += frame.payload().len();
+                    on_frame(frame, rx_timestamp_ns(&self.stream))?;
+                }
+                None if idle => return Ok(false),
+                None => idle = true,
+            }
+        }
+        Ok(true)
+    }
+
+    /// Receives the next frame and forwards its payload to the `target` websocket without any
+    /// intermediate copy. Only data frames (text, binary and continuation) are forwarded, control
+    /// frames are handled by this websocket as usual. The received frame is returned to the caller.
     #[inline]
+    pub fn forward_next<T: Read + Write, const TARGET_CHUNK_SIZE: usize, const TARGET_INITIAL_CAPACITY: usize>(
+        &mut self,
+        target: &mut Websocket<T, TARGET_CHUNK_SIZE, TARGET_INITIAL_CAPACITY>,
+    ) -> Result<Option<WebsocketFrame<'_>>, Error> {
+        let frame = self.receive_next()?;
+        match &frame {
+            Some(WebsocketFrame::Text(_, fin, payload)) => target.send_text(*fin, Some(payload))?,
+            Some(WebsocketFrame::Binary(_, fin, payload)) => target.send_binary(*fin, Some(payload))?,
+            Some(WebsocketFrame::Continuation(_, fin, payload)) => {
+                target.send(*fin, protocol::op::CONTINUATION_FRAME, Some(payload))?
+            }
+            _ => {}
+        }
+        Ok(frame)
+    }
+
+    #[inline]
+    #[must_use = "the websocket is closed if the send fails"]
     pub fn send_text(&mut self, fin: bool, body: Option<&[u8]>) -> Result<(), Error> {
         self.send(fin, protocol::op::TEXT_FRAME, body)
     }
 
     #[inline]
+    #[must_use = "the websocket is closed if the send fails"]
     pub fn send_binary(&mut self, fin: bool, body: Option<&[u8]>) -> Result<(), Error> {
         self.send(fin, protocol::op::BINARY_FRAME, body)
     }
 
     #[inline]
+    #[must_use = "the websocket is closed if the send fails"]
     pub fn send_pong(&mut self, body: Option<&[u8]>) -> Result<(), Error> {
         self.send(true, protocol::op::PONG, body)
     }
 
     #[inline]
+    #[must_use = "the websocket is closed if the send fails"]
     pub fn send_ping(&mut self, body: Option<&[u8]>) -> Result<(), Error> {
         self.send(true, protocol::op::PING, body)
     }
 
+    /// Sends the close frame with the `status_code` and optional `reason` (at most 123 bytes), after
+    /// which the websocket is closed. Nothing is sent if the handshake has not completed yet.
+    #[must_use = "the websocket is closed if the send fails"]
+    pub fn send_close(&mut self, status_code: u16, reason: Option<&[u8]>) -> Result<(), Error> {
+        self.ensure_not_closed()?;
+        let reason = reason.unwrap_or_default();
+        let len = 2 + reason.len();
+        if len > 125 {
+            return Err(Error::Protocol(ProtocolError::ControlFrameTooLarge(len)));
+        }
+        let result = match self.handshake_complete() {
+            true => {
+                let mut payload = [0u8; 125];
+                payload[..2].copy_from_slice(&status_code.to_be_bytes());
+                payload[2..len].copy_from_slice(reason);
+                self.send(true, protocol::op::CONNECTION_CLOSE, Some(&payload[..len]))
+            }
+            false => Ok(()),
+        };
+        self.closed = true;
+        result
+    }
+
+    /// Sends ping carrying the current time, the matching pong reports the round trip time with
+    /// [`WebsocketFrame::round_trip_time`]. Requires [`Websocket::with_heartbeat_frames`] for the
+    /// pong to be delivered.
+    #[inline]
+    #[must_use = "the websocket is closed if the send fails"]
+    pub fn send_timestamped_ping(&mut self) -> Result<(), Error> {
+        self.send_ping(Some(&current_time_nanos().to_be_bytes()))
+    }
+
+    /// Sends ping stamped by the time source passed to [`Websocket::with_rtt_tracking`], the matching
+    /// pong updates the [`Websocket::rtt`] estimate. Only the most recent ping is awaited, so the
+    /// ping sent before the previous one has been answered discards it. Fails with [`Error::IO`]
+    /// if the tracking has not been enabled.
+    #[must_use = "the websocket is closed if the send fails"]
+    pub fn send_latency_ping(&mut self) -> Result<(), Error> {
+        let payload = match self.rtt.as_mut() {
+            Some(rtt) => rtt.next_ping(),
+            None => return Err(io::Error::other("round trip time tracking is not enabled"))?,
+        };
+        self.send_ping(Some(&payload))
+    }
+
+    /// Sends text frame whose payload is made of multiple `segments` (such as static prefix, dynamic
+    /// body and static suffix) without concatenating them into an intermediate buffer first.
+    #[inline]
+    #[must_use = "the websocket is closed if the send fails"]
+    pub fn send_text_vectored(&mut self, fin: bool, segments: &[IoSlice]) -> Result<(), Error> {
+        self.send_vectored(fin, protocol::op::TEXT_FRAME, segments)
+    }
+
+    /// Sends binary frame whose payload is made of multiple `segments`, see [`Websocket::send_text_vectored`].
+    #[inline]
+    #[must_use = "the websocket is closed if the send fails"]
+    pub fn send_binary_vectored(&mut self, fin: bool, segments: &[IoSlice]) -> Result<(), Error> {
+        self.send_vectored(fin, protocol::op::BINARY_FRAME, segments)
+    }
+
+    #[inline]
+    fn send_vectored(&mut self, fin: bool, op_code: u8, segments: &[IoSlice]) -> Result<(), Error> {
+        self.ensure_not_closed()?;
+        #[cfg(feature = "probe")]
+        if let Some(probe) = self.probe.as_mut() {
+            probe.on_event(ProbeEvent::WriteStart, current_time_nanos());
+        }
+        let result = with_stream!(self, |stream| self.state.send_vectored(stream, fin, op_code, segments));
+        match result {
+            Ok(()) => {
+                #[cfg(feature = "tracing")]
+                if crate::trace::sample_frame() {
+                    let len = segments.iter().map(|segment| segment.len()).sum::<usize>();
+                    tracing::trace!(target: "boomnet", op_code, fin, len, "websocket frame sent");
+                }
+                Ok(())
+            }
+            // the message has been rejected, the websocket itself is still usable
+            Err(err @ Error::PendingBufferFull(_)) => Err(err),
+            Err(err) => {
+                self.close_with_error(&err);
+                Err(err)?
+            }
+        }
+    }
+
     #[inline]
     fn send(&mut self, fin: bool, op_code: u8, body: Option<&[u8]>) -> Result<(), Error> {
         self.ensure_not_closed()?;
-        match self.state.send(&mut self.stream, fin, op_code, body) {
-            Ok(()) => Ok(()),
+        #[cfg(feature = "probe")]
+        if let Some(probe) = self.probe.as_mut() {
+            probe.on_event(ProbeEvent::WriteStart, current_time_nanos());
+        }
+        let result = with_stream!(self, |stream| self.state.send(stream, fin, op_code, body));
+        match result {
+            Ok(()) => {
+                #[cfg(feature = "tracing")]
+                if crate::trace::sample_frame() {
+                    let len = body.map_or(0, <[u8]>::len);
+                    tracing::trace!(target: "boomnet", op_code, fin, len, "websocket frame sent");
+                }
+                Ok(())
+            }
+            // the message has been rejected, the websocket itself is still usable
+            Err(err @ Error::PendingBufferFull(_)) => Err(err),
             Err(err) => {
-                self.closed = true;
+                self.close_with_error(&err);
                 Err(err)?
             }
         }
@@ -133,8 +703,54 @@ impl<S: Read + Write> Websocket<S> {
     }
 }
 
+impl<S: Read + Write + Selectable, const CHUNK_SIZE: usize, const INITIAL_CAPACITY: usize>
+    Websocket<S, CHUNK_SIZE, INITIAL_CAPACITY>
+{
+    /// Same as [`Websocket::receive_batch`] but each frame is accompanied with the kernel receive
+    /// timestamp of the last read from the socket (see [`Selectable::rx_timestamp_ns`]), which is
+    /// the read that completed the frame unless it has been buffered by the previous read.
+    pub fn receive_batch_with_rx_timestamp<F>(&mut self, budget: ReadBudget, on_frame: F) -> Result<bool, Error>
+    where
+        F: FnMut(WebsocketFrame<'_>, Option<u64>) -> Result<(), Error>,
+    {
+        self.receive_batch_with(budget, S::rx_timestamp_ns, on_frame)
+    }
+}
+
+impl<S: Read + Write + Cork, const CHUNK_SIZE: usize, const INITIAL_CAPACITY: usize>
+    Websocket<S, CHUNK_SIZE, INITIAL_CAPACITY>
+{
+    /// Holds back the frames sent until [`Websocket::uncork`], so that they are written to the
+    /// underlying stream together (such as encrypted into single TLS record), see [`Cork`].
+    pub fn cork(&mut self) {
+        self.stream.cork()
+    }
+
+    /// Releases the frames held back since [`Websocket::cork`].
+    pub fn uncork(&mut self) -> Result<(), Error> {
+        Ok(self.stream.uncork()?)
+    }
+
+    /// Same as [`Websocket::receive_batch`] but the frames sent by `on_frame` are held back until the
+    /// whole batch has been received, trading the latency of the first response for fewer records
+    /// and system calls. The stream is uncorked even if the batch fails.
+    pub fn receive_batch_corked<F>(&mut self, budget: ReadBudget, on_frame: F) -> Result<bool, Error>
+    where
+        F: FnMut(WebsocketFrame<'_>) -> Result<(), Error>,
+    {
+        self.stream.cork();
+        let result = self.receive_batch(budget, on_frame);
+        let uncorked = self.uncork();
+        let more = result?;
+        uncorked?;
+        Ok(more)
+    }
+}
+
 #[cfg(feature = "mio")]
-impl<S: Source> Source for Websocket<S> {
+impl<S: Source, const CHUNK_SIZE: usize, const INITIAL_CAPACITY: usize> Source
+    for Websocket<S, CHUNK_SIZE, INITIAL_CAPACITY>
+{
     fn register(&mut self, registry: &Registry, token: Token, interests: Interest) -> io::Result<()> {
         registry.register(&mut self.stream, token, interests)
     }
@@ -148,7 +764,9 @@ impl<S: Source> Source for Websocket<S> {
     }
 }
 
-impl<S: Selectable> Selectable for Websocket<S> {
+impl<S: Selectable, const CHUNK_SIZE: usize, const INITIAL_CAPACITY: usize> Selectable
+    for Websocket<S, CHUNK_SIZE, INITIAL_CAPACITY>
+{
     fn connected(&mut self) -> io::Result<bool> {
         self.stream.connected()
     }
@@ -160,17 +778,82 @@ impl<S: Selectable> Selectable for Websocket<S> {
     fn make_readable(&mut self) {
         self.stream.make_readable();
     }
+
+    fn make_unreadable(&mut self) {
+        self.stream.make_unreadable();
+    }
+
+    fn socket_queues(&self) -> Option<SocketQueues> {
+        self.stream.socket_queues()
+    }
+
+    fn rx_timestamp_ns(&self) -> Option<u64> {
+        self.stream.rx_timestamp_ns()
+    }
+
+    #[cfg(feature = "stats")]
+    fn io_counters(&self) -> Option<crate::stream::IoCounters> {
+        self.stream.io_counters()
+    }
+
+    fn apply_socket_options(&mut self, options: &SocketOptions) -> io::Result<()> {
+        self.stream.apply_socket_options(options)
+    }
+
+    fn has_pending_writes(&self) -> bool {
+        self.stream.has_pending_writes()
+    }
+
+    fn flush_pending_writes(&mut self) -> io::Result<()> {
+        self.stream.flush_pending_writes()
+    }
+
+    fn peer_closed(&self) -> bool {
+        self.stream.peer_closed()
+    }
+
+    fn write_closed(&self) -> bool {
+        self.stream.write_closed()
+    }
+
+    fn make_write_closed(&mut self) {
+        self.stream.make_write_closed()
+    }
+
+    fn take_socket_error(&mut self) -> io::Result<Option<io::Error>> {
+        self.stream.take_socket_error()
+    }
+
+    fn connect_error(&self) -> Option<io::Error> {
+        self.stream.connect_error()
+    }
+}
+
+impl<S: ConnectionInfoProvider, const CHUNK_SIZE: usize, const INITIAL_CAPACITY: usize> ConnectionInfoProvider
+    for Websocket<S, CHUNK_SIZE, INITIAL_CAPACITY>
+{
+    fn connection_info(&self) -> &ConnectionInfo {
+        self.stream.connection_info()
+    }
+}
+
+impl<S: ConnectionPropertiesProvider, const CHUNK_SIZE: usize, const INITIAL_CAPACITY: usize>
+    ConnectionPropertiesProvider for Websocket<S, CHUNK_SIZE, INITIAL_CAPACITY>
+{
+    fn connection_properties(&self) -> ConnectionProperties {
+        self.stream.connection_properties()
+    }
 }
 
 #[derive(Debug)]
-enum State {
-    Handshake(Handshaker),
-    Connection(Decoder),
+enum State<const CHUNK_SIZE: usize, const INITIAL_CAPACITY: usize> {
+    Handshake(Box<Handshaker>),
+    Connection(Decoder<CHUNK_SIZE, INITIAL_CAPACITY>),
 }
 
-impl State {
-    pub fn handshake(url: &str) -> Result<Self, Error> {
-        Ok(Self::Handshake(Handshaker::new(url)?))
+impl<const CHUNK_SIZE: usize, const INITIAL_CAPACITY: usize> State<CHUNK_SIZE, INITIAL_CAPACITY> {
+    pub fn handshake(url: &str, options: HandshakeOptions) -> Result<Self, Error> {
+        Ok(Self::Handshake(Box::new(Handshaker::new(url, options)?)))
     }
 
     pub fn connection() -> Self {
@@ -178,34 +861,83 @@ impl State {
     }
 }
 
-impl State {
+impl<const CHUNK_SIZE: usize, const INITIAL_CAPACITY: usize> State<CHUNK_SIZE, INITIAL_CAPACITY> {
     #[inline]
-    fn receive_next<S: Read + Write>(&mut self, stream: &mut S) -> Result<Option<WebsocketFrame>, Error> {
+    fn receive_next<S: Read + Write>(
+        &mut self,
+        stream: &mut S,
+        strict: bool,
+        heartbeats: bool,
+        rtt: Option<&mut RttTracker>,
+        handshake_response: &mut Option<HandshakeResponse>,
+    ) -> Result<Option<WebsocketFrame<'static>>, Error> {
         match self {
-            State::Handshake(handshake) => match handshake.perform_handshake(stream) {
-                Ok(()) => {
-                    handshake.drain_pending_message_buffer(stream, encoder::send)?;
-                    *self = State::connection();
-                    Ok(None)
+            State::Handshake(handshake) => {
+                if let Some(timeout) = handshake.timed_out() {
+                    #[cfg(feature = "tracing")]
+                    tracing::warn!(target: "boomnet", ?timeout, "websocket handshake timed out");
+                    return Err(Error::HandshakeTimeout(timeout));
                 }
-                Err(err) if err.kind() == WouldBlock => Ok(None),
-                Err(err) => Err(err)?,
-            },
+                match handshake.perform_handshake(stream) {
+                    Ok(()) => {
+                        if let Some(response) = handshake.take_response() {
+                            #[cfg(feature = "tracing")]
+                            tracing::debug!(target: "boomnet", "websocket handshake completed");
+                            *handshake_response = Some(response);
+                        }
+                        // the stream may not accept all the pending messages at once, in which
+                        // case the remainder is written on the next poll
+                        if !handshake.drain_pending_message_buffer(stream)? {
+                            return Ok(None);
+                        }
+                        let mut decoder = Decoder::new();
+                        decoder.set_strict(strict);
+                        *self = State::Connection(decoder);
+                        Ok(None)
+                    }
+                    Err(err) if err.kind() == WouldBlock => Ok(None),
+                    Err(err) => {
+                        #[cfg(feature = "tracing")]
+                        tracing::warn!(target: "boomnet", error = %err, "websocket handshake failed");
+                        let pending = handshake.take_pending_messages();
+                        match err.get_ref().and_then(|err| err.downcast_ref::<Redirect>()) {
+                            Some(redirect) => Err(Error::Redirected(redirect.clone(), pending)),
+                            None => Err(Error::HandshakeFailed(err, pending)),
+                        }
+                    }
+                }
+            }
             State::Connection(decoder) => match decoder.decode_next(stream) {
-                Ok(Some(WebsocketFrame::Ping(_, payload))) => {
+                Ok(Some(WebsocketFrame::Ping(ts, payload))) => {
                     self.send(stream, true, protocol::op::PONG, Some(payload))?;
-                    Ok(None)
+                    Ok(heartbeats.then_some(WebsocketFrame::Ping(ts, payload)))
+                }
+                Ok(Some(WebsocketFrame::Pong(ts, payload))) => {
+                    if let Some(rtt) = rtt {
+                        rtt.on_pong(payload);
+                    }
+                    Ok(heartbeats.then_some(WebsocketFrame::Pong(ts, payload)))
                 }
                 Ok(Some(WebsocketFrame::Close(_, payload))) => {
                     let _ = self.send(stream, true, protocol::op::CONNECTION_CLOSE, Some(payload));
+                    // the status code is optional (and may be truncated if the strict checks are off)
+                    if payload.len() < std::mem::size_of::<u16>() {
+                        return Err(ReceivedCloseFrame(protocol::status::NO_STATUS_RECEIVED, String::new()));
+                    }
                     let (status_code, body) = payload.split_at(std::mem::size_of::<u16>());
                     let status_code = u16::from_be_bytes(status_code.try_into()?);
                     let body = String::from_utf8_lossy(body).to_string();
                     Err(ReceivedCloseFrame(status_code, body))
                 }
                 Ok(frame) => Ok(frame),
-                Err(err) if err.kind() == WouldBlock => Ok(None),
-                Err(err) => Err(err)?,
+                Err(Error::IO(err)) if err.kind() == WouldBlock => Ok(None),
+                Err(Error::Protocol(err)) => {
+                    // fail the connection letting the peer know why
+                    let status_code = err.close_status_code().to_be_bytes();
+                    let _ = encoder::send(stream, true, protocol::op::CONNECTION_CLOSE, Some(&status_code));
+                    Err(Error::Protocol(err))
+                }
+                Err(err) => Err(err),
             },
         }
     }
@@ -213,12 +945,26 @@ impl State {
     #[inline]
     fn send<S: Write>(&mut self, stream: &mut S, fin: bool, op_code: u8, body: Option<&[u8]>) -> Result<(), Error> {
         match self {
-            State::Handshake(handshake) => {
-                handshake.buffer_message(fin, op_code, body);
+            State::Handshake(handshake) => handshake.buffer_message(fin, op_code, body),
+            State::Connection(_) => {
+                encoder::send(stream, fin, op_code, body)?;
                 Ok(())
             }
+        }
+    }
+
+    #[inline]
+    fn send_vectored<S: Write>(
+        &mut self,
+        stream: &mut S,
+        fin: bool,
+        op_code: u8,
+        segments: &[IoSlice],
+    ) -> Result<(), Error> {
+        match self {
+            State::Handshake(handshake) => handshake.buffer_message_vectored(fin, op_code, segments),
             State::Connection(_) => {
-                encoder::send(stream, fin, op_code, body)?;
+                encoder::send_vectored(stream, fin, op_code, segments)?;
                 Ok(())
             }
         }
@@ -229,6 +975,10 @@ pub trait IntoWebsocket {
     fn into_websocket(self, url: &str) -> Websocket<Self>
     where
         Self: Sized;
+
+    fn into_websocket_with_options(self, url: &str, options: HandshakeOptions) -> Websocket<Self>
+    where
+        Self: Sized;
 }
 
 impl<T> IntoWebsocket for T
@@ -241,11 +991,26 @@ where
     {
         Websocket::new(self, url).unwrap()
     }
+
+    fn into_websocket_with_options(self, url: &str, options: HandshakeOptions) -> Websocket<Self>
+    where
+        Self: Sized,
+    {
+        Websocket::new_with_options(self, url, options).unwrap()
+    }
 }
 
 #[cfg(any(feature = "tls-webpki", feature = "tls-native"))]
 pub trait IntoTlsWebsocket {
-    fn into_tls_websocket(self, url: &str) -> Websocket<TlsStream<Self>>
+    fn into_tls_websocket(self, url: &str) -> io::Result<Websocket<TlsStream<Self>>>
+    where
+        Self: Sized;
+
+    fn into_tls_websocket_with_options(
+        self,
+        url: &str,
+        options: HandshakeOptions,
+    ) -> io::Result<Websocket<TlsStream<Self>>>
     where
         Self: Sized;
 }
@@ -255,14 +1020,24 @@ impl<T> IntoTlsWebsocket for T
 where
     T: Read + Write + NotTlsStream,
 {
-    fn into_tls_websocket(self, url: &str) -> Websocket<TlsStream<Self>>
+    fn into_tls_websocket(self, url: &str) -> io::Result<Websocket<TlsStream<Self>>>
+    where
+        Self: Sized,
+    {
+        self.into_tls_websocket_with_options(url, HandshakeOptions::default())
+    }
+
+    fn into_tls_websocket_with_options(
+        self,
+        url: &str,
+        options: HandshakeOptions,
+    ) -> io::Result<Websocket<TlsStream<Self>>>
     where
         Self: Sized,
     {
-        let url_tmp = Url::parse(url).unwrap();
-        let server_name = url_tmp.host_str().unwrap();
-        let tls_stream = self.into_tls_stream(server_name);
-        Websocket::new(tls_stream, url).unwrap()
+        let url_tmp = util::parse_url(url)?;
+        let tls_stream = self.into_tls_stream(&url_tmp.connection_info.host);
+        Websocket::new_with_options(tls_stream, url, options)
     }
 }
 
@@ -282,15 +1057,457 @@ where
     where
         Self: Sized,
     {
-        let url = Url::parse(self.as_ref()).map_err(io::Error::other)?;
-        let stream = TcpStream::connect(url.socket_addrs(|| None)?[0])?;
+        let url = util::parse_url(self.as_ref())?;
+        let info = &url.connection_info;
+        let stream = TcpStream::connect((info.host.as_str(), info.port))?;
 
-        let tls_ready_stream = match url.scheme() {
-            "ws" => Ok(TlsReadyStream::Plain(stream)),
-            "wss" => Ok(TlsReadyStream::Tls(TlsStream::wrap(stream, url.host_str().unwrap()))),
-            scheme => Err(io::Error::other(format!("unrecognised url scheme: {}", scheme))),
-        }?;
+        let tls_ready_stream = match url.tls {
+            false => TlsReadyStream::Plain(stream),
+            true => TlsReadyStream::Tls(TlsStream::wrap(stream, &info.host)),
+        };
 
         Websocket::new(tls_ready_stream, self.as_ref())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use crate::stream::buffer::IntoBufferedStream;
+    use crate::time::ManualTimeSource;
+
+    use super::*;
+
+    struct MockStream {
+        input: Cursor<Vec<u8>>,
+        output: Vec<u8>,
+    }
+
+    impl MockStream {
+        fn new(input: &[u8]) -> Self {
+            Self {
+                input: Cursor::new(input.to_vec()),
+                output: Vec::new(),
+            }
+        }
+    }
+
+    impl Read for MockStream {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            match self.input.read(buf)? {
+                0 => Err(io::Error::from(WouldBlock)),
+                n => Ok(n),
+            }
+        }
+    }
+
+    impl Write for MockStream {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.output.write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn should_forward_data_frame_to_target() {
+        let mut source = Websocket::new_connected(MockStream::new(b"\x81\x05hello"));
+        let mut target = Websocket::new_connected(MockStream::new(&[]));
+
+        let frame = loop {
+            if let Some(frame) = source.forward_next(&mut target).unwrap() {
+                break frame;
+            }
+        };
+
+        assert!(matches!(frame, WebsocketFrame::Text(_, true, b"hello")));
+        assert_eq!(b"\x81\x85\x00\x00\x00\x00hello", target.stream.output.as_slice());
+    }
+
+    #[test]
+    fn should_surface_heartbeat_frames() {
+        let sent_ns = current_time_nanos() - 1_000_000;
+        let mut input = b"\x89\x02hi\x8a\x08".to_vec();
+        input.extend_from_slice(&sent_ns.to_be_bytes());
+
+        let mut ws = Websocket::new_connected(MockStream::new(&input));
+        ws.receive_batch(ReadBudget::default(), |_| panic!("heartbeats should not be delivered"))
+            .unwrap();
+        assert_eq!(b"\x8a\x82\x00\x00\x00\x00hi", ws.stream.output.as_slice());
+
+        let mut ws = Websocket::new_connected(MockStream::new(&input)).with_heartbeat_frames(true);
+        let mut frames = Vec::new();
+        ws.receive_batch(ReadBudget::default(), |frame| {
+            frames.push((matches!(frame, WebsocketFrame::Ping(_, b"hi")), frame.round_trip_time()));
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(2, frames.len());
+        assert_eq!((true, None), frames[0]);
+        assert!(frames[1].1.unwrap() >= Duration::from_millis(1));
+        assert_eq!(b"\x8a\x82\x00\x00\x00\x00hi", ws.stream.output.as_slice());
+
+        ws.send_timestamped_ping().unwrap();
+        assert_eq!(b"\x89\x88", &ws.stream.output[8..10]);
+    }
+
+    #[test]
+    fn should_track_round_trip_time() {
+        let time_source = ManualTimeSource::new(1_000_000);
+        let mut ws = Websocket::new_connected(MockStream::new(&[])).with_rtt_tracking(time_source.clone());
+        assert_eq!(None, ws.rtt());
+
+        let pong = |ws: &mut Websocket<MockStream>, rtt: Duration| {
+            ws.send_latency_ping().unwrap();
+            let ping = ws.stream.output.split_off(0);
+            time_source.advance(rtt);
+            // unrelated pong is ignored
+            let mut input = b"\x8a\x02hi\x8a\x08".to_vec();
+            input.extend_from_slice(&ping[6..]);
+            ws.stream.input = Cursor::new(input);
+            for _ in 0..3 {
+                assert!(ws.receive_next().unwrap().is_none());
+            }
+        };
+
+        pong(&mut ws, Duration::from_millis(2));
+        let rtt = ws.rtt().unwrap();
+        assert_eq!(Duration::from_millis(2), rtt.last);
+        assert_eq!(Duration::from_millis(2), rtt.smoothed);
+        assert_eq!(1, rtt.samples);
+
+        pong(&mut ws, Duration::from_millis(10));
+        let rtt = ws.rtt().unwrap();
+        assert_eq!(Duration::from_millis(10), rtt.last);
+        assert_eq!(Duration::from_millis(3), rtt.smoothed);
+        assert_eq!(Duration::from_millis(2), rtt.min);
+        assert_eq!(2, rtt.samples);
+
+        let mut ws = Websocket::new_connected(MockStream::new(&[]));
+        assert!(matches!(ws.send_latency_ping(), Err(Error::IO(_))));
+    }
+
+    #[test]
+    fn should_bound_pending_messages() {
+        let options = HandshakeOptions::default().with_pending_buffer_limit(4, OverflowPolicy::Error);
+        let mut ws = Websocket::new_with_options(MockStream::new(&[]), "ws://localhost/ws", options).unwrap();
+        ws.send_text(true, Some(b"abc")).unwrap();
+        assert!(matches!(ws.send_text(true, Some(b"de")), Err(Error::PendingBufferFull(4))));
+        assert!(!ws.closed());
+        assert_eq!(1, ws.pending_message_count());
+    }
+
+    fn handshake(accept: Option<&str>) -> Result<Websocket<MockStream>, Error> {
+        let options = HandshakeOptions::default()
+            .with_header("X-Api-Key", "secret")
+            .with_subprotocol("v2")
+            .with_subprotocol("v1");
+        let mut ws = Websocket::new_with_options(MockStream::new(&[]), "ws://localhost/ws", options)?;
+        assert!(ws.receive_next()?.is_none());
+        let request = String::from_utf8(ws.stream.output.clone()).unwrap();
+        assert!(request.contains("\r\nSec-WebSocket-Protocol: v2, v1\r\nX-Api-Key: secret\r\n"));
+        let nonce = request
+            .lines()
+            .find_map(|line| line.strip_prefix("Sec-WebSocket-Key: "))
+            .unwrap();
+        let accept = accept
+            .map(|accept| accept.to_owned())
+            .unwrap_or_else(|| handshake::derive_accept(nonce));
+        let response = format!(
+            "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\
+             Sec-WebSocket-Accept: {}\r\nSec-WebSocket-Protocol: v1\r\nX-RateLimit-Limit: 300\r\n\r\n",
+            accept
+        );
+        ws.stream.input = Cursor::new(response.into_bytes());
+        while !ws.handshake_complete() {
+            ws.receive_next()?;
+        }
+        Ok(ws)
+    }
+
+    #[test]
+    fn should_validate_accept_and_expose_response() {
+        let ws = handshake(None).unwrap();
+        let response = ws.handshake_response().unwrap();
+        assert_eq!(Some(b"300".as_slice()), response.header("x-ratelimit-limit"));
+        assert_eq!(5, response.headers().count());
+        assert_eq!(Some("v1"), ws.subprotocol());
+
+        assert!(matches!(handshake(Some("invalid")), Err(Error::HandshakeFailed(..))));
+    }
+
+    #[test]
+    fn should_surface_redirect() {
+        let mut ws = Websocket::new(MockStream::new(&[]), "ws://localhost/ws").unwrap();
+        assert!(ws.receive_next().unwrap().is_none());
+        ws.send_text(true, Some(b"subscribe")).unwrap();
+        ws.stream.input =
+            Cursor::new(b"HTTP/1.1 302 Found\r\nLocation: /v2/ws\r\nContent-Length: 5\r\n\r\nmoved".to_vec());
+        let err = loop {
+            if let Err(err) = ws.receive_next() {
+                break err;
+            }
+        };
+        let Error::Redirected(redirect, pending) = err else {
+            panic!("expected redirect, got {err}");
+        };
+        assert_eq!(302, redirect.status);
+        assert_eq!("ws://localhost/v2/ws", redirect.resolve("ws://localhost/ws").unwrap());
+        assert_eq!(1, pending.len());
+
+        // the redirect remains reachable once converted to the endpoint error
+        let err = io::Error::from(Error::Redirected(redirect, pending));
+        let redirect = Redirect::find(&err).unwrap();
+        assert_eq!("/v2/ws", redirect.location);
+        let redirect = Redirect {
+            status: 301,
+            location: String::from("https://standby.localhost/ws"),
+        };
+        assert_eq!("wss://standby.localhost/ws", redirect.resolve("wss://localhost/ws").unwrap());
+    }
+
+    #[test]
+    fn should_time_out_handshake() {
+        let time_source = ManualTimeSource::new(0);
+        let mut ws = Websocket::new(MockStream::new(&[]), "ws://localhost/ws")
+            .unwrap()
+            .with_handshake_timeout(Duration::from_secs(5), time_source.clone());
+        assert!(ws.receive_next().unwrap().is_none());
+        time_source.advance(Duration::from_secs(5));
+        assert!(ws.receive_next().unwrap().is_none());
+        time_source.advance(Duration::from_secs(1));
+        assert!(matches!(ws.receive_next(), Err(Error::HandshakeTimeout(_))));
+        assert!(ws.closed());
+    }
+
+    #[test]
+    fn should_return_stream_only_when_safe() {
+        let ws = Websocket::new(MockStream::new(&[]), "ws://localhost/ws").unwrap();
+        assert!(ws.into_inner().is_ok());
+
+        let ws = Websocket::new_connected(MockStream::new(&[]));
+        let mut ws = ws.into_inner().err().unwrap();
+        ws.close_with_error(&Error::Closed);
+        assert!(ws.into_inner().is_ok());
+    }
+
+    #[test]
+    fn should_send_vectored_frame() {
+        let mut ws = Websocket::new_connected(MockStream::new(&[]));
+        ws.send_text_vectored(true, &[IoSlice::new(b"{\"id\":"), IoSlice::new(b"1"), IoSlice::new(b"}")])
+            .unwrap();
+        assert_eq!(b"\x81\x88\x00\x00\x00\x00{\"id\":1}", ws.stream.output.as_slice());
+    }
+
+    #[test]
+    fn should_report_batch_backlog() {
+        let mut ws = Websocket::new_connected(MockStream::new(b"\x82\x01a\x82\x02bc\x82\x03def\x82\x04gh"));
+        assert!(ws.receive_batch(ReadBudget::new(1), |_| Ok(())).unwrap());
+        assert!(ws.was_network_read_performed());
+        assert_eq!(2, ws.frames_remaining_hint());
+        assert_eq!(13, ws.bytes_buffered());
+
+        // decoded from the buffer without reading from the stream
+        assert!(ws.receive_batch(ReadBudget::new(2), |_| Ok(())).unwrap());
+        assert!(!ws.was_network_read_performed());
+        assert_eq!(0, ws.frames_remaining_hint());
+        assert_eq!(4, ws.bytes_buffered());
+    }
+
+    #[test]
+    fn should_hold_back_frames_while_corked() {
+        let stream = MockStream::new(b"\x89\x01a\x89\x01b").into_buffered_stream::<64>();
+        let mut ws = Websocket::new_connected(stream);
+        ws.cork();
+        ws.send_text(true, Some(b"hi")).unwrap();
+        ws.send_text(true, Some(b"yo")).unwrap();
+        assert_eq!(16, ws.stream().buffered_len());
+        ws.uncork().unwrap();
+        assert_eq!(0, ws.stream().buffered_len());
+
+        // the pongs are flushed once the whole batch has been received
+        assert!(!ws.receive_batch_corked(ReadBudget::default(), |_| Ok(())).unwrap());
+        assert!(!ws.stream().is_corked());
+        assert_eq!(0, ws.stream().buffered_len());
+    }
+
+    #[test]
+    fn should_reply_to_ping_interleaved_with_fragments() {
+        let mut ws = Websocket::new_connected(MockStream::new(b"\x02\x02ab\x89\x01p\x80\x02cd"));
+        let mut payloads = Vec::new();
+        for _ in 0..4 {
+            match ws.receive_next().unwrap() {
+                Some(WebsocketFrame::Binary(_, false, payload))
+                | Some(WebsocketFrame::Continuation(_, true, payload)) => {
+                    payloads.push(payload.to_vec());
+                    assert_eq!(payloads.len() == 1, ws.in_progress_message_opcode() == Some(raw::op::BINARY_FRAME));
+                }
+                frame => assert!(frame.is_none()),
+            }
+        }
+        assert_eq!(vec![b"ab".to_vec(), b"cd".to_vec()], payloads);
+        assert_eq!(None, ws.in_progress_message_opcode());
+        // pong sent while the fragmented message was in progress
+        assert_eq!(b"\x8a\x81\x00\x00\x00\x00p", ws.stream.output.as_slice());
+    }
+
+    #[test]
+    fn should_reject_protocol_violations() {
+        fn receive_error(input: &[u8], strict: bool) -> Option<Error> {
+            let mut ws = Websocket::new_connected(MockStream::new(input)).with_strict_protocol(strict);
+            for _ in 0..4 {
+                if let Err(err) = ws.receive_next() {
+                    return Some(err);
+                }
+            }
+            None
+        }
+
+        // fragmented ping
+        assert!(matches!(
+            receive_error(b"\x09\x00", true),
+            Some(Error::Protocol(ProtocolError::FragmentedControlFrame))
+        ));
+        // continuation without fragmented message
+        assert!(matches!(
+            receive_error(b"\x80\x01a", true),
+            Some(Error::Protocol(ProtocolError::UnexpectedContinuation))
+        ));
+        // new text frame while the fragmented message is in progress
+        assert!(matches!(
+            receive_error(b"\x01\x01a\x81\x01b", true),
+            Some(Error::Protocol(ProtocolError::ExpectedContinuation))
+        ));
+        assert!(receive_error(b"\x01\x01a\x80\x01b", true).is_none());
+        assert!(receive_error(b"\x80\x01a", false).is_none());
+        // reserved bits, reserved op code and masked server frame are rejected regardless
+        assert!(matches!(
+            receive_error(b"\xc1\x01a", false),
+            Some(Error::Protocol(ProtocolError::ReservedBitsSet(0x40)))
+        ));
+        assert!(matches!(receive_error(b"\x83\x00", false), Some(Error::Protocol(ProtocolError::UnknownOpCode(3)))));
+        assert!(matches!(
+            receive_error(b"\x81\x81\x00\x00\x00\x00a", false),
+            Some(Error::Protocol(ProtocolError::MaskedFrame))
+        ));
+    }
+
+    #[test]
+    fn should_validate_close_frame() {
+        fn close(payload: &[u8]) -> (Error, Vec<u8>) {
+            let mut input = vec![0x88, payload.len() as u8];
+            input.extend_from_slice(payload);
+            let mut ws = Websocket::new_connected(MockStream::new(&input));
+            loop {
+                if let Err(err) = ws.receive_next() {
+                    return (err, ws.stream.output);
+                }
+            }
+        }
+
+        let (err, output) = close(b"\x03\xe8bye");
+        assert!(matches!(err, ReceivedCloseFrame(1000, body) if body == "bye"));
+        assert_eq!(b"\x88\x85\x00\x00\x00\x00\x03\xe8bye", output.as_slice());
+
+        let (err, output) = close(b"");
+        assert!(matches!(err, ReceivedCloseFrame(1005, _)));
+        assert_eq!(b"\x88\x80\x00\x00\x00\x00", output.as_slice());
+
+        // the connection is failed with the status code matching the violation
+        let (err, output) = close(b"\x03");
+        assert!(matches!(err, Error::Protocol(ProtocolError::InvalidClosePayload)));
+        assert_eq!(b"\x88\x82\x00\x00\x00\x00\x03\xea", output.as_slice());
+        let (err, _) = close(b"\x03\xed");
+        assert!(matches!(err, Error::Protocol(ProtocolError::InvalidCloseStatus(1005))));
+        let (err, output) = close(b"\x03\xe8\xff");
+        assert!(matches!(err, Error::Protocol(ProtocolError::InvalidCloseReason)));
+        assert_eq!(b"\x03\xef", &output[6..]);
+    }
+
+    #[test]
+    fn should_send_close_frame() {
+        let mut ws = Websocket::new_connected(MockStream::new(&[]));
+        ws.send_close(1001, Some(b"bye")).unwrap();
+        assert!(ws.closed());
+        assert_eq!(b"\x88\x85\x00\x00\x00\x00\x03\xe9bye", ws.stream.output.as_slice());
+        assert!(matches!(ws.send_close(1000, None), Err(Error::Closed)));
+
+        let mut ws = Websocket::new(MockStream::new(&[]), "ws://localhost/ws").unwrap();
+        ws.send_close(1001, None).unwrap();
+        assert!(ws.closed() && ws.stream.output.is_empty());
+    }
+
+    #[test]
+    fn should_send_and_receive_with_split_halves() {
+        let ws = Websocket::new(MockStream::new(&[]), "ws://localhost/ws").unwrap();
+        let (reader, mut writer) = ws.split();
+        writer.send_text(true, Some(b"early")).unwrap();
+        assert!(!writer.handshake_complete());
+        assert_eq!(1, writer.pending_message_count());
+        assert_eq!(1, reader.unsplit(writer).pending_message_count());
+
+        let (mut reader, mut writer) = Websocket::new_connected(MockStream::new(b"\x89\x00\x81\x02hi")).split();
+        let mut received = Vec::new();
+        while received.is_empty() {
+            if let Some(WebsocketFrame::Text(_, _, data)) = reader.receive_next().unwrap() {
+                received.extend_from_slice(data);
+                writer.send_text(true, Some(data)).unwrap();
+            }
+        }
+        let ws = reader.unsplit(writer);
+        assert_eq!(b"\x8a\x80\x00\x00\x00\x00\x81\x82\x00\x00\x00\x00hi", ws.stream.output.as_slice());
+    }
+
+    #[test]
+    fn should_stop_when_budget_exhausted() {
+        let mut ws = Websocket::new_connected(MockStream::new(b"\x81\x01a\x81\x01b\x81\x01c"));
+
+        let mut payloads = Vec::new();
+        let exhausted = ws
+            .receive_batch(ReadBudget::new(2), |frame| {
+                payloads.push(frame.payload().to_vec());
+                Ok(())
+            })
+            .unwrap();
+        assert!(exhausted);
+        assert_eq!(vec![b"a".to_vec(), b"b".to_vec()], payloads);
+
+        let exhausted = ws
+            .receive_batch(ReadBudget::new(2), |frame| {
+                payloads.push(frame.payload().to_vec());
+                Ok(())
+            })
+            .unwrap();
+        assert!(!exhausted);
+        assert_eq!(b"c", payloads[2].as_slice());
+    }
+
+    #[test]
+    fn should_read_with_custom_chunk_size() {
+        let mut ws = Websocket::new_connected(MockStream::new(b"\x81\x05hello\x82\x03abc")).with_read_buffer::<2, 4>();
+
+        let mut frames = Vec::new();
+        let mut reads = 0;
+        while frames.len() < 2 {
+            reads += 1;
+            if let Some(frame) = ws.receive_next().unwrap() {
+                frames.push(frame.payload().to_vec());
+            }
+        }
+        assert_eq!(vec![b"hello".to_vec(), b"abc".to_vec()], frames);
+        assert!(reads > 6, "expected chunked reads, got {reads}");
+    }
+
+    #[test]
+    #[cfg(any(feature = "tls-webpki", feature = "tls-native"))]
+    fn should_return_error_for_invalid_tls_websocket_url() {
+        use crate::stream::file::FileStream;
+
+        assert!(FileStream::from_bytes(Vec::new())
+            .into_tls_websocket("not a url")
+            .is_err());
+    }
+}
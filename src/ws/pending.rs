@@ -0,0 +1,298 @@
+use std::collections::VecDeque;
+use std::io;
+use std::io::ErrorKind::{Interrupted, WouldBlock, WriteZero};
+use std::io::{IoSlice, Write};
+
+use crate::ws::{raw, Error, PendingMessage};
+
+/// Action taken when the message sent while the handshake is pending does not fit within the limit
+/// set with [`HandshakeOptions::with_pending_buffer_limit`](crate::ws::HandshakeOptions::with_pending_buffer_limit).
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum OverflowPolicy {
+    /// Reject the message with [`Error::PendingBufferFull`], the websocket remains open.
+    Error,
+    /// Discard the oldest pending messages until the new message fits.
+    DropOldest,
+    /// Replace the most recent pending message of the same type (text, binary, ...) with the new
+    /// message, useful when only the latest message matters. Fails as [`OverflowPolicy::Error`] if
+    /// the new message still does not fit.
+    Coalesce,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub(crate) struct PendingBufferLimit {
+    pub max_bytes: usize,
+    pub policy: OverflowPolicy,
+}
+
+#[derive(Debug, Copy, Clone)]
+struct Entry {
+    fin: bool,
+    op_code: u8,
+    has_body: bool,
+    offset: usize,
+    len: usize,
+}
+
+/// Messages queued while the handshake is pending. The bodies are appended to the single arena
+/// that is reused for the whole burst, instead of allocating each message separately.
+#[derive(Debug)]
+pub(crate) struct PendingBuffer {
+    arena: Vec<u8>,
+    entries: VecDeque<Entry>,
+    live_bytes: usize,
+    limit: Option<PendingBufferLimit>,
+    // encoded frame that is being written and how much of it the stream has accepted so far
+    frame: Vec<u8>,
+    written: usize,
+}
+
+impl PendingBuffer {
+    pub fn new(limit: Option<PendingBufferLimit>) -> PendingBuffer {
+        Self {
+            arena: Vec::new(),
+            entries: VecDeque::new(),
+            live_bytes: 0,
+            limit,
+            frame: Vec::new(),
+            written: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len() + usize::from(!self.frame.is_empty())
+    }
+
+    pub fn push(&mut self, fin: bool, op_code: u8, body: Option<&[u8]>) -> Result<(), Error> {
+        let body = body.map(IoSlice::new);
+        self.push_vectored(fin, op_code, body.as_ref().map(std::slice::from_ref))
+    }
+
+    pub fn push_vectored(&mut self, fin: bool, op_code: u8, segments: Option<&[IoSlice]>) -> Result<(), Error> {
+        let len = segments.map_or(0, |segments| segments.iter().map(|segment| segment.len()).sum());
+        self.make_room(op_code, len)?;
+        let offset = self.arena.len();
+        for segment in segments.unwrap_or_default() {
+            self.arena.extend_from_slice(segment);
+        }
+        self.entries.push_back(Entry {
+            fin,
+            op_code,
+            has_body: segments.is_some(),
+            offset,
+            len,
+        });
+        self.live_bytes += len;
+        Ok(())
+    }
+
+    /// Sends the pending messages in the order they were queued and returns `true` once all of them
+    /// have been written. Each frame is written in full before the next one is started, if the stream
+    /// would block mid frame the write offset is kept and the frame is resumed on the next call, so
+    /// that the peer never observes a partial frame. The messages that have not been sent due to
+    /// an error remain queued.
+    pub fn drain<S: Write>(&mut self, stream: &mut S) -> io::Result<bool> {
+        loop {
+            if self.frame.is_empty() {
+                let Some(entry) = self.entries.pop_front() else {
+                    break;
+                };
+                // masking key is set to zero, same as for the frames sent by the websocket
+                let body = &self.arena[entry.offset..entry.offset + entry.len];
+                raw::encode_frame(&mut self.frame, entry.fin, entry.op_code, Some([0; 4]), body);
+                self.live_bytes -= entry.len;
+                self.written = 0;
+            }
+            while self.written < self.frame.len() {
+                match stream.write(&self.frame[self.written..]) {
+                    Ok(0) => return Err(io::Error::from(WriteZero)),
+                    Ok(written) => self.written += written,
+                    Err(err) if err.kind() == WouldBlock => return Ok(false),
+                    Err(err) if err.kind() == Interrupted => {}
+                    Err(err) => return Err(err),
+                }
+            }
+            self.frame.clear();
+            self.written = 0;
+        }
+        self.arena.clear();
+        match stream.flush() {
+            Ok(()) => Ok(true),
+            Err(err) if err.kind() == WouldBlock => Ok(false),
+            Err(err) => Err(err),
+        }
+    }
+
+    pub fn take(&mut self) -> Vec<PendingMessage> {
+        let messages = self
+            .entries
+            .iter()
+            .map(|entry| PendingMessage {
+                fin: entry.fin,
+                op_code: entry.op_code,
+                body: self.body(entry).map(|body| body.to_vec()),
+            })
+            .collect();
+        self.entries.clear();
+        self.arena.clear();
+        self.live_bytes = 0;
+        messages
+    }
+
+    #[inline]
+    fn body(&self, entry: &Entry) -> Option<&[u8]> {
+        entry
+            .has_body
+            .then(|| &self.arena[entry.offset..entry.offset + entry.len])
+    }
+
+    fn make_room(&mut self, op_code: u8, len: usize) -> Result<(), Error> {
+        let Some(limit) = self.limit else {
+            return Ok(());
+        };
+        if self.live_bytes + len > limit.max_bytes {
+            match limit.policy {
+                OverflowPolicy::Error => {}
+                OverflowPolicy::DropOldest => {
+                    while self.live_bytes + len > limit.max_bytes {
+                        match self.entries.pop_front() {
+                            Some(entry) => self.live_bytes -= entry.len,
+                            None => break,
+                        }
+                    }
+                }
+                OverflowPolicy::Coalesce => {
+                    if let Some(index) = self.entries.iter().rposition(|entry| entry.op_code == op_code) {
+                        if let Some(entry) = self.entries.remove(index) {
+                            self.live_bytes -= entry.len;
+                        }
+                    }
+                }
+            }
+            if self.live_bytes + len > limit.max_bytes {
+                return Err(Error::PendingBufferFull(limit.max_bytes));
+            }
+            self.compact();
+        }
+        Ok(())
+    }
+
+    /// Reclaims the arena space left behind by the discarded messages.
+    fn compact(&mut self) {
+        if self.arena.len() - self.live_bytes <= self.live_bytes {
+            return;
+        }
+        let mut offset = 0;
+        for entry in self.entries.iter_mut() {
+            self.arena.copy_within(entry.offset..entry.offset + entry.len, offset);
+            entry.offset = offset;
+            offset += entry.len;
+        }
+        self.arena.truncate(offset);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ws::protocol::op::{BINARY_FRAME, TEXT_FRAME};
+
+    use super::*;
+
+    fn frames(mut buf: &[u8]) -> Vec<(u8, Vec<u8>)> {
+        let mut frames = Vec::new();
+        while let Some((header, payload, len)) = raw::decode_frame(buf) {
+            frames.push((header.op_code, payload.to_vec()));
+            buf = &buf[len..];
+        }
+        assert!(buf.is_empty());
+        frames
+    }
+
+    fn bodies(buffer: &mut PendingBuffer) -> Vec<Vec<u8>> {
+        buffer
+            .take()
+            .into_iter()
+            .map(|message| message.body.unwrap_or_default())
+            .collect()
+    }
+
+    #[test]
+    fn should_apply_overflow_policy() {
+        let limit = |policy| Some(PendingBufferLimit { max_bytes: 8, policy });
+
+        let mut buffer = PendingBuffer::new(limit(OverflowPolicy::Error));
+        buffer.push(true, TEXT_FRAME, Some(b"abcd")).unwrap();
+        buffer.push(true, TEXT_FRAME, Some(b"efgh")).unwrap();
+        assert!(matches!(buffer.push(true, TEXT_FRAME, Some(b"i")), Err(Error::PendingBufferFull(8))));
+        assert_eq!(vec![b"abcd".to_vec(), b"efgh".to_vec()], bodies(&mut buffer));
+
+        let mut buffer = PendingBuffer::new(limit(OverflowPolicy::DropOldest));
+        for body in [b"abc", b"def", b"ghi"] {
+            buffer.push(true, TEXT_FRAME, Some(body)).unwrap();
+        }
+        assert_eq!(vec![b"def".to_vec(), b"ghi".to_vec()], bodies(&mut buffer));
+
+        let mut buffer = PendingBuffer::new(limit(OverflowPolicy::Coalesce));
+        buffer.push(true, TEXT_FRAME, Some(b"sub")).unwrap();
+        buffer.push(true, BINARY_FRAME, Some(b"abc")).unwrap();
+        buffer.push(true, BINARY_FRAME, Some(b"xyz")).unwrap();
+        let mut stream = Vec::new();
+        assert!(buffer.drain(&mut stream).unwrap());
+        assert_eq!(vec![(TEXT_FRAME, b"sub".to_vec()), (BINARY_FRAME, b"xyz".to_vec())], frames(&stream));
+        assert_eq!(0, buffer.len());
+    }
+
+    #[test]
+    fn should_resume_partially_written_frame() {
+        // accepts at most `capacity` bytes before it would block
+        struct ThrottledStream {
+            sent: Vec<u8>,
+            capacity: usize,
+        }
+
+        impl Write for ThrottledStream {
+            fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+                if self.capacity == 0 {
+                    return Err(io::Error::from(WouldBlock));
+                }
+                let len = buf.len().min(self.capacity);
+                self.sent.extend_from_slice(&buf[..len]);
+                self.capacity -= len;
+                Ok(len)
+            }
+
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let mut buffer = PendingBuffer::new(None);
+        buffer.push(true, TEXT_FRAME, Some(b"hello")).unwrap();
+        buffer.push(true, BINARY_FRAME, Some(b"world")).unwrap();
+
+        let mut stream = ThrottledStream {
+            sent: Vec::new(),
+            capacity: 4,
+        };
+        assert!(!buffer.drain(&mut stream).unwrap());
+        assert_eq!(2, buffer.len());
+
+        // message queued in the meantime goes after the partially written one
+        buffer.push(true, TEXT_FRAME, Some(b"!")).unwrap();
+        stream.capacity = 10;
+        assert!(!buffer.drain(&mut stream).unwrap());
+        assert_eq!(2, buffer.len());
+
+        stream.capacity = usize::MAX;
+        assert!(buffer.drain(&mut stream).unwrap());
+        assert_eq!(0, buffer.len());
+        assert_eq!(
+            vec![
+                (TEXT_FRAME, b"hello".to_vec()),
+                (BINARY_FRAME, b"world".to_vec()),
+                (TEXT_FRAME, b"!".to_vec())
+            ],
+            frames(&stream.sent)
+        );
+    }
+}
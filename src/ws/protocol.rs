@@ -1,11 +1,17 @@
+/// Final fragment bit of the first header byte.
 pub const FIN_MASK: u8 = 0b1000_0000;
+/// Reserved bits of the first header byte, must be zero unless an extension has been negotiated.
 pub const RSV1_MASK: u8 = 0b0100_0000;
 pub const RSV2_MASK: u8 = 0b0010_0000;
 pub const RSV3_MASK: u8 = 0b0001_0000;
+/// Opcode bits of the first header byte.
 pub const OP_CODE_MASK: u8 = 0b0000_1111;
+/// Mask bit of the second header byte.
 pub const MASK_MASK: u8 = 0b1000_0000;
+/// Payload length bits of the second header byte.
 pub const PAYLOAD_LENGTH_MASK: u8 = 0b0111_1111;
 
+/// Frame opcodes.
 pub mod op {
     pub const CONTINUATION_FRAME: u8 = 0x0;
     pub const TEXT_FRAME: u8 = 0x1;
@@ -14,3 +20,16 @@ pub mod op {
     pub const PING: u8 = 0x9;
     pub const PONG: u8 = 0xA;
 }
+
+/// Close frame status codes.
+pub mod status {
+    pub const PROTOCOL_ERROR: u16 = 1002;
+    pub const NO_STATUS_RECEIVED: u16 = 1005;
+    pub const INVALID_PAYLOAD: u16 = 1007;
+
+    /// Checks if the status code may be sent in the close frame, the codes reserved for the local
+    /// use (such as 1005 and 1006) and the unassigned ones are rejected.
+    pub const fn is_valid(status_code: u16) -> bool {
+        matches!(status_code, 1000..=1003 | 1007..=1014 | 3000..=4999)
+    }
+}
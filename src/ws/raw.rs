@@ -0,0 +1,187 @@
+//! Low level access to the websocket framing for building custom tooling such as fuzzers, proxies
+//! or traffic generators. The functions operate on byte slices and are independent of any stream,
+//! unlike the [`Websocket`](crate::ws::Websocket) no protocol validation is performed.
+//!
+//! # Examples
+//!
+//! ```
+//! use boomnet::ws::raw::{self, op, FrameHeader};
+//!
+//! let mut buf = Vec::new();
+//! raw::encode_frame(&mut buf, true, op::TEXT_FRAME, Some([1, 2, 3, 4]), b"hello");
+//!
+//! let (header, header_len) = FrameHeader::decode(&buf).unwrap();
+//! assert!(header.fin);
+//! assert_eq!(op::TEXT_FRAME, header.op_code);
+//! assert_eq!(5, header.payload_len);
+//!
+//! let mut payload = buf[header_len..].to_vec();
+//! raw::apply_mask(&mut payload, header.mask.unwrap());
+//! assert_eq!(b"hello", payload.as_slice());
+//! ```
+
+pub use crate::ws::protocol::{
+    op, status, FIN_MASK, MASK_MASK, OP_CODE_MASK, PAYLOAD_LENGTH_MASK, RSV1_MASK, RSV2_MASK, RSV3_MASK,
+};
+
+/// Largest possible frame header: 2 bytes, 8 bytes of the extended payload length and 4 bytes of the mask.
+pub const MAX_HEADER_LEN: usize = 14;
+
+/// Websocket frame header.
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq)]
+pub struct FrameHeader {
+    /// Final fragment of the message.
+    pub fin: bool,
+    /// Reserved bits in the lowest three bits (`RSV1` is `0b100`).
+    pub rsv: u8,
+    /// Frame opcode, see [`op`].
+    pub op_code: u8,
+    /// Masking key, present in the frames sent by the client.
+    pub mask: Option<[u8; 4]>,
+    /// Length of the payload that follows the header.
+    pub payload_len: u64,
+}
+
+impl FrameHeader {
+    /// Number of bytes the header occupies once encoded.
+    pub const fn len(&self) -> usize {
+        let extended_len = match self.payload_len {
+            0..=125 => 0,
+            126..=0xFFFF => 2,
+            _ => 8,
+        };
+        let mask_len = match self.mask {
+            Some(_) => 4,
+            None => 0,
+        };
+        2 + extended_len + mask_len
+    }
+
+    /// Always `false`, as the header is at least two bytes long.
+    pub const fn is_empty(&self) -> bool {
+        false
+    }
+
+    /// Appends the encoded header to the `buf`, using the shortest payload length encoding.
+    pub fn encode(&self, buf: &mut Vec<u8>) {
+        let mut first = self.op_code & OP_CODE_MASK | (self.rsv & 0b111) << 4;
+        if self.fin {
+            first |= FIN_MASK;
+        }
+        let mask = match self.mask {
+            Some(_) => MASK_MASK,
+            None => 0,
+        };
+        buf.push(first);
+        match self.payload_len {
+            len @ 0..=125 => buf.push(mask | len as u8),
+            len @ 126..=0xFFFF => {
+                buf.push(mask | 126);
+                buf.extend_from_slice(&(len as u16).to_be_bytes());
+            }
+            len => {
+                buf.push(mask | 127);
+                buf.extend_from_slice(&len.to_be_bytes());
+            }
+        }
+        if let Some(mask) = self.mask {
+            buf.extend_from_slice(&mask);
+        }
+    }
+
+    /// Decodes the header at the start of the `buf`, returning it together with the number of bytes
+    /// it occupies. Returns `None` if the `buf` does not contain the whole header yet.
+    pub fn decode(buf: &[u8]) -> Option<(FrameHeader, usize)> {
+        let (&first, &second) = (buf.first()?, buf.get(1)?);
+        let (payload_len, mut offset) = match second & PAYLOAD_LENGTH_MASK {
+            126 => (u16::from_be_bytes(buf.get(2..4)?.try_into().unwrap()) as u64, 4),
+            127 => (u64::from_be_bytes(buf.get(2..10)?.try_into().unwrap()), 10),
+            len => (len as u64, 2),
+        };
+        let mask = match second & MASK_MASK {
+            0 => None,
+            _ => {
+                let mask = buf.get(offset..offset + 4)?.try_into().unwrap();
+                offset += 4;
+                Some(mask)
+            }
+        };
+        let header = FrameHeader {
+            fin: first & FIN_MASK != 0,
+            rsv: (first >> 4) & 0b111,
+            op_code: first & OP_CODE_MASK,
+            mask,
+            payload_len,
+        };
+        Some((header, offset))
+    }
+}
+
+/// Appends the whole frame to the `buf`, masking the `payload` if the `mask` is specified.
+pub fn encode_frame(buf: &mut Vec<u8>, fin: bool, op_code: u8, mask: Option<[u8; 4]>, payload: &[u8]) {
+    let header = FrameHeader {
+        fin,
+        rsv: 0,
+        op_code,
+        mask,
+        payload_len: payload.len() as u64,
+    };
+    header.encode(buf);
+    let start = buf.len();
+    buf.extend_from_slice(payload);
+    if let Some(mask) = mask {
+        apply_mask(&mut buf[start..], mask);
+    }
+}
+
+/// Decodes the frame at the start of the `buf`, returning the header, the (still masked) payload
+/// and the total number of bytes the frame occupies. Returns `None` if the frame is incomplete.
+pub fn decode_frame(buf: &[u8]) -> Option<(FrameHeader, &[u8], usize)> {
+    let (header, header_len) = FrameHeader::decode(buf)?;
+    let end = header_len.checked_add(usize::try_from(header.payload_len).ok()?)?;
+    Some((header, buf.get(header_len..end)?, end))
+}
+
+/// Masks (or unmasks) the `payload` in place with the masking key.
+pub fn apply_mask(payload: &mut [u8], mask: [u8; 4]) {
+    for (i, byte) in payload.iter_mut().enumerate() {
+        *byte ^= mask[i & 3];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_encode_and_decode_frames() {
+        for len in [0, 125, 126, 0xFFFF, 0x10000] {
+            let payload = vec![7u8; len];
+            let mut buf = Vec::new();
+            encode_frame(&mut buf, false, op::BINARY_FRAME, None, &payload);
+            let (header, decoded, frame_len) = decode_frame(&buf).unwrap();
+            assert_eq!(buf.len(), frame_len);
+            assert_eq!(header.len() + len, frame_len);
+            assert_eq!(payload.as_slice(), decoded);
+            assert!(!header.fin);
+            assert!(decode_frame(&buf[..frame_len - 1]).is_none());
+        }
+
+        // same as sent by the websocket
+        let mut buf = Vec::new();
+        encode_frame(&mut buf, true, op::TEXT_FRAME, Some([0; 4]), b"hi");
+        assert_eq!(b"\x81\x82\x00\x00\x00\x00hi", buf.as_slice());
+
+        let header = FrameHeader {
+            fin: true,
+            rsv: 0b101,
+            op_code: op::PING,
+            mask: Some([1, 2, 3, 4]),
+            payload_len: 300,
+        };
+        let mut buf = Vec::new();
+        header.encode(&mut buf);
+        assert_eq!(Some((header, 8)), FrameHeader::decode(&buf));
+        assert_eq!(None, FrameHeader::decode(&buf[..7]));
+    }
+}
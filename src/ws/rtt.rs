@@ -0,0 +1,81 @@
+//! Round trip time estimation based on the ping and pong frames, see [`Websocket::with_rtt_tracking`](crate::ws::Websocket::with_rtt_tracking).
+
+use std::fmt;
+use std::time::Duration;
+
+use crate::time::TimeSource;
+
+/// Round trip time measured with the latency pings, see [`Websocket::send_latency_ping`](crate::ws::Websocket::send_latency_ping).
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq)]
+pub struct RttEstimate {
+    /// Most recent sample.
+    pub last: Duration,
+    /// Exponentially weighted moving average of the samples (with `1/8` weight of the new sample,
+    /// as the TCP smoothed round trip time).
+    pub smoothed: Duration,
+    /// Smallest sample seen so far.
+    pub min: Duration,
+    /// Number of samples collected.
+    pub samples: u64,
+}
+
+/// Stamps the pings with the time source and matches the pongs carrying the same payload.
+pub(crate) struct RttTracker {
+    time_source: Box<dyn TimeSource>,
+    // payload of the last ping that has not been answered yet
+    outstanding: Option<[u8; 8]>,
+    estimate: Option<RttEstimate>,
+}
+
+impl fmt::Debug for RttTracker {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RttTracker")
+            .field("outstanding", &self.outstanding)
+            .field("estimate", &self.estimate)
+            .finish()
+    }
+}
+
+impl RttTracker {
+    pub fn new<T: TimeSource + 'static>(time_source: T) -> RttTracker {
+        Self {
+            time_source: Box::new(time_source),
+            outstanding: None,
+            estimate: None,
+        }
+    }
+
+    /// Returns the payload of the next ping, replacing the one still outstanding (if any).
+    pub fn next_ping(&mut self) -> [u8; 8] {
+        let payload = self.time_source.current_time_nanos().to_be_bytes();
+        self.outstanding = Some(payload);
+        payload
+    }
+
+    /// Updates the estimate if the pong answers the outstanding ping.
+    pub fn on_pong(&mut self, payload: &[u8]) {
+        if self.outstanding.as_ref().map(|ping| ping.as_slice()) != Some(payload) {
+            return;
+        }
+        let sent_ns = u64::from_be_bytes(self.outstanding.take().unwrap());
+        let rtt = Duration::from_nanos(self.time_source.current_time_nanos().saturating_sub(sent_ns));
+        self.estimate = Some(match self.estimate {
+            None => RttEstimate {
+                last: rtt,
+                smoothed: rtt,
+                min: rtt,
+                samples: 1,
+            },
+            Some(estimate) => RttEstimate {
+                last: rtt,
+                smoothed: (estimate.smoothed * 7 + rtt) / 8,
+                min: estimate.min.min(rtt),
+                samples: estimate.samples + 1,
+            },
+        });
+    }
+
+    pub const fn estimate(&self) -> Option<RttEstimate> {
+        self.estimate
+    }
+}
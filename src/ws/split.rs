@@ -0,0 +1,148 @@
+//! Reader and writer halves of the [`Websocket`], for the applications where one component drains
+//! the frames while another one sends on the same thread. Both halves share the websocket, so the
+//! messages sent before the handshake has completed are buffered and dispatched once it does, the
+//! same as with the unsplit websocket.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use std::net::TcpStream;
+//! use boomnet::ws::{IntoWebsocket, WebsocketFrame};
+//!
+//! let ws = TcpStream::connect("127.0.0.1:9001").unwrap().into_websocket("ws://127.0.0.1:9001/ws");
+//! let (mut reader, mut writer) = ws.split();
+//!
+//! writer.send_text(true, Some(b"subscribe")).unwrap();
+//! while let Some(WebsocketFrame::Text(_, _, data)) = reader.receive_next().unwrap() {
+//!     writer.send_text(true, Some(data)).unwrap();
+//! }
+//!
+//! let ws = reader.unsplit(writer);
+//! ```
+
+use std::cell::RefCell;
+use std::io::{IoSlice, Read, Write};
+use std::rc::Rc;
+
+use crate::ws::{protocol, Error, Websocket, WebsocketFrame};
+
+type Shared<S, const CHUNK_SIZE: usize, const INITIAL_CAPACITY: usize> =
+    Rc<RefCell<Websocket<S, CHUNK_SIZE, INITIAL_CAPACITY>>>;
+
+/// Receiving half of the [`Websocket`], see [`Websocket::split`].
+pub struct WsReader<S, const CHUNK_SIZE: usize, const INITIAL_CAPACITY: usize> {
+    ws: Shared<S, CHUNK_SIZE, INITIAL_CAPACITY>,
+}
+
+/// Sending half of the [`Websocket`], see [`Websocket::split`].
+pub struct WsWriter<S, const CHUNK_SIZE: usize, const INITIAL_CAPACITY: usize> {
+    ws: Shared<S, CHUNK_SIZE, INITIAL_CAPACITY>,
+}
+
+impl<S, const CHUNK_SIZE: usize, const INITIAL_CAPACITY: usize> Websocket<S, CHUNK_SIZE, INITIAL_CAPACITY> {
+    /// Splits the websocket into the reader and the writer half. The halves are not `Send` and
+    /// must be used from the same thread, use [`WsReader::unsplit`] to get the websocket back.
+    pub fn split(self) -> (WsReader<S, CHUNK_SIZE, INITIAL_CAPACITY>, WsWriter<S, CHUNK_SIZE, INITIAL_CAPACITY>) {
+        let ws = Rc::new(RefCell::new(self));
+        (WsReader { ws: ws.clone() }, WsWriter { ws })
+    }
+}
+
+impl<S, const CHUNK_SIZE: usize, const INITIAL_CAPACITY: usize> WsReader<S, CHUNK_SIZE, INITIAL_CAPACITY> {
+    /// Joins the halves back into the websocket.
+    ///
+    /// # Panics
+    ///
+    /// If the `writer` does not come from the same [`Websocket::split`].
+    pub fn unsplit(
+        self,
+        writer: WsWriter<S, CHUNK_SIZE, INITIAL_CAPACITY>,
+    ) -> Websocket<S, CHUNK_SIZE, INITIAL_CAPACITY> {
+        assert!(Rc::ptr_eq(&self.ws, &writer.ws), "reader and writer come from different websockets");
+        drop(writer);
+        match Rc::try_unwrap(self.ws) {
+            Ok(ws) => ws.into_inner(),
+            Err(_) => unreachable!("websocket is shared only by its halves"),
+        }
+    }
+
+    /// Checks if the websocket is closed, see [`Websocket::closed`].
+    pub fn closed(&self) -> bool {
+        self.ws.borrow().closed()
+    }
+}
+
+impl<S: Read + Write, const CHUNK_SIZE: usize, const INITIAL_CAPACITY: usize>
+    WsReader<S, CHUNK_SIZE, INITIAL_CAPACITY>
+{
+    /// Receives the next frame, see [`Websocket::receive_next`]. The frame borrows the reader so
+    /// it cannot outlive the next call, while the writer remains free to send.
+    #[inline]
+    pub fn receive_next(&mut self) -> Result<Option<WebsocketFrame<'_>>, Error> {
+        // the payload lives in the decoder buffer which only the reader advances
+        self.ws.borrow_mut().receive_next_unbound()
+    }
+}
+
+impl<S, const CHUNK_SIZE: usize, const INITIAL_CAPACITY: usize> WsWriter<S, CHUNK_SIZE, INITIAL_CAPACITY> {
+    /// Checks if the websocket is closed, see [`Websocket::closed`].
+    pub fn closed(&self) -> bool {
+        self.ws.borrow().closed()
+    }
+
+    /// Checks if the handshake has completed, until then the messages are buffered.
+    pub fn handshake_complete(&self) -> bool {
+        self.ws.borrow().handshake_complete()
+    }
+
+    /// Returns the number of messages buffered while the handshake is pending.
+    pub fn pending_message_count(&self) -> usize {
+        self.ws.borrow().pending_message_count()
+    }
+}
+
+impl<S: Read + Write, const CHUNK_SIZE: usize, const INITIAL_CAPACITY: usize>
+    WsWriter<S, CHUNK_SIZE, INITIAL_CAPACITY>
+{
+    #[inline]
+    #[must_use = "the websocket is closed if the send fails"]
+    pub fn send_text(&mut self, fin: bool, body: Option<&[u8]>) -> Result<(), Error> {
+        self.ws.borrow_mut().send(fin, protocol::op::TEXT_FRAME, body)
+    }
+
+    #[inline]
+    #[must_use = "the websocket is closed if the send fails"]
+    pub fn send_binary(&mut self, fin: bool, body: Option<&[u8]>) -> Result<(), Error> {
+        self.ws.borrow_mut().send(fin, protocol::op::BINARY_FRAME, body)
+    }
+
+    #[inline]
+    #[must_use = "the websocket is closed if the send fails"]
+    pub fn send_ping(&mut self, body: Option<&[u8]>) -> Result<(), Error> {
+        self.ws.borrow_mut().send(true, protocol::op::PING, body)
+    }
+
+    #[inline]
+    #[must_use = "the websocket is closed if the send fails"]
+    pub fn send_pong(&mut self, body: Option<&[u8]>) -> Result<(), Error> {
+        self.ws.borrow_mut().send(true, protocol::op::PONG, body)
+    }
+
+    /// See [`Websocket::send_text_vectored`].
+    #[inline]
+    #[must_use = "the websocket is closed if the send fails"]
+    pub fn send_text_vectored(&mut self, fin: bool, segments: &[IoSlice]) -> Result<(), Error> {
+        self.ws
+            .borrow_mut()
+            .send_vectored(fin, protocol::op::TEXT_FRAME, segments)
+    }
+
+    /// See [`Websocket::send_binary_vectored`].
+    #[inline]
+    #[must_use = "the websocket is closed if the send fails"]
+    pub fn send_binary_vectored(&mut self, fin: bool, segments: &[IoSlice]) -> Result<(), Error> {
+        self.ws
+            .borrow_mut()
+            .send_vectored(fin, protocol::op::BINARY_FRAME, segments)
+    }
+}
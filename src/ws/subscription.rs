@@ -0,0 +1,156 @@
+//! Records the subscriptions made on the [`Websocket`] so that they can be replayed on the fresh
+//! connection when the endpoint is recreated by the `IOService`.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use std::io;
+//! use std::net::{SocketAddr, TcpStream};
+//! use boomnet::endpoint::ws::{TlsWebsocket, TlsWebsocketEndpoint};
+//! use boomnet::ws::subscription::SubscriptionManager;
+//! use boomnet::ws::{IntoTlsWebsocket, WebsocketFrame};
+//!
+//! struct TradeEndpoint {
+//!     subscriptions: SubscriptionManager,
+//! }
+//!
+//! impl TlsWebsocketEndpoint for TradeEndpoint {
+//!     type Stream = TcpStream;
+//!
+//!     fn url(&self) -> &str {
+//!         "wss://stream.binance.com:9443/ws"
+//!     }
+//!
+//!     fn create_websocket(&mut self, addr: SocketAddr) -> io::Result<TlsWebsocket<Self::Stream>> {
+//!         let mut ws = TcpStream::connect(addr)?.into_tls_websocket(self.url())?;
+//!         // sent as soon as the handshake completes
+//!         self.subscriptions.replay(&mut ws)?;
+//!         Ok(ws)
+//!     }
+//!
+//!     fn poll(&mut self, ws: &mut TlsWebsocket<Self::Stream>) -> io::Result<()> {
+//!         while let Some(WebsocketFrame::Text(_, _, data)) = ws.receive_next()? {
+//!             if data.starts_with(b"{\"e\":\"trade\"") {
+//!                 let unsubscribe = br#"{"method":"UNSUBSCRIBE","params":["btcusdt@trade"],"id":2}"#;
+//!                 self.subscriptions.unsubscribe(ws, "btcusdt@trade", unsubscribe)?;
+//!             }
+//!         }
+//!         Ok(())
+//!     }
+//! }
+//! ```
+
+use std::io::{Read, Write};
+
+use crate::ws::{Error, Websocket};
+
+/// Keeps the subscribe messages sent on the connection, in the order they were sent. Subscribing
+/// again with the same key replaces the recorded message.
+#[derive(Debug, Default)]
+pub struct SubscriptionManager {
+    subscriptions: Vec<(String, Vec<u8>)>,
+}
+
+impl SubscriptionManager {
+    pub fn new() -> SubscriptionManager {
+        Self::default()
+    }
+
+    /// Records the subscription without sending it, useful to declare the initial subscriptions
+    /// that will be sent by [`SubscriptionManager::replay`] once the websocket is created.
+    pub fn with_subscription(self, key: impl Into<String>, message: impl Into<Vec<u8>>) -> SubscriptionManager {
+        let mut manager = self;
+        manager.record(key.into(), message.into());
+        manager
+    }
+
+    /// Sends the subscribe `message` as text frame and records it under the `key`.
+    pub fn subscribe<S: Read + Write, const CHUNK_SIZE: usize, const INITIAL_CAPACITY: usize>(
+        &mut self,
+        ws: &mut Websocket<S, CHUNK_SIZE, INITIAL_CAPACITY>,
+        key: impl Into<String>,
+        message: &[u8],
+    ) -> Result<(), Error> {
+        ws.send_text(true, Some(message))?;
+        self.record(key.into(), message.to_vec());
+        Ok(())
+    }
+
+    /// Sends the unsubscribe `message` as text frame and forgets the subscription recorded under the `key`.
+    pub fn unsubscribe<S: Read + Write, const CHUNK_SIZE: usize, const INITIAL_CAPACITY: usize>(
+        &mut self,
+        ws: &mut Websocket<S, CHUNK_SIZE, INITIAL_CAPACITY>,
+        key: &str,
+        message: &[u8],
+    ) -> Result<(), Error> {
+        ws.send_text(true, Some(message))?;
+        self.remove(key);
+        Ok(())
+    }
+
+    /// Forgets the subscription recorded under the `key` without sending anything.
+    pub fn remove(&mut self, key: &str) -> bool {
+        let len = self.subscriptions.len();
+        self.subscriptions.retain(|(k, _)| k != key);
+        self.subscriptions.len() != len
+    }
+
+    /// Sends all recorded subscriptions. Messages sent before the handshake completes are queued
+    /// by the websocket, so this is meant to be called on the freshly created websocket.
+    pub fn replay<S: Read + Write, const CHUNK_SIZE: usize, const INITIAL_CAPACITY: usize>(
+        &self,
+        ws: &mut Websocket<S, CHUNK_SIZE, INITIAL_CAPACITY>,
+    ) -> Result<(), Error> {
+        for (_, message) in &self.subscriptions {
+            ws.send_text(true, Some(message))?;
+        }
+        Ok(())
+    }
+
+    /// Returns `true` if there is subscription recorded under the `key`.
+    pub fn contains(&self, key: &str) -> bool {
+        self.subscriptions.iter().any(|(k, _)| k == key)
+    }
+
+    /// Iterates over the recorded subscription keys in the order they will be replayed.
+    pub fn keys(&self) -> impl Iterator<Item = &str> {
+        self.subscriptions.iter().map(|(key, _)| key.as_str())
+    }
+
+    pub fn len(&self) -> usize {
+        self.subscriptions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.subscriptions.is_empty()
+    }
+
+    fn record(&mut self, key: String, message: Vec<u8>) {
+        match self.subscriptions.iter_mut().find(|(k, _)| *k == key) {
+            Some((_, existing)) => *existing = message,
+            None => self.subscriptions.push((key, message)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    #[test]
+    fn should_replay_active_subscriptions() {
+        let mut ws = Websocket::new_connected(Cursor::new(Vec::new()));
+        let mut subscriptions = SubscriptionManager::new().with_subscription("a", b"sub-a".to_vec());
+        subscriptions.subscribe(&mut ws, "b", b"sub-b").unwrap();
+        subscriptions.subscribe(&mut ws, "c", b"sub-c").unwrap();
+        subscriptions.unsubscribe(&mut ws, "b", b"unsub-b").unwrap();
+        subscriptions.subscribe(&mut ws, "a", b"sub-A").unwrap();
+        assert_eq!(vec!["a", "c"], subscriptions.keys().collect::<Vec<_>>());
+
+        let mut ws = Websocket::new_connected(Cursor::new(Vec::new()));
+        subscriptions.replay(&mut ws).unwrap();
+        assert_eq!(b"\x81\x85\x00\x00\x00\x00sub-A\x81\x85\x00\x00\x00\x00sub-c", ws.stream().get_ref().as_slice());
+    }
+}
@@ -0,0 +1,170 @@
+//! Harness to drive [`Websocket`] from the recorded session, useful to test the endpoint logic
+//! deterministically without touching the network.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use boomnet::ws::testing::SessionReplay;
+//! use boomnet::ws::WebsocketFrame;
+//!
+//! let mut session = SessionReplay::from_file("plain_inbound.rec", "wss://stream.binance.com:9443/ws")
+//!     .unwrap()
+//!     .with_disconnect_at(100);
+//!
+//! session.assert_next(|frame| matches!(frame, WebsocketFrame::Text(..)));
+//! // simulated disconnect surfaces as an error
+//! assert!(session.run(|_sequence, _frame| {}).is_err());
+//! ```
+
+use std::fs::File;
+use std::io;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+use crate::stream::replay::ReplayStream;
+use crate::ws::{Error, Websocket, WebsocketFrame};
+
+/// Replays the recorded session through the [`Websocket`] frame by frame. Frames are numbered
+/// from zero in the order they were decoded.
+pub struct SessionReplay<S> {
+    websocket: Websocket<ReplayStream<S>>,
+    sequence: u64,
+    disconnect_at: Option<u64>,
+}
+
+impl SessionReplay<BufReader<File>> {
+    /// Loads the recording that includes the handshake response for the `url`.
+    pub fn from_file(path: impl AsRef<Path>, url: &str) -> io::Result<SessionReplay<BufReader<File>>> {
+        SessionReplay::new(ReplayStream::from_file(path)?, url)
+    }
+}
+
+impl<S: BufRead> SessionReplay<S> {
+    /// Creates the session from the recording that includes the handshake response for the `url`.
+    pub fn new(stream: ReplayStream<S>, url: &str) -> io::Result<SessionReplay<S>> {
+        Ok(Self {
+            websocket: Websocket::new(stream, url)?,
+            sequence: 0,
+            disconnect_at: None,
+        })
+    }
+
+    /// Creates the session from the recording that contains websocket frames only.
+    pub fn without_handshake(stream: ReplayStream<S>) -> SessionReplay<S> {
+        Self {
+            websocket: Websocket::new_connected(stream),
+            sequence: 0,
+            disconnect_at: None,
+        }
+    }
+
+    /// Simulate the connection being reset just before the frame with the given `sequence`
+    /// would have been delivered.
+    pub fn with_disconnect_at(self, sequence: u64) -> SessionReplay<S> {
+        Self {
+            disconnect_at: Some(sequence),
+            ..self
+        }
+    }
+
+    /// Sequence number of the next frame.
+    pub const fn sequence(&self) -> u64 {
+        self.sequence
+    }
+
+    /// Access the underlying websocket, for example to pass it to the endpoint logic under test.
+    pub fn websocket_mut(&mut self) -> &mut Websocket<ReplayStream<S>> {
+        &mut self.websocket
+    }
+
+    /// Returns the next frame or `None` once the recording has been fully replayed.
+    pub fn next_frame(&mut self) -> Result<Option<WebsocketFrame<'_>>, Error> {
+        if self.disconnect_at == Some(self.sequence) {
+            self.disconnect_at = None;
+            return Err(Error::IO(io::Error::new(
+                io::ErrorKind::ConnectionReset,
+                format!("simulated disconnect at sequence {}", self.sequence),
+            )));
+        }
+        loop {
+            match self.websocket.receive_next_unbound() {
+                Ok(Some(frame)) => {
+                    self.sequence += 1;
+                    return Ok(Some(frame));
+                }
+                Ok(None) => continue,
+                Err(Error::IO(err)) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    /// Asserts that the next frame exists and satisfies the `predicate`.
+    #[track_caller]
+    pub fn assert_next<F: FnOnce(&WebsocketFrame) -> bool>(&mut self, predicate: F) {
+        let sequence = self.sequence;
+        match self.next_frame() {
+            Ok(Some(frame)) => assert!(predicate(&frame), "unexpected frame at sequence {}", sequence),
+            Ok(None) => panic!("expected frame at sequence {} but the recording has ended", sequence),
+            Err(err) => panic!("expected frame at sequence {} but got error: {}", sequence, err),
+        }
+    }
+
+    /// Drives the session until the end of the recording invoking `on_frame` with every frame
+    /// and its sequence number. Returns the number of frames replayed.
+    pub fn run<F: FnMut(u64, &WebsocketFrame)>(&mut self, mut on_frame: F) -> Result<u64, Error> {
+        let start = self.sequence;
+        let mut sequence = start;
+        while let Some(frame) = self.next_frame()? {
+            on_frame(sequence, &frame);
+            sequence += 1;
+        }
+        Ok(sequence - start)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    fn recording() -> ReplayStream<Cursor<Vec<u8>>> {
+        let mut frames = Vec::new();
+        for payload in [b"one".as_slice(), b"two", b"three"] {
+            frames.push(0x81);
+            frames.push(payload.len() as u8);
+            frames.extend_from_slice(payload);
+        }
+        ReplayStream::new(Cursor::new(frames)).unwrap()
+    }
+
+    #[test]
+    fn should_replay_all_frames() {
+        let mut session = SessionReplay::without_handshake(recording());
+        session.assert_next(|frame| matches!(frame, WebsocketFrame::Text(_, true, b"one")));
+
+        let mut payloads = Vec::new();
+        let count = session
+            .run(|sequence, frame| {
+                if let WebsocketFrame::Text(_, _, payload) = frame {
+                    payloads.push((sequence, payload.to_vec()));
+                }
+            })
+            .unwrap();
+
+        assert_eq!(2, count);
+        assert_eq!(vec![(1, b"two".to_vec()), (2, b"three".to_vec())], payloads);
+    }
+
+    #[test]
+    fn should_simulate_disconnect() {
+        let mut session = SessionReplay::without_handshake(recording()).with_disconnect_at(1);
+        session.assert_next(|_| true);
+        match session.next_frame() {
+            Err(Error::IO(err)) => assert_eq!(io::ErrorKind::ConnectionReset, err.kind()),
+            _ => panic!("expected simulated disconnect"),
+        }
+        assert_eq!(1, session.sequence());
+    }
+}
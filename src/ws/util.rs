@@ -0,0 +1,90 @@
+//! Websocket url parsing.
+//!
+//! # Examples
+//!
+//! ```
+//! use boomnet::ws::util::parse_url;
+//!
+//! let url = parse_url("wss://[::1]:9443/stream?streams=btcusdt@trade").unwrap();
+//! assert_eq!("::1", url.connection_info.host);
+//! assert_eq!(9443, url.connection_info.port);
+//! assert_eq!("/stream?streams=btcusdt@trade", url.path_with_query);
+//! assert!(url.tls);
+//! ```
+
+use url::{Host, Url};
+
+use crate::endpoint::ConnectionInfo;
+use crate::ws::Error;
+
+/// Parts of the websocket url needed to connect and perform the handshake.
+#[derive(Debug, Clone)]
+pub struct WebsocketUrl {
+    /// Host (IPv6 literals without the brackets) and port to connect to, the port defaults to `80`
+    /// for `ws` and `443` for `wss` scheme.
+    pub connection_info: ConnectionInfo,
+    /// Value of the `Host` header, includes the port only if it is not the scheme default.
+    pub host_header: String,
+    /// Percent-encoded path followed by the query string (if any), as sent in the request line.
+    pub path_with_query: String,
+    /// `true` for the `wss` scheme.
+    pub tls: bool,
+}
+
+/// Parses the websocket `url`, failing with [`Error::InvalidUrl`] if the url is malformed or
+/// with [`Error::UnsupportedUrl`] if the scheme is not `ws` or `wss` or the host is missing.
+pub fn parse_url(url: &str) -> Result<WebsocketUrl, Error> {
+    let url = Url::parse(url)?;
+    let tls = match url.scheme() {
+        "ws" => false,
+        "wss" => true,
+        scheme => return Err(Error::UnsupportedUrl(format!("unrecognised scheme: {scheme}"))),
+    };
+    let host = match url.host() {
+        Some(Host::Ipv6(addr)) => addr.to_string(),
+        Some(host) => host.to_string(),
+        None => return Err(Error::UnsupportedUrl("host not present".to_owned())),
+    };
+    // both schemes have known default port
+    let port = url.port_or_known_default().unwrap_or(if tls { 443 } else { 80 });
+    let host_header = match url.port() {
+        Some(port) => format!("{}:{port}", url.host_str().unwrap_or_default()),
+        None => url.host_str().unwrap_or_default().to_owned(),
+    };
+    let path_with_query = match url.query() {
+        Some(query) => format!("{}?{}", url.path(), query),
+        None => url.path().to_owned(),
+    };
+    Ok(WebsocketUrl {
+        connection_info: ConnectionInfo::new(&host, port),
+        host_header,
+        path_with_query,
+        tls,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn should_parse_websocket_urls() {
+        let url = parse_url("ws://localhost:8080/ws?token=a%20b").unwrap();
+        assert_eq!(("localhost", 8080), (url.connection_info.host.as_str(), url.connection_info.port));
+        assert_eq!("localhost:8080", url.host_header);
+        assert_eq!("/ws?token=a%20b", url.path_with_query);
+        assert!(!url.tls);
+
+        let url = parse_url("wss://stream.binance.com").unwrap();
+        assert_eq!(443, url.connection_info.port);
+        assert_eq!("stream.binance.com", url.host_header);
+        assert_eq!("/", url.path_with_query);
+
+        let url = parse_url("wss://[::1]/ws").unwrap();
+        assert_eq!("::1", url.connection_info.host);
+        assert_eq!("[::1]", url.host_header);
+
+        assert!(matches!(parse_url("https://localhost/ws"), Err(Error::UnsupportedUrl(_))));
+        assert!(matches!(parse_url("localhost/ws"), Err(Error::InvalidUrl(_))));
+    }
+}